@@ -57,12 +57,14 @@ pub fn clean(cache: bool, all: bool, unused: bool) -> Result<()> {
 
     if unused {
         if let Ok(config) = super::load_config() {
-            let mut keep_deps = Vec::new();
-            if let Some(deps) = config.dependencies {
-                for (name, _) in deps {
-                    keep_deps.push(name);
-                }
-            }
+            // Direct dependencies plus what they require, as recorded in cx.lock
+            let deps = config.dependencies.unwrap_or_default();
+            let lockfile = crate::lock::LockFile::load().unwrap_or_default();
+            let keep_deps: Vec<String> = lockfile
+                .reachable(deps.keys().map(String::as_str))
+                .into_iter()
+                .map(String::from)
+                .collect();
             crate::cache::prune_unused(&keep_deps)?;
             cleaned = true;
         } else {
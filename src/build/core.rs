@@ -5,27 +5,53 @@
 //!
 //! ## Features
 //!
-//! - Lock-free parallel compilation using rayon
-//! - Incremental builds (only recompile changed files)
+//! - Parallel compilation on a job scheduler (`-j`, memory throttle, longest units first)
+//! - Incremental builds (recompile on changed sources, headers or flags)
 //! - Compile commands JSON generation for IDE integration
 //! - Chrome trace profiling output
 //! - LTO and sanitizer support
-
-use super::utils::{get_compiler, get_std_flag_gcc, get_std_flag_msvc, load_config, run_script};
-use crate::config::CxConfig;
+//! - Executable, static/shared library and header-only targets
+
+use super::cross::CrossTarget;
+use super::features::{FeatureSelection, Features};
+use super::feedback::{CompileFailure, FeedbackAnalyzer, print_failure_summary};
+use super::incremental::{
+    BuildState, ChangeDetection, CommandSignature, change_detection, check_dependencies,
+    dependency_inputs, diagnostics_path, inputs_changed, link_signature_mismatch,
+    recorded_diagnostics, signature_mismatch, write_diagnostics, write_link_signature,
+    write_signature,
+};
+use super::jobs::{CompileTimes, Scheduler};
+use super::messages::MessageFormat;
+use super::modules::{self, HeaderUnit, ModuleFlavor, ModuleScan, ModuleUnit, Scanner};
+use super::objcache::{KeyContext, ObjectCache, compiler_identity, compute_key};
+use super::overrides::Overrides;
+use super::platform::{self, Platform};
+use super::profiles::resolve_profile;
+use super::targets::{
+    BuildTarget, default_run_target, entry_sources, is_entry_source, resolve_targets, same_source,
+    select_targets,
+};
+use super::unity;
+use super::utils::{
+    get_compiler, get_std_flag_gcc, get_std_flag_msvc, load_config, load_config_for, run_script,
+};
+use crate::config::{BuildType, CxConfig, Lto};
 use crate::deps;
+use crate::diagnostics::{self, Diagnostic};
+use crate::toolchain::CompilerType;
 use crate::ui;
 use anyhow::{Context, Result};
 use colored::*;
-use indicatif::{ProgressBar, ProgressStyle};
-use rayon::prelude::*;
+use indicatif::{ProgressBar, ProgressDrawTarget, ProgressStyle};
 use serde_json::json;
 use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fs;
 use std::path::{Path, PathBuf};
 use std::process::Command;
 use std::sync::{Arc, Mutex};
-use std::time::Instant;
+use std::time::{Duration, Instant};
 use walkdir::WalkDir;
 
 #[derive(serde::Serialize)]
@@ -50,6 +76,20 @@ pub struct BuildOptions {
     pub sanitize: Option<String>,
     /// Named profile for cross-compilation (e.g., "esp32", "linux-arm64")
     pub profile: Option<String>,
+    /// Only build this binary target (`--bin <name>`)
+    pub bin: Option<String>,
+    /// Parallel compile jobs (`-j`); `None` falls back to `CX_JOBS`, `[build] jobs`, all cores
+    pub jobs: Option<usize>,
+    /// Compile every translation unit possible and summarize all failures
+    pub keep_going: bool,
+    /// `--message-format`: also emit JSON build events on stdout
+    pub message_format: MessageFormat,
+    /// Write compiler diagnostics of this build as SARIF (`--sarif <path>`)
+    pub sarif: Option<PathBuf>,
+    /// `--features`, `--all-features`, `--no-default-features`
+    pub features: FeatureSelection,
+    /// Compile sources in unity batches (`--unity`, or `[build] unity`)
+    pub unity: bool,
 }
 
 pub fn artifact_profile_name(release: bool) -> &'static str {
@@ -60,8 +100,13 @@ pub fn artifact_profile_dir(release: bool) -> PathBuf {
     PathBuf::from(".cx").join(artifact_profile_name(release))
 }
 
-pub fn artifact_bin_dir(release: bool) -> PathBuf {
-    artifact_profile_dir(release).join("bin")
+/// `.cx/<triple>/<profile>` for cross builds, `.cx/<profile>` otherwise
+pub fn artifact_triple_dir(profile: &str, triple: Option<&str>) -> PathBuf {
+    let base = match triple {
+        Some(triple) => PathBuf::from(".cx").join(sanitize_filename_component(triple)),
+        None => PathBuf::from(".cx"),
+    };
+    base.join(sanitize_filename_component(profile))
 }
 
 pub fn binary_basename(config: &CxConfig) -> String {
@@ -72,19 +117,97 @@ pub fn binary_basename(config: &CxConfig) -> String {
     }
 }
 
+/// Resolved `[build] type`, falling back to executable for unknown values
+pub(crate) fn build_type_of(config: &CxConfig) -> BuildType {
+    config
+        .build
+        .as_ref()
+        .and_then(|b| b.get_build_type())
+        .unwrap_or_default()
+}
+
 pub fn binary_name(config: &CxConfig, wasm: bool) -> String {
-    let base = binary_basename(config);
-    if wasm {
-        format!("{base}.html")
-    } else if cfg!(target_os = "windows") {
-        format!("{base}.exe")
-    } else {
-        base
+    artifact_file_name(
+        &binary_basename(config),
+        build_type_of(config),
+        wasm,
+        std::env::consts::OS,
+    )
+}
+
+/// Platform file name for an artifact: `app.exe`, `libapp.a`, `app.lib`,
+/// `libapp.so`, `libapp.dylib`, `app.dll`, ...
+fn artifact_file_name(base: &str, build_type: BuildType, wasm: bool, os: &str) -> String {
+    // Don't turn bin = "libfoo" into "liblibfoo.a"
+    let stem = base
+        .strip_prefix("lib")
+        .filter(|rest| !rest.is_empty())
+        .unwrap_or(base);
+    match build_type {
+        BuildType::Executable | BuildType::HeaderOnly => {
+            if wasm {
+                format!("{base}.html")
+            } else if os == "windows" {
+                format!("{base}.exe")
+            } else {
+                base.to_string()
+            }
+        }
+        BuildType::StaticLibrary => {
+            if os == "windows" {
+                format!("{base}.lib")
+            } else {
+                format!("lib{stem}.a")
+            }
+        }
+        BuildType::SharedLibrary => match os {
+            "windows" => format!("{base}.dll"),
+            "macos" | "ios" => format!("lib{stem}.dylib"),
+            _ => format!("lib{stem}.so"),
+        },
     }
 }
 
-pub fn artifact_bin_path(config: &CxConfig, release: bool, wasm: bool) -> PathBuf {
-    artifact_bin_dir(release).join(binary_name(config, wasm))
+/// Where a build puts its binaries, `.cx/[<triple>/]<profile>/bin`, and the
+/// OS whose file naming they follow
+pub struct ArtifactDir {
+    pub bin_dir: PathBuf,
+    pub os: &'static str,
+}
+
+impl ArtifactDir {
+    /// Layout of a build with `profile` (or the built-in debug/release one)
+    pub fn resolve(config: &CxConfig, profile: Option<&str>, release: bool) -> Result<Self> {
+        let resolved = resolve_profile(&config.profiles, profile, release)?;
+        Ok(Self::new(&resolved.name, resolved.cross().as_ref()))
+    }
+
+    fn new(profile: &str, cross: Option<&CrossTarget>) -> Self {
+        Self {
+            bin_dir: artifact_triple_dir(profile, cross.map(|c| c.triple.as_str())).join("bin"),
+            os: cross.map_or(std::env::consts::OS, |c| c.os()),
+        }
+    }
+
+    /// Output path of the package's own artifact (`[build] bin`)
+    pub fn bin_path(&self, config: &CxConfig, wasm: bool) -> PathBuf {
+        self.bin_dir.join(artifact_file_name(
+            &binary_basename(config),
+            build_type_of(config),
+            wasm,
+            self.os,
+        ))
+    }
+
+    /// Output path of one build target (see [`super::targets`])
+    pub fn target_path(&self, wasm: bool, target: &BuildTarget) -> PathBuf {
+        target_output_path(&self.bin_dir, wasm, target, self.os)
+    }
+}
+
+/// Output path of one build target in `bin_dir`, named for `os`
+fn target_output_path(bin_dir: &Path, wasm: bool, target: &BuildTarget, os: &str) -> PathBuf {
+    bin_dir.join(artifact_file_name(&target.name, target.kind, wasm, os))
 }
 
 fn sanitize_filename_component(raw: &str) -> String {
@@ -99,7 +222,7 @@ fn sanitize_filename_component(raw: &str) -> String {
         .collect()
 }
 
-fn normalized_source_key(src_path: &Path) -> String {
+pub(crate) fn normalized_source_key(src_path: &Path) -> String {
     src_path
         .to_string_lossy()
         .replace('\\', "/")
@@ -154,221 +277,143 @@ fn framework_alias_hint(name: &str) -> String {
     format!("cx add {}", name.to_ascii_lowercase())
 }
 
-// --- Helper: Check Dependencies (.d file or .json for MSVC) ---
-fn check_dependencies(obj_path: &Path, src_path: &Path) -> Result<bool> {
-    // 1. Check for MSVC JSON dependencies first
-    // Actually typically /sourceDependencies foo.json -> foo.json.
-    // We will name it <obj>.json explicitly.
-    let json_path = PathBuf::from(format!("{}.json", obj_path.display()));
-
-    if json_path.exists() {
-        let content = fs::read_to_string(&json_path)?;
-        // Parse JSON: {"Data": {"Source": "...", "Includes": ["..."]}}
-        let json: serde_json::Value = serde_json::from_str(&content)?;
-
-        // Check Includes
-        if let Some(includes) = json.pointer("/Data/Includes")
-            && let Some(arr) = includes.as_array()
-        {
-            let obj_mtime = fs::metadata(obj_path)?.modified()?;
-
-            for include in arr {
-                if let Some(path_str) = include.as_str() {
-                    let dep_path = Path::new(path_str);
-                    if dep_path.exists() {
-                        let dep_mtime = fs::metadata(dep_path)?.modified()?;
-                        if dep_mtime > obj_mtime {
-                            return Ok(true); // Dependency newer
-                        }
-                    }
-                }
-            }
-            // Also check source itself just in case? Usually built-in but good to double check.
-            let src_mtime = fs::metadata(src_path)?.modified()?;
-            if src_mtime > obj_mtime {
-                return Ok(true);
-            }
-            return Ok(false); // All clean
-        }
-    }
-
-    // 2. Check for GCC/Clang .d file
-    let d_path = obj_path.with_extension("d");
-
-    // Always check source itself first, even if .d file exists.
-    // This is the most reliable check for the primary source file.
-    if !obj_path.exists() {
-        return Ok(true);
-    }
-    let src_mtime = fs::metadata(src_path)?.modified()?;
-    let obj_mtime = fs::metadata(obj_path)?.modified()?;
-    if src_mtime > obj_mtime {
-        return Ok(true);
-    }
-
-    // Fallback: If no dependency file, we already checked the source
-    if !d_path.exists() {
-        return Ok(false);
-    }
-
-    let dep_content = fs::read_to_string(&d_path)?;
-    // Handle line continuations
-    let content_flat = dep_content.replace("\\\n", " ").replace("\\\r\n", " ");
-
-    // Format is usually: "objfile.o: src.c header.h ..."
-    // Handle Windows paths (e.g. C:\foo.o) by looking for ": " (colon + space)
-    // Splitting on just ':' breaks drive letters.
-    let deps_str = if let Some((_, rhs)) = content_flat.split_once(": ") {
-        rhs
-    } else {
-        // Fallback: if no ": ", maybe just split on first colon if not a drive letter?
-        // Or if the file is corrupted/empty.
-        if let Some((lhs, rhs)) = content_flat.split_once(':') {
-            // Heuristic: if lhs is 1 char (e.g. "C"), it's probably a drive letter and the parse failed.
-            if lhs.trim().len() == 1 {
-                return Ok(true); // Ambiguous, safer to rebuild
-            }
-            rhs
-        } else {
-            // No colon found
-            return Ok(true); // Safe fallback
-        }
-    };
-
-    let obj_mtime = fs::metadata(obj_path)?.modified()?;
-
-    for dep in deps_str.split_whitespace() {
-        let dep_path = Path::new(dep);
-        if dep_path.exists() {
-            let dep_mtime = fs::metadata(dep_path)?.modified()?;
-            if dep_mtime > obj_mtime {
-                return Ok(true); // Dependency is newer
-            }
-        }
+// --- CORE: Build Project ---
+pub fn build_project(config: &CxConfig, options: &BuildOptions) -> Result<bool> {
+    let messages = options.message_format;
+    let start_time = Instant::now();
+    messages.emit(
+        "build-started",
+        json!({
+            "package": config.package.name,
+            "profile": options
+                .profile
+                .as_deref()
+                .unwrap_or(artifact_profile_name(options.release)),
+        }),
+    );
+    let sarif = options.sarif.as_ref().map(|_| Mutex::new(Vec::new()));
+    let result = build(config, options, sarif.as_ref());
+    if let (Some(path), Some(found)) = (&options.sarif, sarif) {
+        let found = found.into_inner().unwrap_or_default();
+        diagnostics::write_sarif(path, "cx build", &found)?;
+        status!(
+            "   {} {} diagnostic(s) written to {}",
+            "📋".blue(),
+            found.len(),
+            path.display()
+        );
     }
-
-    Ok(false) // Up to date
+    messages.emit(
+        "build-finished",
+        json!({
+            "success": matches!(result, Ok(true)),
+            "duration_ms": start_time.elapsed().as_millis() as u64,
+        }),
+    );
+    result
 }
 
-// --- CORE: Build Project ---
-pub fn build_project(config: &CxConfig, options: &BuildOptions) -> Result<bool> {
-    let release = options.release;
+fn build(
+    config: &CxConfig,
+    options: &BuildOptions,
+    sarif: Option<&Mutex<Vec<Diagnostic>>>,
+) -> Result<bool> {
     let verbose = options.verbose;
     let dry_run = options.dry_run;
     let enable_profile = options.enable_profile;
     let wasm = options.wasm;
-    let lto = options.lto;
     let sanitize = options.sanitize.clone();
+    let keep_going = options.keep_going;
+    let messages = options.message_format;
     let start_time = Instant::now();
 
     // --- Profile Resolution with Inheritance ---
-    // Clone config for potential modification based on selected profile
-    let mut effective_config = config.clone();
-
-    if let Some(profile_name) = &options.profile {
-        // Look up the profile in config.profiles
-        if let Some(profile) = config.profiles.get(profile_name) {
-            println!(
-                "   {} Using profile: {}",
-                "🎯".magenta(),
-                profile_name.cyan().bold()
-            );
-
-            // Resolve base profile first (inheritance)
-            let mut resolved_flags: Vec<String> = Vec::new();
-            let mut resolved_libs: Vec<String> = Vec::new();
-            let mut resolved_compiler: Option<String> = None;
-
-            if let Some(base_name) = &profile.base {
-                // Handle built-in profiles (release/debug) or user-defined profiles
-                if base_name == "release" {
-                    // Release implies optimizations, but we handle that via options.release
-                    // Just note it for verbosity
-                    if verbose {
-                        println!("      {} Inheriting from: {}", "└─".dimmed(), base_name);
-                    }
-                } else if base_name == "debug" {
-                    // Debug is default, nothing special needed
-                    if verbose {
-                        println!("      {} Inheriting from: {}", "└─".dimmed(), base_name);
-                    }
-                } else if let Some(base_profile) = config.profiles.get(base_name) {
-                    // Inherit from another user-defined profile
-                    if verbose {
-                        println!("      {} Inheriting from: {}", "└─".dimmed(), base_name);
-                    }
-                    if let Some(ref flags) = base_profile.flags {
-                        resolved_flags.extend(flags.clone());
-                    }
-                    if let Some(ref libs) = base_profile.libs {
-                        resolved_libs.extend(libs.clone());
-                    }
-                    if let Some(ref compiler) = base_profile.compiler {
-                        resolved_compiler = Some(compiler.clone());
-                    }
-                }
-            }
-
-            // Apply this profile's settings (override base)
-            if let Some(ref flags) = profile.flags {
-                resolved_flags.extend(flags.clone());
-            }
-            if let Some(ref libs) = profile.libs {
-                resolved_libs.extend(libs.clone());
-            }
-            if let Some(ref compiler) = profile.compiler {
-                resolved_compiler = Some(compiler.clone());
-            }
-
-            // Apply resolved values to effective_config
-            let build_cfg = effective_config.build.get_or_insert_with(Default::default);
-
-            // Merge flags into build config
-            if !resolved_flags.is_empty() {
-                let existing = build_cfg.flags.get_or_insert_with(Vec::new);
-                existing.extend(resolved_flags);
-            }
-
-            // Override libs
-            if !resolved_libs.is_empty() {
-                let existing = build_cfg.libs.get_or_insert_with(Vec::new);
-                existing.extend(resolved_libs);
-            }
-
-            // Override compiler
-            if let Some(compiler) = resolved_compiler {
-                build_cfg.compiler = Some(compiler);
-            }
-
-            // Apply bin name override
-            if let Some(ref bin) = profile.bin {
-                build_cfg.bin = Some(bin.clone());
+    let resolved = resolve_profile(
+        &config.profiles,
+        options.profile.as_deref(),
+        options.release,
+    )?;
+    if resolved.is_named() {
+        status!(
+            "   {} Using profile: {}",
+            "🎯".magenta(),
+            resolved.name.cyan().bold()
+        );
+        if verbose {
+            for base_name in resolved.chain[1..]
+                .iter()
+                .chain(std::iter::once(&resolved.builtin_base))
+            {
+                status!("      {} Inheriting from: {}", "└─".dimmed(), base_name);
             }
-        } else {
-            return Err(anyhow::anyhow!(
-                "Profile '{}' not found in cx.toml. Available profiles: {:?}",
-                profile_name,
-                config.profiles.keys().collect::<Vec<_>>()
-            ));
         }
     }
+    let cross = resolved.cross();
+
+    // `[target.'cfg(...)']` sections were merged for the host; a cross
+    // target re-reads cx.toml so they are evaluated for its triple instead
+    let host = Platform::host();
+    let platform = cross
+        .as_ref()
+        .map_or_else(|| host.clone(), |c| Platform::for_triple(&c.triple));
+    let mut effective_config = if platform != host
+        && Path::new("cx.toml").exists()
+        && platform::active_sections(config, &platform)?
+            != platform::active_sections(config, &host)?
+    {
+        load_config_for(&platform)?
+    } else {
+        config.clone()
+    };
+    resolved.apply(&mut effective_config);
+    let release = resolved.is_release();
+    // `--lto` turns on whole-program LTO unless the profile already picked a mode
+    let lto = match (options.lto, resolved.lto()) {
+        (true, Lto::Off) => Lto::Fat,
+        (_, lto) => lto,
+    };
+
+    // Features add defines and decide which optional deps and sources take part
+    let features = Features::resolve(&effective_config, &options.features)?;
+    features.apply(&mut effective_config);
 
     // Use effective_config from now on
     let config = &effective_config;
     let current_dir = std::env::current_dir()?;
 
     if let Some(build_cfg) = &config.build
-        && let Some(build_type) = &build_cfg.build_type
+        && let Some(raw_type) = &build_cfg.build_type
+        && build_cfg.get_build_type().is_none()
     {
-        let normalized = build_type.to_lowercase();
-        if normalized != "executable" {
-            eprintln!(
-                "   {} build.type = '{}' is not fully supported yet, defaulting to executable build",
-                "⚠".yellow(),
-                build_type
-            );
-        }
+        eprintln!(
+            "   {} Unknown build.type = '{}', defaulting to executable build",
+            "⚠".yellow(),
+            raw_type
+        );
+        eprintln!(
+            "   {} Supported: executable, static-library, shared-library, header-only",
+            "→".dimmed()
+        );
+    }
+    let build_type = build_type_of(config);
+    let all_targets = resolve_targets(config)?;
+    let targets = select_targets(&all_targets, options.bin.as_deref())?;
+
+    if wasm && build_type.is_library() {
+        anyhow::bail!(
+            "build.type = '{}' is not supported with --wasm",
+            build_type.as_str()
+        );
+    }
+    if wasm && let Some(cross) = &cross {
+        anyhow::bail!(
+            "--wasm can't be combined with a profile target ('{}')",
+            cross.triple
+        );
     }
+    let triple = cross.as_ref().map(|c| c.triple.as_str());
+    let artifacts = ArtifactDir::new(&resolved.name, cross.as_ref());
+    let target_os = artifacts.os;
 
     // Dry-run or Verbose header with modern box styling
     let show_details = verbose || dry_run;
@@ -379,7 +424,7 @@ pub fn build_project(config: &CxConfig, options: &BuildOptions) -> Result<bool>
         } else {
             &config.package.edition
         };
-        let profile_str = if release { "release" } else { "debug" };
+        let profile_str = resolved.name.as_str();
         let compiler_str = if wasm {
             "em++"
         } else {
@@ -391,14 +436,14 @@ pub fn build_project(config: &CxConfig, options: &BuildOptions) -> Result<bool>
                 .unwrap_or("auto")
         };
 
-        println!();
+        status!();
         let mode = if dry_run {
             "DRY RUN".yellow().bold()
         } else {
             "BUILD".cyan().bold()
         };
         let icon = if dry_run { "🔍" } else { "🔧" };
-        println!("  {} {}", icon, mode);
+        status!("  {} {}", icon, mode);
 
         let mut table = ui::Table::new(&["Setting", "Value"]);
 
@@ -426,16 +471,29 @@ pub fn build_project(config: &CxConfig, options: &BuildOptions) -> Result<bool>
             "Compiler".dimmed().to_string(),
             compiler_str.cyan().to_string(),
         ]);
+        if build_type.is_library() {
+            table.add_row(vec![
+                "Type".dimmed().to_string(),
+                build_type.as_str().cyan().to_string(),
+            ]);
+        }
         if wasm {
             table.add_row(vec![
                 "Target".dimmed().to_string(),
                 "WASM (Emscripten)".magenta().to_string(),
             ]);
         }
-        if lto {
+        if let Some(triple) = triple {
+            table.add_row(vec![
+                "Target".dimmed().to_string(),
+                triple.magenta().to_string(),
+            ]);
+        }
+        if lto.is_enabled() {
+            let mode = if lto == Lto::Thin { "Thin" } else { "Enabled" };
             table.add_row(vec![
                 "LTO".dimmed().to_string(),
-                "Enabled".green().bold().to_string(),
+                mode.green().bold().to_string(),
             ]);
         }
         if let Some(san) = &sanitize {
@@ -444,9 +502,16 @@ pub fn build_project(config: &CxConfig, options: &BuildOptions) -> Result<bool>
                 san.yellow().bold().to_string(),
             ]);
         }
+        if !features.enabled.is_empty() {
+            let enabled: Vec<&str> = features.enabled.iter().map(String::as_str).collect();
+            table.add_row(vec![
+                "Features".dimmed().to_string(),
+                enabled.join(", ").cyan().to_string(),
+            ]);
+        }
 
         table.print();
-        println!();
+        status!();
     }
 
     // 1. Pre-build Script
@@ -454,29 +519,41 @@ pub fn build_project(config: &CxConfig, options: &BuildOptions) -> Result<bool>
         && let Some(pre) = &scripts.pre_build
     {
         if verbose {
-            println!("{} Running pre-build script: {}", "→".blue(), pre);
+            status!("{} Running pre-build script: {}", "→".blue(), pre);
         }
         if let Err(e) = run_script(pre, &current_dir) {
-            println!("{} Pre-build script failed: {}", "x".red(), e);
+            status!("{} Pre-build script failed: {}", "x".red(), e);
             return Ok(false);
         }
     }
 
     // 2. Setup Directories
-    let build_dir = artifact_profile_dir(release);
-    let obj_dir = build_dir.join("obj");
-    let bin_dir = artifact_bin_dir(release);
+    let build_dir = artifact_triple_dir(&resolved.name, triple);
+    // Header-only checks keep their objects out of obj/ so `cx test`
+    // never links them.
+    let obj_dir = if build_type == BuildType::HeaderOnly {
+        build_dir.join("header_check").join("obj")
+    } else {
+        build_dir.join("obj")
+    };
+    let bin_dir = artifacts.bin_dir.clone();
 
     fs::create_dir_all(&obj_dir)?;
     fs::create_dir_all(&bin_dir)?;
 
-    let output_bin = artifact_bin_path(config, release, wasm);
-
     if verbose {
-        println!("{}", "Paths:".bold());
-        println!("  Output: {}", output_bin.display().to_string().cyan());
-        println!("  Objects: {}", obj_dir.display().to_string().dimmed());
-        println!();
+        status!("{}", "Paths:".bold());
+        for target in &targets {
+            status!(
+                "  Output: {}",
+                target_output_path(&bin_dir, wasm, target, target_os)
+                    .display()
+                    .to_string()
+                    .cyan()
+            );
+        }
+        status!("  Objects: {}", obj_dir.display().to_string().dimmed());
+        status!();
     }
 
     // 3. Fetch Dependencies
@@ -500,15 +577,15 @@ pub fn build_project(config: &CxConfig, options: &BuildOptions) -> Result<bool>
         && let Some(includes) = &build_cfg.include
     {
         for inc in includes {
+            // Compilers run from inside .cx, so relative paths must be anchored
             let inc_path = Path::new(inc);
-            if inc_path.exists() {
-                include_paths.push(inc_path.to_path_buf());
+            let abs_path = if inc_path.is_absolute() {
+                inc_path.to_path_buf()
             } else {
-                // Try relative to current directory
-                let abs_path = current_dir.join(inc);
-                if abs_path.exists() {
-                    include_paths.push(abs_path);
-                }
+                current_dir.join(inc)
+            };
+            if abs_path.exists() {
+                include_paths.push(abs_path);
             }
         }
     }
@@ -520,7 +597,7 @@ pub fn build_project(config: &CxConfig, options: &BuildOptions) -> Result<bool>
         let framework_lower = framework.to_lowercase();
         match framework_mode(framework_lower.as_str()) {
             FrameworkMode::Integrated => {
-                println!(
+                status!(
                     "   {} Using framework: {}",
                     "🪓".yellow(),
                     "daxe".cyan().bold()
@@ -540,12 +617,12 @@ pub fn build_project(config: &CxConfig, options: &BuildOptions) -> Result<bool>
                 dep_modules.extend(modules);
             }
             FrameworkMode::DependencyAlias => {
-                println!(
+                status!(
                     "   {} Framework '{}' is a dependency-alias entry. Build continues without auto-integration.",
                     "⚠".yellow(),
                     framework
                 );
-                println!(
+                status!(
                     "   {} Add it explicitly with {}",
                     "→".dimmed(),
                     framework_alias_hint(&framework_lower).cyan()
@@ -553,13 +630,13 @@ pub fn build_project(config: &CxConfig, options: &BuildOptions) -> Result<bool>
             }
             FrameworkMode::ArduinoDedicated => {
                 // Arduino mode is handled separately via config.arduino
-                println!(
+                status!(
                     "   {} Framework 'arduino' - use [arduino] section instead",
                     "ℹ".blue()
                 );
             }
             FrameworkMode::Unknown => {
-                println!("   {} Unknown framework: {}", "⚠".yellow(), framework);
+                status!("   {} Unknown framework: {}", "⚠".yellow(), framework);
             }
         }
     }
@@ -569,7 +646,25 @@ pub fn build_project(config: &CxConfig, options: &BuildOptions) -> Result<bool>
     let mut module_files = Vec::new(); // New: Collect module interfaces
     let mut has_cpp = false;
 
-    if let Some(build_cfg) = &config.build
+    if build_type == BuildType::HeaderOnly {
+        // Every public header must compile on its own
+        let is_c = {
+            let edition = config.package.edition.to_lowercase();
+            edition.starts_with('c') && !edition.contains("++")
+        };
+        has_cpp = !is_c;
+        source_files = header_check_sources(
+            config,
+            &current_dir,
+            &build_dir.join("header_check"),
+            is_c,
+            !dry_run,
+        )?;
+        if source_files.is_empty() {
+            status!("{} No headers found to check.", "!".yellow());
+            return Ok(false);
+        }
+    } else if let Some(build_cfg) = &config.build
         && let Some(explicit_sources) = &build_cfg.sources
     {
         for src in explicit_sources {
@@ -587,7 +682,7 @@ pub fn build_project(config: &CxConfig, options: &BuildOptions) -> Result<bool>
                     }
                 }
             } else {
-                println!("{} Source file not found: {}", "!".yellow(), src);
+                status!("{} Source file not found: {}", "!".yellow(), src);
             }
         }
     } else {
@@ -609,19 +704,89 @@ pub fn build_project(config: &CxConfig, options: &BuildOptions) -> Result<bool>
         }
     }
 
-    // Sort modules to ensure deterministic order (though dependencies might require specific order)
-    // For MVP, if daxe.cppm depends on nothing but std, order doesn't matter much.
-    // If multiple modules exist, user might need to order sources manually or we implement deps scan.
+    // Sources of disabled features and other platforms stay out; active
+    // ones join even when they live outside src/ or the explicit source list
+    if build_type != BuildType::HeaderOnly {
+        let platform_sources = platform::conditional_sources(config, &platform)?;
+        let conditional = [features.conditional_sources(), &platform_sources];
+        source_files.retain(|src| !conditional.iter().any(|c| c.excludes(src)));
+        module_files.retain(|src| !conditional.iter().any(|c| c.excludes(src)));
+        for src in conditional.iter().flat_map(|c| c.sources()) {
+            let Some(ext) = src.extension().map(|e| e.to_string_lossy().to_string()) else {
+                continue;
+            };
+            let is_module = ["cppm", "ixx", "mpp"].contains(&ext.as_str());
+            if !is_module && !["cpp", "cc", "cxx", "c"].contains(&ext.as_str()) {
+                continue;
+            }
+            let list = if is_module {
+                &mut module_files
+            } else {
+                &mut source_files
+            };
+            if list.iter().any(|known| same_source(known, &src)) {
+                continue;
+            }
+            if ext != "c" {
+                has_cpp = true;
+            }
+            list.push(src);
+        }
+    }
+
+    // Each binary's entry point links into that binary only; drop the
+    // entries of targets that weren't selected and add declared ones that
+    // aren't part of the source list.
+    let all_entries = entry_sources(&all_targets);
+    if !all_entries.is_empty() {
+        let selected_entries = entry_sources(&targets);
+        source_files.retain(|src| {
+            !is_entry_source(&all_entries, src) || is_entry_source(&selected_entries, src)
+        });
+        for entry in selected_entries {
+            if source_files
+                .iter()
+                .any(|src| is_entry_source(std::slice::from_ref(&entry), src))
+            {
+                continue;
+            }
+            if !entry.exists() {
+                anyhow::bail!("Entry point not found: {}", entry.display());
+            }
+            if entry.extension().is_some_and(|ext| ext != "c") {
+                has_cpp = true;
+            }
+            source_files.push(entry);
+        }
+    }
+
+    // Deterministic scan order; build order comes from the module graph
     module_files.sort();
 
     if source_files.is_empty() && module_files.is_empty() {
-        println!("{} No source files found.", "!".yellow());
+        status!("{} No source files found.", "!".yellow());
         return Ok(false);
     }
 
     // Get toolchain with environment variables
     let toolchain = if wasm {
         None
+    } else if let Some(cross) = &cross {
+        let requested = config.build.as_ref().and_then(|b| b.compiler.as_deref());
+        let Some(tc) = cross.toolchain(requested) else {
+            anyhow::bail!(
+                "No compiler found for target '{}'. Install {}-g++ or clang++, or set compiler in the profile",
+                cross.triple,
+                cross.triple
+            );
+        };
+        status!(
+            "   {} Cross toolchain: {} → {}",
+            "🔧".cyan(),
+            tc.cxx_path.display(),
+            cross.triple.magenta()
+        );
+        Some(tc)
     } else {
         super::utils::get_toolchain(config, has_cpp).ok()
     };
@@ -639,41 +804,67 @@ pub fn build_project(config: &CxConfig, options: &BuildOptions) -> Result<bool>
         get_compiler(config, has_cpp)
     };
 
-    // Helper to check for CCache
-    let ccache_prefix = if !wasm {
-        if Command::new("ccache").arg("--version").output().is_ok() {
-            Some("ccache")
-        } else if Command::new("sccache").arg("--version").output().is_ok() {
-            Some("sccache")
-        } else {
-            None
-        }
+    // Native object cache; ccache/sccache are only used when it's disabled
+    let object_cache = if wasm {
+        None
     } else {
+        ObjectCache::open(config)
+    };
+    let ccache_prefix = if wasm || object_cache.is_some() {
         None // don't use ccache with emscripten unless configured carefully
+    } else if super::utils::find_in_path("ccache").is_some() {
+        Some("ccache")
+    } else if super::utils::find_in_path("sccache").is_some() {
+        Some("sccache")
+    } else {
+        None
     };
 
     if wasm {
         // Simple check if em++ exists
         if Command::new(&compiler).arg("--version").output().is_err() {
-            println!("{} Emscripten (em++) not found in PATH.", "x".red());
-            println!("   Please install Emscripten SDK.");
+            status!("{} Emscripten (em++) not found in PATH.", "x".red());
+            status!("   Please install Emscripten SDK.");
             return Ok(false);
         }
     }
 
     let is_clang_cl = compiler.contains("clang-cl");
     let is_msvc = (compiler.contains("cl.exe") || compiler == "cl") && !is_clang_cl;
+    // Compiler family, for the profile's opt-level / debug / frame pointer flags
+    let compiler_type = match &toolchain {
+        Some(tc) => tc.compiler_type.clone(),
+        None if is_msvc => CompilerType::MSVC,
+        None if is_clang_cl => CompilerType::ClangCL,
+        None if compiler.contains("clang") || compiler.contains("em++") => CompilerType::Clang,
+        None => CompilerType::GCC,
+    };
+    let codegen_flags = resolved.codegen_flags(&compiler_type);
     // let is_clang = compiler.contains("clang");
     // let is_gcc = compiler.contains("g++") || compiler.contains("gcc");
 
     // Verbose: Show toolchain info
     if verbose {
-        println!("{}", "Toolchain:".bold());
-        println!("  Compiler: {}", compiler.cyan());
+        status!("{}", "Toolchain:".bold());
+        status!("  Compiler: {}", compiler.cyan());
         if let Some(cc) = ccache_prefix {
-            println!("  Wrapper: {}", cc.yellow().bold());
+            status!("  Wrapper: {}", cc.yellow().bold());
         }
-        println!(
+        if let Some(cache) = &object_cache {
+            status!(
+                "  Object cache: {}",
+                cache.dir().display().to_string().dimmed()
+            );
+            if let Some(remote) = cache.remote() {
+                let mode = if remote.is_writable() {
+                    "read-write"
+                } else {
+                    "read"
+                };
+                status!("  Remote cache: {} ({})", remote.url().cyan(), mode);
+            }
+        }
+        status!(
             "  Type: {}",
             if is_msvc {
                 "MSVC".yellow()
@@ -682,14 +873,14 @@ pub fn build_project(config: &CxConfig, options: &BuildOptions) -> Result<bool>
             }
         );
         if let Some(ref tc) = toolchain {
-            println!("  Source: Detected via vswhere/explicit config");
+            status!("  Source: Detected via vswhere/explicit config");
             if !tc.env_vars.is_empty() {
-                println!("  Env vars: {} injected", tc.env_vars.len());
+                status!("  Env vars: {} injected", tc.env_vars.len());
             }
         } else {
-            println!("  Source: PATH fallback");
+            status!("  Source: PATH fallback");
         }
-        println!();
+        status!();
     }
 
     // Clone env_vars for use in parallel compilation
@@ -697,6 +888,8 @@ pub fn build_project(config: &CxConfig, options: &BuildOptions) -> Result<bool>
         .as_ref()
         .map(|tc| tc.env_vars.clone())
         .unwrap_or_default();
+    // Part of every object's command signature and cache key
+    let toolchain_fingerprint = compiler_identity(&compiler, &toolchain_env);
 
     // Prepare Common Flags (Includes)
     let mut common_flags = Vec::new();
@@ -708,10 +901,29 @@ pub fn build_project(config: &CxConfig, options: &BuildOptions) -> Result<bool>
         }
     }
 
+    // Cross target: --target (Clang) and --sysroot
+    let cross_link_flags = match &cross {
+        Some(cross) => {
+            common_flags.extend(cross.compile_flags(&compiler));
+            cross.link_flags(&compiler)
+        }
+        None => Vec::new(),
+    };
+    let cross_archiver = cross.as_ref().and_then(|c| c.archiver(&compiler));
+
+    // Preprocessor defines, translated per compiler family
+    let define_flags = config
+        .build
+        .as_ref()
+        .map(|b| b.define_flags(is_msvc))
+        .unwrap_or_default();
+
     // LTO Flags
-    if lto {
+    if lto.is_enabled() {
         if is_msvc {
             common_flags.push("/GL".to_string()); // Whole Program Optimization (Compile)
+        } else if lto == Lto::Thin && compiler_type == CompilerType::Clang {
+            common_flags.push("-flto=thin".to_string());
         } else {
             common_flags.push("-flto".to_string());
         }
@@ -729,6 +941,17 @@ pub fn build_project(config: &CxConfig, options: &BuildOptions) -> Result<bool>
         }
     }
 
+    // Position-independent code for libraries (shared objects require it,
+    // static archives may end up inside one)
+    if matches!(
+        build_type,
+        BuildType::StaticLibrary | BuildType::SharedLibrary
+    ) && !is_msvc
+        && target_os != "windows"
+    {
+        common_flags.push("-fPIC".to_string());
+    }
+
     // UTF-8 Encoding Flags
     let use_utf8 = config
         .build
@@ -745,6 +968,11 @@ pub fn build_project(config: &CxConfig, options: &BuildOptions) -> Result<bool>
         }
     }
 
+    // Fix-it hints in a form SARIF export can read
+    if sarif.is_some() && !is_msvc {
+        common_flags.push("-fdiagnostics-parseable-fixits".to_string());
+    }
+
     // C++20 Modules Support for GCC
     // Even with -std=c++20, GCC requires -fmodules-ts to enable 'import'
     if !is_msvc && has_cpp {
@@ -756,7 +984,7 @@ pub fn build_project(config: &CxConfig, options: &BuildOptions) -> Result<bool>
 
         if is_cpp20_or_newer {
             let is_gcc = if let Some(ref tc) = toolchain {
-                tc.compiler_type == crate::toolchain::CompilerType::GCC
+                tc.compiler_type == CompilerType::GCC
             } else {
                 compiler.contains("g++")
                     || (compiler.contains("gcc") && !compiler.contains("clang"))
@@ -772,16 +1000,16 @@ pub fn build_project(config: &CxConfig, options: &BuildOptions) -> Result<bool>
 
     // Verbose: Show include paths and flags
     if verbose && !include_paths.is_empty() {
-        println!("{}", "Include Paths:".bold());
+        status!("{}", "Include Paths:".bold());
         for path in &include_paths {
-            println!("  -I {}", path.display().to_string().dimmed());
+            status!("  -I {}", path.display().to_string().dimmed());
         }
-        println!();
+        status!();
     }
 
     // 5. Dry-run: Show compile commands that would be executed
     if dry_run {
-        println!("{}", "Compile:".bold());
+        status!("{}", "Compile:".bold());
         for src_path in &source_files {
             let obj_ext = if is_msvc { "obj" } else { "o" };
             let obj_path = object_file_path_for_source(&obj_dir, src_path, obj_ext);
@@ -804,30 +1032,53 @@ pub fn build_project(config: &CxConfig, options: &BuildOptions) -> Result<bool>
                     .unwrap_or(obj_path.as_os_str())
                     .to_string_lossy()
             );
-            println!("{}", cmd.dimmed());
+            status!("{}", cmd.dimmed());
         }
 
         // Show link command
-        println!("\n{}", "Link:".bold());
-        let short_compiler = Path::new(&compiler)
-            .file_name()
-            .unwrap_or(compiler.as_ref())
-            .to_string_lossy();
-        let obj_count = source_files.len();
-        let bin_name = output_bin
-            .file_name()
-            .unwrap_or(output_bin.as_os_str())
-            .to_string_lossy();
-        println!(
-            "  → {} [{} object(s)] → {}",
-            short_compiler,
-            obj_count,
-            bin_name.cyan()
-        );
+        if build_type == BuildType::HeaderOnly {
+            status!(
+                "\n{} {} header(s) checked, nothing to link",
+                "Link:".bold(),
+                source_files.len()
+            );
+        } else {
+            status!("\n{}", "Link:".bold());
+            let shared_count = source_files
+                .iter()
+                .filter(|src| !is_entry_source(&all_entries, src))
+                .count();
+            for target in &targets {
+                let link_tool = super::link::describe_link_tool(
+                    target.kind,
+                    &compiler,
+                    is_msvc,
+                    toolchain.as_ref(),
+                    cross_archiver.as_deref(),
+                    lto.is_enabled(),
+                );
+                let obj_count = if target.entry.is_some() {
+                    shared_count + 1
+                } else {
+                    shared_count
+                };
+                let output = target_output_path(&bin_dir, wasm, target, target_os);
+                let bin_name = output
+                    .file_name()
+                    .unwrap_or(output.as_os_str())
+                    .to_string_lossy();
+                status!(
+                    "  → {} [{} object(s)] → {}",
+                    link_tool,
+                    obj_count,
+                    bin_name.cyan()
+                );
+            }
+        }
 
-        println!();
-        println!("  {} {}", "✓".green(), "Dry run complete".bold());
-        println!("  {}", "No commands were executed.".dimmed());
+        status!();
+        status!("  {} {}", "✓".green(), "Dry run complete".bold());
+        status!("  {}", "No commands were executed.".dimmed());
         return Ok(true);
     }
 
@@ -838,10 +1089,17 @@ pub fn build_project(config: &CxConfig, options: &BuildOptions) -> Result<bool>
     {
         let pch_source = Path::new(pch_str);
         if !pch_source.exists() {
-            println!("{} PCH file not found: {}", "!".yellow(), pch_str);
+            status!("{} PCH file not found: {}", "!".yellow(), pch_str);
         } else {
             let pch_name = pch_source.file_name().unwrap_or_default().to_string_lossy();
 
+            // The PCH must be rebuilt with the flags of the TUs using it
+            let mut pch_signature_args = vec![compiler.clone(), pch_str.clone()];
+            pch_signature_args.extend(define_flags.iter().cloned());
+            pch_signature_args.extend(common_flags.iter().cloned());
+            pch_signature_args.push(config.package.edition.clone());
+            let pch_signature = CommandSignature::new(&toolchain_fingerprint, &pch_signature_args);
+
             if is_msvc {
                 let pch_out = obj_dir.join(format!("{}.pch", pch_name));
                 // Check mtime
@@ -853,16 +1111,18 @@ pub fn build_project(config: &CxConfig, options: &BuildOptions) -> Result<bool>
                         > pch_out
                             .metadata()
                             .and_then(|m| m.modified())
-                            .unwrap_or(std::time::SystemTime::UNIX_EPOCH);
+                            .unwrap_or(std::time::SystemTime::UNIX_EPOCH)
+                    || signature_mismatch(&pch_out, &pch_signature).is_some();
 
                 if need_pch {
-                    println!("{} Compiling PCH (MSVC)...", "⚙".cyan());
+                    status!("{} Compiling PCH (MSVC)...", "⚙".cyan());
                     let mut cmd = Command::new(&compiler);
                     cmd.args(["/nologo", "/c", "/EHsc"]);
                     cmd.arg("/Yc"); // Create PCH
                     cmd.arg(format!("/Fp{}", pch_out.display()));
                     cmd.arg(pch_source);
                     // Add includes/defines
+                    cmd.args(&define_flags);
                     cmd.args(&common_flags);
                     cmd.arg(format!(
                         "/Fo{}",
@@ -881,6 +1141,7 @@ pub fn build_project(config: &CxConfig, options: &BuildOptions) -> Result<bool>
                             String::from_utf8_lossy(&out.stderr)
                         ));
                     }
+                    write_signature(&pch_out, &pch_signature)?;
                 }
                 // Use PCH flags for other files
                 pch_args.push(format!("/Yu{}", pch_name));
@@ -905,10 +1166,11 @@ pub fn build_project(config: &CxConfig, options: &BuildOptions) -> Result<bool>
                         > pch_out
                             .metadata()
                             .and_then(|m| m.modified())
-                            .unwrap_or(std::time::SystemTime::UNIX_EPOCH);
+                            .unwrap_or(std::time::SystemTime::UNIX_EPOCH)
+                    || signature_mismatch(&pch_out, &pch_signature).is_some();
 
                 if need_pch {
-                    println!("{} Compiling PCH (GCC/Clang)...", "⚙".cyan());
+                    status!("{} Compiling PCH (GCC/Clang)...", "⚙".cyan());
 
                     // Handle ccache prefix for PCH? Usually safe.
                     let mut cmd = if let Some(wrapper) = ccache_prefix {
@@ -923,6 +1185,7 @@ pub fn build_project(config: &CxConfig, options: &BuildOptions) -> Result<bool>
                     cmd.arg(pch_source);
                     cmd.arg("-o");
                     cmd.arg(&pch_out);
+                    cmd.args(&define_flags);
                     cmd.args(&common_flags);
                     cmd.arg(format!("-std={}", config.package.edition));
 
@@ -933,6 +1196,7 @@ pub fn build_project(config: &CxConfig, options: &BuildOptions) -> Result<bool>
                             String::from_utf8_lossy(&out.stderr)
                         ));
                     }
+                    write_signature(&pch_out, &pch_signature)?;
                 }
                 // Use PCH
                 // For GCC to find "header.hpp.gch" when user asks for "header.hpp",
@@ -942,149 +1206,332 @@ pub fn build_project(config: &CxConfig, options: &BuildOptions) -> Result<bool>
         }
     }
 
-    // 5c-pre. Compile Dependency Modules (before project modules)
-    // Only compile if project source files actually use `import` statements
-    let mut dep_module_objs: Vec<PathBuf> = Vec::new();
+    // Job limit, memory throttle and last build's compile times
+    let scheduler = Scheduler::new(Some(config), options.jobs);
+    let compile_times = CompileTimes::load(&CompileTimes::path());
+    if verbose {
+        let mut jobs = format!("{} {} job(s)", "Jobs:".bold(), scheduler.jobs());
+        if let Some(min_free) = scheduler.min_free_memory() {
+            jobs.push_str(&format!(
+                ", waiting below {} free memory",
+                crate::cache::format_size(min_free)
+            ));
+        }
+        status!("{jobs}\n");
+    }
 
-    // Check if any source file uses imports
-    let project_uses_imports = source_files.iter().any(|src| {
-        if let Ok(content) = fs::read_to_string(src) {
-            content.lines().any(|line| {
-                let trimmed = line.trim();
-                trimmed.starts_with("import ") && trimmed.contains(';')
-            })
+    // Content-hash records for `[build] incremental = "hash"`
+    let state_path = BuildState::path();
+    let build_state =
+        (change_detection(config) == ChangeDetection::Hash).then(|| BuildState::load(&state_path));
+
+    // 5c. C++20 Modules: scan every unit, order the module units into waves
+    // (a unit's imports come from earlier waves) and compile each wave in
+    // parallel
+    let obj_ext = if is_msvc { "obj" } else { "o" };
+    let module_flavor = ModuleFlavor::detect(
+        &compiler,
+        toolchain.as_ref().map(|tc| &tc.compiler_type),
+        is_msvc,
+    );
+    // Compiled interfaces live next to the objects (GCC keeps gcm.cache)
+    let bmi_dir = obj_dir.join("modules");
+    let bmi_work_dir = bmi_dir
+        .strip_prefix(".cx")
+        .unwrap_or(&bmi_dir)
+        .to_path_buf();
+    // Compilers run from `.cx`, so project-relative paths need a `../`
+    let work_path = |path: &Path| {
+        if path.is_absolute() {
+            path.to_path_buf()
         } else {
-            false
+            Path::new("..").join(path)
         }
-    });
-
-    if !dep_modules.is_empty() && project_uses_imports {
-        println!("{} Compiling dependency modules...", "📦".cyan());
-
-        for (mod_path, dep_root) in &dep_modules {
-            let obj_ext = if is_msvc { "obj" } else { "o" };
-            let obj_path = object_file_path_for_source(&obj_dir, mod_path, obj_ext);
+    };
+    let std_flag = if is_msvc {
+        get_std_flag_msvc(&config.package.edition)
+    } else {
+        get_std_flag_gcc(&config.package.edition)
+    };
+    let mut build_flags: Vec<String> = config
+        .build
+        .as_ref()
+        .and_then(|b| b.get_flags().cloned())
+        .unwrap_or_default();
+    build_flags.extend(define_flags.iter().cloned());
+    let overrides = Overrides::new(config, is_msvc);
+
+    let mut scan_args = vec![std_flag.clone()];
+    scan_args.extend(build_flags.iter().cloned());
+    scan_args.extend(common_flags.iter().cloned());
+    let scanner = Scanner::new(module_flavor, &compiler, &scan_args, &toolchain_env);
+    let scan_source = |src: &Path| {
+        let ddi_path = object_file_path_for_source(&obj_dir, src, obj_ext).with_extension("ddi");
+        scanner.scan(src, &work_path(src), &ddi_path)
+    };
 
-            // Check if needs recompilation
-            let needs_compile = if !obj_path.exists() {
-                true
-            } else {
-                let src_mtime = fs::metadata(mod_path)
-                    .and_then(|m| m.modified())
-                    .unwrap_or(std::time::SystemTime::UNIX_EPOCH);
-                let obj_mtime = fs::metadata(&obj_path)
-                    .and_then(|m| m.modified())
-                    .unwrap_or(std::time::SystemTime::UNIX_EPOCH);
-                src_mtime > obj_mtime
-            };
+    let mut module_units: Vec<ModuleUnit> = scheduler.run(&module_files, |_, src| {
+        Ok(ModuleUnit {
+            source: src.clone(),
+            dep_root: None,
+            scan: scan_source(src),
+        })
+    })?;
+    // Sources declaring a module or partition (`module app:detail;` in a
+    // .cpp) are module units too; the rest only import
+    let source_scans: Vec<ModuleScan> =
+        scheduler.run(&source_files, |_, src| Ok(scan_source(src)))?;
+    let mut source_imports: HashMap<PathBuf, ModuleScan> = HashMap::new();
+    let mut plain_sources = Vec::new();
+    for (src, scan) in source_files.into_iter().zip(source_scans) {
+        if scan.provides.is_some() {
+            module_units.push(ModuleUnit {
+                source: src,
+                dep_root: None,
+                scan,
+            });
+        } else {
+            if scan.uses_modules() {
+                source_imports.insert(src.clone(), scan);
+            }
+            plain_sources.push(src);
+        }
+    }
+    let source_files = plain_sources;
+    let use_modules = !module_units.is_empty() || !source_imports.is_empty();
+
+    // Dependency modules are only built when something imports them
+    if use_modules && !dep_modules.is_empty() {
+        let dep_units: Vec<ModuleUnit> =
+            scheduler.run(&dep_modules, |_, (mod_path, dep_root)| {
+                Ok(ModuleUnit {
+                    source: if mod_path.is_absolute() {
+                        mod_path.clone()
+                    } else {
+                        current_dir.join(mod_path)
+                    },
+                    dep_root: Some(if dep_root.is_absolute() {
+                        dep_root.clone()
+                    } else {
+                        current_dir.join(dep_root)
+                    }),
+                    scan: scan_source(mod_path),
+                })
+            })?;
+        let roots: Vec<String> = module_units
+            .iter()
+            .map(|unit| &unit.scan)
+            .chain(source_imports.values())
+            .flat_map(|scan| scan.requires.iter().cloned())
+            .collect();
+        let needed = modules::reachable(&dep_units, roots.iter().map(String::as_str));
+        module_units.extend(
+            dep_units
+                .into_iter()
+                .enumerate()
+                .filter(|(idx, _)| needed.contains(idx))
+                .map(|(_, unit)| unit),
+        );
+    }
+    let module_waves = modules::waves(&module_units)?;
+
+    // Header units (`import <vector>;`), built before any module
+    let mut header_units: BTreeMap<HeaderUnit, (String, PathBuf)> = BTreeMap::new();
+    let imported_headers = modules::collect_header_units(
+        module_units
+            .iter()
+            .map(|unit| (unit.source.as_path(), &unit.scan))
+            .chain(
+                source_imports
+                    .iter()
+                    .map(|(src, scan)| (src.as_path(), scan)),
+            ),
+    );
+    for (unit, importer_dir) in imported_headers {
+        // Quoted names resolve against the importer, then the include paths
+        let header = if unit.system {
+            unit.name.clone()
+        } else {
+            std::iter::once(importer_dir)
+                .chain(include_paths.iter().cloned())
+                .chain(std::iter::once(PathBuf::from("include")))
+                .map(|dir| dir.join(&unit.name))
+                .find(|candidate| candidate.is_file())
+                .map(|found| work_path(&found).to_string_lossy().to_string())
+                .unwrap_or_else(|| unit.name.clone())
+        };
+        let record = module_flavor.header_unit_path(&bmi_dir, &unit, &header);
+        header_units.insert(unit, (header, record));
+    }
+    let header_unit_args = |scan: &ModuleScan| -> Vec<(HeaderUnit, PathBuf)> {
+        scan.header_units
+            .iter()
+            .filter_map(|unit| {
+                let (_, record) = header_units.get(unit)?;
+                Some((
+                    unit.clone(),
+                    record.strip_prefix(".cx").unwrap_or(record).to_path_buf(),
+                ))
+            })
+            .collect()
+    };
 
-            if needs_compile {
-                let mut args = Vec::new();
-                if let Some(wrapper) = ccache_prefix {
-                    args.push(wrapper.to_string());
-                }
-                args.push(compiler.clone());
+    if use_modules {
+        fs::create_dir_all(bmi_dir.join("header_units"))?;
+    }
 
-                // Module Flags
-                if !is_msvc {
-                    if (compiler.contains("gcc") || compiler.contains("g++"))
-                        && !compiler.contains("clang")
-                    {
-                        args.push("-fmodules-ts".to_string());
-                    }
-                } else {
-                    args.push("/interface".to_string());
-                    args.push("/TP".to_string());
-                }
+    // Every finished compile: JSON events, and diagnostics for `--sarif`
+    let compile_finished =
+        |unit: &Path, output: &str, success: bool, cached: bool, took: Duration| {
+            messages.compile_finished(unit, Path::new(".cx"), output, success, cached, took);
+            if let Some(found) = sarif
+                && !output.is_empty()
+                && let Ok(mut found) = found.lock()
+            {
+                found.extend(diagnostics::parse(output, Path::new(".cx")));
+            }
+        };
+    // Units that aren't recompiled replay what the compiler said last time
+    let replay_diagnostics = |record: &Path| {
+        if let Some(found) = sarif
+            && let Some(output) = recorded_diagnostics(record)
+            && !output.is_empty()
+            && let Ok(mut found) = found.lock()
+        {
+            found.extend(diagnostics::parse(&output, Path::new(".cx")));
+        }
+    };
+    let diagnostics_missing = |record: &Path| {
+        (sarif.is_some() && !diagnostics_path(record).exists())
+            .then(|| "no recorded diagnostics".to_string())
+    };
 
-                // Paths - use absolute paths for both source and output
-                // Dependency modules are external, so use their absolute paths directly
-                let abs_src = if mod_path.is_absolute() {
-                    mod_path.clone()
-                } else {
-                    current_dir.join(mod_path)
-                };
-                let abs_obj = if obj_path.is_absolute() {
-                    obj_path.clone()
-                } else {
-                    current_dir.join(&obj_path)
-                };
-                let abs_dep_root = if dep_root.is_absolute() {
-                    dep_root.clone()
-                } else {
-                    current_dir.join(dep_root)
-                };
+    let header_jobs: Vec<_> = header_units.iter().collect();
+    let rebuilt_headers = scheduler.run(
+        &header_jobs,
+        |_, (unit, (header, record))| -> Result<Option<HeaderUnit>> {
+            let work_record = record.strip_prefix(".cx").unwrap_or(record);
+            let mut args = vec![compiler.clone()];
+            args.extend(module_flavor.header_unit_flags(unit, header, work_record));
+            if is_msvc {
+                args.extend(["/nologo".to_string(), "/EHsc".to_string()]);
+            }
+            args.push(std_flag.clone());
+            args.extend(build_flags.iter().cloned());
+            args.extend(common_flags.iter().cloned());
 
-                if is_msvc {
-                    args.push("/nologo".to_string());
-                    args.push("/c".to_string());
-                    args.push("/EHsc".to_string());
-                    args.push(abs_src.to_string_lossy().to_string());
-                    args.push(format!("/Fo{}", abs_obj.to_string_lossy()));
-                    args.push(get_std_flag_msvc(&config.package.edition));
-                    // Include the dependency root and its include dir
-                    args.push(format!("/I{}", abs_dep_root.display()));
-                    args.push(format!("/I{}", abs_dep_root.join("include").display()));
-                } else {
-                    args.push("-fdiagnostics-color=always".to_string());
-                    args.push("-c".to_string());
-                    args.push(abs_src.to_string_lossy().to_string());
-                    args.push("-o".to_string());
-                    args.push(abs_obj.to_string_lossy().to_string());
-                    args.push(get_std_flag_gcc(&config.package.edition));
-                    // Include the dependency root and its include dir
-                    args.push(format!("-I{}", abs_dep_root.display()));
-                    args.push(format!("-I{}", abs_dep_root.join("include").display()));
-                }
+            let signature = CommandSignature::new(&toolchain_fingerprint, &args);
+            let header_changed = !unit.system
+                && check_dependencies(record, &Path::new(".cx").join(header)).unwrap_or(true);
+            if record.exists()
+                && !header_changed
+                && signature_mismatch(record, &signature).is_none()
+                && diagnostics_missing(record).is_none()
+            {
+                replay_diagnostics(record);
+                return Ok(None);
+            }
+            if verbose {
+                status!("   {} header unit {}", "↻".dimmed(), unit.name);
+            }
 
-                if release {
-                    if is_msvc {
-                        args.push("/O2".to_string());
-                    } else {
-                        args.push("-O3".to_string());
-                    }
-                } else if is_msvc {
-                    args.push("/Z7".to_string());
-                } else {
-                    args.push("-g".to_string());
-                }
-
-                // Run compiler from .cx directory so gcm.cache is created there
-                let cx_dir = current_dir.join(".cx");
-                fs::create_dir_all(&cx_dir)?;
-
-                let mut cmd = Command::new(&args[0]);
-                cmd.args(&args[1..]);
-                cmd.current_dir(&cx_dir);
-                if !toolchain_env.is_empty() {
-                    cmd.envs(&toolchain_env);
-                }
-
-                let output = cmd.output()?;
-                if !output.status.success() {
-                    let stderr = String::from_utf8_lossy(&output.stderr).to_string();
-                    println!(
-                        "{} Failed to compile dependency module {}:\n{}",
-                        "x".red(),
-                        mod_path.display(),
-                        stderr
-                    );
-                    return Ok(false);
-                }
+            let compile_start = Instant::now();
+            messages.emit("compile-started", json!({ "file": unit.name }));
+            let mut cmd = Command::new(&args[0]);
+            cmd.args(&args[1..]);
+            cmd.current_dir(".cx");
+            if !toolchain_env.is_empty() {
+                cmd.envs(&toolchain_env);
+            }
+            let output = cmd.output().context("Failed to execute compiler")?;
+            let compiler_output = format!(
+                "{}{}",
+                String::from_utf8_lossy(&output.stdout),
+                String::from_utf8_lossy(&output.stderr)
+            );
+            compile_finished(
+                Path::new(&unit.name),
+                &compiler_output,
+                output.status.success(),
+                false,
+                compile_start.elapsed(),
+            );
+            if !output.status.success() {
+                anyhow::bail!(
+                    "Failed to compile header unit {}:\n{}",
+                    unit.name,
+                    compiler_output
+                );
+            }
+            if module_flavor == ModuleFlavor::Gcc {
+                fs::write(record, "")?;
             }
+            write_signature(record, &signature)?;
+            write_diagnostics(record, &compiler_output)?;
+            Ok(Some((*unit).clone()))
+        },
+    );
+    let rebuilt_headers: HashSet<HeaderUnit> = match rebuilt_headers {
+        Ok(rebuilt) => rebuilt.into_iter().flatten().collect(),
+        Err(e) => {
+            status!("{} {}", "x".red(), e);
+            return Ok(false);
+        }
+    };
 
-            dep_module_objs.push(obj_path);
+    // Why a unit must be recompiled because of what it imports
+    let stale_import = |scan: &ModuleScan, rebuilt_modules: &HashSet<String>| {
+        if let Some(name) = scan
+            .requires
+            .iter()
+            .find(|name| rebuilt_modules.contains(*name))
+        {
+            Some(format!("imported module {} rebuilt", name))
+        } else {
+            scan.header_units
+                .iter()
+                .find(|unit| rebuilt_headers.contains(*unit))
+                .map(|unit| format!("header unit {} rebuilt", unit.name))
         }
-    }
+    };
 
-    // 5c. Module Compilation (Sequential)
+    let mut rebuilt_modules: HashSet<String> = HashSet::new();
     let mut module_results = Vec::new();
-    if !module_files.is_empty() {
-        println!("{} Compiling modules...", "📦".cyan());
-
-        for src_path in &module_files {
-            let obj_ext = if is_msvc { "obj" } else { "o" };
-            let obj_path = object_file_path_for_source(&obj_dir, src_path, obj_ext);
+    // --keep-going: everything that failed, and modules unusable because of it
+    let mut failures: Vec<CompileFailure> = Vec::new();
+    let mut failed_modules: HashSet<String> = HashSet::new();
+    let mut skipped = 0;
+    let imports_failed = |scan: &ModuleScan, failed_modules: &HashSet<String>| {
+        scan.requires
+            .iter()
+            .any(|name| failed_modules.contains(name))
+    };
+    if !module_units.is_empty() {
+        status!("{} Compiling modules...", "📦".cyan());
+    }
+    for wave in &module_waves {
+        let mut wave = wave.clone();
+        wave.retain(|&idx| {
+            let scan = &module_units[idx].scan;
+            if !imports_failed(scan, &failed_modules) {
+                return true;
+            }
+            skipped += 1;
+            if let Some(name) = &scan.provides {
+                failed_modules.insert(name.clone());
+            }
+            false
+        });
+        compile_times.slowest_first(&mut wave, |&idx| module_units[idx].source.as_path());
+        let compile_unit = |_: usize, &idx: &usize| -> Result<(PathBuf, serde_json::Value, bool)> {
+            let unit = &module_units[idx];
+            let obj_path = object_file_path_for_source(&obj_dir, &unit.source, obj_ext);
+            let bmi = unit
+                .scan
+                .provides
+                .as_deref()
+                .map(|name| module_flavor.bmi_path(&bmi_dir, name));
+            let work_bmi = bmi.as_deref().map(|b| b.strip_prefix(".cx").unwrap_or(b));
 
             // Construct Arguments
             let mut args = Vec::new();
@@ -1092,101 +1539,222 @@ pub fn build_project(config: &CxConfig, options: &BuildOptions) -> Result<bool>
                 args.push(wrapper.to_string());
             }
             args.push(compiler.clone());
+            args.extend(module_flavor.unit_flags(&unit.scan, work_bmi));
 
-            // Module Flags
-            if !is_msvc {
-                // GCC requires -fmodules-ts (Check for gcc/g++ but EXCLUDE clang)
-                if (compiler.contains("gcc") || compiler.contains("g++"))
-                    && !compiler.contains("clang")
-                {
-                    args.push("-fmodules-ts".to_string());
-                }
-            } else {
-                args.push("/interface".to_string()); // MSVC module interface
-                args.push("/TP".to_string()); // Compile as C++
-            }
-
-            // Standard Flags copy-paste (simplified for modules)
-            // Adjust paths for running inside .cx
-            let effective_src = Path::new("..").join(src_path);
+            let effective_src = work_path(&unit.source);
             let effective_obj = obj_path.strip_prefix(".cx").unwrap_or(&obj_path);
-
             if is_msvc {
                 args.push("/nologo".to_string());
                 args.push("/c".to_string());
                 args.push("/EHsc".to_string());
                 args.push(effective_src.to_string_lossy().to_string());
                 args.push(format!("/Fo{}", effective_obj.to_string_lossy()));
-                args.push(get_std_flag_msvc(&config.package.edition));
+                args.push(std_flag.clone());
+                args.push("/sourceDependencies".to_string());
+                args.push(format!("{}.json", effective_obj.display()));
             } else {
                 args.push("-fdiagnostics-color=always".to_string());
                 args.push("-c".to_string());
                 args.push(effective_src.to_string_lossy().to_string());
                 args.push("-o".to_string());
                 args.push(effective_obj.to_string_lossy().to_string());
-                args.push(get_std_flag_gcc(&config.package.edition));
+                args.push(std_flag.clone());
+                args.push("-MMD".to_string());
+                args.push("-MF".to_string());
+                args.push(
+                    effective_obj
+                        .with_extension("d")
+                        .to_string_lossy()
+                        .to_string(),
+                );
             }
 
-            if release {
-                if is_msvc {
-                    args.push("/O2".to_string());
-                } else {
-                    args.push("-O3".to_string());
-                }
-            } else if is_msvc {
-                args.push("/Z7".to_string());
+            args.extend(codegen_flags.iter().cloned());
+
+            if let Some(dep_root) = &unit.dep_root {
+                // Dependency modules see their own headers, not our flags
+                let include = if is_msvc { "/I" } else { "-I" };
+                args.push(format!("{}{}", include, dep_root.display()));
+                args.push(format!("{}{}", include, dep_root.join("include").display()));
             } else {
-                args.push("-g".to_string());
+                args.extend(build_flags.iter().cloned());
+                args.extend(overrides.args_for(&unit.source));
+                args.extend(common_flags.iter().cloned());
             }
+            args.extend(module_flavor.consumer_flags(&bmi_work_dir, &header_unit_args(&unit.scan)));
 
-            // Defines / Includes
-            if let Some(build_cfg) = &config.build
-                && let Some(flags) = build_cfg.get_flags()
-            {
-                for flag in flags {
-                    args.push(flag.clone());
+            let signature = CommandSignature::new(
+                &toolchain_fingerprint,
+                &args[usize::from(ccache_prefix.is_some())..],
+            );
+            let dirty_reason = if !obj_path.exists() {
+                Some("no object file".to_string())
+            } else if bmi.as_ref().is_some_and(|bmi| !bmi.exists()) {
+                Some("no module interface".to_string())
+            } else if let Some(reason) = stale_import(&unit.scan, &rebuilt_modules) {
+                Some(reason)
+            } else if inputs_changed(&obj_path, &unit.source, build_state.as_ref()) {
+                Some("sources changed".to_string())
+            } else {
+                signature_mismatch(&obj_path, &signature).or_else(|| diagnostics_missing(&obj_path))
+            };
+
+            if let Some(reason) = &dirty_reason {
+                if verbose {
+                    status!(
+                        "   {} {}: {}",
+                        "↻".dimmed(),
+                        unit.source.display(),
+                        reason.dimmed()
+                    );
                 }
-            }
-            args.extend(common_flags.iter().cloned());
-            // No PCH for modules usually
 
-            // Run Compiler inside .cx
-            let mut cmd = Command::new(&args[0]);
-            cmd.args(&args[1..]);
-            cmd.current_dir(".cx"); // Force gcm.cache to be in .cx
-            if !toolchain_env.is_empty() {
-                cmd.envs(&toolchain_env);
-            }
+                // Run Compiler inside .cx (GCC writes gcm.cache there)
+                let compile_start = Instant::now();
+                messages.emit(
+                    "compile-started",
+                    json!({ "file": unit.source.display().to_string() }),
+                );
+                let mut cmd = Command::new(&args[0]);
+                cmd.args(&args[1..]);
+                cmd.current_dir(".cx");
+                if !toolchain_env.is_empty() {
+                    cmd.envs(&toolchain_env);
+                }
 
-            let output = cmd.output()?;
-            if !output.status.success() {
-                let stderr = String::from_utf8_lossy(&output.stderr).to_string();
-                println!(
-                    "{} Failed to compile module {}:\n{}",
-                    "x".red(),
-                    src_path.display(),
-                    stderr
+                let output = cmd.output().context("Failed to execute compiler")?;
+                let compiler_output = format!(
+                    "{}{}",
+                    String::from_utf8_lossy(&output.stdout),
+                    String::from_utf8_lossy(&output.stderr)
+                );
+                compile_finished(
+                    &unit.source,
+                    &compiler_output,
+                    output.status.success(),
+                    false,
+                    compile_start.elapsed(),
                 );
-                return Ok(false);
+                if !output.status.success() {
+                    status!(
+                        "{} Failed to compile module {}:\n{}",
+                        "x".red(),
+                        unit.source.display(),
+                        compiler_output
+                    );
+                    return Err(CompileFailure::new(&unit.source, compiler_output).into());
+                }
+                write_signature(&obj_path, &signature)?;
+                write_diagnostics(&obj_path, &compiler_output)?;
+                compile_times.record(&unit.source, compile_start.elapsed());
+            } else {
+                replay_diagnostics(&obj_path);
+            }
+
+            if let Some(state) = &build_state
+                && let Ok(Some(inputs)) = dependency_inputs(&obj_path, &unit.source)
+            {
+                state.record(&obj_path, &inputs);
             }
 
-            // Collect result
             let cx_abs = current_dir.join(".cx");
-            let abs_src = if src_path.is_absolute() {
-                src_path.to_path_buf()
+            let abs_src = if unit.source.is_absolute() {
+                unit.source.clone()
             } else {
-                current_dir.join(src_path)
+                current_dir.join(&unit.source)
             };
-
             let entry = json!({
                 "directory": cx_abs.to_string_lossy().to_string(), // Update compile_commands.json dir
                 "command": args.join(" "),
                 "file": abs_src.to_string_lossy()
             });
-            module_results.push((obj_path, entry));
+            Ok((obj_path, entry, dirty_reason.is_some()))
+        };
+
+        let compiled: Vec<Result<_>> = if keep_going {
+            scheduler.run_all(&wave, compile_unit)
+        } else {
+            match scheduler.run(&wave, compile_unit) {
+                Ok(compiled) => compiled.into_iter().map(Ok).collect(),
+                Err(e) => {
+                    // Compile failures were already printed with their output
+                    if !e.is::<CompileFailure>() {
+                        status!("{} {}", "x".red(), e);
+                    }
+                    return Ok(false);
+                }
+            }
+        };
+        for (&idx, result) in wave.iter().zip(compiled) {
+            let provides = &module_units[idx].scan.provides;
+            match result {
+                Ok((obj_path, entry, rebuilt)) => {
+                    if rebuilt && let Some(name) = provides {
+                        rebuilt_modules.insert(name.clone());
+                    }
+                    module_results.push((obj_path, entry));
+                }
+                Err(e) => {
+                    failures.push(e.downcast::<CompileFailure>()?);
+                    if let Some(name) = provides {
+                        failed_modules.insert(name.clone());
+                    }
+                }
+            }
         }
     }
 
+    // 5d. Unity builds: batch plain sources into generated files that include
+    // them. Entry points, module consumers and overridden sources stay alone
+    // (`main` too, since `cx test` links every other object)
+    let unity_batches = match unity::batch_size(config, options.unity) {
+        Some(batch_size) if build_type != BuildType::HeaderOnly => {
+            let batchable: Vec<PathBuf> = source_files
+                .iter()
+                .filter(|src| {
+                    !is_entry_source(&all_entries, src)
+                        && src.file_stem().is_none_or(|stem| stem != "main")
+                        && !source_imports.contains_key(*src)
+                        && !overrides.excludes_from_unity(src)
+                })
+                .cloned()
+                .collect();
+            let unity_dir = Path::new(".cx")
+                .join("build")
+                .join("unity")
+                .join(build_dir.strip_prefix(".cx").unwrap_or(&build_dir));
+            unity::write_batches(
+                &unity_dir,
+                &current_dir,
+                unity::plan(&batchable, batch_size),
+            )?
+        }
+        _ => Vec::new(),
+    };
+    unity::prune_objects(&obj_dir, &unity_batches, obj_ext);
+    let batched: HashSet<PathBuf> = unity_batches
+        .iter()
+        .flat_map(|batch| batch.members.iter().cloned())
+        .collect();
+    let mut source_files: Vec<PathBuf> = source_files
+        .into_iter()
+        .filter(|src| !batched.contains(src))
+        .collect();
+    source_files.extend(unity_batches.iter().map(|batch| batch.source.clone()));
+    let unity_members: HashMap<PathBuf, Vec<PathBuf>> = unity_batches
+        .into_iter()
+        .map(|batch| (batch.source, batch.members))
+        .collect();
+    if !unity_members.is_empty() {
+        fs::create_dir_all(obj_dir.join("unity"))?;
+        status!(
+            "   {} Unity build: {} source(s) in {} batch(es)",
+            "⚡".cyan(),
+            batched.len(),
+            unity_members.len()
+        );
+    }
+
     // Profiling Setup
     let trace_events = if enable_profile {
         Some(Arc::new(Mutex::new(Vec::new())))
@@ -1203,219 +1771,407 @@ pub fn build_project(config: &CxConfig, options: &BuildOptions) -> Result<bool>
         .progress_chars("█▓░");
 
     let pb = ProgressBar::new(source_files.len() as u64);
+    if messages.is_json() {
+        pb.set_draw_target(ProgressDrawTarget::hidden());
+    }
     pb.set_style(spinner_style);
     pb.set_message("Compiling...");
 
-    let use_modules = !module_results.is_empty();
-
-    let results: Vec<(PathBuf, serde_json::Value)> = source_files
-        .par_iter()
-        .map(|src_path| -> Result<(PathBuf, serde_json::Value)> {
-            let stem = src_path
-                .file_stem()
-                .unwrap_or(src_path.as_os_str())
-                .to_string_lossy();
-            let obj_ext = if is_msvc { "obj" } else { "o" };
-            let obj_path = object_file_path_for_source(&obj_dir, src_path, obj_ext);
+    // Preprocessing for the cache key can't see modules or precompiled headers,
+    // and a cache hit has no compiler output for `--sarif`
+    let cache_objects =
+        object_cache.is_some() && !use_modules && pch_args.is_empty() && sarif.is_none();
+
+    // Longest compiles start first; results go back to source order below
+    let mut schedule: Vec<usize> = (0..source_files.len()).collect();
+    compile_times.slowest_first(&mut schedule, |&idx| source_files[idx].as_path());
+    if !failed_modules.is_empty() {
+        schedule.retain(|idx| {
+            let blocked = source_imports
+                .get(&source_files[*idx])
+                .is_some_and(|scan| imports_failed(scan, &failed_modules));
+            skipped += usize::from(blocked);
+            !blocked
+        });
+        pb.set_length(schedule.len() as u64);
+    }
 
-            // Construct Arguments
-            let mut args = Vec::new();
+    let compile_source = |worker: usize,
+                          &idx: &usize|
+     -> Result<(usize, PathBuf, Vec<serde_json::Value>)> {
+        let src_path = &source_files[idx];
+        let stem = src_path
+            .file_stem()
+            .unwrap_or(src_path.as_os_str())
+            .to_string_lossy();
+        let unity_batch = unity_members.get(src_path);
+        let obj_path = if unity_batch.is_some() {
+            object_file_path_for_source(&obj_dir.join("unity"), src_path, obj_ext)
+        } else {
+            object_file_path_for_source(&obj_dir, src_path, obj_ext)
+        };
+        let imports = source_imports.get(src_path);
 
-            // CCache injection
-            if let Some(wrapper) = ccache_prefix {
-                args.push(wrapper.to_string());
-            }
-            args.push(compiler.clone());
+        // Construct Arguments
+        let mut args = Vec::new();
 
-            if use_modules && !is_msvc && compiler.contains("clang") {
-                args.push(format!("-fprebuilt-module-path={}", obj_dir.display()));
-            }
+        // CCache injection
+        if let Some(wrapper) = ccache_prefix {
+            args.push(wrapper.to_string());
+        }
+        args.push(compiler.clone());
 
-            // Adjust paths for running inside .cx
-            let effective_src = Path::new("..").join(src_path);
-            let effective_obj = obj_path.strip_prefix(".cx").unwrap_or(&obj_path);
+        if let Some(scan) = imports {
+            args.extend(module_flavor.consumer_flags(&bmi_work_dir, &header_unit_args(scan)));
+        }
 
-            if is_msvc {
-                // MSVC Flags
-                args.push("/nologo".to_string()); // Suppress copyright
-                args.push("/c".to_string());
-                args.push("/EHsc".to_string()); // Standard C++ exceptions
-                args.push(effective_src.to_string_lossy().to_string());
-                args.push(format!("/Fo{}", effective_obj.to_string_lossy()));
-                args.push(get_std_flag_msvc(&config.package.edition));
+        // Adjust paths for running inside .cx
+        let effective_src = Path::new("..").join(src_path);
+        let effective_obj = obj_path.strip_prefix(".cx").unwrap_or(&obj_path);
 
-                // Recursive Header Tracking for MSVC
-                // /sourceDependencies <file> available in VS 2019+
-                args.push("/sourceDependencies".to_string());
-                args.push(format!("{}.json", effective_obj.display()));
-            } else {
-                // GCC/Clang Flags
-                args.push("-fdiagnostics-color=always".to_string());
-                args.push("-c".to_string());
-                args.push(effective_src.to_string_lossy().to_string());
-                args.push("-o".to_string());
-                args.push(effective_obj.to_string_lossy().to_string());
-                args.push(get_std_flag_gcc(&config.package.edition));
+        if is_msvc {
+            // MSVC Flags
+            args.push("/nologo".to_string()); // Suppress copyright
+            args.push("/c".to_string());
+            args.push("/EHsc".to_string()); // Standard C++ exceptions
+            args.push(effective_src.to_string_lossy().to_string());
+            args.push(format!("/Fo{}", effective_obj.to_string_lossy()));
+            args.push(get_std_flag_msvc(&config.package.edition));
+
+            // Recursive Header Tracking for MSVC
+            // /sourceDependencies <file> available in VS 2019+
+            args.push("/sourceDependencies".to_string());
+            args.push(format!("{}.json", effective_obj.display()));
+        } else {
+            // GCC/Clang Flags
+            args.push("-fdiagnostics-color=always".to_string());
+            args.push("-c".to_string());
+            args.push(effective_src.to_string_lossy().to_string());
+            args.push("-o".to_string());
+            args.push(effective_obj.to_string_lossy().to_string());
+            args.push(get_std_flag_gcc(&config.package.edition));
+
+            // Generate Dependency File
+            args.push("-MMD".to_string());
+            args.push("-MF".to_string());
+            let d_path = PathBuf::from(effective_obj).with_extension("d");
+            args.push(d_path.to_string_lossy().to_string());
+        }
 
-                // Generate Dependency File
-                args.push("-MMD".to_string());
-                args.push("-MF".to_string());
-                let d_path = PathBuf::from(effective_obj).with_extension("d");
-                args.push(d_path.to_string_lossy().to_string());
-            }
+        args.extend(codegen_flags.iter().cloned());
+        if !release {
+            args.push(if is_msvc { "/W4" } else { "-Wall" }.to_string());
+        }
 
-            if release {
-                if is_msvc {
-                    args.push("/O2".to_string());
+        if let Some(build_cfg) = &config.build
+            && let Some(flags) = build_cfg.get_flags()
+        {
+            for flag in flags {
+                // Translate MSVC-style flags for GCC/Clang
+                let translated = if !is_msvc && flag.starts_with("/D") {
+                    format!("-D{}", &flag[2..])
+                } else if !is_msvc && flag.starts_with("/I") {
+                    format!("-I{}", &flag[2..])
+                } else if is_msvc && flag.starts_with("-D") {
+                    format!("/D{}", &flag[2..])
+                } else if is_msvc && flag.starts_with("-I") {
+                    format!("/I{}", &flag[2..])
                 } else {
-                    args.push("-O3".to_string());
-                }
-            } else if is_msvc {
-                args.push("/Z7".to_string()); // Debug info
-                args.push("/W4".to_string());
-            } else {
-                args.push("-g".to_string());
-                args.push("-Wall".to_string());
+                    flag.clone()
+                };
+                args.push(translated);
             }
+        }
+        args.extend(define_flags.iter().cloned());
+        let file_flags = if unity_batch.is_some() {
+            Vec::new()
+        } else {
+            overrides.args_for(src_path)
+        };
+        if verbose {
+            let mut effective = build_flags.clone();
+            effective.extend(file_flags.iter().cloned());
+            pb.suspend(|| {
+                status!(
+                    "   {} {}: {}",
+                    "⚑".dimmed(),
+                    src_path.display(),
+                    effective.join(" ").dimmed()
+                )
+            });
+        }
+        args.extend(file_flags);
+        args.extend(common_flags.iter().cloned());
+        args.extend(pch_args.iter().cloned());
+
+        // Prepare JSON entry for Intellisense
+        let cx_abs = current_dir.join(".cx");
+        let abs_src = if src_path.is_absolute() {
+            src_path.to_path_buf()
+        } else {
+            current_dir.join(src_path)
+        };
 
-            if let Some(build_cfg) = &config.build
-                && let Some(flags) = build_cfg.get_flags()
-            {
-                for flag in flags {
-                    // Translate MSVC-style flags for GCC/Clang
-                    let translated = if !is_msvc && flag.starts_with("/D") {
-                        format!("-D{}", &flag[2..])
-                    } else if !is_msvc && flag.starts_with("/I") {
-                        format!("-I{}", &flag[2..])
-                    } else if is_msvc && flag.starts_with("-D") {
-                        format!("/D{}", &flag[2..])
-                    } else if is_msvc && flag.starts_with("-I") {
-                        format!("/I{}", &flag[2..])
-                    } else {
-                        flag.clone()
-                    };
-                    args.push(translated);
-                }
+        let entry = json!({
+            "directory": cx_abs.to_string_lossy().to_string(),
+            "command": args.join(" "),
+            "file": abs_src.to_string_lossy()
+        });
+        // A batch is listed as its members, each with its own command
+        let entries = match unity_batch {
+            Some(members) => {
+                let batch_src = effective_src.to_string_lossy().to_string();
+                members
+                    .iter()
+                    .map(|member| {
+                        let member_src = work_path(member).to_string_lossy().to_string();
+                        let command: Vec<&str> = args
+                            .iter()
+                            .map(|arg| {
+                                if *arg == batch_src {
+                                    member_src.as_str()
+                                } else {
+                                    arg.as_str()
+                                }
+                            })
+                            .collect();
+                        json!({
+                            "directory": cx_abs.to_string_lossy().to_string(),
+                            "command": command.join(" "),
+                            "file": current_dir.join(member).to_string_lossy()
+                        })
+                    })
+                    .collect()
             }
-            args.extend(common_flags.iter().cloned());
-            args.extend(pch_args.iter().cloned());
-
-            // Prepare JSON entry for Intellisense
-            let cx_abs = current_dir.join(".cx");
-            let abs_src = if src_path.is_absolute() {
-                src_path.to_path_buf()
-            } else {
-                current_dir.join(src_path)
-            };
+            None => vec![entry],
+        };
 
-            let entry = json!({
-                "directory": cx_abs.to_string_lossy().to_string(),
-                "command": args.join(" "),
-                "file": abs_src.to_string_lossy()
+        // Incremental Check: sources/headers by mtime, flags by signature
+        let signature = CommandSignature::new(
+            &toolchain_fingerprint,
+            &args[usize::from(ccache_prefix.is_some())..],
+        );
+        let dirty_reason = if !obj_path.exists() {
+            Some("no object file".to_string())
+        } else if let Some(reason) = imports.and_then(|scan| stale_import(scan, &rebuilt_modules)) {
+            Some(reason)
+        } else if inputs_changed(&obj_path, src_path, build_state.as_ref()) {
+            Some("sources changed".to_string())
+        } else {
+            signature_mismatch(&obj_path, &signature).or_else(|| diagnostics_missing(&obj_path))
+        };
+        let needs_compile = dirty_reason.is_some();
+        if verbose && let Some(reason) = &dirty_reason {
+            pb.suspend(|| {
+                status!(
+                    "   {} {}: {}",
+                    "↻".dimmed(),
+                    src_path.display(),
+                    reason.dimmed()
+                )
             });
+        }
 
-            // Incremental Check
-            let needs_compile = if !obj_path.exists() {
-                true
-            } else {
-                check_dependencies(&obj_path, src_path).unwrap_or(true)
+        // Profiling Start
+        let compile_start = Instant::now();
+
+        if needs_compile {
+            messages.emit(
+                "compile-started",
+                json!({ "file": src_path.display().to_string() }),
+            );
+        }
+
+        // Object cache: a hit replaces the compile
+        let mut cache_key = None;
+        let mut cache_hit = false;
+        if needs_compile
+            && cache_objects
+            && let Some(cache) = &object_cache
+        {
+            let volatile = [
+                effective_src.to_string_lossy().to_string(),
+                effective_obj.to_string_lossy().to_string(),
+                effective_obj
+                    .with_extension("d")
+                    .to_string_lossy()
+                    .to_string(),
+            ];
+            let key_ctx = KeyContext {
+                args: &args[usize::from(ccache_prefix.is_some())..],
+                volatile: &volatile,
+                toolchain: &toolchain_fingerprint,
+                is_msvc,
+                env: &toolchain_env,
+                work_dir: Path::new(".cx"),
+                project_dir: &current_dir,
             };
+            let dep_json = PathBuf::from(format!("{}.json", obj_path.display()));
+            cache_key = compute_key(&key_ctx, Some(&dep_json));
+            if let Some(key) = &cache_key
+                && cache.fetch(key, &obj_path)
+            {
+                write_signature(&obj_path, &signature)?;
+                // The compiler output of a cached object is unknown
+                fs::remove_file(diagnostics_path(&obj_path)).ok();
+                cache_hit = true;
+                compile_finished(src_path, "", true, true, compile_start.elapsed());
+            }
+        }
 
-            // Profiling Start
-            let compile_start = Instant::now();
-            if needs_compile {
-                pb.set_message(format!("Compiling {}", stem));
-                let mut cmd = Command::new(&args[0]);
-                cmd.args(&args[1..]);
-                cmd.current_dir(".cx");
+        if needs_compile && !cache_hit {
+            pb.set_message(format!("Compiling {}", stem));
+            let mut cmd = Command::new(&args[0]);
+            cmd.args(&args[1..]);
+            cmd.current_dir(".cx");
 
-                // Apply toolchain environment variables (INCLUDE, LIB, etc.)
-                if !toolchain_env.is_empty() {
-                    cmd.envs(&toolchain_env);
-                }
+            // Apply toolchain environment variables (INCLUDE, LIB, etc.)
+            if !toolchain_env.is_empty() {
+                cmd.envs(&toolchain_env);
+            }
 
-                let output = cmd.output().context("Failed to execute compiler")?;
+            let output = cmd.output().context("Failed to execute compiler")?;
+            let stderr = String::from_utf8_lossy(&output.stderr).to_string();
+            let stdout = String::from_utf8_lossy(&output.stdout).to_string();
+            compile_finished(
+                src_path,
+                &format!("{stdout}{stderr}"),
+                output.status.success(),
+                false,
+                compile_start.elapsed(),
+            );
 
-                if !output.status.success() {
-                    let stderr = String::from_utf8_lossy(&output.stderr).to_string();
-                    let stdout = String::from_utf8_lossy(&output.stdout).to_string();
+            if !output.status.success() {
+                let error_msg = format!(
+                    "Error compiling {}:\n{}{}",
+                    src_path.display(),
+                    stdout,
+                    stderr
+                );
+                pb.println(format!("{} {}", "x".red(), error_msg));
+
+                // Educational Feedback
+                if let Some(suggestion) = FeedbackAnalyzer::analyze(&stderr) {
+                    pb.println(format!(
+                        "\n{} {}\n",
+                        "💡 Suggestion:".bold().yellow(),
+                        suggestion
+                    ));
+                }
 
-                    let error_msg = format!(
-                        "Error compiling {}:\n{}{}",
+                return Err(CompileFailure::new(src_path, format!("{stdout}{stderr}")).into());
+            } else {
+                write_signature(&obj_path, &signature)?;
+                write_diagnostics(&obj_path, &format!("{stdout}{stderr}"))?;
+                compile_times.record(src_path, compile_start.elapsed());
+                if let Some(cache) = &object_cache
+                    && let Some(key) = &cache_key
+                {
+                    cache.store(key, &obj_path);
+                }
+
+                // Print warnings if any (buffered)
+                if !stderr.is_empty() {
+                    pb.println(format!(
+                        "{} Warning in {}:\n{}",
+                        "!".yellow(),
                         src_path.display(),
-                        stdout,
                         stderr
-                    );
-                    pb.println(format!("{} {}", "x".red(), error_msg));
-
-                    // Educational Feedback
-                    if let Some(suggestion) = super::feedback::FeedbackAnalyzer::analyze(&stderr) {
-                        pb.println(format!(
-                            "\n{} {}\n",
-                            "💡 Suggestion:".bold().yellow(),
-                            suggestion
-                        ));
-                    }
-
-                    return Err(anyhow::anyhow!("Compilation failed"));
-                } else {
-                    // Print warnings if any (buffered)
-                    let stderr = String::from_utf8_lossy(&output.stderr);
-                    let stdout = String::from_utf8_lossy(&output.stdout);
-                    if !stderr.is_empty() {
-                        pb.println(format!(
-                            "{} Warning in {}:\n{}",
-                            "!".yellow(),
-                            src_path.display(),
-                            stderr
-                        ));
-                    }
-                    // Some compilers print warnings to stdout too
-                    if !stdout.is_empty() {
-                        pb.println(format!(
-                            "{} Output in {}:\n{}",
-                            "!".cyan(),
-                            src_path.display(),
-                            stdout
-                        ));
-                    }
+                    ));
+                }
+                // Some compilers print warnings to stdout too
+                if !stdout.is_empty() {
+                    pb.println(format!(
+                        "{} Output in {}:\n{}",
+                        "!".cyan(),
+                        src_path.display(),
+                        stdout
+                    ));
                 }
             }
+        }
 
-            // Profiling End
-            if let Some(events) = &trace_events {
-                let duration = compile_start.elapsed();
-                let ts = compile_start
-                    .checked_duration_since(build_start_time)
-                    .unwrap_or_default()
-                    .as_micros();
-                let dur = duration.as_micros();
-                // Capture thread ID (best effort)
-                let tid = rayon::current_thread_index().unwrap_or(0);
-
-                if let Ok(mut lock) = events.lock() {
-                    lock.push(TraceEvent {
-                        name: src_path
-                            .file_name()
-                            .unwrap_or_default()
-                            .to_string_lossy()
-                            .to_string(),
-                        cat: "compilation".to_string(),
-                        ph: "X".to_string(),
-                        ts,
-                        dur,
-                        pid: 1,
-                        tid,
-                    });
-                }
+        if !needs_compile {
+            replay_diagnostics(&obj_path);
+        }
+        if let Some(state) = &build_state
+            && let Ok(Some(inputs)) = dependency_inputs(&obj_path, src_path)
+        {
+            state.record(&obj_path, &inputs);
+        }
+
+        // Profiling End
+        if let Some(events) = &trace_events {
+            let duration = compile_start.elapsed();
+            let ts = compile_start
+                .checked_duration_since(build_start_time)
+                .unwrap_or_default()
+                .as_micros();
+            let dur = duration.as_micros();
+            let tid = worker;
+
+            if let Ok(mut lock) = events.lock() {
+                lock.push(TraceEvent {
+                    name: src_path
+                        .file_name()
+                        .unwrap_or_default()
+                        .to_string_lossy()
+                        .to_string(),
+                    cat: "compilation".to_string(),
+                    ph: "X".to_string(),
+                    ts,
+                    dur,
+                    pid: 1,
+                    tid,
+                });
             }
+        }
 
-            pb.inc(1);
-            Ok((obj_path, entry))
-        })
-        .collect::<Result<Vec<_>>>()?; // Collects errors if any
+        pb.inc(1);
+        Ok((idx, obj_path, entries))
+    };
+    let mut results = if keep_going {
+        let mut compiled = Vec::new();
+        for result in scheduler.run_all(&schedule, compile_source) {
+            match result {
+                Ok(result) => compiled.push(result),
+                Err(e) => failures.push(e.downcast::<CompileFailure>()?),
+            }
+        }
+        compiled
+    } else {
+        scheduler.run(&schedule, compile_source)?
+    };
+    results.sort_by_key(|(idx, _, _)| *idx);
 
     pb.finish_with_message("Compilation complete");
+    if let Err(e) = compile_times.save(&CompileTimes::path()) {
+        eprintln!("   {} Compile times: {}", "⚠".yellow(), e);
+    }
+
+    if let Some(cache) = &object_cache {
+        if cache.hits() > 0 {
+            status!(
+                "   {} Object cache: {} hit(s), {} miss(es)",
+                "⚡".green(),
+                cache.hits(),
+                cache.misses()
+            );
+        }
+        if let Some(remote) = cache.remote()
+            && (remote.hits() > 0 || remote.uploads() > 0)
+        {
+            status!(
+                "   {} Remote cache: {} download(s), {} upload(s)",
+                "🌐".blue(),
+                remote.hits(),
+                remote.uploads()
+            );
+        }
+        if let Err(e) = cache.finish() {
+            eprintln!("   {} Object cache: {}", "⚠".yellow(), e);
+        }
+    }
 
     // Profiling Dump
     if let Some(events) = trace_events
@@ -1427,16 +2183,29 @@ pub fn build_project(config: &CxConfig, options: &BuildOptions) -> Result<bool>
             fs::create_dir_all(parent)?;
         }
         fs::write(&trace_path, json)?;
-        println!(
+        status!(
             "   {} Build trace saved to {} (Chrome Tracing)",
             "📊".blue(),
             trace_path.display()
         );
     }
 
+    if let Some(state) = &build_state {
+        state.save(&state_path)?;
+    }
+
+    if !failures.is_empty() {
+        print_failure_summary(&failures, skipped);
+        return Ok(false);
+    }
+
     // Unzip results separate object files and JSON entries
-    let (mut object_files, mut json_entries): (Vec<PathBuf>, Vec<serde_json::Value>) =
-        results.into_iter().unzip();
+    let mut object_files = Vec::new();
+    let mut json_entries = Vec::new();
+    for (_, obj, entries) in results {
+        object_files.push(obj);
+        json_entries.extend(entries);
+    }
 
     // Merge module results
     for (obj, entry) in module_results {
@@ -1444,9 +2213,6 @@ pub fn build_project(config: &CxConfig, options: &BuildOptions) -> Result<bool>
         json_entries.push(entry);
     }
 
-    // Add dependency module objects
-    object_files.extend(dep_module_objs);
-
     // 6. Generate compile_commands.json in .cx/build/
     let json_str = serde_json::to_string_pretty(&json_entries)?;
     let compile_commands_path = Path::new(".cx").join("build").join("compile_commands.json");
@@ -1456,252 +2222,273 @@ pub fn build_project(config: &CxConfig, options: &BuildOptions) -> Result<bool>
     fs::write(&compile_commands_path, json_str)?;
 
     // 7. Linking
-    let mut needs_link = !output_bin.exists();
-    if !needs_link {
-        let bin_time = fs::metadata(&output_bin)?.modified()?;
-
-        // Check if any object file is newer than the binary
-        for obj in &object_files {
-            if fs::metadata(obj)?.modified()? > bin_time {
-                needs_link = true;
-                break;
-            }
+    if build_type == BuildType::HeaderOnly {
+        // Nothing to link; the wrappers compiling is the whole build
+        if let Some(scripts) = &config.scripts
+            && let Some(post) = &scripts.post_build
+            && let Err(e) = run_script(post, &current_dir)
+        {
+            status!("{} Post-build script failed: {}", "x".red(), e);
+            return Ok(false);
         }
+        status!(
+            "{} Checked {} header(s) in {:.2?}",
+            "✓".green(),
+            object_files.len(),
+            start_time.elapsed()
+        );
+        return Ok(true);
+    }
 
-        // Check if any library dependency (static lib) is newer than the binary
-        if !needs_link {
-            for lib in &dep_libs {
-                let lib_path = Path::new(lib);
-                // Ignore system flags
-                if lib.starts_with('-') || lib.starts_with('/') {
-                    continue;
-                }
+    // Objects shared by every target: everything except binary entry points
+    let obj_ext = if is_msvc { "obj" } else { "o" };
+    let entry_objects: Vec<PathBuf> = all_entries
+        .iter()
+        .map(|src| object_file_path_for_source(&obj_dir, src, obj_ext))
+        .collect();
+    let shared_objects: Vec<PathBuf> = object_files
+        .iter()
+        .filter(|obj| !entry_objects.contains(obj))
+        .cloned()
+        .collect();
+
+    let mut linked_any = false;
+    for target in &targets {
+        let output = target_output_path(&bin_dir, wasm, target, target_os);
+        let mut target_objects = shared_objects.clone();
+        if let Some(entry) = &target.entry {
+            target_objects.push(object_file_path_for_source(&obj_dir, entry, obj_ext));
+        }
 
-                if lib_path.exists()
-                    && let Ok(meta) = fs::metadata(lib_path)
-                    && let Ok(mtime) = meta.modified()
-                    && mtime > bin_time
-                {
+        let link_ctx = super::link::LinkContext {
+            config,
+            build_type: target.kind,
+            compiler: &compiler,
+            is_msvc,
+            toolchain: toolchain.as_ref(),
+            toolchain_env: &toolchain_env,
+            object_files: &target_objects,
+            dep_libs: &dep_libs,
+            include_paths: &include_paths,
+            extra_cflags: &extra_cflags,
+            output: &output,
+            obj_dir: &obj_dir,
+            lto,
+            strip: resolved.strip(),
+            sanitize: sanitize.as_deref(),
+            target_flags: &cross_link_flags,
+            archiver: cross_archiver.as_deref(),
+        };
+        let link_signature =
+            CommandSignature::new(&toolchain_fingerprint, &super::link::link_args(&link_ctx));
+
+        let real_output = super::link::real_output_path(config, target.kind, &output);
+        let mut link_inputs = target_objects.clone();
+        link_inputs.extend(
+            dep_libs
+                .iter()
+                .filter(|lib| !lib.starts_with('-') && !lib.starts_with('/'))
+                .map(PathBuf::from)
+                .filter(|lib| lib.exists()),
+        );
+        let hash_fresh = build_state
+            .as_ref()
+            .and_then(|state| state.is_fresh(&real_output, &link_inputs));
+
+        let mut needs_link = !real_output.exists();
+        if let Some(fresh) = hash_fresh {
+            needs_link = !fresh;
+        } else if !needs_link {
+            let bin_time = fs::metadata(&real_output)?.modified()?;
+
+            // Check if any object file is newer than the binary
+            for obj in &target_objects {
+                if fs::metadata(obj)?.modified()? > bin_time {
                     needs_link = true;
                     break;
                 }
             }
-        }
-    }
-
-    if needs_link {
-        println!("   {} Linking...", "🔗".cyan());
-
-        // Check if we have MSVC .lib files in dependencies (requires MSVC-compatible linker)
-        let has_msvc_libs = dep_libs.iter().any(|lib| lib.ends_with(".lib"));
-        let is_windows = cfg!(target_os = "windows");
-        let is_mingw_clang = !is_msvc && is_windows && compiler.contains("clang");
 
-        // Use clang-cl if we have MinGW clang but need to link MSVC libs
-        let effective_compiler = if is_mingw_clang && has_msvc_libs {
-            println!(
-                "   {} Using clang-cl for MSVC library compatibility",
-                "⚡".yellow()
-            );
-            "clang-cl".to_string()
-        } else {
-            compiler.clone()
-        };
-        let use_clang_cl = effective_compiler == "clang-cl";
-        let is_msvc_like = is_msvc || use_clang_cl;
-
-        let mut cmd = Command::new(&effective_compiler);
-        let mut msvc_linker_args: Vec<String> = Vec::new();
+            // Check if any library dependency (static lib) is newer than the binary
+            if !needs_link {
+                for lib in &dep_libs {
+                    let lib_path = Path::new(lib);
+                    // Ignore system flags
+                    if lib.starts_with('-') || lib.starts_with('/') {
+                        continue;
+                    }
 
-        // Link Flags for LTO
-        if lto {
-            if is_msvc_like {
-                cmd.arg("/LTCG");
-            } else {
-                cmd.arg("-flto");
+                    if lib_path.exists()
+                        && let Ok(meta) = fs::metadata(lib_path)
+                        && let Ok(mtime) = meta.modified()
+                        && mtime > bin_time
+                    {
+                        needs_link = true;
+                        break;
+                    }
+                }
             }
         }
 
-        // Link Flags for Sanitizers
-        if let Some(checks) = &sanitize
-            && !is_msvc_like
+        // Same inputs but different libs, ldflags, strip or LTO
+        if !needs_link && let Some(reason) = link_signature_mismatch(&real_output, &link_signature)
         {
-            cmd.arg(format!("-fsanitize={}", checks));
+            if verbose {
+                status!("   {} {}: {}", "↻".dimmed(), target.name, reason.dimmed());
+            }
+            needs_link = true;
         }
 
-        cmd.args(&object_files);
-
-        // Add include paths for source files in dep_libs (e.g., GLAD's gl.c)
-        // When .c/.cpp files are passed to the linker, MSVC compiles them on the fly
-        // and needs include paths to find headers like <glad/gl.h>
-        let has_source_files = dep_libs.iter().any(|lib| {
-            let lower = lib.to_lowercase();
-            lower.ends_with(".c")
-                || lower.ends_with(".cpp")
-                || lower.ends_with(".cc")
-                || lower.ends_with(".cxx")
+        let artifact = json!({
+            "target": target.name,
+            "kind": target.kind.as_str(),
+            "path": real_output.display().to_string(),
+            "fresh": !needs_link,
         });
-
-        if has_source_files {
-            for path in &include_paths {
-                if is_msvc_like {
-                    cmd.arg(format!("/I{}", path.display()));
-                } else {
-                    cmd.arg(format!("-I{}", path.display()));
-                }
+        if !needs_link {
+            if let Some(state) = &build_state {
+                state.record(&real_output, &link_inputs);
             }
-            cmd.args(&extra_cflags);
+            messages.emit("artifact", artifact);
+            continue;
         }
 
-        if is_msvc_like {
-            // Use to_string_lossy and quote the path to handle spaces and special chars
-            let output_path = output_bin.to_string_lossy();
-            cmd.arg(format!("/Fe:{}", output_path));
-            cmd.arg(format!("/Fo:{}", obj_dir.to_string_lossy()));
+        let action = if target.kind == BuildType::StaticLibrary {
+            format!("   {} Archiving", "📚".cyan())
         } else {
-            cmd.arg("-o").arg(&output_bin);
-        }
-
-        for lib in &dep_libs {
-            cmd.arg(lib);
-        }
-
-        // Extract library search paths from dep_libs for user-specified libs
-        // This allows libs = ["glfw3"] to find glfw3.lib in dependency directories
-        let mut lib_search_paths = std::collections::HashSet::new();
-        for lib in &dep_libs {
-            let lib_path = Path::new(lib);
-            if lib_path
-                .extension()
-                .map(|e| e == "lib" || e == "a")
-                .unwrap_or(false)
-                && let Some(parent) = lib_path.parent()
-            {
-                lib_search_paths.insert(parent.to_path_buf());
-            }
-        }
-
-        // For GCC/Clang, add -L flags before the libs
-        if !is_msvc_like {
-            for search_path in &lib_search_paths {
-                cmd.arg(format!("-L{}", search_path.display()));
-            }
-
-            // For GCC/MinGW on Windows, handle subsystem (console vs windows)
-            // Default to console to avoid "undefined reference to WinMain" errors
-            if is_windows {
-                let subsystem = config
-                    .build
-                    .as_ref()
-                    .and_then(|b| b.subsystem.as_ref())
-                    .map(|s| s.to_lowercase())
-                    .unwrap_or_else(|| "console".to_string());
-
-                match subsystem.as_str() {
-                    "windows" => cmd.arg("-mwindows"),
-                    _ => cmd.arg("-mconsole"),
-                };
-            }
-        }
-
-        if let Some(build_cfg) = &config.build
-            && let Some(libs) = &build_cfg.libs
-        {
-            for lib in libs {
-                if is_msvc_like {
-                    cmd.arg(format!("{}.lib", lib));
-                } else {
-                    cmd.arg(format!("-l{}", lib));
-                }
-            }
-        }
-
-        if let Some(build_cfg) = &config.build
-            && let Some(ldflags) = &build_cfg.ldflags
-        {
-            if is_msvc_like {
-                msvc_linker_args.extend(ldflags.iter().cloned());
-            } else {
-                cmd.args(ldflags);
-            }
+            format!("   {} Linking", "🔗".cyan())
+        };
+        if all_targets.len() > 1 {
+            status!("{} {}...", action, target.name.bold());
+        } else {
+            status!("{}...", action);
         }
 
-        // For MSVC, pass /LIBPATH: flags via /link at the end
-        // Also ensure dynamic CRT (/MD) for compatibility with prebuilt libs like GLFW
-        if is_msvc_like {
-            if !lib_search_paths.is_empty() {
-                cmd.arg("/MD"); // Use dynamic CRT to match prebuilt dependencies
-                for search_path in &lib_search_paths {
-                    msvc_linker_args.push(format!("/LIBPATH:{}", search_path.display()));
-                }
-            }
-
-            // Add subsystem flag if specified (e.g., for SDL2 with SDL2main.lib)
-            if let Some(build_cfg) = &config.build
-                && let Some(subsystem) = &build_cfg.subsystem
-            {
-                let subsystem_flag = match subsystem.to_lowercase().as_str() {
-                    "windows" => "/SUBSYSTEM:WINDOWS",
-                    "console" => "/SUBSYSTEM:CONSOLE",
-                    _ => "/SUBSYSTEM:CONSOLE",
-                };
-                msvc_linker_args.push(subsystem_flag.to_string());
-            }
-
-            if !msvc_linker_args.is_empty() {
-                cmd.arg("/link");
-                cmd.args(&msvc_linker_args);
-            }
+        let linked = super::link::link_artifact(&link_ctx)?;
+        messages.emit(
+            "link",
+            json!({
+                "target": target.name,
+                "kind": target.kind.as_str(),
+                "output": real_output.display().to_string(),
+                "success": linked,
+            }),
+        );
+        if !linked {
+            return Ok(false);
         }
-
-        // Apply toolchain environment variables (LIB, LIBPATH, etc.)
-        if !toolchain_env.is_empty() {
-            cmd.envs(&toolchain_env);
+        write_link_signature(&real_output, &link_signature)?;
+        messages.emit("artifact", artifact);
+        if let Some(state) = &build_state {
+            state.record(&real_output, &link_inputs);
         }
+        linked_any = true;
+    }
 
-        let output = cmd.output()?;
-        if !output.status.success() {
-            println!("{}", String::from_utf8_lossy(&output.stdout));
-            let stderr = String::from_utf8_lossy(&output.stderr);
-            println!("{}", stderr);
-            println!("{} Linking failed", "x".red());
-
-            if let Some(suggestion) = super::feedback::FeedbackAnalyzer::analyze(&stderr) {
-                println!("\n{} {}\n", "💡 Suggestion:".bold().yellow(), suggestion);
-            }
-
-            return Ok(false);
-        }
+    if let Some(state) = &build_state {
+        state.save(&state_path)?;
+    }
 
+    if linked_any {
         // 8. Post-build Script
         if let Some(scripts) = &config.scripts
             && let Some(post) = &scripts.post_build
             && let Err(e) = run_script(post, &current_dir)
         {
-            println!("{} Post-build script failed: {}", "x".red(), e);
+            status!("{} Post-build script failed: {}", "x".red(), e);
             return Ok(false);
         }
 
-        println!(
+        status!(
             "{} Build finished in {:.2?}",
             "✓".green(),
             start_time.elapsed()
         );
     } else {
-        println!("{} Up to date", "⚡".green());
+        status!("{} Up to date", "⚡".green());
     }
 
     Ok(true)
 }
 
+/// Generate one wrapper TU per public header for `type = "header-only"`.
+///
+/// Headers come from `[build].include` directories, falling back to
+/// `include/` and then `src/`. Wrappers are only rewritten when their
+/// content changes so the usual incremental check applies.
+fn header_check_sources(
+    config: &CxConfig,
+    current_dir: &Path,
+    check_dir: &Path,
+    is_c: bool,
+    write: bool,
+) -> Result<Vec<PathBuf>> {
+    let header_dirs: Vec<PathBuf> = match config.build.as_ref().and_then(|b| b.include.as_ref()) {
+        Some(includes) if !includes.is_empty() => includes.iter().map(PathBuf::from).collect(),
+        _ if Path::new("include").is_dir() => vec![PathBuf::from("include")],
+        _ => vec![PathBuf::from("src")],
+    };
+
+    let header_exts: &[&str] = if is_c {
+        &["h"]
+    } else {
+        &["h", "hpp", "hh", "hxx", "h++"]
+    };
+
+    let mut headers = Vec::new();
+    for dir in &header_dirs {
+        for entry in WalkDir::new(dir).into_iter().filter_map(|e| e.ok()) {
+            let path = entry.path();
+            if path.is_file()
+                && path
+                    .extension()
+                    .is_some_and(|ext| header_exts.contains(&ext.to_string_lossy().as_ref()))
+            {
+                headers.push(path.to_path_buf());
+            }
+        }
+    }
+    headers.sort();
+    headers.dedup();
+
+    if write {
+        fs::create_dir_all(check_dir)?;
+    }
+
+    let wrapper_ext = if is_c { "c" } else { "cpp" };
+    let mut wrappers = Vec::with_capacity(headers.len());
+    for header in headers {
+        let abs_header = if header.is_absolute() {
+            header.clone()
+        } else {
+            current_dir.join(&header)
+        };
+        let wrapper = check_dir.join(object_file_name_for_source(&header, wrapper_ext));
+        let content = format!(
+            "// Generated by caxe: checks that {} compiles on its own\n#include \"{}\"\n",
+            header.display(),
+            abs_header.to_string_lossy().replace('\\', "/")
+        );
+        if write && fs::read_to_string(&wrapper).ok().as_deref() != Some(content.as_str()) {
+            fs::write(&wrapper, content)?;
+        }
+        wrappers.push(wrapper);
+    }
+
+    Ok(wrappers)
+}
+
 // --- COMMAND: Build & Run ---
+/// Build (`options.bin` picks the target) and run the program
 pub fn build_and_run(
-    release: bool,
-    verbose: bool,
-    dry_run: bool,
+    options: &BuildOptions,
     run_args: Vec<String>,
     script_path: Option<String>,
 ) -> Result<()> {
+    let release = options.release;
+    let verbose = options.verbose;
+    let dry_run = options.dry_run;
+
     // 1. Determine Configuration
     let config = if let Some(path_str) = &script_path {
         // SCENARIO 1: Explicit Script Mode (e.g. `cx run 1.cpp`)
@@ -1857,6 +2644,14 @@ pub fn build_and_run(
                     });
                 }
 
+                // A script builds exactly one binary, whatever targets the project declares
+                project_cfg.bin_targets = None;
+                project_cfg.lib_targets = None;
+                if let Some(build_cfg) = &mut project_cfg.build {
+                    build_cfg.autobins = Some(false);
+                    build_cfg.build_type = None;
+                }
+
                 // Fixup flags for second branch (no [build] section)
                 if let Some(build_cfg) = &mut project_cfg.build {
                     // Check if we have .cppm files in sources
@@ -1977,22 +2772,27 @@ pub fn build_and_run(
         run_args
     };
 
+    let targets = resolve_targets(&config)?;
+    let run_target = match &options.bin {
+        Some(name) => select_targets(&targets, Some(name))?.remove(0),
+        None => default_run_target(&config, &targets)?,
+    };
+
     let options = BuildOptions {
-        release,
-        verbose,
-        dry_run,
-        ..Default::default()
+        bin: Some(run_target.name.clone()),
+        ..options.clone()
     };
 
     let success = build_project(&config, &options)?;
     if !success {
         std::process::exit(1);
     }
+    let bin_path = ArtifactDir::resolve(&config, options.profile.as_deref(), release)?
+        .target_path(false, &run_target);
 
     // In dry-run mode, don't actually run
     if dry_run {
-        println!("\n{}", "Run:".bold());
-        let bin_path = artifact_bin_path(&config, release, false);
+        status!("\n{}", "Run:".bold());
 
         // If script mode and 'src/' lookup happened, path might be tricky for bin name logic?
         // Ephemeral config uses file stem as bin name, so it should be fine.
@@ -2006,20 +2806,18 @@ pub fn build_and_run(
         } else {
             format!(" {}", run_args.join(" "))
         };
-        println!("  → {}{}", bin_short.cyan(), args_str);
+        status!("  → {}{}", bin_short.cyan(), args_str);
         return Ok(());
     }
 
-    let bin_path = artifact_bin_path(&config, release, false);
-
     if !bin_path.exists() {
         anyhow::bail!("Binary not found at {}", bin_path.display());
     }
 
     if verbose {
-        println!("{} Running: {}\n", "🚀".green(), bin_path.display());
+        status!("{} Running: {}\n", "🚀".green(), bin_path.display());
     } else {
-        println!("{} Running...\n", "▶".green());
+        status!("{} Running...\n", "▶".green());
     }
 
     let mut run_cmd = Command::new(bin_path);
@@ -2063,7 +2861,9 @@ mod tests {
     #[test]
     fn artifact_bin_path_uses_dot_cx_layout() {
         let config = test_config("demo");
-        let path = artifact_bin_path(&config, false, false);
+        let path = ArtifactDir::resolve(&config, None, false)
+            .unwrap()
+            .bin_path(&config, false);
         let expected = if cfg!(target_os = "windows") {
             ".cx/debug/bin/demo.exe"
         } else {
@@ -2075,6 +2875,75 @@ mod tests {
         );
     }
 
+    #[test]
+    fn artifact_dir_follows_profile_target() {
+        let mut config = test_config("demo");
+        config.profiles.insert(
+            "mingw".to_string(),
+            toml::from_str("base = \"release\"\ntarget = \"x86_64-w64-mingw32\"").unwrap(),
+        );
+
+        let mingw = ArtifactDir::resolve(&config, Some("mingw"), true).unwrap();
+        assert_eq!(
+            mingw.bin_path(&config, false),
+            Path::new(".cx/x86_64-w64-mingw32/mingw/bin/demo.exe")
+        );
+        let release = ArtifactDir::resolve(&config, None, true).unwrap();
+        assert_eq!(release.bin_dir, Path::new(".cx/release/bin"));
+    }
+
+    #[test]
+    fn artifact_file_name_follows_platform_conventions() {
+        let cases = [
+            (BuildType::Executable, "linux", "demo"),
+            (BuildType::Executable, "windows", "demo.exe"),
+            (BuildType::StaticLibrary, "linux", "libdemo.a"),
+            (BuildType::StaticLibrary, "macos", "libdemo.a"),
+            (BuildType::StaticLibrary, "windows", "demo.lib"),
+            (BuildType::SharedLibrary, "linux", "libdemo.so"),
+            (BuildType::SharedLibrary, "macos", "libdemo.dylib"),
+            (BuildType::SharedLibrary, "windows", "demo.dll"),
+        ];
+        for (build_type, os, expected) in cases {
+            assert_eq!(artifact_file_name("demo", build_type, false, os), expected);
+        }
+    }
+
+    #[test]
+    fn artifact_file_name_does_not_double_lib_prefix() {
+        assert_eq!(
+            artifact_file_name("libdemo", BuildType::StaticLibrary, false, "linux"),
+            "libdemo.a"
+        );
+        assert_eq!(
+            artifact_file_name("libdemo", BuildType::SharedLibrary, false, "macos"),
+            "libdemo.dylib"
+        );
+        assert_eq!(
+            artifact_file_name("lib", BuildType::StaticLibrary, false, "linux"),
+            "liblib.a"
+        );
+    }
+
+    #[test]
+    fn binary_name_uses_configured_build_type() {
+        let mut config = test_config("demo");
+        config.build = Some(crate::config::BuildConfig {
+            build_type: Some("static-library".to_string()),
+            ..Default::default()
+        });
+        let expected = if cfg!(target_os = "windows") {
+            "demo.lib"
+        } else {
+            "libdemo.a"
+        };
+        assert_eq!(binary_name(&config, false), expected);
+
+        // Unknown types keep the executable naming
+        config.build.as_mut().unwrap().build_type = Some("plugin".to_string());
+        assert_eq!(build_type_of(&config), BuildType::Executable);
+    }
+
     #[test]
     fn framework_mode_classifies_dependency_aliases() {
         assert_eq!(framework_mode("daxe"), FrameworkMode::Integrated);
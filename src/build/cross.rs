@@ -0,0 +1,282 @@
+//! Cross-compilation driven by a profile's `target` triple.
+//!
+//! ```toml
+//! [profile:linux-arm64]
+//! target = "aarch64-linux-gnu"
+//! sysroot = "/opt/sysroots/aarch64"
+//! linker = "lld"
+//! ```
+//!
+//! Clang gets `--target=<triple>`; for GCC the cross compiler is
+//! `<triple>-g++` / `<triple>-gcc`. With no `compiler` set, an installed
+//! `<triple>-g++` is preferred and `clang++` is the fallback. `sysroot` adds
+//! `--sysroot`, `linker` becomes `-fuse-ld=<linker>` and `ar` replaces the
+//! archiver for static libraries. Artifacts go to `.cx/<triple>/<profile>/`
+//! so cross and host builds never share objects.
+
+use super::utils::find_in_path;
+use crate::config::Profile;
+use crate::toolchain::{CompilerType, Toolchain};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// A cross-compilation preset from `cx target list`
+pub struct TargetPreset {
+    pub name: &'static str,
+    pub triple: &'static str,
+    pub description: &'static str,
+    /// Compiler to use instead of `<triple>-g++` / `clang++`
+    pub compiler: Option<&'static str>,
+    /// Extra compiler flags written into the profile
+    pub flags: &'static [&'static str],
+}
+
+/// Built-in targets
+pub const TARGET_PRESETS: &[TargetPreset] = &[
+    TargetPreset {
+        name: "windows-x64",
+        triple: "x86_64-pc-windows-msvc",
+        description: "Windows 64-bit (MSVC ABI)",
+        compiler: Some("clang++"),
+        flags: &[],
+    },
+    TargetPreset {
+        name: "windows-x64-gnu",
+        triple: "x86_64-w64-mingw32",
+        description: "Windows 64-bit GNU (MinGW)",
+        compiler: None,
+        flags: &[],
+    },
+    TargetPreset {
+        name: "linux-x64",
+        triple: "x86_64-linux-gnu",
+        description: "Linux 64-bit",
+        compiler: None,
+        flags: &[],
+    },
+    TargetPreset {
+        name: "linux-arm64",
+        triple: "aarch64-linux-gnu",
+        description: "Linux ARM64",
+        compiler: None,
+        flags: &[],
+    },
+    TargetPreset {
+        name: "macos-x64",
+        triple: "x86_64-apple-darwin",
+        description: "macOS Intel",
+        compiler: Some("clang++"),
+        flags: &[],
+    },
+    TargetPreset {
+        name: "macos-arm64",
+        triple: "aarch64-apple-darwin",
+        description: "macOS Apple Silicon",
+        compiler: Some("clang++"),
+        flags: &[],
+    },
+    TargetPreset {
+        name: "wasm32",
+        triple: "wasm32-unknown-emscripten",
+        description: "WebAssembly (Emscripten)",
+        compiler: Some("em++"),
+        flags: &[],
+    },
+    TargetPreset {
+        name: "esp32",
+        triple: "xtensa-esp32-elf",
+        description: "ESP32 microcontroller (ESP-IDF)",
+        compiler: None,
+        flags: &["-mlongcalls", "-ffunction-sections", "-fdata-sections"],
+    },
+];
+
+pub fn find_preset(name: &str) -> Option<&'static TargetPreset> {
+    TARGET_PRESETS.iter().find(|p| p.name == name)
+}
+
+/// Cross settings of the selected profile
+#[derive(Debug, Clone, Default)]
+pub struct CrossTarget {
+    pub triple: String,
+    pub sysroot: Option<PathBuf>,
+    pub linker: Option<String>,
+    pub ar: Option<String>,
+}
+
+impl CrossTarget {
+    /// From a resolved profile; `None` unless it sets a `target`
+    pub fn from_profile(profile: &Profile) -> Option<Self> {
+        Some(Self {
+            triple: profile.target.clone()?,
+            sysroot: profile.sysroot.clone().map(PathBuf::from),
+            linker: profile.linker.clone(),
+            ar: profile.ar.clone(),
+        })
+    }
+
+    /// Find the compiler for this target, honouring an explicit `compiler`
+    pub fn toolchain(&self, compiler: Option<&str>) -> Option<Toolchain> {
+        let (path, compiler_type) = resolve_compiler(&self.triple, compiler)?;
+        let version = Command::new(&path)
+            .arg("--version")
+            .output()
+            .ok()
+            .and_then(|o| {
+                String::from_utf8_lossy(&o.stdout)
+                    .lines()
+                    .next()
+                    .map(str::to_string)
+            })
+            .unwrap_or_else(|| "unknown".to_string());
+        let mut toolchain = Toolchain::new_simple(compiler_type, path, version);
+        if let Some(linker) = &self.linker {
+            toolchain.linker_path = PathBuf::from(linker);
+        }
+        Some(toolchain)
+    }
+
+    /// `--target` (Clang only) and `--sysroot`, for compiling and linking
+    pub fn compile_flags(&self, compiler: &str) -> Vec<String> {
+        let mut flags = Vec::new();
+        if needs_target_flag(compiler, &self.triple) {
+            flags.push(format!("--target={}", self.triple));
+        }
+        if let Some(sysroot) = &self.sysroot {
+            flags.push(format!("--sysroot={}", sysroot.display()));
+        }
+        flags
+    }
+
+    /// Compile flags plus `-fuse-ld`
+    pub fn link_flags(&self, compiler: &str) -> Vec<String> {
+        let mut flags = self.compile_flags(compiler);
+        if let Some(linker) = &self.linker {
+            flags.push(format!("-fuse-ld={linker}"));
+        }
+        flags
+    }
+
+    /// Archiver: `ar` from the profile, else `<triple>-ar` or `llvm-ar`
+    pub fn archiver(&self, compiler: &str) -> Option<String> {
+        if let Some(ar) = &self.ar {
+            return Some(ar.clone());
+        }
+        let candidate = if is_clang(compiler) {
+            "llvm-ar".to_string()
+        } else {
+            format!("{}-ar", self.triple)
+        };
+        find_in_path(&candidate).map(|p| p.to_string_lossy().to_string())
+    }
+
+    /// Operating system of the target, as in `std::env::consts::OS`
+    pub fn os(&self) -> &'static str {
+        target_os(&self.triple)
+    }
+}
+
+/// `windows`, `macos`, `linux`, ... for a triple (`linux` when unsure)
+pub fn target_os(triple: &str) -> &'static str {
+    let triple = triple.to_lowercase();
+    if triple.contains("windows") || triple.contains("mingw") {
+        "windows"
+    } else if triple.contains("apple") || triple.contains("darwin") {
+        if triple.contains("ios") {
+            "ios"
+        } else {
+            "macos"
+        }
+    } else if triple.contains("emscripten") || triple.contains("wasm") {
+        "emscripten"
+    } else if triple.contains("-elf") || triple.contains("none") {
+        "none"
+    } else {
+        "linux"
+    }
+}
+
+/// Compiler path and family for `triple`, or `None` when it isn't installed
+pub fn resolve_compiler(triple: &str, requested: Option<&str>) -> Option<(PathBuf, CompilerType)> {
+    let gcc = format!("{triple}-g++");
+    let candidates: Vec<String> = match requested.map(str::to_lowercase).as_deref() {
+        None => vec![gcc, "clang++".to_string()],
+        Some("gcc" | "g++") => vec![gcc],
+        Some("clang" | "clang++") => vec!["clang++".to_string()],
+        Some(_) => vec![requested.unwrap_or_default().to_string()],
+    };
+    candidates.into_iter().find_map(|candidate| {
+        let path = Path::new(&candidate);
+        let found = if path.components().count() > 1 {
+            path.is_file().then(|| path.to_path_buf())
+        } else {
+            find_in_path(&candidate)
+        }?;
+        let compiler_type = if is_clang(&candidate) {
+            CompilerType::Clang
+        } else {
+            CompilerType::GCC
+        };
+        Some((found, compiler_type))
+    })
+}
+
+fn is_clang(compiler: &str) -> bool {
+    let name = Path::new(compiler)
+        .file_name()
+        .map(|n| n.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    name.contains("clang") || name.starts_with("em++") || name.starts_with("emcc")
+}
+
+/// Clang needs `--target` unless it is already a `<triple>-clang++` wrapper
+fn needs_target_flag(compiler: &str, triple: &str) -> bool {
+    let name = Path::new(compiler)
+        .file_name()
+        .map(|n| n.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    name.contains("clang") && !name.starts_with(&triple.to_lowercase())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cross(sysroot: Option<&str>, linker: Option<&str>) -> CrossTarget {
+        CrossTarget {
+            triple: "aarch64-linux-gnu".to_string(),
+            sysroot: sysroot.map(PathBuf::from),
+            linker: linker.map(str::to_string),
+            ar: None,
+        }
+    }
+
+    #[test]
+    fn test_target_flags_per_compiler() {
+        let target = cross(Some("/opt/arm"), Some("lld"));
+        assert_eq!(
+            target.compile_flags("/usr/bin/clang++"),
+            vec!["--target=aarch64-linux-gnu", "--sysroot=/opt/arm"]
+        );
+        assert_eq!(
+            target.compile_flags("aarch64-linux-gnu-g++"),
+            vec!["--sysroot=/opt/arm"]
+        );
+        assert_eq!(
+            target.link_flags("aarch64-linux-gnu-clang++"),
+            vec!["--sysroot=/opt/arm", "-fuse-ld=lld"]
+        );
+        assert!(cross(None, None).compile_flags("g++").is_empty());
+    }
+
+    #[test]
+    fn test_target_os() {
+        assert_eq!(target_os("x86_64-w64-mingw32"), "windows");
+        assert_eq!(target_os("x86_64-pc-windows-msvc"), "windows");
+        assert_eq!(target_os("aarch64-apple-darwin"), "macos");
+        assert_eq!(target_os("aarch64-linux-gnu"), "linux");
+        assert_eq!(target_os("xtensa-esp32-elf"), "none");
+        assert!(find_preset("linux-arm64").is_some());
+        assert!(find_preset("amiga").is_none());
+    }
+}
@@ -0,0 +1,381 @@
+//! Optional compile-time features from `[features]`.
+//!
+//! ```toml
+//! [features]
+//! default = ["logging"]
+//! logging = []
+//! tls = { enables = ["dep:openssl"], defines = ["USE_TLS=1"], sources = ["src/tls/**/*.cpp"] }
+//!
+//! [dependencies]
+//! openssl = { pkg = "openssl", optional = true }
+//! ```
+//!
+//! As in cargo, `default` lists the features enabled unless
+//! `--no-default-features` is passed, and a feature can enable other
+//! features or an optional dependency (`dep:<name>`). Every enabled feature
+//! defines `CX_FEATURE_<NAME>` plus its own `defines`. Sources matching a
+//! feature's `sources` globs are only compiled while it is enabled; optional
+//! dependencies are only fetched and linked while a feature enables them.
+
+use super::overrides::{glob_regex, normalize};
+use crate::config::CxConfig;
+use anyhow::{Result, bail};
+use regex::Regex;
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+/// Features requested on the command line
+#[derive(Debug, Clone, Default, clap::Args)]
+pub struct FeatureSelection {
+    /// Features to enable (comma or space separated)
+    #[arg(short = 'F', long, value_delimiter = ',')]
+    pub features: Vec<String>,
+    /// Enable every feature declared in [features]
+    #[arg(long)]
+    pub all_features: bool,
+    /// Don't enable the `default` feature
+    #[arg(long)]
+    pub no_default_features: bool,
+}
+
+impl FeatureSelection {
+    fn requested(&self) -> impl Iterator<Item = &str> {
+        self.features
+            .iter()
+            .flat_map(|f| f.split_whitespace())
+            .filter(|f| !f.is_empty())
+    }
+}
+
+/// The features in effect for one build
+#[derive(Debug, Default)]
+pub struct Features {
+    /// Enabled features, `default` excluded
+    pub enabled: BTreeSet<String>,
+    /// Optional dependencies turned on by `dep:` entries
+    pub dependencies: BTreeSet<String>,
+    defines: Vec<String>,
+    sources: ConditionalSources,
+}
+
+/// Source globs that build only while their owner (a feature or a
+/// platform section) is active
+#[derive(Debug, Default)]
+pub struct ConditionalSources {
+    active_globs: Vec<String>,
+    active_patterns: Vec<Regex>,
+    inactive_patterns: Vec<Regex>,
+}
+
+impl ConditionalSources {
+    pub fn add(&mut self, globs: &[String], active: bool) {
+        let patterns = globs.iter().map(|g| glob_regex(g));
+        if active {
+            self.active_globs.extend(globs.iter().cloned());
+            self.active_patterns.extend(patterns);
+        } else {
+            self.inactive_patterns.extend(patterns);
+        }
+    }
+
+    /// Whether `source` belongs only to inactive owners
+    pub fn excludes(&self, source: &Path) -> bool {
+        let path = normalize(source);
+        self.inactive_patterns.iter().any(|p| p.is_match(&path))
+            && !self.active_patterns.iter().any(|p| p.is_match(&path))
+    }
+
+    /// Existing files matching the globs of active owners
+    pub fn sources(&self) -> Vec<PathBuf> {
+        let mut found = BTreeSet::new();
+        for glob in &self.active_globs {
+            let pattern = glob_regex(glob);
+            let base = glob_base(glob);
+            if !base.exists() {
+                continue;
+            }
+            for entry in WalkDir::new(&base).into_iter().filter_map(|e| e.ok()) {
+                if entry.file_type().is_file() && pattern.is_match(&normalize(entry.path())) {
+                    found.insert(entry.path().to_path_buf());
+                }
+            }
+        }
+        found.into_iter().collect()
+    }
+}
+
+impl Features {
+    /// Resolve `selection` against `[features]`, following `enables`
+    pub fn resolve(config: &CxConfig, selection: &FeatureSelection) -> Result<Self> {
+        let empty = Default::default();
+        let declared = config.features.as_ref().unwrap_or(&empty);
+
+        let mut roots: Vec<String> = Vec::new();
+        if selection.all_features {
+            roots.extend(declared.keys().filter(|k| *k != "default").cloned());
+        }
+        if !selection.no_default_features && declared.contains_key("default") {
+            roots.push("default".to_string());
+        }
+        for name in selection.requested() {
+            if !declared.contains_key(name) {
+                let mut available: Vec<&String> =
+                    declared.keys().filter(|k| *k != "default").collect();
+                available.sort();
+                bail!(
+                    "Unknown feature '{}'. Available features: {:?}",
+                    name,
+                    available
+                );
+            }
+            roots.push(name.to_string());
+        }
+
+        let mut features = Self::default();
+        let mut visited = BTreeSet::new();
+        while let Some(name) = roots.pop() {
+            if !visited.insert(name.clone()) {
+                continue;
+            }
+            for entry in declared[&name].enables() {
+                match enabled_item(config, &name, entry)? {
+                    Item::Feature(feature) => roots.push(feature),
+                    Item::Dependency(dep) => {
+                        features.dependencies.insert(dep);
+                    }
+                }
+            }
+        }
+        visited.remove("default");
+
+        // Sorted, so the defines (and compile commands) are stable
+        let mut declared: Vec<_> = declared.iter().collect();
+        declared.sort_by_key(|(name, _)| *name);
+        for (name, feature) in declared {
+            if name == "default" {
+                continue;
+            }
+            let enabled = visited.contains(name);
+            if enabled {
+                features.defines.push(feature_define(name));
+                features.defines.extend(feature.defines().iter().cloned());
+            }
+            features.sources.add(feature.sources(), enabled);
+        }
+        features.enabled = visited;
+        Ok(features)
+    }
+
+    /// Drop inactive optional dependencies and add the feature defines
+    pub fn apply(&self, config: &mut CxConfig) {
+        if let Some(deps) = &mut config.dependencies {
+            deps.retain(|name, dep| !dep.is_optional() || self.dependencies.contains(name));
+        }
+        if !self.defines.is_empty() {
+            let build_cfg = config.build.get_or_insert_with(Default::default);
+            build_cfg
+                .defines
+                .get_or_insert_with(Vec::new)
+                .extend(self.defines.iter().cloned());
+        }
+    }
+
+    /// Whether `source` belongs only to disabled features
+    pub fn excludes(&self, source: &Path) -> bool {
+        self.sources.excludes(source)
+    }
+
+    /// `sources` globs of enabled and disabled features
+    pub fn conditional_sources(&self) -> &ConditionalSources {
+        &self.sources
+    }
+}
+
+enum Item {
+    Feature(String),
+    Dependency(String),
+}
+
+/// What one `enables` entry of `feature` refers to
+fn enabled_item(config: &CxConfig, feature: &str, entry: &str) -> Result<Item> {
+    let deps = config.dependencies.as_ref();
+    if let Some(dep) = entry.strip_prefix("dep:") {
+        return match deps.and_then(|d| d.get(dep)) {
+            Some(d) if d.is_optional() => Ok(Item::Dependency(dep.to_string())),
+            Some(_) => bail!(
+                "Feature '{}' enables 'dep:{}', but that dependency isn't optional = true",
+                feature,
+                dep
+            ),
+            None => bail!("Feature '{}' enables unknown dependency '{}'", feature, dep),
+        };
+    }
+    if config
+        .features
+        .as_ref()
+        .is_some_and(|f| f.contains_key(entry))
+    {
+        return Ok(Item::Feature(entry.to_string()));
+    }
+    // Like cargo, a bare optional dependency name works too
+    if deps
+        .and_then(|d| d.get(entry))
+        .is_some_and(|d| d.is_optional())
+    {
+        return Ok(Item::Dependency(entry.to_string()));
+    }
+    bail!("Feature '{}' enables unknown feature '{}'", feature, entry)
+}
+
+/// `CX_FEATURE_<NAME>`, defined for every enabled feature
+pub fn feature_define(name: &str) -> String {
+    let name: String = name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() {
+                c.to_ascii_uppercase()
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    format!("CX_FEATURE_{name}")
+}
+
+/// Every feature that enables `name`, directly or transitively, including itself
+pub fn enabling_features(config: &CxConfig, name: &str) -> BTreeSet<String> {
+    let empty = Default::default();
+    let declared = config.features.as_ref().unwrap_or(&empty);
+    let mut reverse: BTreeMap<&str, Vec<&str>> = BTreeMap::new();
+    for (feature, cfg) in declared {
+        for entry in cfg.enables() {
+            reverse.entry(entry.as_str()).or_default().push(feature);
+        }
+    }
+
+    let mut found = BTreeSet::new();
+    let mut pending = vec![name];
+    while let Some(current) = pending.pop() {
+        if current == "default" || !found.insert(current.to_string()) {
+            continue;
+        }
+        pending.extend(reverse.get(current).into_iter().flatten());
+    }
+    found
+}
+
+/// Directory before the first wildcard component of `glob`
+fn glob_base(glob: &str) -> PathBuf {
+    let glob = glob.replace('\\', "/");
+    let mut base = PathBuf::new();
+    for component in glob.split('/') {
+        if component.contains(['*', '?']) {
+            return if base.as_os_str().is_empty() {
+                PathBuf::from(".")
+            } else {
+                base
+            };
+        }
+        base.push(component);
+    }
+    base
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> CxConfig {
+        toml::from_str(
+            r#"
+[package]
+name = "net"
+version = "0.1.0"
+
+[features]
+default = ["logging"]
+logging = { defines = ["LOG_LEVEL=2"] }
+tls = { enables = ["crypto", "dep:openssl"], sources = ["src/tls/**/*.cpp"] }
+crypto = []
+
+[dependencies]
+openssl = { pkg = "openssl", optional = true }
+fmt = "https://github.com/fmtlib/fmt"
+"#,
+        )
+        .unwrap()
+    }
+
+    fn select(features: &[&str], no_default: bool) -> FeatureSelection {
+        FeatureSelection {
+            features: features.iter().map(|f| f.to_string()).collect(),
+            all_features: false,
+            no_default_features: no_default,
+        }
+    }
+
+    #[test]
+    fn test_default_features() {
+        let mut config = config();
+        let features = Features::resolve(&config, &FeatureSelection::default()).unwrap();
+        assert_eq!(features.enabled, BTreeSet::from(["logging".to_string()]));
+        assert!(features.excludes(Path::new("src/tls/conn.cpp")));
+        assert!(!features.excludes(Path::new("src/main.cpp")));
+
+        features.apply(&mut config);
+        let deps = config.dependencies.unwrap();
+        assert!(deps.contains_key("fmt"));
+        assert!(!deps.contains_key("openssl"));
+        assert_eq!(
+            config.build.unwrap().defines.unwrap(),
+            vec!["CX_FEATURE_LOGGING", "LOG_LEVEL=2"]
+        );
+    }
+
+    #[test]
+    fn test_enabled_features_follow_enables() {
+        let mut config = config();
+        let features = Features::resolve(&config, &select(&["tls"], true)).unwrap();
+        assert_eq!(
+            features.enabled,
+            BTreeSet::from(["crypto".to_string(), "tls".to_string()])
+        );
+        assert!(!features.excludes(Path::new("src/tls/conn.cpp")));
+
+        features.apply(&mut config);
+        assert!(config.dependencies.unwrap().contains_key("openssl"));
+        assert_eq!(
+            config.build.unwrap().defines.unwrap(),
+            vec!["CX_FEATURE_CRYPTO", "CX_FEATURE_TLS"]
+        );
+    }
+
+    #[test]
+    fn test_unknown_feature_is_an_error() {
+        let err = Features::resolve(&config(), &select(&["quic"], false)).unwrap_err();
+        assert!(err.to_string().contains("Unknown feature 'quic'"));
+    }
+
+    #[test]
+    fn test_comma_separated_and_all_features() {
+        let features = Features::resolve(&config(), &select(&["crypto tls"], true)).unwrap();
+        assert_eq!(features.enabled.len(), 2);
+        let all = FeatureSelection {
+            all_features: true,
+            ..Default::default()
+        };
+        assert_eq!(Features::resolve(&config(), &all).unwrap().enabled.len(), 3);
+    }
+
+    #[test]
+    fn test_enabling_features() {
+        assert_eq!(
+            enabling_features(&config(), "crypto"),
+            BTreeSet::from(["crypto".to_string(), "tls".to_string()])
+        );
+        assert_eq!(feature_define("simd-avx2"), "CX_FEATURE_SIMD_AVX2");
+        assert_eq!(glob_base("src/tls/**/*.cpp"), PathBuf::from("src/tls"));
+        assert_eq!(glob_base("*.c"), PathBuf::from("."));
+    }
+}
@@ -1,4 +1,6 @@
+use crate::diagnostics::strip_ansi;
 use colored::*;
+use std::path::{Path, PathBuf};
 
 pub struct FeedbackAnalyzer;
 
@@ -9,9 +11,9 @@ impl FeedbackAnalyzer {
             || output.contains("entry point must be defined")
         {
             return Some(format!(
-                "Your project is missing a {} function.\nEnsure you have a valid entry point or set {} if this is a library.",
+                "Your project is missing a {} function.\nEnsure you have a valid entry point or set {} in [build] if this is a library.",
                 "main()".bold().yellow(),
-                "bin = \"lib\"".bold().green()
+                "type = \"static-library\"".bold().green()
             ));
         }
 
@@ -43,6 +45,73 @@ impl FeedbackAnalyzer {
     }
 }
 
+/// A translation unit that failed to compile, with the compiler's output
+#[derive(Debug)]
+pub struct CompileFailure {
+    pub file: PathBuf,
+    pub output: String,
+}
+
+impl CompileFailure {
+    pub fn new(file: &Path, output: String) -> Self {
+        Self {
+            file: file.to_path_buf(),
+            output: strip_ansi(&output),
+        }
+    }
+
+    fn error_lines(&self) -> impl Iterator<Item = &str> {
+        self.output.lines().map(str::trim).filter(|line| {
+            line.contains(": error") || line.contains(": fatal error") || line.starts_with("error")
+        })
+    }
+
+    /// First diagnostic that is an error (not a note or warning)
+    pub fn first_error(&self) -> Option<&str> {
+        self.error_lines().next()
+    }
+
+    pub fn error_count(&self) -> usize {
+        self.error_lines().count().max(1)
+    }
+}
+
+impl std::fmt::Display for CompileFailure {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Compilation failed")
+    }
+}
+
+impl std::error::Error for CompileFailure {}
+
+/// `--keep-going` summary: every failed file with its first error
+pub fn print_failure_summary(failures: &[CompileFailure], skipped: usize) {
+    status!(
+        "\n{} {} file(s) failed to compile:",
+        "x".red(),
+        failures.len()
+    );
+    for failure in failures {
+        let count = failure.error_count();
+        status!(
+            "   {} ({} error{})",
+            failure.file.display().to_string().bold(),
+            count,
+            if count == 1 { "" } else { "s" }
+        );
+        if let Some(line) = failure.first_error() {
+            status!("      {}", line.dimmed());
+        }
+    }
+    if skipped > 0 {
+        status!(
+            "   {} {} file(s) skipped: they import modules that failed",
+            "!".yellow(),
+            skipped
+        );
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -68,4 +137,23 @@ mod tests {
         let msg = FeedbackAnalyzer::analyze(err).unwrap();
         assert!(msg.contains("missing a main() function"));
     }
+
+    #[test]
+    fn test_compile_failure_summary() {
+        let output = "\u{1b}[01m\u{1b}[Ksrc/a.cpp:3:5:\u{1b}[m\u{1b}[K \u{1b}[01;31m\u{1b}[Kerror: \u{1b}[m\u{1b}[K'foo' was not declared\n\
+                      src/a.cpp:4:1: note: suggested alternative\n\
+                      src/a.cpp:9:2: error: expected ';'\n";
+        let failure = CompileFailure::new(Path::new("src/a.cpp"), output.to_string());
+        assert_eq!(
+            failure.first_error(),
+            Some("src/a.cpp:3:5: error: 'foo' was not declared")
+        );
+        assert_eq!(failure.error_count(), 2);
+
+        let msvc = CompileFailure::new(
+            Path::new("a.cpp"),
+            "a.cpp(3): error C2065: 'foo': undeclared identifier\n".to_string(),
+        );
+        assert_eq!(msvc.error_count(), 1);
+    }
 }
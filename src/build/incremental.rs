@@ -0,0 +1,506 @@
+//! Incremental build decisions.
+//!
+//! An object is recompiled when:
+//!
+//! - one of its inputs (source plus the headers listed in its `.d` file or
+//!   MSVC `/sourceDependencies` JSON) changed — by mtime, or by content hash
+//!   with `[build] incremental = "hash"`
+//! - the command that produced it changed. Every object gets a `<obj>.cmd`
+//!   file next to its `.d` file recording the compiler invocation; other
+//!   flags, profile, sanitizer, LTO, compiler or toolchain make it stale
+//!   even if its mtime says otherwise.
+//! - `--sarif` is given and there is no `<obj>.diag` with the compiler
+//!   output of its last compile (e.g. it was restored from a cache). Up to
+//!   date objects replay their `.diag` into the report instead.
+//! - for linked artifacts, the link command changed (`libs`, `ldflags`,
+//!   `strip`, `lto`, ...): each artifact gets a `<artifact>.cmd` next to it.
+//!
+//! Hash mode keeps its records in `.cx/build/build_state.json`, so git
+//! checkouts, `cx vendor` copies and CI cache restores that only touch
+//! mtimes no longer trigger rebuilds (or hide them).
+
+use crate::config::CxConfig;
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::UNIX_EPOCH;
+
+/// The invocation that produced an object
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub(crate) struct CommandSignature {
+    /// `objcache::compiler_identity` of the compiler
+    pub toolchain: String,
+    /// Compiler followed by its arguments (without any ccache wrapper)
+    pub args: Vec<String>,
+}
+
+impl CommandSignature {
+    pub fn new(toolchain: &str, args: &[String]) -> Self {
+        Self {
+            toolchain: toolchain.to_string(),
+            args: args.to_vec(),
+        }
+    }
+
+    /// Why `self` (the command about to run) differs from `recorded`
+    fn change_reason(&self, recorded: &CommandSignature) -> Option<String> {
+        if self == recorded {
+            return None;
+        }
+        if self.args.first() != recorded.args.first() {
+            return Some(format!(
+                "compiler changed ({} → {})",
+                recorded.args.first().map(String::as_str).unwrap_or("?"),
+                self.args.first().map(String::as_str).unwrap_or("?")
+            ));
+        }
+        if self.toolchain != recorded.toolchain {
+            return Some("toolchain changed".to_string());
+        }
+
+        let added: Vec<&str> = self
+            .args
+            .iter()
+            .filter(|a| !recorded.args.contains(a))
+            .map(String::as_str)
+            .collect();
+        let removed: Vec<&str> = recorded
+            .args
+            .iter()
+            .filter(|a| !self.args.contains(a))
+            .map(String::as_str)
+            .collect();
+        let mut parts = Vec::new();
+        if !added.is_empty() {
+            parts.push(format!("added {}", added.join(" ")));
+        }
+        if !removed.is_empty() {
+            parts.push(format!("removed {}", removed.join(" ")));
+        }
+        if parts.is_empty() {
+            Some("flag order changed".to_string())
+        } else {
+            Some(format!("flags changed: {}", parts.join("; ")))
+        }
+    }
+}
+
+/// Where the signature of `obj_path` lives
+pub(crate) fn signature_path(obj_path: &Path) -> PathBuf {
+    obj_path.with_extension("cmd")
+}
+
+/// Where the link signature of `artifact` lives. Appended rather than
+/// swapped in, since `app.exe` and `app.lib` can share a directory.
+pub(crate) fn link_signature_path(artifact: &Path) -> PathBuf {
+    let mut path = artifact.as_os_str().to_owned();
+    path.push(".cmd");
+    PathBuf::from(path)
+}
+
+/// Compare against the recorded signature; `Some(reason)` means rebuild
+pub(crate) fn signature_mismatch(obj_path: &Path, current: &CommandSignature) -> Option<String> {
+    recorded_mismatch(&signature_path(obj_path), current)
+}
+
+/// Compare against the artifact's recorded link command; `Some(reason)`
+/// means relink
+pub(crate) fn link_signature_mismatch(
+    artifact: &Path,
+    current: &CommandSignature,
+) -> Option<String> {
+    recorded_mismatch(&link_signature_path(artifact), current)
+}
+
+fn recorded_mismatch(signature_file: &Path, current: &CommandSignature) -> Option<String> {
+    let recorded = fs::read_to_string(signature_file)
+        .ok()
+        .and_then(|content| serde_json::from_str::<CommandSignature>(&content).ok());
+    match recorded {
+        Some(recorded) => current.change_reason(&recorded),
+        None => Some("no recorded command line".to_string()),
+    }
+}
+
+/// Record the signature after a successful compile
+pub(crate) fn write_signature(obj_path: &Path, current: &CommandSignature) -> std::io::Result<()> {
+    let json = serde_json::to_string(current)?;
+    fs::write(signature_path(obj_path), json)
+}
+
+/// Record the link command after a successful link
+pub(crate) fn write_link_signature(
+    artifact: &Path,
+    current: &CommandSignature,
+) -> std::io::Result<()> {
+    let json = serde_json::to_string(current)?;
+    fs::write(link_signature_path(artifact), json)
+}
+
+/// Where the compiler output of `obj_path` is kept, so `--sarif` can report
+/// the diagnostics of objects that are not recompiled
+pub(crate) fn diagnostics_path(obj_path: &Path) -> PathBuf {
+    obj_path.with_extension("diag")
+}
+
+/// Record the compiler output next to the signature
+pub(crate) fn write_diagnostics(obj_path: &Path, output: &str) -> std::io::Result<()> {
+    fs::write(diagnostics_path(obj_path), output)
+}
+
+/// Compiler output of the compile that produced `obj_path`, `None` when it
+/// wasn't recorded (e.g. the object came from a cache)
+pub(crate) fn recorded_diagnostics(obj_path: &Path) -> Option<String> {
+    fs::read_to_string(diagnostics_path(obj_path)).ok()
+}
+
+/// How inputs are judged unchanged
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub(crate) enum ChangeDetection {
+    /// Input newer than the output means dirty (default)
+    #[default]
+    Mtime,
+    /// Input content differs from what the output was built from
+    Hash,
+}
+
+/// `[build] incremental`: "mtime" (default) or "hash"
+pub(crate) fn change_detection(config: &CxConfig) -> ChangeDetection {
+    match config
+        .build
+        .as_ref()
+        .and_then(|b| b.incremental.as_deref())
+        .map(str::to_lowercase)
+        .as_deref()
+    {
+        Some("hash") | Some("content") => ChangeDetection::Hash,
+        _ => ChangeDetection::Mtime,
+    }
+}
+
+/// Compilers run from inside `.cx`, so relative paths in dependency files
+/// are relative to it
+fn resolve_dep_path(raw: &str) -> PathBuf {
+    let path = Path::new(raw);
+    if path.is_absolute() {
+        path.to_path_buf()
+    } else {
+        Path::new(".cx").join(path)
+    }
+}
+
+/// Everything an object was compiled from: the source plus the headers from
+/// its `.d` file (GCC/Clang) or `<obj>.json` (MSVC `/sourceDependencies`).
+/// `Ok(None)` when a dependency file exists but can't be interpreted.
+pub(crate) fn dependency_inputs(obj_path: &Path, src_path: &Path) -> Result<Option<Vec<PathBuf>>> {
+    let mut inputs = vec![src_path.to_path_buf()];
+
+    // 1. MSVC: /sourceDependencies <obj>.json
+    // Parse JSON: {"Data": {"Source": "...", "Includes": ["..."]}}
+    let json_path = PathBuf::from(format!("{}.json", obj_path.display()));
+    if json_path.exists() {
+        let content = fs::read_to_string(&json_path)?;
+        let json: serde_json::Value = serde_json::from_str(&content)?;
+        if let Some(arr) = json.pointer("/Data/Includes").and_then(|i| i.as_array()) {
+            inputs.extend(arr.iter().filter_map(|i| i.as_str()).map(resolve_dep_path));
+            return Ok(Some(inputs));
+        }
+    }
+
+    // 2. GCC/Clang .d file
+    let d_path = obj_path.with_extension("d");
+    if !d_path.exists() {
+        return Ok(Some(inputs));
+    }
+
+    let dep_content = fs::read_to_string(&d_path)?;
+    // Handle line continuations. Only the first rule lists the object's
+    // inputs; GCC adds module rules (`x.c++m: gcm.cache/x.gcm`) after it.
+    let content_flat = dep_content.replace("\\\n", " ").replace("\\\r\n", " ");
+    let content_flat = content_flat.lines().next().unwrap_or_default();
+
+    // Format is usually: "objfile.o: src.c header.h ..."
+    // Handle Windows paths (e.g. C:\foo.o) by looking for ": " (colon + space)
+    // Splitting on just ':' breaks drive letters.
+    let deps_str = if let Some((_, rhs)) = content_flat.split_once(": ") {
+        rhs
+    } else if let Some((lhs, rhs)) = content_flat.split_once(':') {
+        // Heuristic: if lhs is 1 char (e.g. "C"), it's probably a drive letter and the parse failed.
+        if lhs.trim().len() == 1 {
+            return Ok(None); // Ambiguous, safer to rebuild
+        }
+        rhs
+    } else {
+        // No colon found: corrupted or empty
+        return Ok(None);
+    };
+
+    inputs.extend(deps_str.split_whitespace().map(resolve_dep_path));
+    Ok(Some(inputs))
+}
+
+/// Mtime check: is any input newer than the object?
+pub(crate) fn check_dependencies(obj_path: &Path, src_path: &Path) -> Result<bool> {
+    if !obj_path.exists() {
+        return Ok(true);
+    }
+    let obj_mtime = fs::metadata(obj_path)?.modified()?;
+
+    // Always check the source itself, even without a dependency file
+    if fs::metadata(src_path)?.modified()? > obj_mtime {
+        return Ok(true);
+    }
+
+    let Some(inputs) = dependency_inputs(obj_path, src_path)? else {
+        return Ok(true);
+    };
+    for dep_path in inputs {
+        if dep_path.exists() && fs::metadata(&dep_path)?.modified()? > obj_mtime {
+            return Ok(true); // Dependency is newer
+        }
+    }
+
+    Ok(false) // Up to date
+}
+
+fn mtime_nanos(path: &Path) -> Option<u128> {
+    let modified = fs::metadata(path).ok()?.modified().ok()?;
+    Some(modified.duration_since(UNIX_EPOCH).ok()?.as_nanos())
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+struct FileStamp {
+    mtime: u128,
+    size: u64,
+    hash: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+struct OutputRecord {
+    /// Content hash of the output when recorded; anything else rebuilt or
+    /// replaced it since
+    output: String,
+    /// Input path → content hash
+    inputs: BTreeMap<String, String>,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct StateFile {
+    #[serde(default)]
+    outputs: HashMap<String, OutputRecord>,
+    #[serde(default)]
+    files: HashMap<String, FileStamp>,
+}
+
+/// Content hashes of object and artifact inputs (`.cx/build/build_state.json`)
+#[derive(Default)]
+pub(crate) struct BuildState {
+    outputs: Mutex<HashMap<String, OutputRecord>>,
+    /// Hash cache: a file whose mtime and size are unchanged isn't re-read
+    files: Mutex<HashMap<String, FileStamp>>,
+}
+
+impl BuildState {
+    pub fn path() -> PathBuf {
+        Path::new(".cx").join("build").join("build_state.json")
+    }
+
+    /// Load the state database; a missing or corrupt file starts empty
+    pub fn load(path: &Path) -> Self {
+        let state: StateFile = fs::read_to_string(path)
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default();
+        Self {
+            outputs: Mutex::new(state.outputs),
+            files: Mutex::new(state.files),
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let state = StateFile {
+            outputs: self.outputs.lock().map(|o| o.clone()).unwrap_or_default(),
+            files: self.files.lock().map(|f| f.clone()).unwrap_or_default(),
+        };
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, serde_json::to_string(&state)?)?;
+        Ok(())
+    }
+
+    /// Content hash of `path`, or `None` if it can't be read
+    fn hash_file(&self, path: &Path) -> Option<String> {
+        let key = path.to_string_lossy().replace('\\', "/");
+        let meta = fs::metadata(path).ok()?;
+        let mtime = mtime_nanos(path)?;
+        let size = meta.len();
+
+        if let Ok(files) = self.files.lock()
+            && let Some(stamp) = files.get(&key)
+            && stamp.mtime == mtime
+            && stamp.size == size
+        {
+            return Some(stamp.hash.clone());
+        }
+
+        let digest = Sha256::digest(fs::read(path).ok()?);
+        let hash: String = digest.iter().map(|b| format!("{b:02x}")).collect();
+        if let Ok(mut files) = self.files.lock() {
+            files.insert(
+                key,
+                FileStamp {
+                    mtime,
+                    size,
+                    hash: hash.clone(),
+                },
+            );
+        }
+        Some(hash)
+    }
+
+    fn hash_inputs(&self, inputs: &[PathBuf]) -> BTreeMap<String, String> {
+        inputs
+            .iter()
+            .filter_map(|input| {
+                let hash = self.hash_file(input)?;
+                Some((input.to_string_lossy().replace('\\', "/"), hash))
+            })
+            .collect()
+    }
+
+    /// `Some(true)` if `output` was built from exactly these input contents,
+    /// `Some(false)` if any changed, `None` if there is no usable record
+    /// (never recorded, output missing, or rebuilt behind our back). Only
+    /// contents count: touching or restoring the output keeps it fresh.
+    pub fn is_fresh(&self, output: &Path, inputs: &[PathBuf]) -> Option<bool> {
+        let key = output.to_string_lossy().replace('\\', "/");
+        let record = self.outputs.lock().ok()?.get(&key).cloned()?;
+        if self.hash_file(output)? != record.output {
+            return None;
+        }
+        Some(self.hash_inputs(inputs) == record.inputs)
+    }
+
+    /// Remember what `output` was just built (or found up to date) from
+    pub fn record(&self, output: &Path, inputs: &[PathBuf]) {
+        let Some(output_hash) = self.hash_file(output) else {
+            return;
+        };
+        let record = OutputRecord {
+            output: output_hash,
+            inputs: self.hash_inputs(inputs),
+        };
+        if let Ok(mut outputs) = self.outputs.lock() {
+            outputs.insert(output.to_string_lossy().replace('\\', "/"), record);
+        }
+    }
+}
+
+/// Have the object's inputs changed since it was built? Uses content hashes
+/// when a `state` is given and has a record, mtimes otherwise.
+pub(crate) fn inputs_changed(obj_path: &Path, src_path: &Path, state: Option<&BuildState>) -> bool {
+    if let Some(state) = state
+        && let Ok(Some(inputs)) = dependency_inputs(obj_path, src_path)
+        && let Some(fresh) = state.is_fresh(obj_path, &inputs)
+    {
+        return !fresh;
+    }
+    check_dependencies(obj_path, src_path).unwrap_or(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sig(toolchain: &str, args: &[&str]) -> CommandSignature {
+        CommandSignature {
+            toolchain: toolchain.to_string(),
+            args: args.iter().map(|a| a.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn identical_signatures_are_clean() {
+        let a = sig("abc", &["g++", "-c", "main.cpp", "-g"]);
+        assert_eq!(a.change_reason(&a.clone()), None);
+    }
+
+    #[test]
+    fn reports_flag_and_compiler_changes() {
+        let old = sig("abc", &["g++", "-c", "main.cpp", "-g"]);
+
+        let release = sig("abc", &["g++", "-c", "main.cpp", "-O3"]);
+        assert_eq!(
+            release.change_reason(&old).unwrap(),
+            "flags changed: added -O3; removed -g"
+        );
+
+        let clang = sig("abc", &["clang++", "-c", "main.cpp", "-g"]);
+        assert_eq!(
+            clang.change_reason(&old).unwrap(),
+            "compiler changed (g++ → clang++)"
+        );
+
+        let upgraded = sig("def", &["g++", "-c", "main.cpp", "-g"]);
+        assert_eq!(upgraded.change_reason(&old).unwrap(), "toolchain changed");
+    }
+
+    #[test]
+    fn hash_state_ignores_mtime_only_changes() {
+        let dir = tempfile::tempdir().unwrap();
+        let src = dir.path().join("main.cpp");
+        let obj = dir.path().join("main.o");
+        fs::write(&src, "int main() { return 0; }\n").unwrap();
+        fs::write(&obj, "object").unwrap();
+
+        let state = BuildState::default();
+        let inputs = vec![src.clone()];
+        assert_eq!(state.is_fresh(&obj, &inputs), None);
+        state.record(&obj, &inputs);
+        assert_eq!(state.is_fresh(&obj, &inputs), Some(true));
+
+        // Rewriting identical content (new mtime) is not a change
+        fs::write(&src, "int main() { return 0; }\n").unwrap();
+        assert_eq!(state.is_fresh(&obj, &inputs), Some(true));
+
+        // So is touching or restoring the object itself
+        fs::write(&obj, "object").unwrap();
+        assert_eq!(state.is_fresh(&obj, &inputs), Some(true));
+
+        // A different object than the one recorded has no usable record
+        fs::write(&obj, "other object").unwrap();
+        assert_eq!(state.is_fresh(&obj, &inputs), None);
+        fs::write(&obj, "object").unwrap();
+
+        fs::write(&src, "int main() { return 42; }\n").unwrap();
+        assert_eq!(state.is_fresh(&obj, &inputs), Some(false));
+
+        // Survives a save/load round trip
+        let state_path = dir.path().join("build_state.json");
+        state.save(&state_path).unwrap();
+        let loaded = BuildState::load(&state_path);
+        assert_eq!(loaded.is_fresh(&obj, &inputs), Some(false));
+    }
+
+    #[test]
+    fn parses_make_style_dependency_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let obj = dir.path().join("main.o");
+        fs::write(
+            dir.path().join("main.d"),
+            "main.o: ../src/main.cpp \\\n ../src/util.h\n",
+        )
+        .unwrap();
+
+        let inputs = dependency_inputs(&obj, Path::new("src/main.cpp"))
+            .unwrap()
+            .unwrap();
+        assert_eq!(inputs[0], PathBuf::from("src/main.cpp"));
+        assert_eq!(inputs[2], Path::new(".cx").join("../src/util.h"));
+    }
+}
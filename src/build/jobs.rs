@@ -0,0 +1,312 @@
+//! Compile job scheduling.
+//!
+//! The number of parallel jobs comes from `-j/--jobs`, then `CX_JOBS`, then
+//! `[build] jobs`, defaulting to one per core. With `[build]
+//! min_free_memory = "2G"` (or `CX_MIN_FREE_MEMORY`) no new job starts
+//! while less memory than that is available, unless nothing else is
+//! running (Linux only; elsewhere the setting is ignored).
+//!
+//! Jobs start in the order they are given. Builds use it to start the
+//! translation units that took longest last time first (compile times are
+//! kept in `.cx/build/compile_times.json`), so one slow TU doesn't end up
+//! compiling alone at the end.
+
+use super::objcache::parse_size;
+use crate::config::CxConfig;
+use anyhow::Result;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::time::Duration;
+
+/// How often a throttled worker re-checks available memory
+const MEMORY_POLL: Duration = Duration::from_millis(200);
+
+pub(crate) struct Scheduler {
+    jobs: usize,
+    min_free_memory: Option<u64>,
+}
+
+impl Scheduler {
+    /// `jobs` is the `-j` value, if given; 0 means one per core
+    pub fn new(config: Option<&CxConfig>, jobs: Option<usize>) -> Self {
+        let build = config.and_then(|c| c.build.as_ref());
+        let jobs = jobs
+            .or_else(|| std::env::var("CX_JOBS").ok()?.trim().parse().ok())
+            .or_else(|| build.and_then(|b| b.jobs))
+            .filter(|&jobs| jobs > 0)
+            .unwrap_or_else(|| {
+                std::thread::available_parallelism()
+                    .map(|n| n.get())
+                    .unwrap_or(1)
+            });
+        let min_free_memory = std::env::var("CX_MIN_FREE_MEMORY")
+            .ok()
+            .or_else(|| build.and_then(|b| b.min_free_memory.clone()))
+            .and_then(|size| parse_size(&size))
+            .filter(|&bytes| bytes > 0);
+        Self {
+            jobs,
+            min_free_memory,
+        }
+    }
+
+    pub fn jobs(&self) -> usize {
+        self.jobs
+    }
+
+    pub fn min_free_memory(&self) -> Option<u64> {
+        self.min_free_memory
+    }
+
+    /// Run `job` over `items` on up to `jobs` threads, starting items in
+    /// order. `job` also gets the worker number (for traces). After the
+    /// first error no new items start and that error is returned.
+    pub fn run<T, R, F>(&self, items: &[T], job: F) -> Result<Vec<R>>
+    where
+        T: Sync,
+        R: Send,
+        F: Fn(usize, &T) -> Result<R> + Sync,
+    {
+        let mut collected = Vec::with_capacity(items.len());
+        let mut first_error = None;
+        for result in self.execute(items, job, true) {
+            match result {
+                Some(Ok(value)) => collected.push(value),
+                Some(Err(e)) => {
+                    first_error.get_or_insert(e);
+                }
+                // Skipped after a failure
+                None => {}
+            }
+        }
+        match first_error {
+            Some(e) => Err(e),
+            None => Ok(collected),
+        }
+    }
+
+    /// Like [`run`](Self::run), but keeps going after errors
+    /// (`--keep-going`) and returns every item's result, in item order
+    pub fn run_all<T, R, F>(&self, items: &[T], job: F) -> Vec<Result<R>>
+    where
+        T: Sync,
+        R: Send,
+        F: Fn(usize, &T) -> Result<R> + Sync,
+    {
+        self.execute(items, job, false)
+            .into_iter()
+            .map(|result| result.unwrap_or_else(|| Err(anyhow::anyhow!("Job did not run"))))
+            .collect()
+    }
+
+    fn execute<T, R, F>(&self, items: &[T], job: F, stop_on_error: bool) -> Vec<Option<Result<R>>>
+    where
+        T: Sync,
+        R: Send,
+        F: Fn(usize, &T) -> Result<R> + Sync,
+    {
+        let next = AtomicUsize::new(0);
+        let running = AtomicUsize::new(0);
+        let failed = AtomicBool::new(false);
+        let results: Vec<Mutex<Option<Result<R>>>> =
+            items.iter().map(|_| Mutex::new(None)).collect();
+
+        let workers = self.jobs.min(items.len());
+        std::thread::scope(|scope| {
+            for worker in 0..workers {
+                let (next, running, failed, results, job) =
+                    (&next, &running, &failed, &results, &job);
+                scope.spawn(move || {
+                    loop {
+                        self.wait_for_memory(running);
+                        let idx = next.fetch_add(1, Ordering::SeqCst);
+                        if idx >= items.len() || failed.load(Ordering::SeqCst) {
+                            break;
+                        }
+                        running.fetch_add(1, Ordering::SeqCst);
+                        let result = job(worker, &items[idx]);
+                        running.fetch_sub(1, Ordering::SeqCst);
+                        if stop_on_error && result.is_err() {
+                            failed.store(true, Ordering::SeqCst);
+                        }
+                        if let Ok(mut slot) = results[idx].lock() {
+                            *slot = Some(result);
+                        }
+                    }
+                });
+            }
+        });
+
+        results
+            .into_iter()
+            .map(|slot| slot.into_inner().ok().flatten())
+            .collect()
+    }
+
+    /// Hold a new job back while memory is short and others are running
+    fn wait_for_memory(&self, running: &AtomicUsize) {
+        let Some(min_free) = self.min_free_memory else {
+            return;
+        };
+        while running.load(Ordering::SeqCst) > 0
+            && available_memory().is_some_and(|available| available < min_free)
+        {
+            std::thread::sleep(MEMORY_POLL);
+        }
+    }
+}
+
+/// Available physical memory in bytes (`MemAvailable` on Linux)
+fn available_memory() -> Option<u64> {
+    let meminfo = fs::read_to_string("/proc/meminfo").ok()?;
+    parse_meminfo(&meminfo)
+}
+
+fn parse_meminfo(meminfo: &str) -> Option<u64> {
+    let line = meminfo
+        .lines()
+        .find(|line| line.starts_with("MemAvailable:"))?;
+    let kib: u64 = line.split_whitespace().nth(1)?.parse().ok()?;
+    Some(kib * 1024)
+}
+
+/// Per-source compile times from earlier builds
+#[derive(Default)]
+pub(crate) struct CompileTimes {
+    micros: Mutex<HashMap<String, u64>>,
+}
+
+impl CompileTimes {
+    pub fn path() -> PathBuf {
+        Path::new(".cx").join("build").join("compile_times.json")
+    }
+
+    pub fn load(path: &Path) -> Self {
+        let micros = fs::read_to_string(path)
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default();
+        Self {
+            micros: Mutex::new(micros),
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let micros = self
+            .micros
+            .lock()
+            .map_err(|_| anyhow::anyhow!("poisoned"))?;
+        fs::write(path, serde_json::to_string(&*micros)?)?;
+        Ok(())
+    }
+
+    pub fn record(&self, source: &Path, took: Duration) {
+        if let Ok(mut micros) = self.micros.lock() {
+            micros.insert(source.display().to_string(), took.as_micros() as u64);
+        }
+    }
+
+    fn get(&self, source: &Path) -> Option<u64> {
+        let micros = self.micros.lock().ok()?;
+        micros.get(&source.display().to_string()).copied()
+    }
+
+    /// Slowest first; sources never timed go before all others (they may
+    /// be anything), keeping their relative order
+    pub fn slowest_first<'a, T>(&self, items: &mut [T], source: impl Fn(&T) -> &'a Path) {
+        items.sort_by_key(|item| std::cmp::Reverse(self.get(source(item)).unwrap_or(u64::MAX)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scheduler(jobs: usize) -> Scheduler {
+        Scheduler {
+            jobs,
+            min_free_memory: None,
+        }
+    }
+
+    #[test]
+    fn test_run_keeps_item_order_in_results() {
+        let items: Vec<u32> = (0..20).collect();
+        let doubled = scheduler(4).run(&items, |_, n| Ok(n * 2)).unwrap();
+        assert_eq!(doubled, items.iter().map(|n| n * 2).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_run_respects_job_limit() {
+        let running = AtomicUsize::new(0);
+        let peak = AtomicUsize::new(0);
+        let items: Vec<u32> = (0..12).collect();
+        scheduler(2)
+            .run(&items, |_, _| {
+                let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+                peak.fetch_max(now, Ordering::SeqCst);
+                std::thread::sleep(Duration::from_millis(5));
+                running.fetch_sub(1, Ordering::SeqCst);
+                Ok(())
+            })
+            .unwrap();
+        assert!(peak.load(Ordering::SeqCst) <= 2);
+    }
+
+    #[test]
+    fn test_run_stops_after_error() {
+        let items: Vec<u32> = (0..50).collect();
+        let err = scheduler(1)
+            .run(&items, |_, &n| {
+                if n == 3 {
+                    anyhow::bail!("job {n} failed")
+                }
+                Ok(n)
+            })
+            .unwrap_err();
+        assert_eq!(err.to_string(), "job 3 failed");
+    }
+
+    #[test]
+    fn test_run_all_continues_after_errors() {
+        let items: Vec<u32> = (0..10).collect();
+        let results = scheduler(2).run_all(&items, |_, &n| {
+            if n % 3 == 0 {
+                anyhow::bail!("job {n} failed")
+            }
+            Ok(n)
+        });
+        assert_eq!(results.len(), 10);
+        assert_eq!(results.iter().filter(|r| r.is_err()).count(), 4);
+        assert_eq!(*results[4].as_ref().unwrap(), 4);
+    }
+
+    #[test]
+    fn test_parse_meminfo() {
+        let meminfo =
+            "MemTotal:       16384000 kB\nMemFree:         1000 kB\nMemAvailable:    2048 kB\n";
+        assert_eq!(parse_meminfo(meminfo), Some(2048 * 1024));
+        assert_eq!(parse_meminfo("MemTotal: 1 kB\n"), None);
+    }
+
+    #[test]
+    fn test_slowest_first() {
+        let times = CompileTimes::default();
+        times.record(Path::new("fast.cpp"), Duration::from_millis(10));
+        times.record(Path::new("slow.cpp"), Duration::from_secs(3));
+        let sources = [
+            PathBuf::from("fast.cpp"),
+            PathBuf::from("slow.cpp"),
+            PathBuf::from("new.cpp"),
+        ];
+        let mut order: Vec<usize> = (0..sources.len()).collect();
+        times.slowest_first(&mut order, |&idx| sources[idx].as_path());
+        assert_eq!(order, vec![2, 1, 0]);
+    }
+}
@@ -0,0 +1,568 @@
+//! Final artifact production.
+//!
+//! Turns compiled objects into whatever `[build] type` asks for:
+//!
+//! - executables (default)
+//! - shared libraries (`.so` / `.dylib` / `.dll` + import `.lib`), with
+//!   SONAME / install_name / `/VERSION` derived from `package.version`
+//! - static archives (`ar`, `llvm-ar`, `gcc-ar` or `lib.exe`)
+
+use crate::config::{BuildType, CxConfig, Lto};
+use crate::toolchain::Toolchain;
+use anyhow::Result;
+use colored::*;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Everything the link step needs from `build_project`
+pub(crate) struct LinkContext<'a> {
+    pub config: &'a CxConfig,
+    pub build_type: BuildType,
+    pub compiler: &'a str,
+    pub is_msvc: bool,
+    pub toolchain: Option<&'a Toolchain>,
+    pub toolchain_env: &'a HashMap<String, String>,
+    pub object_files: &'a [PathBuf],
+    pub dep_libs: &'a [String],
+    pub include_paths: &'a [PathBuf],
+    pub extra_cflags: &'a [String],
+    pub output: &'a Path,
+    pub obj_dir: &'a Path,
+    pub lto: Lto,
+    /// Strip symbols from the output (`strip = true` in the profile)
+    pub strip: bool,
+    pub sanitize: Option<&'a str>,
+    /// `--target`, `--sysroot` and `-fuse-ld` of a cross build
+    pub target_flags: &'a [String],
+    /// Archiver set by a cross profile (`ar = "..."`)
+    pub archiver: Option<&'a str>,
+}
+
+/// Link or archive the artifact. Returns `Ok(false)` when the tool failed
+/// (output has already been printed).
+pub(crate) fn link_artifact(ctx: &LinkContext) -> Result<bool> {
+    match ctx.build_type {
+        BuildType::StaticLibrary => archive_static(ctx),
+        BuildType::HeaderOnly => Ok(true),
+        BuildType::Executable | BuildType::SharedLibrary => link_binary(ctx),
+    }
+}
+
+/// Archiver or linker followed by its arguments, for the artifact's link
+/// signature (empty for header-only packages, which link nothing)
+pub(crate) fn link_args(ctx: &LinkContext) -> Vec<String> {
+    let cmd = match ctx.build_type {
+        BuildType::StaticLibrary => archive_command(ctx),
+        BuildType::HeaderOnly => return Vec::new(),
+        BuildType::Executable | BuildType::SharedLibrary => link_command(ctx),
+    };
+    std::iter::once(cmd.get_program())
+        .chain(cmd.get_args())
+        .map(|arg| arg.to_string_lossy().into_owned())
+        .collect()
+}
+
+/// Short description of the link step for `--dry-run`
+pub(crate) fn describe_link_tool(
+    build_type: BuildType,
+    compiler: &str,
+    is_msvc: bool,
+    toolchain: Option<&Toolchain>,
+    archiver: Option<&str>,
+    lto: bool,
+) -> String {
+    let short = |tool: &str| {
+        Path::new(tool)
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_else(|| tool.to_string())
+    };
+    match build_type {
+        BuildType::StaticLibrary => {
+            let (tool, msvc_style) = archiver
+                .map(|ar| (ar.to_string(), is_msvc))
+                .unwrap_or_else(|| archiver_for(compiler, is_msvc, toolchain, lto));
+            if msvc_style {
+                short(&tool)
+            } else {
+                format!("{} rcs", short(&tool))
+            }
+        }
+        BuildType::SharedLibrary => {
+            if is_msvc {
+                format!("{} /LD", short(compiler))
+            } else if cfg!(target_os = "macos") {
+                format!("{} -dynamiclib", short(compiler))
+            } else {
+                format!("{} -shared", short(compiler))
+            }
+        }
+        _ => short(compiler),
+    }
+}
+
+/// Parse `package.version` for shared library versioning.
+fn library_version(config: &CxConfig) -> Option<semver::Version> {
+    semver::Version::parse(config.package.version.trim()).ok()
+}
+
+/// Real file name and SONAME for a versioned Linux/ELF shared library.
+///
+/// `libfoo.so` at 1.2.3 becomes `libfoo.so.1.2.3` with SONAME `libfoo.so.1`.
+pub(crate) fn versioned_so_names(file_name: &str, version: &semver::Version) -> (String, String) {
+    (
+        format!(
+            "{file_name}.{}.{}.{}",
+            version.major, version.minor, version.patch
+        ),
+        format!("{file_name}.{}", version.major),
+    )
+}
+
+/// Versioned ELF shared objects are written to `libfoo.so.X.Y.Z` and exposed
+/// through `libfoo.so.X` / `libfoo.so` symlinks. Returns (real name, SONAME).
+fn elf_library_names(
+    config: &CxConfig,
+    build_type: BuildType,
+    output: &Path,
+) -> Option<(String, String)> {
+    if build_type != BuildType::SharedLibrary
+        || cfg!(target_os = "windows")
+        || cfg!(target_os = "macos")
+    {
+        return None;
+    }
+    let file_name = output.file_name()?.to_string_lossy().to_string();
+    library_version(config).map(|v| versioned_so_names(&file_name, &v))
+}
+
+/// The file the linker actually writes (differs from `output` only for
+/// versioned ELF shared libraries).
+pub(crate) fn real_output_path(config: &CxConfig, build_type: BuildType, output: &Path) -> PathBuf {
+    match elf_library_names(config, build_type, output) {
+        Some((real, _)) => output.with_file_name(real),
+        None => output.to_path_buf(),
+    }
+}
+
+/// Look for a tool next to the compiler (e.g. `lib.exe` beside `cl.exe`,
+/// `llvm-ar` beside `clang++`).
+fn sibling_tool(compiler: &Path, name: &str) -> Option<PathBuf> {
+    let parent = compiler.parent().filter(|p| !p.as_os_str().is_empty())?;
+    let file = if cfg!(target_os = "windows") {
+        format!("{name}.exe")
+    } else {
+        name.to_string()
+    };
+    let candidate = parent.join(file);
+    candidate.exists().then_some(candidate)
+}
+
+/// Pick the archiver for static libraries. The bool is true for
+/// `lib.exe`-style (`/OUT:`) tools.
+fn archiver_for(
+    compiler: &str,
+    is_msvc: bool,
+    toolchain: Option<&Toolchain>,
+    lto: bool,
+) -> (String, bool) {
+    let compiler_path = toolchain
+        .map(|tc| tc.cxx_path.clone())
+        .unwrap_or_else(|| PathBuf::from(compiler));
+    let compiler_name = compiler_path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+
+    if is_msvc {
+        let tool = sibling_tool(&compiler_path, "lib")
+            .map(|p| p.to_string_lossy().to_string())
+            .unwrap_or_else(|| "lib".to_string());
+        return (tool, true);
+    }
+
+    if compiler_name.contains("clang-cl") {
+        let tool = sibling_tool(&compiler_path, "llvm-lib")
+            .map(|p| p.to_string_lossy().to_string())
+            .unwrap_or_else(|| "llvm-lib".to_string());
+        return (tool, true);
+    }
+
+    if compiler_name.contains("clang") {
+        if let Some(tool) = sibling_tool(&compiler_path, "llvm-ar") {
+            return (tool.to_string_lossy().to_string(), false);
+        }
+    } else if lto {
+        // GCC LTO objects need the plugin-aware wrapper, keeping any
+        // cross prefix (aarch64-linux-gnu-g++ -> aarch64-linux-gnu-gcc-ar)
+        let gcc_ar = compiler_name
+            .replacen("g++", "gcc-ar", 1)
+            .replace(".exe", "");
+        let gcc_ar = if gcc_ar.contains("gcc-ar") {
+            gcc_ar
+        } else {
+            "gcc-ar".to_string()
+        };
+        if let Some(tool) = sibling_tool(&compiler_path, &gcc_ar) {
+            return (tool.to_string_lossy().to_string(), false);
+        }
+    }
+
+    ("ar".to_string(), false)
+}
+
+fn archive_command(ctx: &LinkContext) -> Command {
+    let (tool, msvc_style) = ctx
+        .archiver
+        .map(|ar| (ar.to_string(), ctx.is_msvc))
+        .unwrap_or_else(|| {
+            archiver_for(
+                ctx.compiler,
+                ctx.is_msvc,
+                ctx.toolchain,
+                ctx.lto.is_enabled(),
+            )
+        });
+
+    // Loose objects from dependencies (e.g. the MSVC PCH object) belong
+    // in the archive; libraries are left for the final consumer to link.
+    let extra_objects = ctx.dep_libs.iter().filter(|lib| {
+        let lower = lib.to_lowercase();
+        lower.ends_with(".o") || lower.ends_with(".obj")
+    });
+
+    let mut cmd = Command::new(&tool);
+    if msvc_style {
+        cmd.arg("/nologo");
+        if ctx.lto.is_enabled() {
+            cmd.arg("/LTCG");
+        }
+        cmd.arg(format!("/OUT:{}", ctx.output.display()));
+    } else {
+        cmd.arg("rcs").arg(ctx.output);
+    }
+    cmd.args(ctx.object_files);
+    cmd.args(extra_objects);
+
+    if !ctx.toolchain_env.is_empty() {
+        cmd.envs(ctx.toolchain_env);
+    }
+    cmd
+}
+
+fn archive_static(ctx: &LinkContext) -> Result<bool> {
+    // Archivers append to existing archives; start fresh so removed
+    // sources don't linger.
+    if ctx.output.exists() {
+        fs::remove_file(ctx.output)?;
+    }
+
+    let mut cmd = archive_command(ctx);
+    let tool = cmd.get_program().to_string_lossy().into_owned();
+    let output = match cmd.output() {
+        Ok(output) => output,
+        Err(e) => {
+            status!("{} Failed to run archiver '{}': {}", "x".red(), tool, e);
+            return Ok(false);
+        }
+    };
+    if !output.status.success() {
+        status!("{}", String::from_utf8_lossy(&output.stdout));
+        status!("{}", String::from_utf8_lossy(&output.stderr));
+        status!("{} Archiving failed", "x".red());
+        return Ok(false);
+    }
+
+    Ok(true)
+}
+
+/// MinGW clang can't link MSVC `.lib` dependencies; clang-cl can
+fn needs_clang_cl(ctx: &LinkContext) -> bool {
+    let has_msvc_libs = ctx.dep_libs.iter().any(|lib| lib.ends_with(".lib"));
+    let is_mingw_clang =
+        !ctx.is_msvc && cfg!(target_os = "windows") && ctx.compiler.contains("clang");
+    is_mingw_clang && has_msvc_libs
+}
+
+fn link_command(ctx: &LinkContext) -> Command {
+    let config = ctx.config;
+    let dep_libs = ctx.dep_libs;
+    let shared = ctx.build_type == BuildType::SharedLibrary;
+
+    let is_windows = cfg!(target_os = "windows");
+    let is_macos = cfg!(target_os = "macos");
+
+    // Use clang-cl if we have MinGW clang but need to link MSVC libs
+    let effective_compiler = if needs_clang_cl(ctx) {
+        "clang-cl".to_string()
+    } else {
+        ctx.compiler.to_string()
+    };
+    let use_clang_cl = effective_compiler == "clang-cl";
+    let is_msvc_like = ctx.is_msvc || use_clang_cl;
+
+    let version = library_version(config);
+    let file_name = ctx
+        .output
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let elf_names = elf_library_names(config, ctx.build_type, ctx.output);
+    let link_output = real_output_path(config, ctx.build_type, ctx.output);
+
+    let mut cmd = Command::new(&effective_compiler);
+    let mut msvc_linker_args: Vec<String> = Vec::new();
+
+    if shared {
+        if is_msvc_like {
+            cmd.arg("/LD");
+            let implib = ctx.output.with_extension("lib");
+            msvc_linker_args.push(format!("/IMPLIB:{}", implib.display()));
+            if let Some(v) = &version {
+                msvc_linker_args.push(format!("/VERSION:{}.{}", v.major, v.minor));
+            }
+        } else if is_macos {
+            cmd.arg("-dynamiclib");
+            cmd.arg(format!("-Wl,-install_name,@rpath/{file_name}"));
+            if let Some(v) = &version {
+                cmd.arg(format!(
+                    "-Wl,-current_version,{}.{}.{}",
+                    v.major, v.minor, v.patch
+                ));
+                cmd.arg(format!("-Wl,-compatibility_version,{}.0.0", v.major));
+            }
+        } else {
+            cmd.arg("-shared");
+            if is_windows {
+                let implib = ctx.output.with_extension("lib");
+                cmd.arg(format!("-Wl,--out-implib,{}", implib.display()));
+            } else if let Some((_, soname)) = &elf_names {
+                cmd.arg(format!("-Wl,-soname,{soname}"));
+            }
+        }
+    }
+
+    cmd.args(ctx.target_flags);
+
+    // Link Flags for LTO
+    if ctx.lto.is_enabled() {
+        if is_msvc_like {
+            cmd.arg("/LTCG");
+        } else if ctx.lto == Lto::Thin && ctx.compiler.contains("clang") {
+            cmd.arg("-flto=thin");
+        } else {
+            cmd.arg("-flto");
+        }
+    }
+
+    if ctx.strip && !is_msvc_like {
+        // ld64 doesn't take -s
+        if cfg!(target_os = "macos") {
+            cmd.arg("-Wl,-S,-x");
+        } else {
+            cmd.arg("-s");
+        }
+    }
+
+    // Link Flags for Sanitizers
+    if let Some(checks) = ctx.sanitize
+        && !is_msvc_like
+    {
+        cmd.arg(format!("-fsanitize={}", checks));
+    }
+
+    cmd.args(ctx.object_files);
+
+    // Add include paths for source files in dep_libs (e.g., GLAD's gl.c)
+    // When .c/.cpp files are passed to the linker, MSVC compiles them on the fly
+    // and needs include paths to find headers like <glad/gl.h>
+    let has_source_files = dep_libs.iter().any(|lib| {
+        let lower = lib.to_lowercase();
+        lower.ends_with(".c")
+            || lower.ends_with(".cpp")
+            || lower.ends_with(".cc")
+            || lower.ends_with(".cxx")
+    });
+
+    if has_source_files {
+        for path in ctx.include_paths {
+            if is_msvc_like {
+                cmd.arg(format!("/I{}", path.display()));
+            } else {
+                cmd.arg(format!("-I{}", path.display()));
+            }
+        }
+        cmd.args(ctx.extra_cflags);
+    }
+
+    if is_msvc_like {
+        // Use to_string_lossy and quote the path to handle spaces and special chars
+        let output_path = link_output.to_string_lossy();
+        cmd.arg(format!("/Fe:{}", output_path));
+        cmd.arg(format!("/Fo:{}", ctx.obj_dir.to_string_lossy()));
+    } else {
+        cmd.arg("-o").arg(&link_output);
+    }
+
+    for lib in dep_libs {
+        cmd.arg(lib);
+    }
+
+    // Extract library search paths from dep_libs for user-specified libs
+    // This allows libs = ["glfw3"] to find glfw3.lib in dependency directories
+    let mut lib_search_paths = std::collections::HashSet::new();
+    for lib in dep_libs {
+        let lib_path = Path::new(lib);
+        if lib_path
+            .extension()
+            .map(|e| e == "lib" || e == "a")
+            .unwrap_or(false)
+            && let Some(parent) = lib_path.parent()
+        {
+            lib_search_paths.insert(parent.to_path_buf());
+        }
+    }
+
+    // For GCC/Clang, add -L flags before the libs
+    if !is_msvc_like {
+        for search_path in &lib_search_paths {
+            cmd.arg(format!("-L{}", search_path.display()));
+        }
+
+        // For GCC/MinGW on Windows, handle subsystem (console vs windows)
+        // Default to console to avoid "undefined reference to WinMain" errors
+        if is_windows && !shared {
+            let subsystem = config
+                .build
+                .as_ref()
+                .and_then(|b| b.subsystem.as_ref())
+                .map(|s| s.to_lowercase())
+                .unwrap_or_else(|| "console".to_string());
+
+            match subsystem.as_str() {
+                "windows" => cmd.arg("-mwindows"),
+                _ => cmd.arg("-mconsole"),
+            };
+        }
+    }
+
+    if let Some(build_cfg) = &config.build
+        && let Some(libs) = &build_cfg.libs
+    {
+        for lib in libs {
+            if is_msvc_like {
+                cmd.arg(format!("{}.lib", lib));
+            } else {
+                cmd.arg(format!("-l{}", lib));
+            }
+        }
+    }
+
+    if let Some(build_cfg) = &config.build
+        && let Some(ldflags) = &build_cfg.ldflags
+    {
+        if is_msvc_like {
+            msvc_linker_args.extend(ldflags.iter().cloned());
+        } else {
+            cmd.args(ldflags);
+        }
+    }
+
+    // For MSVC, pass /LIBPATH: flags via /link at the end
+    // Also ensure dynamic CRT (/MD) for compatibility with prebuilt libs like GLFW
+    if is_msvc_like {
+        if !lib_search_paths.is_empty() {
+            cmd.arg("/MD"); // Use dynamic CRT to match prebuilt dependencies
+            for search_path in &lib_search_paths {
+                msvc_linker_args.push(format!("/LIBPATH:{}", search_path.display()));
+            }
+        }
+
+        // Add subsystem flag if specified (e.g., for SDL2 with SDL2main.lib)
+        if !shared
+            && let Some(build_cfg) = &config.build
+            && let Some(subsystem) = &build_cfg.subsystem
+        {
+            let subsystem_flag = match subsystem.to_lowercase().as_str() {
+                "windows" => "/SUBSYSTEM:WINDOWS",
+                "console" => "/SUBSYSTEM:CONSOLE",
+                _ => "/SUBSYSTEM:CONSOLE",
+            };
+            msvc_linker_args.push(subsystem_flag.to_string());
+        }
+
+        if !msvc_linker_args.is_empty() {
+            cmd.arg("/link");
+            cmd.args(&msvc_linker_args);
+        }
+    }
+
+    // Apply toolchain environment variables (LIB, LIBPATH, etc.)
+    if !ctx.toolchain_env.is_empty() {
+        cmd.envs(ctx.toolchain_env);
+    }
+    cmd
+}
+
+fn link_binary(ctx: &LinkContext) -> Result<bool> {
+    if needs_clang_cl(ctx) {
+        status!(
+            "   {} Using clang-cl for MSVC library compatibility",
+            "⚡".yellow()
+        );
+    }
+
+    let output = link_command(ctx).output()?;
+    if !output.status.success() {
+        status!("{}", String::from_utf8_lossy(&output.stdout));
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        status!("{}", stderr);
+        status!("{} Linking failed", "x".red());
+
+        if let Some(suggestion) = super::feedback::FeedbackAnalyzer::analyze(&stderr) {
+            status!("\n{} {}\n", "💡 Suggestion:".bold().yellow(), suggestion);
+        }
+
+        return Ok(false);
+    }
+
+    if let Some((real, soname)) = elf_library_names(ctx.config, ctx.build_type, ctx.output) {
+        let dir = ctx.output.parent().unwrap_or(Path::new("."));
+        replace_symlink(&real, &dir.join(&soname))?;
+        replace_symlink(&soname, ctx.output)?;
+    }
+
+    Ok(true)
+}
+
+/// Point `link` at `target` (relative to the link's directory).
+#[cfg(unix)]
+fn replace_symlink(target: &str, link: &Path) -> Result<()> {
+    if fs::symlink_metadata(link).is_ok() {
+        fs::remove_file(link)?;
+    }
+    std::os::unix::fs::symlink(target, link)?;
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn replace_symlink(target: &str, link: &Path) -> Result<()> {
+    let dir = link.parent().unwrap_or(Path::new("."));
+    fs::copy(dir.join(target), link)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn versioned_so_names_follow_soname_convention() {
+        let version = semver::Version::parse("1.4.2").unwrap();
+        let (real, soname) = versioned_so_names("libdemo.so", &version);
+        assert_eq!(real, "libdemo.so.1.4.2");
+        assert_eq!(soname, "libdemo.so.1");
+    }
+}
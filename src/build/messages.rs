@@ -0,0 +1,118 @@
+//! Machine-readable build events (`--message-format=json`).
+//!
+//! In JSON mode `cx build`, `cx run` and `cx test` print one JSON object per
+//! line on stdout, each tagged with a `reason` (as cargo does):
+//!
+//! - `build-started`: `package`, `profile`
+//! - `compile-started`: `file`
+//! - `compile-finished`: `file`, `success`, `cached`, `duration_ms`
+//! - `diagnostic`: `file`, `line`, `column`, `severity`, `code`, `message`,
+//!   plus the translation `unit` that produced it
+//! - `link`: `target`, `kind`, `output`, `success`
+//! - `artifact`: `target`, `kind`, `path`, `fresh`
+//! - `test-result`: `name`, `outcome` (`pass`, `fail`, `compile-error`), `duration_ms`
+//! - `build-finished`: `success`, `duration_ms`
+//!
+//! Stdout carries nothing but these events (and, for `cx run`, the output
+//! of the program itself). Human-readable progress, script and test program
+//! output go uncolored to stderr, printed through [`status!`](crate::status).
+
+use crate::diagnostics;
+use serde_json::{Value, json};
+use std::io::Write;
+use std::path::Path;
+use std::process::Stdio;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+/// Set once JSON mode has claimed stdout for events
+static HUMAN_ON_STDERR: AtomicBool = AtomicBool::new(false);
+
+/// Whether human-readable output goes to stderr instead of stdout
+pub fn human_on_stderr() -> bool {
+    HUMAN_ON_STDERR.load(Ordering::Relaxed)
+}
+
+/// Stdout for a child whose output is meant for humans (build scripts,
+/// test programs)
+pub fn human_stdout() -> Stdio {
+    if human_on_stderr() {
+        std::io::stderr().into()
+    } else {
+        Stdio::inherit()
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum MessageFormat {
+    /// Colored progress output
+    #[default]
+    Human,
+    /// JSON-lines event stream
+    Json,
+}
+
+impl MessageFormat {
+    pub fn is_json(self) -> bool {
+        self == Self::Json
+    }
+
+    /// In JSON mode, keep stdout for events from here on
+    pub fn claim_stdout(self) {
+        if self.is_json() {
+            HUMAN_ON_STDERR.store(true, Ordering::Relaxed);
+        }
+    }
+
+    /// Print one event (JSON mode only); `fields` must be an object
+    pub fn emit(self, reason: &str, fields: Value) {
+        if !self.is_json() {
+            return;
+        }
+        let mut event = json!({ "reason": reason });
+        if let (Some(event), Value::Object(fields)) = (event.as_object_mut(), fields) {
+            event.extend(fields);
+        }
+        // One locked write per event keeps lines whole across compile threads
+        let _ = writeln!(std::io::stdout().lock(), "{}", event);
+    }
+
+    /// `compile-finished`, preceded by a `diagnostic` for each message in
+    /// the compiler's `output` (compilers run in `work_dir`)
+    pub fn compile_finished(
+        self,
+        unit: &Path,
+        work_dir: &Path,
+        output: &str,
+        success: bool,
+        cached: bool,
+        took: Duration,
+    ) {
+        if !self.is_json() {
+            return;
+        }
+        for diagnostic in diagnostics::parse(output, work_dir) {
+            self.emit(
+                "diagnostic",
+                json!({
+                    "unit": unit.display().to_string(),
+                    "file": diagnostic.file.display().to_string(),
+                    "line": diagnostic.line,
+                    "column": diagnostic.column,
+                    "severity": diagnostic.severity,
+                    "code": diagnostic.code,
+                    "message": diagnostic.message,
+                }),
+            );
+        }
+        self.emit(
+            "compile-finished",
+            json!({
+                "file": unit.display().to_string(),
+                "success": success,
+                "cached": cached,
+                "duration_ms": took.as_millis() as u64,
+            }),
+        );
+    }
+}
@@ -5,7 +5,7 @@
 //!
 //! ## Features
 //!
-//! - **Parallel compilation**: Uses rayon for lock-free, multi-core builds
+//! - **Parallel compilation**: Up to `-j` jobs at once, scheduled by [`jobs`]
 //! - **Incremental builds**: Only recompiles changed files
 //! - **Arduino support**: Build and upload Arduino sketches
 //! - **File watching**: Auto-rebuild on file save
@@ -15,22 +15,53 @@
 //!
 //! - [`core`] - Main build logic and parallel compilation
 //! - [`utils`] - Toolchain detection and helper functions
+//! - [`cross`] - Cross-compilation from a profile's `target` triple
+//! - [`features`] - Optional compile-time features from `[features]`
+//! - [`incremental`] - Per-object command signatures for rebuild decisions
+//! - [`jobs`] - Job limits, memory throttling and slowest-first scheduling
+//! - [`link`] - Linking executables, shared and static libraries
+//! - [`messages`] - JSON build events for `--message-format=json`
+//! - [`modules`] - C++20 module scanning and build ordering
+//! - [`objcache`] - Native content-addressed object cache (`~/.cx/objcache`)
+//! - [`overrides`] - Per-file flags from `[[build.overrides]]`
+//! - [`platform`] - `[target.'cfg(...)']` platform-conditional sections
+//! - [`profiles`] - `[profile:<name>]` inheritance chains
+//! - [`remote_cache`] - Shared HTTP object cache behind the local one
+//! - [`targets`] - `[[bin]]` / `[[lib]]` / `src/bin/` target resolution
 //! - [`test`] - Test runner for C/C++ unit tests
+//! - [`unity`] - Unity (jumbo) builds from batched `#include` files
 //! - [`arduino`] - Arduino/IoT build support
 
 pub mod arduino;
 mod clean;
 mod core;
+pub mod cross;
+pub mod features;
 mod feedback;
+mod incremental;
+pub mod jobs;
+mod link;
+pub mod messages;
+mod modules;
+pub mod objcache;
+pub(crate) mod overrides;
+pub mod platform;
+pub mod profiles;
+mod remote_cache;
+mod targets;
 mod test;
+mod unity;
 pub mod utils;
 mod watcher;
 
 pub use clean::clean;
 pub use core::{
-    BuildOptions, artifact_bin_dir, artifact_bin_path, artifact_profile_dir, artifact_profile_name,
+    ArtifactDir, BuildOptions, artifact_profile_dir, artifact_profile_name, artifact_triple_dir,
     binary_basename, binary_name, build_and_run, build_project,
 };
+pub use features::FeatureSelection;
+pub use messages::MessageFormat;
+pub use targets::BuildTarget;
 pub use test::run_tests;
 pub use utils::load_config;
 pub use watcher::watch;
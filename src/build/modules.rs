@@ -0,0 +1,850 @@
+//! C++20 module dependency scanning and build ordering.
+//!
+//! Every translation unit with module syntax is scanned for the module it
+//! provides and the modules, partitions and header units it imports. The
+//! compiler does the scanning when it can (P1689 output from
+//! `clang-scan-deps -format=p1689`, GCC 14+ `-fdeps-format=p1689r5` or MSVC
+//! `/scanDependencies`); otherwise a small lexer reads the declarations
+//! straight from the source. Results are cached next to the object as
+//! `<obj>.ddi`.
+//!
+//! Units are then grouped into waves: everything a unit imports is provided
+//! by an earlier wave, so each wave compiles in parallel. Header units
+//! (`import <vector>;`, `import "util.hpp";`) are built before the first
+//! wave.
+
+use crate::toolchain::CompilerType;
+use anyhow::{Result, bail};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Module declarations and imports of one translation unit
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq, Eq)]
+pub(crate) struct ModuleScan {
+    /// Module or partition (`name:part`) declared by the unit
+    pub provides: Option<String>,
+    /// `export module ...` (interface) rather than `module name:part;`
+    #[serde(default)]
+    pub is_interface: bool,
+    /// Imported modules; partitions are qualified (`name:part`). An
+    /// implementation unit (`module name;`) requires its own interface.
+    #[serde(default)]
+    pub requires: Vec<String>,
+    #[serde(default)]
+    pub header_units: Vec<HeaderUnit>,
+}
+
+impl ModuleScan {
+    pub fn uses_modules(&self) -> bool {
+        self.provides.is_some() || !self.requires.is_empty() || !self.header_units.is_empty()
+    }
+
+    fn require(&mut self, name: String) {
+        if !name.is_empty() && !self.requires.contains(&name) {
+            self.requires.push(name);
+        }
+    }
+
+    fn import_header(&mut self, unit: HeaderUnit) {
+        if !unit.name.is_empty() && !self.header_units.contains(&unit) {
+            self.header_units.push(unit);
+        }
+    }
+}
+
+/// An imported header: `import <vector>;` or `import "util.hpp";`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub(crate) struct HeaderUnit {
+    /// Header name as written between the delimiters
+    pub name: String,
+    /// `<...>` rather than `"..."`
+    pub system: bool,
+}
+
+/// A unit that provides a module (interface or partition)
+pub(crate) struct ModuleUnit {
+    pub source: PathBuf,
+    /// Set for modules shipped by a dependency
+    pub dep_root: Option<PathBuf>,
+    pub scan: ModuleScan,
+}
+
+// --- Fallback lexer ---
+
+/// Blank out comments, keeping string literals (header-unit names live there)
+fn strip_comments(content: &str) -> String {
+    let mut out = String::with_capacity(content.len());
+    let mut chars = content.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '/' if chars.peek() == Some(&'/') => {
+                for c in chars.by_ref() {
+                    if c == '\n' {
+                        out.push('\n');
+                        break;
+                    }
+                }
+            }
+            '/' if chars.peek() == Some(&'*') => {
+                chars.next();
+                let mut prev = ' ';
+                for c in chars.by_ref() {
+                    if c == '\n' {
+                        out.push('\n');
+                    }
+                    if prev == '*' && c == '/' {
+                        break;
+                    }
+                    prev = c;
+                }
+                out.push(' ');
+            }
+            '"' | '\'' => {
+                out.push(c);
+                let mut escaped = false;
+                for next in chars.by_ref() {
+                    out.push(next);
+                    if next == '\n' || (next == c && !escaped) {
+                        break;
+                    }
+                    escaped = next == '\\' && !escaped;
+                }
+            }
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+/// `keyword` followed by something that can't continue an identifier
+fn strip_keyword<'a>(text: &'a str, keyword: &str) -> Option<&'a str> {
+    let rest = text.strip_prefix(keyword)?;
+    match rest.chars().next() {
+        Some(c) if c.is_alphanumeric() || c == '_' => None,
+        _ => Some(rest.trim_start()),
+    }
+}
+
+/// Drop `[[attributes]]` and whitespace from a module name
+fn clean_name(text: &str) -> String {
+    let text = text.split("[[").next().unwrap_or_default();
+    text.chars().filter(|c| !c.is_whitespace()).collect()
+}
+
+/// Module declarations and import statements, without the trailing `;`
+fn module_statements(text: &str) -> Vec<String> {
+    let mut statements = Vec::new();
+    let mut pending: Option<String> = None;
+    for line in text.lines() {
+        let trimmed = line.trim();
+        if let Some(mut current) = pending.take() {
+            current.push(' ');
+            current.push_str(trimmed);
+            match current.split_once(';') {
+                Some((statement, _)) => statements.push(statement.trim().to_string()),
+                None => pending = Some(current),
+            }
+            continue;
+        }
+        if trimmed.starts_with('#') {
+            continue;
+        }
+        let body = strip_keyword(trimmed, "export").unwrap_or(trimmed);
+        if strip_keyword(body, "module").is_none() && strip_keyword(body, "import").is_none() {
+            continue;
+        }
+        match trimmed.split_once(';') {
+            Some((statement, _)) => statements.push(statement.trim().to_string()),
+            None => pending = Some(trimmed.to_string()),
+        }
+    }
+    statements
+}
+
+/// Read module declarations and imports without running the preprocessor.
+/// Imports inside `#if` blocks all count.
+pub(crate) fn lex_source(content: &str) -> ModuleScan {
+    let mut scan = ModuleScan::default();
+    // Primary module name, for `import :part;`
+    let mut module_name: Option<String> = None;
+
+    for statement in module_statements(&strip_comments(content)) {
+        let (exported, body) = match strip_keyword(&statement, "export") {
+            Some(body) => (true, body),
+            None => (false, statement.as_str()),
+        };
+
+        if let Some(rest) = strip_keyword(body, "module") {
+            // `module;` opens the global module fragment, `module :private;`
+            // the private one
+            if !rest.starts_with(|c: char| c.is_alphabetic() || c == '_') {
+                continue;
+            }
+            let name = clean_name(rest);
+            module_name = Some(name.split(':').next().unwrap_or_default().to_string());
+            if name.contains(':') || exported {
+                scan.provides = Some(name);
+                scan.is_interface = exported;
+            } else {
+                scan.require(name);
+            }
+        } else if let Some(rest) = strip_keyword(body, "import") {
+            if let Some(header) = rest.strip_prefix('<') {
+                scan.import_header(HeaderUnit {
+                    name: header
+                        .split('>')
+                        .next()
+                        .unwrap_or_default()
+                        .trim()
+                        .to_string(),
+                    system: true,
+                });
+            } else if let Some(header) = rest.strip_prefix('"') {
+                scan.import_header(HeaderUnit {
+                    name: header.split('"').next().unwrap_or_default().to_string(),
+                    system: false,
+                });
+            } else if let Some(partition) = rest.strip_prefix(':') {
+                if let Some(module) = &module_name {
+                    scan.require(format!("{}:{}", module, clean_name(partition)));
+                }
+            } else if rest.starts_with(|c: char| c.is_alphabetic() || c == '_') {
+                scan.require(clean_name(rest));
+            }
+        }
+    }
+    scan
+}
+
+// --- Compiler scanning (P1689) ---
+
+/// Parse a P1689 dependency file
+pub(crate) fn parse_p1689(json: &str) -> Option<ModuleScan> {
+    let value: serde_json::Value = serde_json::from_str(json).ok()?;
+    let rule = value.get("rules")?.as_array()?.first()?;
+    let mut scan = ModuleScan::default();
+
+    if let Some(provided) = rule
+        .get("provides")
+        .and_then(|p| p.as_array())
+        .and_then(|p| p.first())
+    {
+        scan.provides = provided
+            .get("logical-name")
+            .and_then(|n| n.as_str())
+            .map(str::to_string);
+        scan.is_interface = provided
+            .get("is-interface")
+            .and_then(|i| i.as_bool())
+            .unwrap_or(true);
+    }
+
+    let requires = rule.get("requires").and_then(|r| r.as_array());
+    for required in requires.into_iter().flatten() {
+        let Some(name) = required.get("logical-name").and_then(|n| n.as_str()) else {
+            continue;
+        };
+        match required.get("lookup-method").and_then(|m| m.as_str()) {
+            Some("include-angle") => scan.import_header(HeaderUnit {
+                name: name.to_string(),
+                system: true,
+            }),
+            Some("include-quote") => scan.import_header(HeaderUnit {
+                name: name.to_string(),
+                system: false,
+            }),
+            _ => scan.require(name.to_string()),
+        }
+    }
+    Some(scan)
+}
+
+/// What a cached `<obj>.ddi` was produced from
+#[derive(Serialize, Deserialize)]
+struct CachedScan {
+    args: Vec<String>,
+    scan: ModuleScan,
+}
+
+/// Scans sources, preferring the compiler's own dependency scanner
+pub(crate) struct Scanner<'a> {
+    pub flavor: ModuleFlavor,
+    pub compiler: &'a str,
+    /// Preprocessor-relevant flags (defines, include paths, standard)
+    pub args: &'a [String],
+    pub env: &'a HashMap<String, String>,
+    /// Cleared after the first failure (e.g. GCC older than 14)
+    compiler_works: AtomicBool,
+}
+
+impl<'a> Scanner<'a> {
+    pub fn new(
+        flavor: ModuleFlavor,
+        compiler: &'a str,
+        args: &'a [String],
+        env: &'a HashMap<String, String>,
+    ) -> Self {
+        Self {
+            flavor,
+            compiler,
+            args,
+            env,
+            compiler_works: AtomicBool::new(true),
+        }
+    }
+
+    /// Scan `source` (`work_source` is the same file as seen from `.cx`).
+    /// Files without module syntax are never handed to the compiler.
+    pub fn scan(&self, source: &Path, work_source: &Path, ddi_path: &Path) -> ModuleScan {
+        let Ok(content) = fs::read_to_string(source) else {
+            return ModuleScan::default();
+        };
+        let lexed = lex_source(&content);
+        if !lexed.uses_modules() {
+            return lexed;
+        }
+
+        let source_mtime = fs::metadata(source).and_then(|m| m.modified()).ok();
+        let ddi_mtime = fs::metadata(ddi_path).and_then(|m| m.modified()).ok();
+        if ddi_mtime >= source_mtime
+            && let Ok(cached) = fs::read_to_string(ddi_path)
+            && let Ok(cached) = serde_json::from_str::<CachedScan>(&cached)
+            && cached.args == self.args
+        {
+            return cached.scan;
+        }
+
+        let scan = self
+            .scan_with_compiler(work_source, ddi_path)
+            .unwrap_or(lexed);
+        let cached = CachedScan {
+            args: self.args.to_vec(),
+            scan,
+        };
+        if let Ok(json) = serde_json::to_string(&cached) {
+            let _ = fs::write(ddi_path, json);
+        }
+        cached.scan
+    }
+
+    fn scan_with_compiler(&self, work_source: &Path, ddi_path: &Path) -> Option<ModuleScan> {
+        if !self.compiler_works.load(Ordering::Relaxed) {
+            return None;
+        }
+        let p1689_path = ddi_path.with_extension("p1689.json");
+        let p1689_arg = p1689_path
+            .strip_prefix(".cx")
+            .unwrap_or(&p1689_path)
+            .to_string_lossy()
+            .to_string();
+        let source = work_source.to_string_lossy().to_string();
+
+        let mut cmd = match self.flavor {
+            ModuleFlavor::Clang => {
+                let scanner = clang_scan_deps(self.compiler)?;
+                let mut cmd = Command::new(scanner);
+                cmd.args(["-format=p1689", "--", self.compiler]);
+                cmd.args(self.args);
+                cmd.args(["-x", "c++", &source, "-c", "-o"]);
+                cmd.arg(format!("{p1689_arg}.o"));
+                cmd
+            }
+            ModuleFlavor::Gcc => {
+                let mut cmd = Command::new(self.compiler);
+                cmd.args(self.args);
+                cmd.args(["-fmodules-ts", "-E", "-x", "c++", &source]);
+                cmd.arg("-fdeps-format=p1689r5");
+                cmd.arg(format!("-fdeps-file={p1689_arg}"));
+                cmd.arg(format!("-fdeps-target={p1689_arg}.o"));
+                cmd.args(["-MD", "-MF"]);
+                cmd.arg(format!("{p1689_arg}.d"));
+                cmd.arg("-o");
+                cmd.arg(format!("{p1689_arg}.i"));
+                cmd
+            }
+            ModuleFlavor::Msvc => {
+                let mut cmd = Command::new(self.compiler);
+                cmd.args(self.args);
+                cmd.args(["/nologo", "/TP", "/scanDependencies", &p1689_arg, &source]);
+                cmd
+            }
+        };
+        cmd.current_dir(".cx");
+        if !self.env.is_empty() {
+            cmd.envs(self.env);
+        }
+
+        let output = cmd.output().ok();
+        let json = match (&output, self.flavor) {
+            (Some(out), ModuleFlavor::Clang) if out.status.success() => {
+                Some(String::from_utf8_lossy(&out.stdout).to_string())
+            }
+            (Some(out), _) if out.status.success() => fs::read_to_string(&p1689_path).ok(),
+            _ => None,
+        };
+        for leftover in ["", ".d", ".i"] {
+            let _ = fs::remove_file(format!("{}{}", p1689_path.display(), leftover));
+        }
+
+        let scan = json.as_deref().and_then(parse_p1689);
+        if scan.is_none() {
+            self.compiler_works.store(false, Ordering::Relaxed);
+        }
+        scan
+    }
+}
+
+/// `clang-scan-deps` next to the compiler, or on PATH
+fn clang_scan_deps(compiler: &str) -> Option<PathBuf> {
+    let name = if cfg!(windows) {
+        "clang-scan-deps.exe"
+    } else {
+        "clang-scan-deps"
+    };
+    let sibling = Path::new(compiler).with_file_name(name);
+    if sibling.is_file() {
+        return Some(sibling);
+    }
+    super::utils::find_in_path("clang-scan-deps")
+}
+
+// --- Ordering ---
+
+/// Group units into waves: everything a unit imports from another unit is
+/// provided by an earlier wave. Imports nobody here provides (`std`, header
+/// units, ...) are left to the compiler.
+pub(crate) fn waves(units: &[ModuleUnit]) -> Result<Vec<Vec<usize>>> {
+    let mut providers: HashMap<&str, usize> = HashMap::new();
+    for (idx, unit) in units.iter().enumerate() {
+        if let Some(name) = &unit.scan.provides
+            && let Some(previous) = providers.insert(name, idx)
+        {
+            bail!(
+                "Module '{}' is declared by both {} and {}",
+                name,
+                units[previous].source.display(),
+                unit.source.display()
+            );
+        }
+    }
+
+    let deps: Vec<Vec<usize>> = units
+        .iter()
+        .enumerate()
+        .map(|(idx, unit)| {
+            let mut deps: Vec<usize> = unit
+                .scan
+                .requires
+                .iter()
+                .filter_map(|name| providers.get(name.as_str()).copied())
+                .filter(|&dep| dep != idx)
+                .collect();
+            deps.sort_unstable();
+            deps.dedup();
+            deps
+        })
+        .collect();
+
+    let mut wave_of: Vec<Option<usize>> = vec![None; units.len()];
+    let mut waves: Vec<Vec<usize>> = Vec::new();
+    let mut remaining = units.len();
+    while remaining > 0 {
+        let wave: Vec<usize> = (0..units.len())
+            .filter(|&idx| wave_of[idx].is_none())
+            .filter(|&idx| {
+                deps[idx]
+                    .iter()
+                    .all(|&dep| wave_of[dep].is_some_and(|w| w < waves.len()))
+            })
+            .collect();
+        if wave.is_empty() {
+            bail!(
+                "Module import cycle: {}",
+                describe_cycle(units, &deps, &wave_of)
+            );
+        }
+        for &idx in &wave {
+            wave_of[idx] = Some(waves.len());
+        }
+        remaining -= wave.len();
+        waves.push(wave);
+    }
+    Ok(waves)
+}
+
+/// `a → b → a` for some cycle among the unplaced units
+fn describe_cycle(units: &[ModuleUnit], deps: &[Vec<usize>], placed: &[Option<usize>]) -> String {
+    let label = |idx: usize| {
+        units[idx]
+            .scan
+            .provides
+            .clone()
+            .unwrap_or_else(|| units[idx].source.display().to_string())
+    };
+    // Every unplaced unit has an unplaced dependency; walk until one repeats
+    let Some(mut current) = (0..units.len()).find(|&idx| placed[idx].is_none()) else {
+        return String::new();
+    };
+    let mut path = Vec::new();
+    while !path.contains(&current) {
+        path.push(current);
+        match deps[current].iter().find(|&&dep| placed[dep].is_none()) {
+            Some(&next) => current = next,
+            None => break,
+        }
+    }
+    let start = path.iter().position(|&idx| idx == current).unwrap_or(0);
+    let mut names: Vec<String> = path[start..].iter().map(|&idx| label(idx)).collect();
+    names.push(label(current));
+    names.join(" → ")
+}
+
+/// Units reachable through imports from `roots` (dependency modules are only
+/// built when something imports them)
+pub(crate) fn reachable<'a>(
+    units: &[ModuleUnit],
+    roots: impl IntoIterator<Item = &'a str>,
+) -> HashSet<usize> {
+    let providers: HashMap<&str, usize> = units
+        .iter()
+        .enumerate()
+        .filter_map(|(idx, unit)| unit.scan.provides.as_deref().map(|name| (name, idx)))
+        .collect();
+    let mut seen = HashSet::new();
+    let mut stack: Vec<usize> = roots
+        .into_iter()
+        .filter_map(|name| providers.get(name).copied())
+        .collect();
+    while let Some(idx) = stack.pop() {
+        if seen.insert(idx) {
+            stack.extend(
+                units[idx]
+                    .scan
+                    .requires
+                    .iter()
+                    .filter_map(|name| providers.get(name.as_str()).copied()),
+            );
+        }
+    }
+    seen
+}
+
+/// Sort module sources so that imported modules come first (lexer only;
+/// used where modules are compiled one by one)
+pub(crate) fn order_by_imports(sources: &[PathBuf]) -> Result<Vec<PathBuf>> {
+    let units: Vec<ModuleUnit> = sources
+        .iter()
+        .map(|source| ModuleUnit {
+            source: source.clone(),
+            dep_root: None,
+            scan: lex_source(&fs::read_to_string(source).unwrap_or_default()),
+        })
+        .collect();
+    Ok(waves(&units)?
+        .into_iter()
+        .flatten()
+        .map(|idx| units[idx].source.clone())
+        .collect())
+}
+
+// --- Compiler flags ---
+
+/// Compiler family, as far as module flags are concerned
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum ModuleFlavor {
+    Gcc,
+    Clang,
+    Msvc,
+}
+
+impl ModuleFlavor {
+    pub fn detect(compiler: &str, compiler_type: Option<&CompilerType>, is_msvc: bool) -> Self {
+        if is_msvc {
+            return Self::Msvc;
+        }
+        match compiler_type {
+            Some(CompilerType::MSVC) => Self::Msvc,
+            Some(CompilerType::Clang) | Some(CompilerType::ClangCL) => Self::Clang,
+            Some(CompilerType::GCC) => Self::Gcc,
+            None if compiler.contains("clang") || compiler.contains("em++") => Self::Clang,
+            None => Self::Gcc,
+        }
+    }
+
+    fn bmi_extension(self) -> &'static str {
+        match self {
+            Self::Gcc => "gcm",
+            Self::Clang => "pcm",
+            Self::Msvc => "ifc",
+        }
+    }
+
+    /// Where the compiled interface of `module` ends up (project-relative).
+    /// GCC keeps its own `gcm.cache` in the working directory.
+    pub fn bmi_path(self, bmi_dir: &Path, module: &str) -> PathBuf {
+        let file = format!("{}.{}", module.replace(':', "-"), self.bmi_extension());
+        match self {
+            Self::Gcc => Path::new(".cx").join("gcm.cache").join(file),
+            Self::Clang | Self::Msvc => bmi_dir.join(file),
+        }
+    }
+
+    /// Record of a built header unit (project-relative). GCC picks the
+    /// location of the real CMI itself, so it only gets a stamp file.
+    pub fn header_unit_path(self, bmi_dir: &Path, unit: &HeaderUnit, resolved: &str) -> PathBuf {
+        let name: String = resolved
+            .chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() || c == '.' {
+                    c
+                } else {
+                    '_'
+                }
+            })
+            .collect();
+        let prefix = if unit.system { "sys" } else { "usr" };
+        let extension = match self {
+            Self::Gcc => "stamp",
+            _ => self.bmi_extension(),
+        };
+        bmi_dir
+            .join("header_units")
+            .join(format!("{prefix}_{name}.{extension}"))
+    }
+
+    /// Flags before the source of a unit providing a module or partition;
+    /// `bmi` is its interface path as seen from `.cx`
+    pub fn unit_flags(self, scan: &ModuleScan, bmi: Option<&Path>) -> Vec<String> {
+        let mut flags = Vec::new();
+        match self {
+            Self::Gcc => {
+                flags.push("-fmodules-ts".to_string());
+                // `.cppm`/`.ixx`/`.mpp` aren't C++ extensions to GCC
+                flags.extend(["-x".to_string(), "c++".to_string()]);
+            }
+            Self::Clang => {
+                if let Some(bmi) = bmi {
+                    flags.extend(["-x".to_string(), "c++-module".to_string()]);
+                    flags.push(format!("-fmodule-output={}", bmi.display()));
+                }
+            }
+            Self::Msvc => {
+                if scan.provides.is_some() {
+                    flags.push(if scan.is_interface {
+                        "/interface".to_string()
+                    } else {
+                        "/internalPartition".to_string()
+                    });
+                }
+                flags.push("/TP".to_string());
+                if let Some(bmi) = bmi {
+                    flags.extend(["/ifcOutput".to_string(), bmi.display().to_string()]);
+                }
+            }
+        }
+        flags
+    }
+
+    /// Flags letting a unit find the interfaces it imports; `bmi_dir` and
+    /// the header unit records are as seen from `.cx`
+    pub fn consumer_flags(
+        self,
+        bmi_dir: &Path,
+        header_units: &[(HeaderUnit, PathBuf)],
+    ) -> Vec<String> {
+        let mut flags = Vec::new();
+        match self {
+            // gcm.cache in the working directory
+            Self::Gcc => {}
+            Self::Clang => {
+                flags.push(format!("-fprebuilt-module-path={}", bmi_dir.display()));
+                for (_, bmi) in header_units {
+                    flags.push(format!("-fmodule-file={}", bmi.display()));
+                }
+            }
+            Self::Msvc => {
+                flags.extend(["/ifcSearchDir".to_string(), bmi_dir.display().to_string()]);
+                for (unit, bmi) in header_units {
+                    let kind = if unit.system { "angle" } else { "quote" };
+                    flags.push(format!("/headerUnit:{kind}"));
+                    flags.push(format!("{}={}", unit.name, bmi.display()));
+                }
+            }
+        }
+        flags
+    }
+
+    /// Flags compiling a header unit; `header` is the header name for system
+    /// headers and its path as seen from `.cx` otherwise
+    pub fn header_unit_flags(self, unit: &HeaderUnit, header: &str, bmi: &Path) -> Vec<String> {
+        let header = header.to_string();
+        match (self, unit.system) {
+            (Self::Gcc, true) => vec![
+                "-fmodules-ts".into(),
+                "-fmodule-header=system".into(),
+                "-x".into(),
+                "c++-system-header".into(),
+                header,
+            ],
+            (Self::Gcc, false) => vec![
+                "-fmodules-ts".into(),
+                "-fmodule-header".into(),
+                "-x".into(),
+                "c++-header".into(),
+                header,
+            ],
+            (Self::Clang, system) => vec![
+                format!("-fmodule-header={}", if system { "system" } else { "user" }),
+                "-x".into(),
+                if system {
+                    "c++-system-header".into()
+                } else {
+                    "c++-user-header".into()
+                },
+                header,
+                "-o".into(),
+                bmi.display().to_string(),
+            ],
+            (Self::Msvc, system) => vec![
+                "/exportHeader".into(),
+                format!("/headerName:{}", if system { "angle" } else { "quote" }),
+                header,
+                "/ifcOutput".into(),
+                bmi.display().to_string(),
+            ],
+        }
+    }
+}
+
+/// Header units imported anywhere, each with the directory of one importer
+/// (quoted names are looked up relative to it first)
+pub(crate) fn collect_header_units<'a>(
+    scans: impl IntoIterator<Item = (&'a Path, &'a ModuleScan)>,
+) -> BTreeMap<HeaderUnit, PathBuf> {
+    let mut units = BTreeMap::new();
+    for (source, scan) in scans {
+        for unit in &scan.header_units {
+            units
+                .entry(unit.clone())
+                .or_insert_with(|| source.parent().map(Path::to_path_buf).unwrap_or_default());
+        }
+    }
+    units
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unit(name: &str, source: &str) -> ModuleUnit {
+        ModuleUnit {
+            source: PathBuf::from(name),
+            dep_root: None,
+            scan: lex_source(source),
+        }
+    }
+
+    #[test]
+    fn test_lex_declarations_and_imports() {
+        let scan = lex_source(
+            "module;\n#include <cstdio>\n// import fake;\n/* import also_fake; */\n\
+             export module app;\nexport import :util;\nimport base;\nimport <vector>;\n\
+             import \"config.hpp\";\nimport\n  std;\nmodule :private;\n",
+        );
+        assert_eq!(scan.provides.as_deref(), Some("app"));
+        assert!(scan.is_interface);
+        assert_eq!(scan.requires, vec!["app:util", "base", "std"]);
+        assert_eq!(
+            scan.header_units,
+            vec![
+                HeaderUnit {
+                    name: "vector".into(),
+                    system: true
+                },
+                HeaderUnit {
+                    name: "config.hpp".into(),
+                    system: false
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_lex_partitions_and_implementation_units() {
+        let partition = lex_source("module app:detail;\nimport :types;\n");
+        assert_eq!(partition.provides.as_deref(), Some("app:detail"));
+        assert!(!partition.is_interface);
+        assert_eq!(partition.requires, vec!["app:types"]);
+
+        let implementation = lex_source("module app;\nint important = 1;\n");
+        assert_eq!(implementation.provides, None);
+        assert_eq!(implementation.requires, vec!["app"]);
+
+        let plain = lex_source("#include \"x.h\"\nint main() { return 0; }\n");
+        assert!(!plain.uses_modules());
+    }
+
+    #[test]
+    fn test_parse_p1689() {
+        let json = r#"{"version":1,"revision":0,"rules":[{"primary-output":"a.o",
+            "provides":[{"logical-name":"app:util","is-interface":false}],
+            "requires":[{"logical-name":"base"},
+                        {"logical-name":"vector","lookup-method":"include-angle"}]}]}"#;
+        let scan = parse_p1689(json).unwrap();
+        assert_eq!(scan.provides.as_deref(), Some("app:util"));
+        assert!(!scan.is_interface);
+        assert_eq!(scan.requires, vec!["base"]);
+        assert_eq!(scan.header_units.len(), 1);
+        assert!(scan.header_units[0].system);
+    }
+
+    #[test]
+    fn test_waves_follow_imports() {
+        let units = vec![
+            unit(
+                "a.cppm",
+                "export module app;\nexport import :util;\nimport base;",
+            ),
+            unit("b.cppm", "export module base;\nimport std;"),
+            unit("c.cppm", "export module app:util;\nimport base;"),
+            unit("d.cppm", "export module other;"),
+        ];
+        assert_eq!(waves(&units).unwrap(), vec![vec![1, 3], vec![2], vec![0]]);
+    }
+
+    #[test]
+    fn test_waves_report_cycles_and_duplicates() {
+        let cycle = vec![
+            unit("a.cppm", "export module a;\nimport b;"),
+            unit("b.cppm", "export module b;\nimport a;"),
+        ];
+        let err = waves(&cycle).unwrap_err().to_string();
+        assert!(err.contains("a → b → a"), "{err}");
+
+        let duplicate = vec![
+            unit("a.cppm", "export module a;"),
+            unit("b.cppm", "export module a;"),
+        ];
+        assert!(
+            waves(&duplicate)
+                .unwrap_err()
+                .to_string()
+                .contains("a.cppm")
+        );
+    }
+
+    #[test]
+    fn test_reachable_skips_unused_modules() {
+        let units = vec![
+            unit("fmt.cppm", "export module fmt;\nimport fmt_core;"),
+            unit("core.cppm", "export module fmt_core;"),
+            unit("json.cppm", "export module json;"),
+        ];
+        let needed = reachable(&units, ["fmt"]);
+        assert_eq!(needed, HashSet::from([0, 1]));
+    }
+}
@@ -0,0 +1,429 @@
+//! Native compilation cache.
+//!
+//! Objects are stored content-addressed under `~/.cx/objcache` (or
+//! `CX_OBJCACHE_DIR`), keyed on the preprocessed source, the compiler's
+//! `--version` banner and the compile flags, so `cx clean && cx build`, switching
+//! branches or flipping between profiles reuses earlier work across every
+//! project on the machine — without ccache installed.
+//!
+//! Configure with `[cache] local = false` / `max_size = "5G"`, or disable
+//! per invocation with `CX_OBJCACHE=0`. A shared HTTP cache can sit behind
+//! it (see [`super::remote_cache`]).
+
+use super::remote_cache::RemoteCache;
+use crate::config::CxConfig;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::SystemTime;
+use walkdir::WalkDir;
+
+/// Bump when the key layout changes so old entries are never matched
+const KEY_VERSION: &str = "cx-objcache-v1";
+const DEFAULT_MAX_SIZE: u64 = 5 * 1024 * 1024 * 1024;
+const STATS_FILE: &str = "stats.json";
+
+/// Counters persisted in `<cache>/stats.json`
+#[derive(Serialize, Deserialize, Default, Debug, Clone, Copy)]
+pub struct CacheCounters {
+    pub hits: u64,
+    pub misses: u64,
+    /// Hits served by the remote cache (included in `hits`)
+    #[serde(default)]
+    pub remote_hits: u64,
+    /// Approximate bytes stored; recomputed exactly whenever we evict
+    pub size: u64,
+}
+
+/// Object cache directory: `CX_OBJCACHE_DIR` or `~/.cx/objcache`
+pub fn cache_dir() -> Option<PathBuf> {
+    if let Some(dir) = std::env::var_os("CX_OBJCACHE_DIR") {
+        return Some(PathBuf::from(dir));
+    }
+    dirs::home_dir().map(|home| home.join(".cx").join("objcache"))
+}
+
+/// Parse a size such as "5G", "512M", "100k" or plain bytes
+pub fn parse_size(raw: &str) -> Option<u64> {
+    let raw = raw.trim();
+    let split = raw
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(raw.len());
+    let (number, unit) = raw.split_at(split);
+    let number: f64 = number.parse().ok()?;
+    let multiplier: u64 = match unit.trim().to_ascii_lowercase().as_str() {
+        "" | "b" => 1,
+        "k" | "kb" | "kib" => 1024,
+        "m" | "mb" | "mib" => 1024 * 1024,
+        "g" | "gb" | "gib" => 1024 * 1024 * 1024,
+        _ => return None,
+    };
+    Some((number * multiplier as f64) as u64)
+}
+
+/// `[cache] max_size`, defaulting to 5 GiB
+pub fn max_size(config: Option<&CxConfig>) -> u64 {
+    config
+        .and_then(|c| c.cache.as_ref())
+        .and_then(|c| c.max_size.as_deref())
+        .and_then(parse_size)
+        .unwrap_or(DEFAULT_MAX_SIZE)
+}
+
+pub fn read_counters(dir: &Path) -> CacheCounters {
+    fs::read_to_string(dir.join(STATS_FILE))
+        .ok()
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default()
+}
+
+fn write_counters(dir: &Path, counters: &CacheCounters) -> Result<()> {
+    fs::write(dir.join(STATS_FILE), serde_json::to_string(counters)?)?;
+    Ok(())
+}
+
+/// Every cached object with its size and last use, oldest first
+pub fn entries(dir: &Path) -> Vec<(PathBuf, u64, SystemTime)> {
+    let mut entries: Vec<(PathBuf, u64, SystemTime)> = WalkDir::new(dir)
+        .min_depth(2)
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_type().is_file())
+        .filter_map(|e| {
+            let meta = e.metadata().ok()?;
+            let used = meta.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+            Some((e.into_path(), meta.len(), used))
+        })
+        .collect();
+    entries.sort_by_key(|(_, _, used)| *used);
+    entries
+}
+
+/// Objects with a temporary name while being written
+fn is_partial(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext == "tmp")
+}
+
+/// Drop least-recently-used objects until the cache is below 90% of
+/// `max_size`. Returns the resulting total size.
+pub fn evict(dir: &Path, max_size: u64) -> Result<u64> {
+    let entries = entries(dir);
+    let mut total: u64 = entries.iter().map(|(_, size, _)| size).sum();
+    let target = max_size / 10 * 9;
+    for (path, size, _) in entries {
+        if total <= target && !is_partial(&path) {
+            continue;
+        }
+        if fs::remove_file(&path).is_ok() {
+            total = total.saturating_sub(size);
+        }
+    }
+    Ok(total)
+}
+
+/// Handle to the object cache for one build
+pub(crate) struct ObjectCache {
+    dir: PathBuf,
+    max_size: u64,
+    hits: AtomicU64,
+    misses: AtomicU64,
+    stored: AtomicU64,
+    remote: Option<RemoteCache>,
+}
+
+impl ObjectCache {
+    /// `None` when disabled via `[cache] local = false` or `CX_OBJCACHE=0`
+    pub fn open(config: &CxConfig) -> Option<Self> {
+        let disabled_by_env =
+            std::env::var("CX_OBJCACHE").is_ok_and(|v| matches!(v.as_str(), "0" | "false" | "off"));
+        let enabled = config.cache.as_ref().and_then(|c| c.local).unwrap_or(true);
+        if disabled_by_env || !enabled {
+            return None;
+        }
+
+        let dir = cache_dir()?;
+        fs::create_dir_all(&dir).ok()?;
+        Some(Self {
+            dir,
+            max_size: max_size(Some(config)),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+            stored: AtomicU64::new(0),
+            remote: RemoteCache::from_config(config.cache.as_ref()),
+        })
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    pub fn remote(&self) -> Option<&RemoteCache> {
+        self.remote.as_ref()
+    }
+
+    fn entry_path(&self, key: &str) -> PathBuf {
+        self.dir.join(&key[..2]).join(format!("{key}.obj"))
+    }
+
+    /// Copy a cached object to `obj_path`, trying the local cache, then
+    /// the remote one. `true` on a hit.
+    pub fn fetch(&self, key: &str, obj_path: &Path) -> bool {
+        let entry = self.entry_path(key);
+        if entry.exists() && fs::copy(&entry, obj_path).is_ok() {
+            // Mark as recently used for LRU eviction
+            if let Ok(file) = fs::File::options().write(true).open(&entry) {
+                let _ = file.set_modified(SystemTime::now());
+            }
+            self.hits.fetch_add(1, Ordering::Relaxed);
+            return true;
+        }
+
+        if let Some(remote) = &self.remote
+            && let Some(bytes) = remote.get(key)
+            && fs::write(obj_path, &bytes).is_ok()
+        {
+            self.write_entry(key, &bytes);
+            self.hits.fetch_add(1, Ordering::Relaxed);
+            return true;
+        }
+
+        self.misses.fetch_add(1, Ordering::Relaxed);
+        false
+    }
+
+    /// Store a freshly compiled object locally (and remotely in write
+    /// mode). Failures only cost a future hit.
+    pub fn store(&self, key: &str, obj_path: &Path) {
+        let Ok(bytes) = fs::read(obj_path) else {
+            return;
+        };
+        self.write_entry(key, &bytes);
+        if let Some(remote) = &self.remote {
+            remote.put(key, &bytes);
+        }
+    }
+
+    fn write_entry(&self, key: &str, bytes: &[u8]) {
+        let entry = self.entry_path(key);
+        let Some(parent) = entry.parent() else {
+            return;
+        };
+        if fs::create_dir_all(parent).is_err() {
+            return;
+        }
+        // Write under a unique name, then rename so readers never see a
+        // partial object (other cx processes may share the cache)
+        let tmp = entry.with_extension(format!("{}.tmp", std::process::id()));
+        if fs::write(&tmp, bytes).is_ok() && fs::rename(&tmp, &entry).is_ok() {
+            self.stored.fetch_add(bytes.len() as u64, Ordering::Relaxed);
+        } else {
+            let _ = fs::remove_file(&tmp);
+        }
+    }
+
+    pub fn hits(&self) -> u64 {
+        self.hits.load(Ordering::Relaxed)
+    }
+
+    pub fn misses(&self) -> u64 {
+        self.misses.load(Ordering::Relaxed)
+    }
+
+    /// Persist counters and enforce the size limit
+    pub fn finish(&self) -> Result<()> {
+        let mut counters = read_counters(&self.dir);
+        counters.hits += self.hits();
+        counters.misses += self.misses();
+        counters.remote_hits += self.remote.as_ref().map_or(0, |r| r.hits());
+        counters.size += self.stored.load(Ordering::Relaxed);
+        if counters.size > self.max_size {
+            counters.size = evict(&self.dir, self.max_size)
+                .with_context(|| format!("Failed to trim {}", self.dir.display()))?;
+        }
+        write_counters(&self.dir, &counters)
+    }
+}
+
+/// Stable identity of a compiler for cache keys and command signatures: a
+/// SHA-256 of its path and `--version` banner. MSVC prints the banner on
+/// stderr, so both streams are hashed.
+pub(crate) fn compiler_identity(compiler: &str, env: &HashMap<String, String>) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(compiler.as_bytes());
+    hasher.update([0]);
+    if let Ok(output) = Command::new(compiler).arg("--version").envs(env).output() {
+        hasher.update(&output.stdout);
+        hasher.update([0]);
+        hasher.update(&output.stderr);
+    }
+    let digest = hasher.finalize();
+    digest.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+/// Inputs of a cache key besides the preprocessed source
+pub(crate) struct KeyContext<'a> {
+    /// Compiler followed by its arguments (without any ccache wrapper)
+    pub args: &'a [String],
+    /// Per-object paths (source, object, dependency file) left out of the
+    /// key so identical code in other projects or profiles still matches
+    pub volatile: &'a [String],
+    /// [`compiler_identity`] of the compiler
+    pub toolchain: &'a str,
+    pub is_msvc: bool,
+    pub env: &'a HashMap<String, String>,
+    /// Where the compiler runs (`.cx`)
+    pub work_dir: &'a Path,
+    /// Project root; normalised out of the key unless debug info embeds it
+    pub project_dir: &'a Path,
+}
+
+/// Preprocess the source and hash everything that affects the object.
+/// `None` when preprocessing fails; the real compile reports the error.
+///
+/// Preprocessing also refreshes the object's dependency file (`.d` via
+/// `-MMD`, `<obj>.json` from `/showIncludes` on MSVC), so a cache hit
+/// leaves the same incremental state as a compile.
+pub(crate) fn compute_key(ctx: &KeyContext, dep_json: Option<&Path>) -> Option<String> {
+    let (program, rest) = ctx.args.split_first()?;
+
+    let mut pp_args: Vec<String> = Vec::with_capacity(rest.len() + 1);
+    let mut iter = rest.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "-c" => pp_args.push("-E".to_string()),
+            "/c" => {
+                pp_args.push("/E".to_string());
+                pp_args.push("/showIncludes".to_string());
+            }
+            "-o" | "/sourceDependencies" => {
+                iter.next();
+            }
+            a if ctx.is_msvc && a.starts_with("/Fo") => {}
+            _ => pp_args.push(arg.clone()),
+        }
+    }
+
+    let mut cmd = Command::new(program);
+    cmd.args(&pp_args).current_dir(ctx.work_dir);
+    if !ctx.env.is_empty() {
+        cmd.envs(ctx.env);
+    }
+    let output = cmd.output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+
+    if ctx.is_msvc
+        && let Some(json_path) = dep_json
+    {
+        write_msvc_dependencies(&String::from_utf8_lossy(&output.stderr), json_path);
+    }
+
+    let project = ctx.project_dir.to_string_lossy().to_string();
+    let debug_info = ctx
+        .args
+        .iter()
+        .any(|a| a.starts_with("-g") || a.starts_with("/Z7") || a.starts_with("/Zi"));
+
+    let mut hasher = Sha256::new();
+    hasher.update(KEY_VERSION.as_bytes());
+    hasher.update([0]);
+    hasher.update(ctx.toolchain.as_bytes());
+    hasher.update([0]);
+    for arg in ctx.args {
+        if ctx.volatile.iter().any(|v| arg.contains(v.as_str())) {
+            continue;
+        }
+        hasher.update(arg.replace(&project, ".").as_bytes());
+        hasher.update([0]);
+    }
+    // Debug info records the compile directory, so those objects are only
+    // shared within one project
+    if debug_info {
+        hasher.update(project.as_bytes());
+        hasher.update([0]);
+    }
+    hasher.update(
+        String::from_utf8_lossy(&output.stdout)
+            .replace(&project, ".")
+            .as_bytes(),
+    );
+
+    let digest = hasher.finalize();
+    Some(digest.iter().map(|b| format!("{b:02x}")).collect())
+}
+
+/// Turn `/showIncludes` notes into the `/sourceDependencies` JSON layout
+/// the incremental check reads
+fn write_msvc_dependencies(show_includes: &str, json_path: &Path) {
+    let includes: Vec<&str> = show_includes
+        .lines()
+        .filter_map(|line| line.split_once("including file:"))
+        .map(|(_, path)| path.trim())
+        .collect();
+    let json = serde_json::json!({
+        "Version": "1.1",
+        "Data": { "Includes": includes }
+    });
+    let _ = fs::write(json_path, json.to_string());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_size_suffixes() {
+        assert_eq!(parse_size("1024"), Some(1024));
+        assert_eq!(parse_size("2k"), Some(2048));
+        assert_eq!(parse_size("512M"), Some(512 * 1024 * 1024));
+        assert_eq!(parse_size("1.5G"), Some(1536 * 1024 * 1024));
+        assert_eq!(parse_size("lots"), None);
+    }
+
+    #[test]
+    fn evicts_oldest_entries_first() {
+        let dir = tempfile::tempdir().unwrap();
+        let old = dir.path().join("aa").join("aa11.obj");
+        let new = dir.path().join("bb").join("bb22.obj");
+        fs::create_dir_all(old.parent().unwrap()).unwrap();
+        fs::create_dir_all(new.parent().unwrap()).unwrap();
+        fs::write(&old, vec![0u8; 600]).unwrap();
+        fs::write(&new, vec![0u8; 600]).unwrap();
+        let file = fs::File::options().write(true).open(&old).unwrap();
+        file.set_modified(SystemTime::UNIX_EPOCH).unwrap();
+
+        let total = evict(dir.path(), 1000).unwrap();
+        assert_eq!(total, 600);
+        assert!(!old.exists());
+        assert!(new.exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn compiler_identity_follows_version_banner() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let compiler = dir.path().join("fake-cc");
+        let write_compiler = |version: &str| {
+            fs::write(&compiler, format!("#!/bin/sh\necho 'fake-cc {version}'\n")).unwrap();
+            fs::set_permissions(&compiler, fs::Permissions::from_mode(0o755)).unwrap();
+        };
+        let env = HashMap::new();
+        let path = compiler.to_string_lossy().to_string();
+
+        write_compiler("12.2.0");
+        let first = compiler_identity(&path, &env);
+        assert_eq!(first.len(), 64);
+        assert_eq!(first, compiler_identity(&path, &env));
+
+        write_compiler("13.1.0");
+        assert_ne!(first, compiler_identity(&path, &env));
+    }
+}
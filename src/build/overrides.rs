@@ -0,0 +1,174 @@
+//! Per-source compile flags from `[[build.overrides]]`.
+//!
+//! ```toml
+//! [[build.overrides]]
+//! files = ["src/gen/**/*.c", "src/third_party/*.cpp"]
+//! flags = ["-Wno-unused-parameter", "-O0"]
+//! defines = ["GENERATED=1"]
+//! ```
+//!
+//! Globs are matched against the source path relative to the project root:
+//! `*` and `?` stay within one path component, `**` spans any number of
+//! them. Matching overrides add their flags after `[build] flags`, in the
+//! order they are declared, so a later override wins over an earlier one.
+//!
+//! A unity batch is compiled with one set of flags, so unity builds compile
+//! sources matched by an override with `flags` or `defines` on their own;
+//! `unity = false` does the same for an override without any flags.
+
+use crate::config::{CxConfig, define_flag};
+use regex::Regex;
+use std::path::Path;
+
+struct Rule {
+    patterns: Vec<Regex>,
+    args: Vec<String>,
+    standalone: bool,
+}
+
+pub(crate) struct Overrides {
+    rules: Vec<Rule>,
+}
+
+impl Overrides {
+    pub fn new(config: &CxConfig, is_msvc: bool) -> Self {
+        let rules = config
+            .build
+            .as_ref()
+            .and_then(|b| b.overrides.as_ref())
+            .into_iter()
+            .flatten()
+            .map(|o| {
+                let mut args = o.flags.clone();
+                args.extend(o.defines.iter().map(|d| define_flag(d, is_msvc)));
+                Rule {
+                    patterns: o.files.iter().map(|f| glob_regex(f)).collect(),
+                    standalone: !args.is_empty() || o.unity == Some(false),
+                    args,
+                }
+            })
+            .collect();
+        Self { rules }
+    }
+
+    /// Extra arguments for `source`, empty when no override matches
+    pub fn args_for(&self, source: &Path) -> Vec<String> {
+        let path = normalize(source);
+        self.rules
+            .iter()
+            .filter(|rule| rule.patterns.iter().any(|p| p.is_match(&path)))
+            .flat_map(|rule| rule.args.iter().cloned())
+            .collect()
+    }
+
+    /// Whether `source` must stay out of unity batches
+    pub fn excludes_from_unity(&self, source: &Path) -> bool {
+        let path = normalize(source);
+        self.rules
+            .iter()
+            .filter(|rule| rule.standalone)
+            .any(|rule| rule.patterns.iter().any(|p| p.is_match(&path)))
+    }
+}
+
+/// Whether `source` matches one of an override's `files` globs
+pub(crate) fn matches(files: &[String], source: &Path) -> bool {
+    let path = normalize(source);
+    files.iter().any(|f| glob_regex(f).is_match(&path))
+}
+
+/// Project-relative path with forward slashes
+pub(crate) fn normalize(source: &Path) -> String {
+    let relative = std::env::current_dir()
+        .ok()
+        .and_then(|cwd| source.strip_prefix(cwd).ok().map(Path::to_path_buf))
+        .unwrap_or_else(|| source.to_path_buf());
+    let path = relative.to_string_lossy().replace('\\', "/");
+    path.strip_prefix("./").unwrap_or(&path).to_string()
+}
+
+pub(crate) fn glob_regex(glob: &str) -> Regex {
+    let glob = glob.replace('\\', "/");
+    let glob = glob.strip_prefix("./").unwrap_or(&glob);
+    let mut pattern = String::from("^");
+    let mut chars = glob.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '*' if chars.peek() == Some(&'*') => {
+                chars.next();
+                if chars.peek() == Some(&'/') {
+                    chars.next();
+                    pattern.push_str("(?:.*/)?");
+                } else {
+                    pattern.push_str(".*");
+                }
+            }
+            '*' => pattern.push_str("[^/]*"),
+            '?' => pattern.push_str("[^/]"),
+            _ => pattern.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    pattern.push('$');
+    // Everything outside the wildcards is escaped, so this always compiles
+    Regex::new(&pattern).expect("glob regex")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matches(glob: &str, path: &str) -> bool {
+        glob_regex(glob).is_match(&normalize(Path::new(path)))
+    }
+
+    #[test]
+    fn test_glob_matching() {
+        assert!(matches("src/gen/**/*.c", "src/gen/a.c"));
+        assert!(matches("src/gen/**/*.c", "src/gen/deep/er/b.c"));
+        assert!(!matches("src/gen/**/*.c", "src/gen/a.cpp"));
+        assert!(matches("src/*.cpp", "./src/main.cpp"));
+        assert!(!matches("src/*.cpp", "src/sub/main.cpp"));
+        assert!(matches("src/legacy?.c", "src/legacy2.c"));
+        assert!(matches("**/vendor.c", "src/third_party/vendor.c"));
+        assert!(!matches("src/a+b.c", "src/aab.c"));
+    }
+
+    #[test]
+    fn test_overrides_apply_in_order() {
+        let config: CxConfig = toml::from_str(
+            r#"
+[package]
+name = "app"
+version = "0.1.0"
+
+[[build.overrides]]
+files = ["src/gen/**"]
+flags = ["-O0"]
+defines = ["GENERATED=1"]
+
+[[build.overrides]]
+files = ["src/gen/fast.c"]
+flags = ["-O2"]
+
+[[build.overrides]]
+files = ["src/legacy/*.c"]
+unity = false
+"#,
+        )
+        .unwrap();
+        let overrides = Overrides::new(&config, false);
+        assert_eq!(
+            overrides.args_for(Path::new("src/gen/fast.c")),
+            vec!["-O0", "-DGENERATED=1", "-O2"]
+        );
+        assert!(overrides.args_for(Path::new("src/main.c")).is_empty());
+        assert_eq!(
+            Overrides::new(&config, true).args_for(Path::new("src/gen/x.c")),
+            vec!["-O0", "/DGENERATED=1"]
+        );
+        assert!(overrides.excludes_from_unity(Path::new("src/gen/x.c")));
+        assert!(overrides.excludes_from_unity(Path::new("src/legacy/old.c")));
+        assert!(overrides.args_for(Path::new("src/legacy/old.c")).is_empty());
+        assert!(!overrides.excludes_from_unity(Path::new("src/main.c")));
+    }
+}
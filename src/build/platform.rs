@@ -0,0 +1,412 @@
+//! Platform-conditional sections: `[target.'<cfg>'.build]` and
+//! `[target.'<cfg>'.dependencies]`.
+//!
+//! ```toml
+//! [target.'cfg(target_os = "linux")'.build]
+//! libs = ["pthread"]
+//!
+//! [target.'cfg(windows)'.build]
+//! libs = ["ws2_32"]
+//! sources = ["src/win32/**/*.cpp"]
+//!
+//! [target.'cfg(unix)'.dependencies]
+//! libuv = "https://github.com/libuv/libuv"
+//!
+//! [target.aarch64-linux-gnu.build]
+//! defines = ["HAVE_NEON=1"]
+//! ```
+//!
+//! Keys are either a target triple or a cargo-style `cfg(...)` predicate
+//! over `unix`, `windows`, `target_os`, `target_arch`, `target_family` and
+//! `target_env`, combined with `all()`, `any()` and `not()`. Matching
+//! sections are evaluated by `load_config` against the host (and again
+//! against a profile's `target` triple): their `flags`, `defines`, `libs`,
+//! `ldflags` and `include` are appended to `[build]` and their dependencies
+//! merged in. `sources` globs work like feature sources: built on matching
+//! platforms, left out everywhere else.
+
+use super::cross::target_os;
+use super::features::ConditionalSources;
+use crate::config::CxConfig;
+use anyhow::{Result, bail};
+use std::collections::HashMap;
+
+/// What `cfg(...)` predicates are evaluated against
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Platform {
+    pub triple: String,
+    pub os: String,
+    pub arch: String,
+    pub env: String,
+}
+
+impl Platform {
+    /// The machine cx runs on
+    pub fn host() -> Self {
+        let os = std::env::consts::OS;
+        let arch = std::env::consts::ARCH;
+        let env = if cfg!(target_env = "msvc") {
+            "msvc"
+        } else if cfg!(target_env = "musl") {
+            "musl"
+        } else if cfg!(target_env = "gnu") {
+            "gnu"
+        } else {
+            ""
+        };
+        let triple = match os {
+            "windows" => format!("{arch}-pc-windows-{env}"),
+            "macos" => format!("{arch}-apple-darwin"),
+            "linux" => format!("{arch}-unknown-linux-{env}"),
+            _ => format!("{arch}-unknown-{os}"),
+        };
+        Self {
+            triple,
+            os: os.to_string(),
+            arch: arch.to_string(),
+            env: env.to_string(),
+        }
+    }
+
+    /// A cross target
+    pub fn for_triple(triple: &str) -> Self {
+        let parts: Vec<&str> = triple.split('-').collect();
+        let env = parts
+            .last()
+            .filter(|_| parts.len() > 2)
+            .map(|last| {
+                ["msvc", "musl", "gnu"]
+                    .into_iter()
+                    .find(|env| last.starts_with(env))
+                    .unwrap_or_default()
+            })
+            .unwrap_or_default();
+        let env = if triple.contains("mingw") { "gnu" } else { env };
+        Self {
+            triple: triple.to_string(),
+            os: target_os(triple).to_string(),
+            arch: parts.first().copied().unwrap_or_default().to_string(),
+            env: env.to_string(),
+        }
+    }
+
+    /// `unix`, `windows` or `wasm`, as in `target_family`
+    fn family(&self) -> Option<&'static str> {
+        match self.os.as_str() {
+            "windows" => Some("windows"),
+            "emscripten" => Some("wasm"),
+            "none" => None,
+            _ => Some("unix"),
+        }
+    }
+
+    /// Whether a `[target.<key>]` section applies
+    pub fn matches(&self, key: &str) -> Result<bool> {
+        let key = key.trim();
+        if key.starts_with("cfg(") {
+            let mut parser = Parser::new(key);
+            let predicate = parser.predicate()?;
+            parser.finish()?;
+            return match predicate {
+                Predicate::Call(name, args) if name == "cfg" && args.len() == 1 => {
+                    self.eval(&args[0])
+                }
+                _ => bail!("Invalid target key '{}': expected cfg(<predicate>)", key),
+            };
+        }
+        Ok(same_triple(key, &self.triple))
+    }
+
+    fn eval(&self, predicate: &Predicate) -> Result<bool> {
+        Ok(match predicate {
+            Predicate::Flag(name) => match name.as_str() {
+                "unix" => self.family() == Some("unix"),
+                "windows" => self.family() == Some("windows"),
+                _ => bail!("Unknown cfg flag '{}' (expected unix or windows)", name),
+            },
+            Predicate::Equals(key, value) => {
+                let actual = match key.as_str() {
+                    "target_os" => self.os.as_str(),
+                    "target_arch" => self.arch.as_str(),
+                    "target_env" => self.env.as_str(),
+                    "target_family" => self.family().unwrap_or_default(),
+                    _ => bail!(
+                        "Unknown cfg key '{}' (expected target_os, target_arch, target_env or target_family)",
+                        key
+                    ),
+                };
+                actual == value
+            }
+            Predicate::Call(name, args) => match name.as_str() {
+                "all" => {
+                    for arg in args {
+                        if !self.eval(arg)? {
+                            return Ok(false);
+                        }
+                    }
+                    true
+                }
+                "any" => {
+                    for arg in args {
+                        if self.eval(arg)? {
+                            return Ok(true);
+                        }
+                    }
+                    false
+                }
+                "not" if args.len() == 1 => !self.eval(&args[0])?,
+                _ => bail!("Unknown cfg operator '{}'", name),
+            },
+        })
+    }
+}
+
+/// Triples are equal ignoring the `unknown` / `pc` vendor
+fn same_triple(a: &str, b: &str) -> bool {
+    let parts = |t: &str| -> Vec<String> {
+        t.to_lowercase()
+            .split('-')
+            .filter(|p| *p != "unknown" && *p != "pc")
+            .map(str::to_string)
+            .collect()
+    };
+    parts(a) == parts(b)
+}
+
+/// Keys of the `[target.*]` sections that apply to `platform`, sorted
+pub fn active_sections(config: &CxConfig, platform: &Platform) -> Result<Vec<String>> {
+    let mut active = Vec::new();
+    for key in config.platforms.iter().flat_map(|p| p.keys()) {
+        if platform.matches(key)? {
+            active.push(key.clone());
+        }
+    }
+    active.sort();
+    Ok(active)
+}
+
+/// Merge the sections matching `platform` into `[build]` and `[dependencies]`
+pub fn apply(config: &mut CxConfig, platform: &Platform) -> Result<()> {
+    let Some(platforms) = config.platforms.clone() else {
+        return Ok(());
+    };
+    for key in active_sections(config, platform)? {
+        let section = &platforms[&key];
+        if let Some(deps) = &section.dependencies {
+            config
+                .dependencies
+                .get_or_insert_with(HashMap::new)
+                .extend(deps.iter().map(|(k, v)| (k.clone(), v.clone())));
+        }
+        let Some(build) = &section.build else {
+            continue;
+        };
+        let build_cfg = config.build.get_or_insert_with(Default::default);
+        for (into, from) in [
+            (&mut build_cfg.flags, &build.flags),
+            (&mut build_cfg.defines, &build.defines),
+            (&mut build_cfg.libs, &build.libs),
+            (&mut build_cfg.ldflags, &build.ldflags),
+            (&mut build_cfg.include, &build.include),
+        ] {
+            if let Some(from) = from {
+                into.get_or_insert_with(Vec::new)
+                    .extend(from.iter().cloned());
+            }
+        }
+    }
+    Ok(())
+}
+
+/// `sources` of every section, active on `platform` or not
+pub fn conditional_sources(config: &CxConfig, platform: &Platform) -> Result<ConditionalSources> {
+    let mut sources = ConditionalSources::default();
+    let mut sections: Vec<_> = config.platforms.iter().flatten().collect();
+    sections.sort_by_key(|(key, _)| *key);
+    for (key, section) in sections {
+        if let Some(globs) = section.build.as_ref().and_then(|b| b.sources.as_ref()) {
+            sources.add(globs, platform.matches(key)?);
+        }
+    }
+    Ok(sources)
+}
+
+#[derive(Debug)]
+enum Predicate {
+    /// `unix`
+    Flag(String),
+    /// `target_os = "linux"`
+    Equals(String, String),
+    /// `all(...)`, `any(...)`, `not(...)` and the outer `cfg(...)`
+    Call(String, Vec<Predicate>),
+}
+
+struct Parser<'a> {
+    source: &'a str,
+    rest: &'a str,
+}
+
+impl<'a> Parser<'a> {
+    fn new(source: &'a str) -> Self {
+        Self {
+            source,
+            rest: source,
+        }
+    }
+
+    fn error(&self, expected: &str) -> anyhow::Error {
+        anyhow::anyhow!(
+            "Invalid target key '{}': expected {} at '{}'",
+            self.source,
+            expected,
+            self.rest
+        )
+    }
+
+    fn eat(&mut self, token: char) -> bool {
+        self.rest = self.rest.trim_start();
+        match self.rest.strip_prefix(token) {
+            Some(rest) => {
+                self.rest = rest;
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn ident(&mut self) -> Result<String> {
+        self.rest = self.rest.trim_start();
+        let end = self
+            .rest
+            .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+            .unwrap_or(self.rest.len());
+        if end == 0 {
+            return Err(self.error("a name"));
+        }
+        let (ident, rest) = self.rest.split_at(end);
+        self.rest = rest;
+        Ok(ident.to_string())
+    }
+
+    fn string(&mut self) -> Result<String> {
+        if !self.eat('"') {
+            return Err(self.error("a quoted value"));
+        }
+        let end = self
+            .rest
+            .find('"')
+            .ok_or_else(|| self.error("a closing quote"))?;
+        let value = self.rest[..end].to_string();
+        self.rest = &self.rest[end + 1..];
+        Ok(value)
+    }
+
+    fn predicate(&mut self) -> Result<Predicate> {
+        let name = self.ident()?;
+        if self.eat('=') {
+            return Ok(Predicate::Equals(name, self.string()?));
+        }
+        if !self.eat('(') {
+            return Ok(Predicate::Flag(name));
+        }
+        let mut args = Vec::new();
+        while !self.eat(')') {
+            args.push(self.predicate()?);
+            if !self.eat(',') && !self.rest.trim_start().starts_with(')') {
+                return Err(self.error("',' or ')'"));
+            }
+        }
+        Ok(Predicate::Call(name, args))
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        if self.rest.trim().is_empty() {
+            Ok(())
+        } else {
+            Err(self.error("the end of the key"))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn linux() -> Platform {
+        Platform::for_triple("x86_64-unknown-linux-gnu")
+    }
+
+    #[test]
+    fn test_cfg_predicates() {
+        let linux = linux();
+        let windows = Platform::for_triple("x86_64-pc-windows-msvc");
+        assert!(linux.matches("cfg(unix)").unwrap());
+        assert!(!windows.matches("cfg(unix)").unwrap());
+        assert!(windows.matches("cfg(windows)").unwrap());
+        assert!(linux.matches(r#"cfg(target_os = "linux")"#).unwrap());
+        assert!(windows.matches(r#"cfg(target_env = "msvc")"#).unwrap());
+        assert!(
+            linux
+                .matches(r#"cfg(all(unix, target_arch = "x86_64", not(target_os = "macos")))"#)
+                .unwrap()
+        );
+        assert!(
+            !linux
+                .matches(r#"cfg(any(windows, target_os = "macos"))"#)
+                .unwrap()
+        );
+        assert!(linux.matches("x86_64-linux-gnu").unwrap());
+        assert!(!linux.matches("aarch64-linux-gnu").unwrap());
+        assert!(
+            Platform::for_triple("x86_64-w64-mingw32")
+                .matches(r#"cfg(all(windows, target_env = "gnu"))"#)
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn test_invalid_cfg_is_an_error() {
+        let err = linux().matches("cfg(linux)").unwrap_err();
+        assert!(err.to_string().contains("Unknown cfg flag 'linux'"));
+        let err = linux().matches(r#"cfg(target_os = "linux""#).unwrap_err();
+        assert!(err.to_string().contains("Invalid target key"));
+        assert!(linux().matches(r#"cfg(target_vendor = "apple")"#).is_err());
+    }
+
+    #[test]
+    fn test_apply_merges_matching_sections() {
+        let mut config: CxConfig = toml::from_str(
+            r#"
+[package]
+name = "net"
+version = "0.1.0"
+
+[build]
+libs = ["m"]
+
+[target.'cfg(target_os = "linux")'.build]
+libs = ["pthread"]
+sources = ["src/posix/*.cpp"]
+
+[target.'cfg(windows)'.build]
+libs = ["ws2_32"]
+sources = ["src/win32/*.cpp"]
+
+[target.'cfg(unix)'.dependencies]
+uv = "https://github.com/libuv/libuv"
+"#,
+        )
+        .unwrap();
+
+        apply(&mut config, &linux()).unwrap();
+        assert_eq!(
+            config.build.as_ref().unwrap().libs,
+            Some(vec!["m".into(), "pthread".into()])
+        );
+        assert!(config.dependencies.as_ref().unwrap().contains_key("uv"));
+
+        let sources = conditional_sources(&config, &linux()).unwrap();
+        assert!(sources.excludes(std::path::Path::new("src/win32/socket.cpp")));
+        assert!(!sources.excludes(std::path::Path::new("src/posix/socket.cpp")));
+    }
+}
@@ -0,0 +1,484 @@
+//! Build profiles: the built-in `debug` / `release` and named
+//! `[profile:<name>]` (or `[profile.<name>]`) tables.
+//!
+//! ```toml
+//! [profile.release]
+//! opt-level = "s"
+//! debug = "line-tables"
+//! lto = "thin"
+//! strip = true
+//!
+//! [profile.bench]
+//! base = "release"
+//! frame-pointers = true
+//!
+//! [profile:arm]
+//! target = "aarch64-linux-gnu"
+//! flags = ["-mcpu=cortex-a72"]
+//! ```
+//!
+//! A `base` chain can be any length and is applied from the root down:
+//! `flags`, `defines`, `libs`, `ldflags` and `include` accumulate, `sources`
+//! replaces the inherited list, `dependencies` merge by name and every other
+//! field takes the value closest to the selected profile. Every chain ends in
+//! `debug` or `release` (the one `--release` picks when it names neither);
+//! one that loops back on itself is an error. Named profiles build into
+//! `.cx/<name>/`.
+
+use super::cross::CrossTarget;
+use crate::config::{CxConfig, DebugInfo, Lto, OptLevel, Profile};
+use crate::toolchain::CompilerType;
+use anyhow::{Result, bail};
+use std::collections::HashMap;
+
+/// Built-in profiles every chain ends in
+const BUILTIN_PROFILES: &[&str] = &["debug", "release"];
+
+/// A profile with its whole `base` chain folded in
+#[derive(Debug, Clone, Default)]
+pub struct ResolvedProfile {
+    /// Selected profile, which also names the artifact directory
+    pub name: String,
+    /// Named profiles from the selected one up to the built-in base
+    pub chain: Vec<String>,
+    /// `debug` or `release`
+    pub builtin_base: String,
+    /// Merged settings
+    pub profile: Profile,
+}
+
+/// Defaults of the built-in profiles, before `[profile.debug]` / `[profile.release]`
+fn builtin_defaults(name: &str) -> Profile {
+    if name == "release" {
+        Profile {
+            opt_level: Some(OptLevel::O3),
+            debug: Some(DebugInfo::None),
+            ..Default::default()
+        }
+    } else {
+        Profile {
+            debug: Some(DebugInfo::Full),
+            ..Default::default()
+        }
+    }
+}
+
+/// Resolve `selected` (or the built-in one `release` picks) and its ancestors
+pub fn resolve_profile(
+    profiles: &HashMap<String, Profile>,
+    selected: Option<&str>,
+    release: bool,
+) -> Result<ResolvedProfile> {
+    let fallback = if release { "release" } else { "debug" };
+    let mut resolved = ResolvedProfile {
+        name: selected.unwrap_or(fallback).to_string(),
+        builtin_base: fallback.to_string(),
+        ..Default::default()
+    };
+    let mut current = resolved.name.clone();
+    while !BUILTIN_PROFILES.contains(&current.as_str()) {
+        if let Some(start) = resolved.chain.iter().position(|n| *n == current) {
+            let mut cycle = resolved.chain[start..].to_vec();
+            cycle.push(current);
+            bail!("Profile inheritance cycle: {}", cycle.join(" -> "));
+        }
+        let Some(profile) = profiles.get(&current) else {
+            match resolved.chain.last() {
+                Some(child) => bail!(
+                    "Profile '{}' inherits from unknown profile '{}'",
+                    child,
+                    current
+                ),
+                None => {
+                    let mut available: Vec<&str> = profiles
+                        .keys()
+                        .map(String::as_str)
+                        .chain(BUILTIN_PROFILES.iter().copied())
+                        .collect();
+                    available.sort();
+                    available.dedup();
+                    bail!(
+                        "Profile '{}' not found in cx.toml. Available profiles: {:?}",
+                        current,
+                        available
+                    )
+                }
+            }
+        };
+        resolved.chain.push(current.clone());
+        match &profile.base {
+            Some(base) => current = base.clone(),
+            None => break,
+        }
+    }
+    if BUILTIN_PROFILES.contains(&current.as_str()) {
+        resolved.builtin_base = current;
+    }
+
+    let builtin = &resolved.builtin_base;
+    let mut merged = builtin_defaults(builtin);
+    if let Some(custom) = profiles.get(builtin) {
+        if let Some(base) = &custom.base {
+            bail!(
+                "Built-in profile '{}' can't inherit (base = \"{}\")",
+                builtin,
+                base
+            );
+        }
+        inherit(&mut merged, custom);
+    }
+    for name in resolved.chain.iter().rev() {
+        inherit(&mut merged, &profiles[name]);
+    }
+    merged.base = Some(builtin.clone());
+    resolved.profile = merged;
+    Ok(resolved)
+}
+
+/// Layer `child` over the settings inherited so far
+fn inherit(merged: &mut Profile, child: &Profile) {
+    fn extend(into: &mut Option<Vec<String>>, from: &Option<Vec<String>>) {
+        if let Some(from) = from {
+            into.get_or_insert_with(Vec::new)
+                .extend(from.iter().cloned());
+        }
+    }
+    fn replace<T: Clone>(into: &mut Option<T>, from: &Option<T>) {
+        if from.is_some() {
+            into.clone_from(from);
+        }
+    }
+
+    extend(&mut merged.flags, &child.flags);
+    extend(&mut merged.defines, &child.defines);
+    extend(&mut merged.libs, &child.libs);
+    extend(&mut merged.ldflags, &child.ldflags);
+    extend(&mut merged.include, &child.include);
+    replace(&mut merged.sources, &child.sources);
+    replace(&mut merged.target, &child.target);
+    replace(&mut merged.sysroot, &child.sysroot);
+    replace(&mut merged.linker, &child.linker);
+    replace(&mut merged.ar, &child.ar);
+    replace(&mut merged.compiler, &child.compiler);
+    replace(&mut merged.bin, &child.bin);
+    replace(&mut merged.pch, &child.pch);
+    replace(&mut merged.edition, &child.edition);
+    replace(&mut merged.opt_level, &child.opt_level);
+    replace(&mut merged.debug, &child.debug);
+    replace(&mut merged.lto, &child.lto);
+    replace(&mut merged.strip, &child.strip);
+    replace(&mut merged.frame_pointers, &child.frame_pointers);
+    replace(&mut merged.assertions, &child.assertions);
+    if let Some(deps) = &child.dependencies {
+        merged
+            .dependencies
+            .get_or_insert_with(HashMap::new)
+            .extend(deps.iter().map(|(k, v)| (k.clone(), v.clone())));
+    }
+}
+
+impl ResolvedProfile {
+    /// Merge the profile into `config`
+    pub fn apply(&self, config: &mut CxConfig) {
+        fn extend(into: &mut Option<Vec<String>>, from: &Option<Vec<String>>) {
+            if let Some(from) = from.as_ref().filter(|f| !f.is_empty()) {
+                into.get_or_insert_with(Vec::new)
+                    .extend(from.iter().cloned());
+            }
+        }
+
+        let profile = &self.profile;
+        if let Some(edition) = &profile.edition {
+            config.package.edition = edition.clone();
+        }
+        if let Some(deps) = &profile.dependencies {
+            config
+                .dependencies
+                .get_or_insert_with(HashMap::new)
+                .extend(deps.iter().map(|(k, v)| (k.clone(), v.clone())));
+        }
+
+        let build_cfg = config.build.get_or_insert_with(Default::default);
+        extend(&mut build_cfg.flags, &profile.flags);
+        extend(&mut build_cfg.defines, &profile.defines);
+        if profile.assertions == Some(false) {
+            build_cfg
+                .defines
+                .get_or_insert_with(Vec::new)
+                .push("NDEBUG".to_string());
+        }
+        extend(&mut build_cfg.libs, &profile.libs);
+        extend(&mut build_cfg.ldflags, &profile.ldflags);
+        extend(&mut build_cfg.include, &profile.include);
+        if let Some(sources) = &profile.sources {
+            build_cfg.sources = Some(sources.clone());
+        }
+        if let Some(compiler) = &profile.compiler {
+            build_cfg.compiler = Some(compiler.clone());
+        }
+        if let Some(bin) = &profile.bin {
+            build_cfg.bin = Some(bin.clone());
+        }
+        if let Some(pch) = &profile.pch {
+            build_cfg.pch = Some(pch.clone());
+        }
+    }
+
+    /// Cross settings when the chain sets a `target`
+    pub fn cross(&self) -> Option<CrossTarget> {
+        CrossTarget::from_profile(&self.profile)
+    }
+
+    /// Whether the chain ends in `release`
+    pub fn is_release(&self) -> bool {
+        self.builtin_base == "release"
+    }
+
+    /// Whether a named (not built-in) profile was selected
+    pub fn is_named(&self) -> bool {
+        !self.chain.is_empty()
+    }
+
+    pub fn lto(&self) -> Lto {
+        self.profile.lto.unwrap_or_default()
+    }
+
+    pub fn strip(&self) -> bool {
+        self.profile.strip.unwrap_or(false)
+    }
+
+    /// Optimisation, debug info and frame pointer flags for `compiler`
+    pub fn codegen_flags(&self, compiler: &CompilerType) -> Vec<String> {
+        let msvc_style = matches!(compiler, CompilerType::MSVC | CompilerType::ClangCL);
+        let clang = *compiler == CompilerType::Clang;
+        let mut flags = Vec::new();
+
+        if let Some(level) = self.profile.opt_level {
+            let flag = if msvc_style {
+                match level {
+                    OptLevel::O0 | OptLevel::Debug => "/Od",
+                    OptLevel::O1 | OptLevel::Size | OptLevel::MinSize => "/O1",
+                    // MSVC has nothing above /O2
+                    OptLevel::O2 | OptLevel::O3 => "/O2",
+                }
+            } else {
+                match level {
+                    OptLevel::O0 => "-O0",
+                    OptLevel::O1 => "-O1",
+                    OptLevel::O2 => "-O2",
+                    OptLevel::O3 => "-O3",
+                    OptLevel::Size => "-Os",
+                    // -Oz is Clang-only before GCC 12
+                    OptLevel::MinSize if clang => "-Oz",
+                    OptLevel::MinSize => "-Os",
+                    OptLevel::Debug => "-Og",
+                }
+            };
+            flags.push(flag.to_string());
+        }
+
+        match self.profile.debug.unwrap_or(DebugInfo::None) {
+            DebugInfo::None => {}
+            _ if msvc_style => flags.push("/Z7".to_string()),
+            DebugInfo::LineTables if clang => flags.push("-gline-tables-only".to_string()),
+            DebugInfo::LineTables => flags.push("-g1".to_string()),
+            DebugInfo::Full => flags.push("-g".to_string()),
+        }
+
+        if let Some(keep) = self.profile.frame_pointers {
+            let flag = match (msvc_style, keep) {
+                (true, true) => "/Oy-",
+                (true, false) => "/Oy",
+                (false, true) => "-fno-omit-frame-pointer",
+                (false, false) => "-fomit-frame-pointer",
+            };
+            flags.push(flag.to_string());
+        }
+        flags
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn profiles(toml: &str) -> HashMap<String, Profile> {
+        toml::from_str(toml).unwrap()
+    }
+
+    #[test]
+    fn test_multi_level_inheritance() {
+        let profiles = profiles(
+            r#"
+[arm]
+base = "release"
+target = "aarch64-linux-gnu"
+flags = ["-mcpu=cortex-a72"]
+sources = ["src/*.cpp"]
+
+[arm-fast]
+base = "arm"
+flags = ["-O3"]
+edition = "c++20"
+
+[arm-fast-static]
+base = "arm-fast"
+ldflags = ["-static"]
+sources = ["src/main.cpp"]
+compiler = "clang++"
+"#,
+        );
+        let resolved = resolve_profile(&profiles, Some("arm-fast-static"), false).unwrap();
+        assert_eq!(resolved.chain, vec!["arm-fast-static", "arm-fast", "arm"]);
+        assert_eq!(resolved.builtin_base, "release");
+        assert!(resolved.is_release());
+
+        let profile = &resolved.profile;
+        assert_eq!(
+            profile.flags.as_deref().unwrap(),
+            ["-mcpu=cortex-a72", "-O3"]
+        );
+        assert_eq!(profile.ldflags.as_deref().unwrap(), ["-static"]);
+        assert_eq!(profile.sources.as_deref().unwrap(), ["src/main.cpp"]);
+        assert_eq!(profile.edition.as_deref(), Some("c++20"));
+        assert_eq!(profile.compiler.as_deref(), Some("clang++"));
+        assert_eq!(resolved.cross().unwrap().triple, "aarch64-linux-gnu");
+    }
+
+    #[test]
+    fn test_apply_overrides_build_and_dependencies() {
+        let profiles = profiles(
+            r#"
+[embedded]
+include = ["board/include"]
+defines = ["BOARD=2"]
+pch = "src/board_pch.hpp"
+edition = "c++17"
+dependencies = { etl = "https://github.com/ETLCPP/etl" }
+"#,
+        );
+        let mut config: CxConfig = toml::from_str(
+            r#"
+[package]
+name = "fw"
+version = "0.1.0"
+edition = "c++20"
+
+[build]
+include = ["include"]
+
+[dependencies]
+fmt = "https://github.com/fmtlib/fmt"
+"#,
+        )
+        .unwrap();
+
+        resolve_profile(&profiles, Some("embedded"), false)
+            .unwrap()
+            .apply(&mut config);
+        assert_eq!(config.package.edition, "c++17");
+        let deps = config.dependencies.as_ref().unwrap();
+        assert!(deps.contains_key("fmt") && deps.contains_key("etl"));
+        let build_cfg = config.build.unwrap();
+        assert_eq!(build_cfg.include.unwrap(), vec!["include", "board/include"]);
+        assert_eq!(build_cfg.defines.unwrap(), vec!["BOARD=2"]);
+        assert_eq!(build_cfg.pch.as_deref(), Some("src/board_pch.hpp"));
+    }
+
+    #[test]
+    fn test_cycles_and_unknown_bases_are_errors() {
+        let profiles = profiles(
+            r#"
+[a]
+base = "b"
+
+[b]
+base = "c"
+
+[c]
+base = "a"
+
+[orphan]
+base = "missing"
+"#,
+        );
+        let err = resolve_profile(&profiles, Some("a"), false).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Profile inheritance cycle: a -> b -> c -> a"
+        );
+        let err = resolve_profile(&profiles, Some("orphan"), false).unwrap_err();
+        assert!(err.to_string().contains("unknown profile 'missing'"));
+        let err = resolve_profile(&profiles, Some("nope"), false).unwrap_err();
+        assert!(err.to_string().contains("Profile 'nope' not found"));
+    }
+
+    #[test]
+    fn test_builtin_profiles_are_configurable() {
+        let profiles = profiles(
+            r#"
+[release]
+opt-level = "s"
+debug = "line-tables"
+lto = "thin"
+strip = true
+assertions = false
+
+[debug]
+opt-level = "g"
+
+[bench]
+base = "release"
+frame-pointers = true
+opt-level = 2
+"#,
+        );
+
+        let release = resolve_profile(&profiles, None, true).unwrap();
+        assert_eq!(release.name, "release");
+        assert!(!release.is_named());
+        assert_eq!(release.lto(), Lto::Thin);
+        assert!(release.strip());
+        assert_eq!(
+            release.codegen_flags(&CompilerType::Clang),
+            vec!["-Os", "-gline-tables-only"]
+        );
+        assert_eq!(
+            release.codegen_flags(&CompilerType::GCC),
+            vec!["-Os", "-g1"]
+        );
+        assert_eq!(
+            release.codegen_flags(&CompilerType::MSVC),
+            vec!["/O1", "/Z7"]
+        );
+        let mut config = CxConfig::default();
+        release.apply(&mut config);
+        assert_eq!(config.build.unwrap().defines.unwrap(), vec!["NDEBUG"]);
+
+        let debug = resolve_profile(&profiles, None, false).unwrap();
+        assert_eq!(debug.codegen_flags(&CompilerType::GCC), vec!["-Og", "-g"]);
+
+        let bench = resolve_profile(&profiles, Some("bench"), false).unwrap();
+        assert_eq!(bench.name, "bench");
+        assert!(bench.is_release() && bench.is_named());
+        assert_eq!(
+            bench.codegen_flags(&CompilerType::GCC),
+            vec!["-O2", "-g1", "-fno-omit-frame-pointer"]
+        );
+    }
+
+    #[test]
+    fn test_builtin_defaults_and_base_on_builtin() {
+        let empty = HashMap::new();
+        let release = resolve_profile(&empty, None, true).unwrap();
+        assert_eq!(release.codegen_flags(&CompilerType::GCC), vec!["-O3"]);
+        assert_eq!(release.codegen_flags(&CompilerType::MSVC), vec!["/O2"]);
+        let debug = resolve_profile(&empty, Some("debug"), true).unwrap();
+        assert!(!debug.is_release());
+        assert_eq!(debug.codegen_flags(&CompilerType::Clang), vec!["-g"]);
+
+        let profiles = profiles("[release]\nbase = \"debug\"\n");
+        let err = resolve_profile(&profiles, None, true).unwrap_err();
+        assert!(err.to_string().contains("can't inherit"));
+    }
+}
@@ -0,0 +1,191 @@
+//! Shared remote object cache over plain HTTP.
+//!
+//! Sits behind the local object cache: a local miss tries `GET`, a fresh
+//! compile `PUT`s the object when writing is enabled. Any server that
+//! answers `GET`/`PUT` on a path works:
+//!
+//! - `layout = "flat"` (default): `<remote>/<key>` — nginx/Apache WebDAV,
+//!   a simple upload-capable file server, ...
+//! - `layout = "bazel"`: `<remote>/ac/<key>` — bazel-remote's HTTP API
+//!   (run it with `--disable_http_ac_validation`)
+//!
+//! Developers usually read only (`remote_mode = "read"`, the default); CI
+//! fills the cache with `remote_mode = "read-write"` or
+//! `CX_CACHE_REMOTE_MODE=read-write`.
+
+use crate::config::CacheConfig;
+use colored::*;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::time::Duration;
+
+/// Largest object we are willing to download
+const MAX_OBJECT_SIZE: u64 = 1024 * 1024 * 1024;
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Layout {
+    Flat,
+    Bazel,
+}
+
+pub(crate) struct RemoteCache {
+    base: String,
+    layout: Layout,
+    write: bool,
+    agent: ureq::Agent,
+    /// Set after the first network error so a dead server costs one timeout
+    unavailable: AtomicBool,
+    hits: AtomicU64,
+    uploads: AtomicU64,
+}
+
+impl RemoteCache {
+    /// From `[cache] remote`, overridable with `CX_CACHE_REMOTE` and
+    /// `CX_CACHE_REMOTE_MODE`. Always `None` when cx is offline.
+    pub fn from_config(config: Option<&CacheConfig>) -> Option<Self> {
+        if crate::deps::is_offline() {
+            return None;
+        }
+        let base = std::env::var("CX_CACHE_REMOTE")
+            .ok()
+            .or_else(|| config.and_then(|c| c.remote.clone()))
+            .filter(|url| !url.trim().is_empty())?;
+        let mode = std::env::var("CX_CACHE_REMOTE_MODE")
+            .ok()
+            .or_else(|| config.and_then(|c| c.remote_mode.clone()))
+            .unwrap_or_else(|| "read".to_string());
+        let layout = match config.and_then(|c| c.remote_layout.as_deref()) {
+            Some("bazel") | Some("bazel-remote") => Layout::Bazel,
+            _ => Layout::Flat,
+        };
+
+        let agent_config = ureq::Agent::config_builder()
+            .timeout_global(Some(TIMEOUT))
+            .http_status_as_error(false)
+            .build();
+
+        Some(Self {
+            base: base.trim_end_matches('/').to_string(),
+            layout,
+            write: matches!(
+                mode.to_lowercase().as_str(),
+                "read-write" | "readwrite" | "rw" | "write"
+            ),
+            agent: ureq::Agent::new_with_config(agent_config),
+            unavailable: AtomicBool::new(false),
+            hits: AtomicU64::new(0),
+            uploads: AtomicU64::new(0),
+        })
+    }
+
+    pub fn url(&self) -> &str {
+        &self.base
+    }
+
+    pub fn is_writable(&self) -> bool {
+        self.write
+    }
+
+    fn object_url(&self, key: &str) -> String {
+        match self.layout {
+            Layout::Flat => format!("{}/{}", self.base, key),
+            Layout::Bazel => format!("{}/ac/{}", self.base, key),
+        }
+    }
+
+    fn give_up(&self, action: &str, err: impl std::fmt::Display) {
+        if !self.unavailable.swap(true, Ordering::Relaxed) {
+            eprintln!(
+                "   {} Remote cache {} failed ({}); continuing without it",
+                "⚠".yellow(),
+                action,
+                err
+            );
+        }
+    }
+
+    /// Download the object stored under `key`; `None` on a miss
+    pub fn get(&self, key: &str) -> Option<Vec<u8>> {
+        if self.unavailable.load(Ordering::Relaxed) {
+            return None;
+        }
+        let mut response = match self.agent.get(&self.object_url(key)).call() {
+            Ok(response) => response,
+            Err(e) => {
+                self.give_up("GET", e);
+                return None;
+            }
+        };
+        if response.status() != 200 {
+            return None;
+        }
+        match response
+            .body_mut()
+            .with_config()
+            .limit(MAX_OBJECT_SIZE)
+            .read_to_vec()
+        {
+            Ok(bytes) => {
+                self.hits.fetch_add(1, Ordering::Relaxed);
+                Some(bytes)
+            }
+            Err(e) => {
+                self.give_up("GET", e);
+                None
+            }
+        }
+    }
+
+    /// Upload an object (write mode only)
+    pub fn put(&self, key: &str, bytes: &[u8]) {
+        if !self.write || self.unavailable.load(Ordering::Relaxed) {
+            return;
+        }
+        match self.agent.put(&self.object_url(key)).send(bytes) {
+            Ok(response) if response.status().is_success() => {
+                self.uploads.fetch_add(1, Ordering::Relaxed);
+            }
+            Ok(response) => self.give_up("PUT", format!("HTTP {}", response.status())),
+            Err(e) => self.give_up("PUT", e),
+        }
+    }
+
+    pub fn hits(&self) -> u64 {
+        self.hits.load(Ordering::Relaxed)
+    }
+
+    pub fn uploads(&self) -> u64 {
+        self.uploads.load(Ordering::Relaxed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_layout_and_mode_from_config() {
+        if std::env::var_os("CX_CACHE_REMOTE").is_some()
+            || std::env::var_os("CX_CACHE_REMOTE_MODE").is_some()
+            || crate::deps::is_offline()
+        {
+            return;
+        }
+        assert!(RemoteCache::from_config(None).is_none());
+
+        let mut config = CacheConfig {
+            remote: Some("http://cache.local/cx/".to_string()),
+            ..Default::default()
+        };
+        let remote = RemoteCache::from_config(Some(&config)).unwrap();
+        assert_eq!(remote.url(), "http://cache.local/cx");
+        assert!(!remote.is_writable());
+        assert_eq!(remote.object_url("ab12"), "http://cache.local/cx/ab12");
+
+        config.remote_mode = Some("read-write".to_string());
+        config.remote_layout = Some("bazel".to_string());
+        let remote = RemoteCache::from_config(Some(&config)).unwrap();
+        assert!(remote.is_writable());
+        assert_eq!(remote.object_url("ab12"), "http://cache.local/cx/ac/ab12");
+    }
+}
@@ -0,0 +1,321 @@
+//! Build target resolution.
+//!
+//! A package produces a single artifact by default (`[build] bin` / `type`).
+//! `[[bin]]`, `[[lib]]` and the `src/bin/*.cpp` convention turn it into
+//! several: shared sources compile once, each binary links its own entry
+//! point on top of them, and libraries are built from the shared objects only.
+
+use super::core::{binary_basename, build_type_of, normalized_source_key};
+use crate::config::{BuildType, CxConfig};
+use anyhow::{Result, bail};
+use std::path::{Path, PathBuf};
+
+const ENTRY_EXTENSIONS: [&str; 4] = ["cpp", "cc", "cxx", "c"];
+
+/// One artifact produced by the build
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BuildTarget {
+    pub name: String,
+    pub kind: BuildType,
+    /// Entry point of a binary; `None` links every project object
+    pub entry: Option<PathBuf>,
+}
+
+impl BuildTarget {
+    pub fn is_executable(&self) -> bool {
+        self.kind == BuildType::Executable
+    }
+}
+
+fn find_entry(dir: &Path, stem: &str) -> Option<PathBuf> {
+    ENTRY_EXTENSIONS
+        .iter()
+        .map(|ext| dir.join(format!("{stem}.{ext}")))
+        .find(|p| p.exists())
+}
+
+/// Sources directly inside src/bin/, sorted by path
+fn discover_bin_sources() -> Vec<PathBuf> {
+    let Ok(entries) = std::fs::read_dir(Path::new("src").join("bin")) else {
+        return Vec::new();
+    };
+    let mut found: Vec<PathBuf> = entries
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .filter(|p| {
+            p.is_file()
+                && p.extension()
+                    .is_some_and(|ext| ENTRY_EXTENSIONS.contains(&ext.to_string_lossy().as_ref()))
+        })
+        .map(|p| {
+            PathBuf::from("src")
+                .join("bin")
+                .join(p.file_name().unwrap_or_default())
+        })
+        .collect();
+    found.sort();
+    found
+}
+
+/// `src/a.cpp` and `./src/a.cpp` name the same file
+pub(crate) fn same_source(a: &Path, b: &Path) -> bool {
+    normalized_source_key(a) == normalized_source_key(b)
+}
+
+/// Resolve every target the package declares, in a stable order: the
+/// package target, explicit `[[bin]]`s, discovered `src/bin/` files, then
+/// `[[lib]]`s.
+pub fn resolve_targets(config: &CxConfig) -> Result<Vec<BuildTarget>> {
+    let package_name = binary_basename(config);
+    let package_type = build_type_of(config);
+    let autobins = config
+        .build
+        .as_ref()
+        .and_then(|b| b.autobins)
+        .unwrap_or(true);
+
+    let explicit_bins = config.bin_targets.as_deref().unwrap_or_default();
+    let explicit_libs = config.lib_targets.as_deref().unwrap_or_default();
+    let discovered = if autobins && package_type != BuildType::HeaderOnly {
+        discover_bin_sources()
+    } else {
+        Vec::new()
+    };
+
+    if explicit_bins.is_empty() && explicit_libs.is_empty() && discovered.is_empty() {
+        return Ok(vec![BuildTarget {
+            name: package_name,
+            kind: package_type,
+            entry: None,
+        }]);
+    }
+
+    if package_type == BuildType::HeaderOnly {
+        bail!("[[bin]] and [[lib]] targets are not supported for header-only packages");
+    }
+
+    let mut bins = Vec::new();
+    for bin in explicit_bins {
+        let entry = match &bin.main {
+            Some(main) => PathBuf::from(main),
+            None => match find_entry(&Path::new("src").join("bin"), &bin.name) {
+                Some(path) => path,
+                None => bail!(
+                    "[[bin]] '{}' has no `main` and src/bin/{}.cpp does not exist",
+                    bin.name,
+                    bin.name
+                ),
+            },
+        };
+        bins.push(BuildTarget {
+            name: bin.name.clone(),
+            kind: BuildType::Executable,
+            entry: Some(entry),
+        });
+    }
+
+    for src in discovered {
+        if bins
+            .iter()
+            .any(|t| t.entry.as_deref().is_some_and(|e| same_source(e, &src)))
+        {
+            continue;
+        }
+        let name = src
+            .file_stem()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .to_string();
+        bins.push(BuildTarget {
+            name,
+            kind: BuildType::Executable,
+            entry: Some(src),
+        });
+    }
+
+    let mut targets = Vec::new();
+
+    // The package itself: src/main.* for executables, the shared objects
+    // for library packages.
+    if package_type == BuildType::Executable {
+        if let Some(main) = find_entry(Path::new("src"), "main")
+            && !bins
+                .iter()
+                .any(|t| t.entry.as_deref().is_some_and(|e| same_source(e, &main)))
+        {
+            targets.push(BuildTarget {
+                name: package_name.clone(),
+                kind: BuildType::Executable,
+                entry: Some(main),
+            });
+        }
+    } else {
+        targets.push(BuildTarget {
+            name: package_name.clone(),
+            kind: package_type,
+            entry: None,
+        });
+    }
+
+    targets.extend(bins);
+
+    for lib in explicit_libs {
+        let kind = match lib.lib_type.as_deref() {
+            None => BuildType::StaticLibrary,
+            Some(raw) => match BuildType::parse(raw) {
+                Some(kind @ (BuildType::StaticLibrary | BuildType::SharedLibrary)) => kind,
+                _ => bail!(
+                    "[[lib]] type = '{}' is invalid (expected static-library or shared-library)",
+                    raw
+                ),
+            },
+        };
+        let name = lib.name.clone().unwrap_or_else(|| package_name.clone());
+        // `type = "static-library"` plus a matching [[lib]] is the same target
+        if targets.iter().any(|t| t.name == name && t.kind == kind) {
+            continue;
+        }
+        targets.push(BuildTarget {
+            name,
+            kind,
+            entry: None,
+        });
+    }
+
+    for (i, target) in targets.iter().enumerate() {
+        if targets[..i]
+            .iter()
+            .any(|t| t.name == target.name && t.kind.is_library() == target.kind.is_library())
+        {
+            bail!("Duplicate target name '{}' in cx.toml", target.name);
+        }
+    }
+
+    Ok(targets)
+}
+
+/// Narrow `targets` down to the binary requested with `--bin`
+pub fn select_targets(targets: &[BuildTarget], bin: Option<&str>) -> Result<Vec<BuildTarget>> {
+    let Some(name) = bin else {
+        return Ok(targets.to_vec());
+    };
+    match targets.iter().find(|t| t.is_executable() && t.name == name) {
+        Some(target) => Ok(vec![target.clone()]),
+        None => bail!(
+            "No binary target named '{}'. Available: {}",
+            name,
+            bin_names(targets)
+        ),
+    }
+}
+
+/// Pick the binary `cx run` should execute when `--bin` is omitted
+pub fn default_run_target(config: &CxConfig, targets: &[BuildTarget]) -> Result<BuildTarget> {
+    let executables: Vec<&BuildTarget> = targets.iter().filter(|t| t.is_executable()).collect();
+    let package_name = binary_basename(config);
+
+    if let Some(target) = executables.iter().find(|t| t.name == package_name) {
+        return Ok((*target).clone());
+    }
+    match executables.as_slice() {
+        [] => {
+            let kind = build_type_of(config);
+            bail!(
+                "Nothing to run: this project builds a {} (build.type = \"{}\"). Use `cx build` instead.",
+                kind.as_str().replace('-', " "),
+                kind.as_str()
+            )
+        }
+        [only] => Ok((*only).clone()),
+        _ => bail!(
+            "Multiple binaries available; pick one with --bin. Available: {}",
+            bin_names(targets)
+        ),
+    }
+}
+
+/// Entry points of all binaries; their objects never go into libraries
+/// or other binaries.
+pub fn entry_sources(targets: &[BuildTarget]) -> Vec<PathBuf> {
+    targets.iter().filter_map(|t| t.entry.clone()).collect()
+}
+
+pub fn is_entry_source(entries: &[PathBuf], src: &Path) -> bool {
+    entries.iter().any(|e| same_source(e, src))
+}
+
+fn bin_names(targets: &[BuildTarget]) -> String {
+    let names: Vec<&str> = targets
+        .iter()
+        .filter(|t| t.is_executable())
+        .map(|t| t.name.as_str())
+        .collect();
+    if names.is_empty() {
+        "(none)".to_string()
+    } else {
+        names.join(", ")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn target(name: &str, kind: BuildType, entry: Option<&str>) -> BuildTarget {
+        BuildTarget {
+            name: name.to_string(),
+            kind,
+            entry: entry.map(PathBuf::from),
+        }
+    }
+
+    #[test]
+    fn single_target_when_nothing_declared() {
+        let config = CxConfig {
+            package: crate::config::PackageConfig {
+                name: "solo".to_string(),
+                version: "0.1.0".to_string(),
+                edition: "c++20".to_string(),
+            },
+            build: Some(crate::config::BuildConfig {
+                autobins: Some(false),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let targets = resolve_targets(&config).unwrap();
+        assert_eq!(targets, vec![target("solo", BuildType::Executable, None)]);
+    }
+
+    #[test]
+    fn select_targets_filters_by_bin_name() {
+        let targets = vec![
+            target("app", BuildType::Executable, Some("src/main.cpp")),
+            target("server", BuildType::Executable, Some("src/bin/server.cpp")),
+            target("app", BuildType::StaticLibrary, None),
+        ];
+        let selected = select_targets(&targets, Some("server")).unwrap();
+        assert_eq!(selected.len(), 1);
+        assert_eq!(selected[0].name, "server");
+
+        let err = select_targets(&targets, Some("nope")).unwrap_err();
+        assert!(err.to_string().contains("Available: app, server"));
+
+        assert_eq!(select_targets(&targets, None).unwrap().len(), 3);
+    }
+
+    #[test]
+    fn entry_sources_match_normalized_paths() {
+        let targets = vec![
+            target(
+                "server",
+                BuildType::Executable,
+                Some("./src/bin/server.cpp"),
+            ),
+            target("lib", BuildType::StaticLibrary, None),
+        ];
+        let entries = entry_sources(&targets);
+        assert!(is_entry_source(&entries, Path::new("src/bin/server.cpp")));
+        assert!(!is_entry_source(&entries, Path::new("src/util.cpp")));
+    }
+}
@@ -9,17 +9,22 @@
 //! - Parallel test compilation
 //! - Test filtering with `--filter`
 
+use super::features::{FeatureSelection, Features};
+use super::jobs::Scheduler;
+use super::messages::MessageFormat;
+use super::platform::{Platform, conditional_sources};
+use super::targets::{entry_sources, resolve_targets};
 use super::utils::{get_compiler, get_std_flag_gcc, get_std_flag_msvc, get_toolchain, load_config};
 use crate::config::CxConfig;
 use anyhow::Result;
 use colored::*;
-use indicatif::{ProgressBar, ProgressStyle};
-use rayon::prelude::*;
+use indicatif::{ProgressBar, ProgressDrawTarget, ProgressStyle};
+use serde_json::json;
 use std::collections::HashSet;
 use std::fs;
 use std::path::{Path, PathBuf};
 use std::process::Command;
-use std::time::SystemTime;
+use std::time::{Instant, SystemTime};
 use walkdir::WalkDir;
 
 #[derive(Clone)]
@@ -119,9 +124,14 @@ fn should_recompile_test_binary(
     src_mtime > bin_mtime || global_input_mtime > bin_mtime
 }
 
-pub fn run_tests(filter: Option<String>) -> Result<()> {
+pub fn run_tests(
+    filter: Option<String>,
+    jobs: Option<usize>,
+    messages: MessageFormat,
+    selection: &FeatureSelection,
+) -> Result<()> {
     // Load config or default
-    let config = load_config().unwrap_or_else(|_| CxConfig {
+    let mut config = load_config().unwrap_or_else(|_| CxConfig {
         package: crate::config::PackageConfig {
             name: "test_runner".into(),
             version: "0.0.0".into(),
@@ -129,6 +139,9 @@ pub fn run_tests(filter: Option<String>) -> Result<()> {
         },
         ..Default::default()
     });
+    let features = Features::resolve(&config, selection)?;
+    features.apply(&mut config);
+    let config = config;
 
     let test_dir_str = config
         .test
@@ -138,7 +151,7 @@ pub fn run_tests(filter: Option<String>) -> Result<()> {
     let test_dir = Path::new(&test_dir_str);
 
     if !test_dir.exists() {
-        println!("{} No {}/ directory found.", "!".yellow(), test_dir_str);
+        status!("{} No {}/ directory found.", "!".yellow(), test_dir_str);
         return Ok(());
     }
 
@@ -155,9 +168,9 @@ pub fn run_tests(filter: Option<String>) -> Result<()> {
         dep_libs = libs;
     }
 
-    println!("{} Running tests...", "🧪".magenta());
+    status!("{} Running tests...", "🧪".magenta());
     if let Some(f) = &filter {
-        println!("   Filter: {}", f.cyan());
+        status!("   Filter: {}", f.cyan());
     }
     let build_base = super::core::artifact_profile_dir(false);
     let test_build_dir = build_base.join("tests");
@@ -184,9 +197,20 @@ pub fn run_tests(filter: Option<String>) -> Result<()> {
         .iter()
         .map(|src| super::core::object_file_name_for_source(src, expected_obj_ext))
         .collect();
-    let main_obj_names: HashSet<String> = project_sources
+    // Every target's entry point defines `main`; a lone package target has
+    // no explicit entry, so its `main.*` is the one to leave out.
+    let entries = entry_sources(&resolve_targets(&config)?);
+    let is_implicit_main =
+        |src: &Path| entries.is_empty() && src.file_stem().is_some_and(|stem| stem == "main");
+    // Objects of disabled features (or other platforms' sources) may linger
+    // from an earlier build
+    let platform_sources = conditional_sources(&config, &Platform::host())?;
+    let excluded_obj_names: HashSet<String> = project_sources
         .iter()
-        .filter(|src| src.file_stem().is_some_and(|stem| stem == "main"))
+        .filter(|src| {
+            is_implicit_main(src) || features.excludes(src) || platform_sources.excludes(src)
+        })
+        .chain(&entries)
         .map(|src| super::core::object_file_name_for_source(src, expected_obj_ext))
         .collect();
 
@@ -199,8 +223,8 @@ pub fn run_tests(filter: Option<String>) -> Result<()> {
                     .unwrap_or_default()
                     .to_string_lossy()
                     .into_owned();
-                // Exclude main object files and module objects based on exact deterministic names.
-                if main_obj_names.contains(file_name.as_str()) {
+                // Exclude entry-point, disabled-feature and module objects by their deterministic names.
+                if excluded_obj_names.contains(file_name.as_str()) {
                     continue;
                 }
                 if module_obj_names.contains(file_name.as_str()) {
@@ -210,11 +234,11 @@ pub fn run_tests(filter: Option<String>) -> Result<()> {
             }
         }
     } else {
-        println!(
+        status!(
             "{} Warning: Project not built. Running tests without linking project sources.",
             "!".yellow()
         );
-        println!("   Run 'cx build' first to link project code.");
+        status!("   Run 'cx build' first to link project code.");
     }
 
     let mut test_files = Vec::new();
@@ -239,7 +263,7 @@ pub fn run_tests(filter: Option<String>) -> Result<()> {
     }
 
     if test_files.is_empty() {
-        println!("{} No tests found.", "!".yellow());
+        status!("{} No tests found.", "!".yellow());
         return Ok(());
     }
 
@@ -252,7 +276,7 @@ pub fn run_tests(filter: Option<String>) -> Result<()> {
         .unwrap_or(false);
 
     if single_binary {
-        println!("{} Building single test runner...", "🔨".cyan());
+        status!("{} Building single test runner...", "🔨".cyan());
         let test_name = config.package.name.clone(); // Use package name for the single test runner
         let output_bin_path = if cfg!(target_os = "windows") {
             test_build_dir.join(format!("{}.exe", test_name))
@@ -331,35 +355,63 @@ pub fn run_tests(filter: Option<String>) -> Result<()> {
         // Execute Compilation
         let start = std::time::Instant::now();
         let output = cmd.output()?;
+        messages.compile_finished(
+            Path::new(&output_bin),
+            Path::new("."),
+            &format!(
+                "{}{}",
+                String::from_utf8_lossy(&output.stdout),
+                String::from_utf8_lossy(&output.stderr)
+            ),
+            output.status.success(),
+            false,
+            start.elapsed(),
+        );
         if !output.status.success() {
-            println!("{} Test Runner Compilation Failed:", "x".red());
-            println!("{}", String::from_utf8_lossy(&output.stdout));
-            println!("{}", String::from_utf8_lossy(&output.stderr));
+            messages.emit(
+                "test-result",
+                json!({ "name": test_name, "outcome": "compile-error", "duration_ms": 0 }),
+            );
+            status!("{} Test Runner Compilation Failed:", "x".red());
+            status!("{}", String::from_utf8_lossy(&output.stdout));
+            status!("{}", String::from_utf8_lossy(&output.stderr));
             return Ok(());
         }
-        println!("   {} Compiled in {:.2?}s", "✓".green(), start.elapsed());
+        status!("   {} Compiled in {:.2?}s", "✓".green(), start.elapsed());
 
         // Run It
-        println!("{} Running tests...", "🚀".cyan());
+        status!("{} Running tests...", "🚀".cyan());
         let mut run_cmd = Command::new(&output_bin_path);
         // Pass filter as argument if present (standard for Catch2/GTest/doctest)
         if let Some(f) = &filter {
             run_cmd.arg(f);
         }
 
+        run_cmd.stdout(super::messages::human_stdout());
+
+        let run_start = Instant::now();
         let status = run_cmd.status()?;
+        messages.emit(
+            "test-result",
+            json!({
+                "name": test_name,
+                "outcome": if status.success() { "pass" } else { "fail" },
+                "duration_ms": run_start.elapsed().as_millis() as u64,
+            }),
+        );
         if status.success() {
-            println!("{}", "TESTS PASSED".green().bold());
+            status!("{}", "TESTS PASSED".green().bold());
         } else {
-            println!("{}", "TESTS FAILED".red().bold());
+            status!("{}", "TESTS FAILED".red().bold());
         }
         return Ok(());
     }
 
-    // Compile modules first if any exist (sequential, like core.rs)
+    // Compile modules first if any exist (sequential, imported modules first)
     let mut module_objs: Vec<PathBuf> = Vec::new();
     if !module_files.is_empty() {
-        println!("{} Compiling project modules...", "📦".cyan());
+        let module_files = super::modules::order_by_imports(&module_files)?;
+        status!("{} Compiling project modules...", "📦".cyan());
         fs::create_dir_all(obj_dir.as_path())?;
 
         let compiler = cpp_compiler.command.clone();
@@ -447,9 +499,9 @@ pub fn run_tests(filter: Option<String>) -> Result<()> {
 
                         let output1 = cmd1.output()?;
                         if !output1.status.success() {
-                            println!("{} Module precompilation failed: {}", "x".red(), stem);
-                            println!("{}", String::from_utf8_lossy(&output1.stdout));
-                            println!("{}", String::from_utf8_lossy(&output1.stderr));
+                            status!("{} Module precompilation failed: {}", "x".red(), stem);
+                            status!("{}", String::from_utf8_lossy(&output1.stdout));
+                            status!("{}", String::from_utf8_lossy(&output1.stderr));
                             anyhow::bail!("Module compilation failed");
                         }
 
@@ -473,16 +525,17 @@ pub fn run_tests(filter: Option<String>) -> Result<()> {
 
                         let output2 = cmd2.output()?;
                         if !output2.status.success() {
-                            println!("{} Module object compilation failed: {}", "x".red(), stem);
-                            println!("{}", String::from_utf8_lossy(&output2.stdout));
-                            println!("{}", String::from_utf8_lossy(&output2.stderr));
+                            status!("{} Module object compilation failed: {}", "x".red(), stem);
+                            status!("{}", String::from_utf8_lossy(&output2.stdout));
+                            status!("{}", String::from_utf8_lossy(&output2.stderr));
                             anyhow::bail!("Module compilation failed");
                         }
                     } else {
                         // GCC with -fmodules-ts
                         let mut cmd = Command::new(&compiler);
                         if is_gcc {
-                            cmd.arg("-fmodules-ts");
+                            // `.cppm`/`.ixx`/`.mpp` aren't C++ extensions to GCC
+                            cmd.args(["-fmodules-ts", "-x", "c++"]);
                         }
                         cmd.args(["-c"]);
                         cmd.arg(mod_path);
@@ -499,9 +552,9 @@ pub fn run_tests(filter: Option<String>) -> Result<()> {
 
                         let output = cmd.output()?;
                         if !output.status.success() {
-                            println!("{} Module compilation failed: {}", "x".red(), stem);
-                            println!("{}", String::from_utf8_lossy(&output.stdout));
-                            println!("{}", String::from_utf8_lossy(&output.stderr));
+                            status!("{} Module compilation failed: {}", "x".red(), stem);
+                            status!("{}", String::from_utf8_lossy(&output.stdout));
+                            status!("{}", String::from_utf8_lossy(&output.stderr));
                             anyhow::bail!("Module compilation failed");
                         }
                     }
@@ -511,9 +564,9 @@ pub fn run_tests(filter: Option<String>) -> Result<()> {
                 if is_msvc {
                     let output = cmd.output()?;
                     if !output.status.success() {
-                        println!("{} Module compilation failed: {}", "x".red(), stem);
-                        println!("{}", String::from_utf8_lossy(&output.stdout));
-                        println!("{}", String::from_utf8_lossy(&output.stderr));
+                        status!("{} Module compilation failed: {}", "x".red(), stem);
+                        status!("{}", String::from_utf8_lossy(&output.stdout));
+                        status!("{}", String::from_utf8_lossy(&output.stderr));
                         anyhow::bail!("Module compilation failed");
                     }
                 }
@@ -531,6 +584,9 @@ pub fn run_tests(filter: Option<String>) -> Result<()> {
             .unwrap_or_else(|_| ProgressStyle::default_bar())
             .progress_chars("●○·"),
     );
+    if messages.is_json() {
+        pb.set_draw_target(ProgressDrawTarget::hidden());
+    }
 
     let mut global_input_paths = project_objs.clone();
     global_input_paths.extend(module_objs.clone());
@@ -546,9 +602,9 @@ pub fn run_tests(filter: Option<String>) -> Result<()> {
     let global_input_mtime = latest_mtime(&global_input_paths);
 
     // Phase 1: Parallel Compilation
-    let compiled_results: Vec<(String, Option<String>)> = test_files
-        .par_iter()
-        .map(|(path, is_cpp)| {
+    let scheduler = Scheduler::new(Some(&config), jobs);
+    let compiled_results: Vec<(String, Option<String>)> =
+        scheduler.run(&test_files, |_, (path, is_cpp)| {
             let test_name = path
                 .file_stem()
                 .unwrap_or_default()
@@ -563,7 +619,7 @@ pub fn run_tests(filter: Option<String>) -> Result<()> {
 
             if !should_recompile_test_binary(path, &output_bin_path, global_input_mtime) {
                 pb.inc(1); // Skip compile step
-                return (test_name, Some(output_bin));
+                return Ok((test_name, Some(output_bin)));
             }
 
             // Check if this test uses modules (has import statements)
@@ -673,6 +729,9 @@ pub fn run_tests(filter: Option<String>) -> Result<()> {
                     cmd.arg(flag);
                 }
             }
+            if let Some(build_cfg) = &config.build {
+                cmd.args(build_cfg.define_flags(is_msvc));
+            }
 
             // Link Libs & Project Objects
             if is_msvc {
@@ -709,14 +768,31 @@ pub fn run_tests(filter: Option<String>) -> Result<()> {
                 cmd.envs(&toolchain_env);
             }
 
+            let compile_start = Instant::now();
+            messages.emit(
+                "compile-started",
+                json!({ "file": path.display().to_string() }),
+            );
             let output = cmd.output();
             let success = match output {
                 Ok(out) => {
+                    messages.compile_finished(
+                        path,
+                        Path::new("."),
+                        &format!(
+                            "{}{}",
+                            String::from_utf8_lossy(&out.stdout),
+                            String::from_utf8_lossy(&out.stderr)
+                        ),
+                        out.status.success(),
+                        false,
+                        compile_start.elapsed(),
+                    );
                     if !out.status.success() {
                         pb.suspend(|| {
-                            println!("{} COMPILE FAIL: {}", "x".red(), test_name.bold());
-                            println!("{}", String::from_utf8_lossy(&out.stdout));
-                            println!("{}", String::from_utf8_lossy(&out.stderr));
+                            status!("{} COMPILE FAIL: {}", "x".red(), test_name.bold());
+                            status!("{}", String::from_utf8_lossy(&out.stdout));
+                            status!("{}", String::from_utf8_lossy(&out.stderr));
                         });
                         false
                     } else {
@@ -725,7 +801,7 @@ pub fn run_tests(filter: Option<String>) -> Result<()> {
                 }
                 Err(e) => {
                     pb.suspend(|| {
-                        println!("{} COMPILER ERROR: {} ({})", "x".red(), test_name.bold(), e);
+                        status!("{} COMPILER ERROR: {} ({})", "x".red(), test_name.bold(), e);
                     });
                     false
                 }
@@ -733,12 +809,11 @@ pub fn run_tests(filter: Option<String>) -> Result<()> {
 
             pb.inc(1);
             if success {
-                (test_name, Some(output_bin))
+                Ok((test_name, Some(output_bin)))
             } else {
-                (test_name, None)
+                Ok((test_name, None))
             }
-        })
-        .collect();
+        })?;
 
     // Phase 2: Sequential Execution (Running Tests)
     let mut passed_tests = 0;
@@ -747,6 +822,12 @@ pub fn run_tests(filter: Option<String>) -> Result<()> {
     for (test_name, bin_path) in compiled_results {
         total_tests += 1;
 
+        if bin_path.is_none() {
+            messages.emit(
+                "test-result",
+                json!({ "name": test_name, "outcome": "compile-error", "duration_ms": 0 }),
+            );
+        }
         if let Some(output_bin) = bin_path {
             pb.set_message(format!("Running {}", test_name));
 
@@ -756,13 +837,25 @@ pub fn run_tests(filter: Option<String>) -> Result<()> {
                 format!("./{}", output_bin)
             };
 
-            let run_status = Command::new(&run_path).status();
+            let run_start = Instant::now();
+            let run_status = Command::new(&run_path)
+                .stdout(super::messages::human_stdout())
+                .status();
+            let passed = run_status.as_ref().is_ok_and(|status| status.success());
+            messages.emit(
+                "test-result",
+                json!({
+                    "name": test_name,
+                    "outcome": if passed { "pass" } else { "fail" },
+                    "duration_ms": run_start.elapsed().as_millis() as u64,
+                }),
+            );
 
             match run_status {
                 Ok(status) => {
                     if status.success() {
                         pb.suspend(|| {
-                            println!(
+                            status!(
                                 "   {} TEST {} ... {}",
                                 "✓".green(),
                                 test_name.bold(),
@@ -772,7 +865,7 @@ pub fn run_tests(filter: Option<String>) -> Result<()> {
                         passed_tests += 1;
                     } else {
                         pb.suspend(|| {
-                            println!(
+                            status!(
                                 "   {} TEST {} ... {}",
                                 "x".red(),
                                 test_name.bold(),
@@ -783,7 +876,7 @@ pub fn run_tests(filter: Option<String>) -> Result<()> {
                 }
                 Err(_) => {
                     pb.suspend(|| {
-                        println!(
+                        status!(
                             "   {} TEST {} ... {}",
                             "x".red(),
                             test_name.bold(),
@@ -799,11 +892,11 @@ pub fn run_tests(filter: Option<String>) -> Result<()> {
 
     pb.finish_and_clear();
 
-    println!("\nTest Result: {}/{} passed.", passed_tests, total_tests);
+    status!("\nTest Result: {}/{} passed.", passed_tests, total_tests);
     if total_tests > 0 && passed_tests == total_tests {
-        println!("{}", "ALL TESTS PASSED ✨".green().bold());
+        status!("{}", "ALL TESTS PASSED ✨".green().bold());
     } else if total_tests > 0 {
-        println!("{}", "SOME TESTS FAILED 💀".red().bold());
+        status!("{}", "SOME TESTS FAILED 💀".red().bold());
         anyhow::bail!("Tests failed: {}/{} passed", passed_tests, total_tests);
     }
 
@@ -0,0 +1,214 @@
+//! Unity (jumbo) builds from `cx build --unity` or `[build] unity`.
+//!
+//! ```toml
+//! [build]
+//! unity = { batch_size = 16 }
+//!
+//! [[build.overrides]]
+//! files = ["src/legacy/**"]
+//! unity = false
+//! ```
+//!
+//! Sources are sorted and split into batches of `batch_size`, C and C++
+//! apart. Each batch is a generated `unity_<n>.cpp` (or `.c`) under
+//! `.cx/build/unity/<profile>/` that `#include`s its members, so common
+//! headers are parsed once per batch instead of once per source. Batches
+//! compile in parallel like any other source, and `compile_commands.json`
+//! still lists every member with its own command. Entry points, sources
+//! importing modules and sources matched by an override compile on their
+//! own.
+
+use super::core::object_file_path_for_source;
+use crate::config::CxConfig;
+use anyhow::Result;
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Sources per batch when `batch_size` isn't set
+pub const DEFAULT_BATCH_SIZE: usize = 16;
+
+/// One generated unity source and the sources it includes
+#[derive(Debug, Clone, PartialEq)]
+pub struct Batch {
+    pub source: PathBuf,
+    pub members: Vec<PathBuf>,
+}
+
+/// Batch size, or `None` when unity builds are off
+pub fn batch_size(config: &CxConfig, requested: bool) -> Option<usize> {
+    let configured = config.build.as_ref().and_then(|b| b.unity.as_ref());
+    if !requested && configured.is_none() {
+        return None;
+    }
+    let size = configured
+        .and_then(|u| u.batch_size)
+        .unwrap_or(DEFAULT_BATCH_SIZE);
+    Some(size.max(1))
+}
+
+/// Split `sources` into groups of at most `batch_size`; a group of one
+/// gains nothing from batching and is left out
+pub fn plan(sources: &[PathBuf], batch_size: usize) -> Vec<Vec<PathBuf>> {
+    let (mut c, mut cpp): (Vec<PathBuf>, Vec<PathBuf>) = sources
+        .iter()
+        .cloned()
+        .partition(|src| src.extension().is_some_and(|ext| ext == "c"));
+    c.sort();
+    cpp.sort();
+    cpp.chunks(batch_size)
+        .chain(c.chunks(batch_size))
+        .filter(|group| group.len() > 1)
+        .map(<[PathBuf]>::to_vec)
+        .collect()
+}
+
+/// Write one unity source per group into `dir`; unchanged files keep their
+/// mtime and files of earlier, larger plans are removed
+pub fn write_batches(
+    dir: &Path,
+    current_dir: &Path,
+    groups: Vec<Vec<PathBuf>>,
+) -> Result<Vec<Batch>> {
+    fs::create_dir_all(dir)?;
+    let mut batches = Vec::with_capacity(groups.len());
+    for (idx, members) in groups.into_iter().enumerate() {
+        let is_c = members
+            .iter()
+            .all(|src| src.extension().is_some_and(|ext| ext == "c"));
+        let source = dir.join(format!("unity_{idx}.{}", if is_c { "c" } else { "cpp" }));
+        let content = batch_content(current_dir, &members);
+        if fs::read_to_string(&source).ok().as_deref() != Some(content.as_str()) {
+            fs::write(&source, content)?;
+        }
+        batches.push(Batch { source, members });
+    }
+
+    let keep: HashSet<&Path> = batches.iter().map(|b| b.source.as_path()).collect();
+    for entry in fs::read_dir(dir)?.filter_map(|e| e.ok()) {
+        let path = entry.path();
+        if path.is_file() && !keep.contains(path.as_path()) {
+            let _ = fs::remove_file(&path);
+        }
+    }
+    Ok(batches)
+}
+
+fn batch_content(current_dir: &Path, members: &[PathBuf]) -> String {
+    let mut content = format!(
+        "// Generated by caxe: unity batch of {} sources\n",
+        members.len()
+    );
+    for member in members {
+        let abs = if member.is_absolute() {
+            member.clone()
+        } else {
+            current_dir.join(member)
+        };
+        content.push_str(&format!(
+            "#include \"{}\"\n",
+            abs.to_string_lossy().replace('\\', "/")
+        ));
+    }
+    content
+}
+
+/// Drop objects the current plan doesn't link, so `cx test` (which links
+/// everything in `obj/`) never sees a source twice: standalone objects of
+/// batched sources and batch objects of other plans. Batch objects live in
+/// `obj/unity/`.
+pub fn prune_objects(obj_dir: &Path, batches: &[Batch], obj_ext: &str) {
+    let unity_obj_dir = obj_dir.join("unity");
+    if batches.is_empty() {
+        let _ = fs::remove_dir_all(&unity_obj_dir);
+        return;
+    }
+    for member in batches.iter().flat_map(|b| &b.members) {
+        let _ = fs::remove_file(object_file_path_for_source(obj_dir, member, obj_ext));
+    }
+    let keep: HashSet<PathBuf> = batches
+        .iter()
+        .map(|b| object_file_path_for_source(&unity_obj_dir, &b.source, obj_ext))
+        .collect();
+    let Ok(entries) = fs::read_dir(&unity_obj_dir) else {
+        return;
+    };
+    for entry in entries.filter_map(|e| e.ok()) {
+        let path = entry.path();
+        if path.extension().is_some_and(|ext| ext == obj_ext) && !keep.contains(&path) {
+            let _ = fs::remove_file(&path);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn paths(names: &[&str]) -> Vec<PathBuf> {
+        names.iter().map(PathBuf::from).collect()
+    }
+
+    #[test]
+    fn test_plan_groups_by_language() {
+        let sources = paths(&[
+            "src/d.cpp",
+            "src/a.cpp",
+            "src/c.cpp",
+            "src/b.cpp",
+            "src/x.c",
+            "src/y.c",
+            "src/e.cpp",
+        ]);
+        assert_eq!(
+            plan(&sources, 2),
+            vec![
+                paths(&["src/a.cpp", "src/b.cpp"]),
+                paths(&["src/c.cpp", "src/d.cpp"]),
+                paths(&["src/x.c", "src/y.c"]),
+            ]
+        );
+        assert_eq!(plan(&sources, 16).len(), 2);
+        assert!(plan(&paths(&["src/only.cpp"]), 16).is_empty());
+    }
+
+    #[test]
+    fn test_write_batches_includes_members() {
+        let dir = tempfile::tempdir().unwrap();
+        let unity_dir = dir.path().join("unity");
+        fs::create_dir_all(&unity_dir).unwrap();
+        fs::write(unity_dir.join("unity_7.cpp"), "stale").unwrap();
+
+        let batches = write_batches(
+            &unity_dir,
+            Path::new("/project"),
+            vec![
+                paths(&["src/a.cpp", "src/b.cpp"]),
+                paths(&["src/x.c", "src/y.c"]),
+            ],
+        )
+        .unwrap();
+        assert_eq!(batches[0].source, unity_dir.join("unity_0.cpp"));
+        assert_eq!(batches[1].source, unity_dir.join("unity_1.c"));
+        assert_eq!(
+            fs::read_to_string(&batches[0].source).unwrap(),
+            "// Generated by caxe: unity batch of 2 sources\n\
+             #include \"/project/src/a.cpp\"\n\
+             #include \"/project/src/b.cpp\"\n"
+        );
+        assert!(!unity_dir.join("unity_7.cpp").exists());
+    }
+
+    #[test]
+    fn test_batch_size() {
+        let mut config: CxConfig =
+            toml::from_str("[package]\nname = \"app\"\nversion = \"0.1.0\"\n").unwrap();
+        assert_eq!(batch_size(&config, false), None);
+        assert_eq!(batch_size(&config, true), Some(DEFAULT_BATCH_SIZE));
+        config = toml::from_str(
+            "[package]\nname = \"app\"\nversion = \"0.1.0\"\n\n[build]\nunity = { batch_size = 4 }\n",
+        )
+        .unwrap();
+        assert_eq!(batch_size(&config, false), Some(4));
+    }
+}
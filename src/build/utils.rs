@@ -1,3 +1,4 @@
+use super::platform::{self, Platform};
 use crate::config::{CxConfig, Profile};
 use crate::toolchain::{self, CompilerType, Toolchain, ToolchainError};
 use anyhow::{Context, Result};
@@ -9,6 +10,11 @@ use std::process::Command;
 
 // --- Helper: Load Config with Profile Parsing ---
 pub fn load_config() -> Result<CxConfig> {
+    load_config_for(&Platform::host())
+}
+
+/// Load cx.toml with `[target.'cfg(...)']` sections evaluated for `platform`
+pub fn load_config_for(platform: &Platform) -> Result<CxConfig> {
     if !Path::new("cx.toml").exists() {
         return Err(anyhow::anyhow!(
             "cx.toml not found in current directory.\n\n\
@@ -22,15 +28,31 @@ pub fn load_config() -> Result<CxConfig> {
     let raw_value: toml::Value = toml::from_str(&config_str)
         .context("Failed to parse cx.toml - check for syntax errors (missing quotes, brackets)")?;
 
-    // Extract profiles from [profile:name] tables
+    // Extract profiles from [profile:name] and [profile.name] tables
     let mut profiles: HashMap<String, Profile> = HashMap::new();
     if let toml::Value::Table(root) = &raw_value {
-        for (key, value) in root {
-            if key.starts_with("profile:") {
-                let profile_name = key.strip_prefix("profile:").unwrap().to_string();
-                if let Ok(profile) = value.clone().try_into::<Profile>() {
-                    profiles.insert(profile_name, profile);
-                }
+        let dotted = root
+            .get("profile")
+            .and_then(|v| v.as_table())
+            .into_iter()
+            .flatten()
+            .map(|(name, value)| (name.as_str(), format!("profile.{name}"), value));
+        let prefixed = root.iter().filter_map(|(key, value)| {
+            let name = key.strip_prefix("profile:")?;
+            Some((name, key.clone(), value))
+        });
+        for (name, header, value) in prefixed.chain(dotted) {
+            let profile = value
+                .clone()
+                .try_into::<Profile>()
+                .map_err(|e| profile_error(&header, value, e))?;
+            if profiles.insert(name.to_string(), profile).is_some() {
+                anyhow::bail!(
+                    "Profile '{}' is defined by both [profile:{}] and [profile.{}] in cx.toml",
+                    name,
+                    name,
+                    name
+                );
             }
         }
     }
@@ -41,6 +63,8 @@ pub fn load_config() -> Result<CxConfig> {
     // Merge extracted profiles into config
     config.profiles = profiles;
 
+    platform::apply(&mut config, platform)?;
+
     // Deprecation warning for cflags
     if let Some(ref build_cfg) = config.build
         && build_cfg.uses_deprecated_cflags()
@@ -54,6 +78,26 @@ pub fn load_config() -> Result<CxConfig> {
     Ok(config)
 }
 
+/// Error for a profile table that doesn't deserialize, naming the bad key
+fn profile_error(header: &str, value: &toml::Value, err: toml::de::Error) -> anyhow::Error {
+    let Some(table) = value.as_table() else {
+        return anyhow::anyhow!("[{}] in cx.toml must be a table", header);
+    };
+    // Deserializing key by key pins the error on the offending entry
+    for (key, field) in table {
+        let single = toml::Table::from_iter([(key.clone(), field.clone())]);
+        if let Err(e) = toml::Value::Table(single).try_into::<Profile>() {
+            return anyhow::anyhow!(
+                "Invalid key '{}' in [{}] of cx.toml: {}",
+                key,
+                header,
+                e.message()
+            );
+        }
+    }
+    anyhow::anyhow!("Invalid [{}] in cx.toml: {}", header, err.message())
+}
+
 // --- Helper: Check if a command exists (for fallback only) ---
 fn is_command_available(cmd: &str) -> bool {
     let mut command = Command::new(cmd);
@@ -63,6 +107,19 @@ fn is_command_available(cmd: &str) -> bool {
     command.arg("--version").output().is_ok()
 }
 
+// --- Helper: Locate an executable on PATH without spawning it ---
+pub(crate) fn find_in_path(cmd: &str) -> Option<std::path::PathBuf> {
+    let path_var = std::env::var_os("PATH")?;
+    std::env::split_paths(&path_var).find_map(|dir| {
+        let candidate = dir.join(cmd);
+        if candidate.is_file() {
+            return Some(candidate);
+        }
+        let exe = candidate.with_extension("exe");
+        (cfg!(windows) && exe.is_file()).then_some(exe)
+    })
+}
+
 // --- Helper: Get Toolchain (uses vswhere on Windows) ---
 pub fn get_toolchain(config: &CxConfig, _has_cpp: bool) -> Result<Toolchain, ToolchainError> {
     // 1. Check if user specified a compiler in config
@@ -85,7 +142,7 @@ pub fn get_toolchain(config: &CxConfig, _has_cpp: bool) -> Result<Toolchain, Too
     // 2. Try to detect toolchain using proper discovery
     match toolchain::get_or_detect_toolchain(preferred, false) {
         Ok(tc) => {
-            println!(
+            status!(
                 "   {} Detected toolchain: {} ({})",
                 "🔧".cyan(),
                 tc.cxx_path.display(),
@@ -100,13 +157,13 @@ pub fn get_toolchain(config: &CxConfig, _has_cpp: bool) -> Result<Toolchain, Too
                 let msg = format!("{}", e);
                 // Don't show VS Install help for intentional non-MSVC compiler preferences
                 if !msg.contains("Clang/GCC") {
-                    println!("{} {}", "x".red(), e);
-                    println!();
-                    println!("{}:", "To fix this".bold());
-                    println!("  1. Install Visual Studio Build Tools from:");
-                    println!("     https://visualstudio.microsoft.com/visual-cpp-build-tools/");
-                    println!("  2. Select 'Desktop development with C++' workload");
-                    println!();
+                    status!("{} {}", "x".red(), e);
+                    status!();
+                    status!("{}:", "To fix this".bold());
+                    status!("  1. Install Visual Studio Build Tools from:");
+                    status!("     https://visualstudio.microsoft.com/visual-cpp-build-tools/");
+                    status!("  2. Select 'Desktop development with C++' workload");
+                    status!();
                 }
             }
             Err(e)
@@ -127,7 +184,7 @@ pub fn get_compiler(config: &CxConfig, has_cpp: bool) -> String {
     }
 
     // Fallback to old PATH-based detection (backward compatibility)
-    println!(
+    status!(
         "   {} Falling back to PATH-based compiler detection",
         "⚠".yellow()
     );
@@ -180,7 +237,7 @@ pub fn run_script(script: &str, project_dir: &Path) -> Result<()> {
     if script.ends_with(".rhai") {
         let script_path = project_dir.join(script);
         if script_path.exists() {
-            println!("   {} Running Rhai script: '{}'...", "📜".magenta(), script);
+            status!("   {} Running Rhai script: '{}'...", "📜".magenta(), script);
             let engine = rhai::Engine::new();
             engine
                 .run_file(script_path)
@@ -189,16 +246,18 @@ pub fn run_script(script: &str, project_dir: &Path) -> Result<()> {
         }
     }
 
-    println!("   {} Running script: '{}'...", "📜".magenta(), script);
+    status!("   {} Running script: '{}'...", "📜".magenta(), script);
     let status = if cfg!(target_os = "windows") {
         Command::new("cmd")
             .args(["/C", script])
             .current_dir(project_dir)
+            .stdout(super::messages::human_stdout())
             .status()?
     } else {
         Command::new("sh")
             .args(["-c", script])
             .current_dir(project_dir)
+            .stdout(super::messages::human_stdout())
             .status()?
     };
 
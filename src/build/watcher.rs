@@ -9,6 +9,7 @@
 //! - `cx watch --test` - TDD mode: run tests on every change
 
 use super::core;
+use super::messages::MessageFormat;
 use anyhow::{Result, anyhow};
 use colored::*;
 use notify::{Config, RecursiveMode, Watcher};
@@ -49,7 +50,7 @@ fn run_and_clear(run_tests: bool) {
     println!("{} File changed. Rebuilding...", "🔄".yellow());
 
     let result: Result<()> = if run_tests {
-        super::test::run_tests(None)
+        super::test::run_tests(None, None, MessageFormat::Human, &Default::default())
     } else {
         match super::load_config() {
             Ok(config) => {
@@ -1,14 +1,19 @@
 //! Global dependency cache management.
 //!
-//! This module handles the `~/.cx/cache` directory where downloaded dependencies are stored.
+//! This module handles the `~/.cx/cache` directory where downloaded dependencies are stored
+//! (see [`crate::deps::GitCache`]),
+//! and reports on the object cache in `~/.cx/objcache` (see [`crate::build::objcache`]).
 //!
 //! ## Commands
 //!
 //! - `cx cache path` - Print cache directory location
-//! - `cx cache list` - List cached libraries
-//! - `cx cache clean` - Clear all cached dependencies
+//! - `cx cache list` - List cached checkouts
+//! - `cx cache clean` - Clear all cached dependencies (`--objects` for the object cache)
+//! - `cx cache stats` - Object cache size, limit and hit rate
 //! - `cx cache prune` - Remove unused dependencies
 
+use crate::build::objcache;
+use crate::deps::GitCache;
 use crate::ui;
 use anyhow::{Context, Result};
 use colored::*;
@@ -23,34 +28,17 @@ pub fn print_path() -> Result<()> {
 }
 
 pub fn list() -> Result<()> {
-    let home = dirs::home_dir().context("Could not find home directory")?;
-    let cache_dir = home.join(".cx").join("cache");
-
-    if !cache_dir.exists() {
+    let checkouts = GitCache::new()?.checkouts();
+    if checkouts.is_empty() {
         println!("{} Cache is empty.", "ℹ".blue());
         return Ok(());
     }
 
-    let entries = fs::read_dir(&cache_dir)?;
-    let mut table = ui::Table::new(&["Cached Library"]);
-    let mut count = 0;
-
-    for entry in entries {
-        if let Ok(entry) = entry
-            && let Ok(ft) = entry.file_type()
-            && ft.is_dir()
-        {
-            let name = entry.file_name();
-            table.add_row(vec![name.to_string_lossy().to_string()]);
-            count += 1;
-        }
-    }
-
-    if count == 0 {
-        println!("{} (empty)", "ℹ".blue());
-    } else {
-        table.print();
+    let mut table = ui::Table::new(&["Cached Checkout"]);
+    for name in checkouts {
+        table.add_row(vec![name]);
     }
+    table.print();
 
     Ok(())
 }
@@ -70,39 +58,92 @@ pub fn clean() -> Result<()> {
     Ok(())
 }
 
-pub fn prune_unused(keep_deps: &[String]) -> Result<()> {
-    let home = dirs::home_dir().context("Could not find home directory")?;
-    let cache_dir = home.join(".cx").join("cache");
+pub fn clean_objects() -> Result<()> {
+    let dir = objcache::cache_dir().context("Could not find home directory")?;
+    if dir.exists() {
+        println!("{} Cleaning object cache...", "🧹".yellow());
+        fs::remove_dir_all(&dir)?;
+        println!("{} Object cache cleaned.", "✓".green());
+    } else {
+        println!("{} Object cache already empty.", "✓".green());
+    }
+    Ok(())
+}
 
-    if !cache_dir.exists() {
+pub(crate) fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KiB", "MiB", "GiB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{bytes} B")
+    } else {
+        format!("{value:.1} {}", UNITS[unit])
+    }
+}
+
+pub fn stats() -> Result<()> {
+    let dir = objcache::cache_dir().context("Could not find home directory")?;
+    // Pick up [cache] max_size when run inside a project
+    let config = crate::build::load_config().ok();
+    let limit = objcache::max_size(config.as_ref());
+
+    let entries = objcache::entries(&dir);
+    let total: u64 = entries.iter().map(|(_, size, _)| size).sum();
+    let counters = objcache::read_counters(&dir);
+    let lookups = counters.hits + counters.misses;
+    let hit_rate = if lookups == 0 {
+        "-".to_string()
+    } else {
+        format!("{:.1}%", counters.hits as f64 * 100.0 / lookups as f64)
+    };
+
+    let mut table = ui::Table::new(&["Object Cache", "Value"]);
+    table.add_row(vec!["Location".to_string(), dir.display().to_string()]);
+    table.add_row(vec!["Objects".to_string(), entries.len().to_string()]);
+    table.add_row(vec![
+        "Size".to_string(),
+        format!("{} / {}", format_size(total), format_size(limit)),
+    ]);
+    table.add_row(vec![
+        "Hits".to_string(),
+        format!("{} ({} remote)", counters.hits, counters.remote_hits),
+    ]);
+    table.add_row(vec!["Misses".to_string(), counters.misses.to_string()]);
+    table.add_row(vec!["Hit rate".to_string(), hit_rate]);
+    if let Some(remote) = config
+        .as_ref()
+        .and_then(|c| c.cache.as_ref())
+        .and_then(|c| c.remote.as_deref())
+    {
+        table.add_row(vec!["Remote".to_string(), remote.to_string()]);
+    }
+    table.print();
+
+    Ok(())
+}
+
+pub fn prune_unused(keep_deps: &[String]) -> Result<()> {
+    let cache = GitCache::new()?;
+    if cache.is_empty() {
         println!("{} Cache is already empty.", "✓".green());
         return Ok(());
     }
 
     println!("{} Pruning unused packages...", "🧹".yellow());
-    let entries = fs::read_dir(&cache_dir)?;
-    let mut removed_count = 0;
     let keep_set: HashSet<&str> = keep_deps.iter().map(String::as_str).collect();
-
-    for entry in entries.flatten() {
-        let path = entry.path();
-        if path.is_dir() {
-            let name = entry.file_name().to_string_lossy().to_string();
-            if !keep_set.contains(name.as_str()) {
-                println!("   {} Removing unused: {}", "🗑️".red(), name);
-                if let Err(e) = fs::remove_dir_all(&path) {
-                    println!("     Error removing {}: {}", name, e);
-                } else {
-                    removed_count += 1;
-                }
-            }
-        }
+    let removed = cache.prune(&keep_set)?;
+    for name in &removed {
+        println!("   {} Removed unused: {}", "🗑️".red(), name);
     }
 
-    if removed_count == 0 {
+    if removed.is_empty() {
         println!("{} All cached packages are in use.", "✓".green());
     } else {
-        println!("{} Removed {} unused packages.", "✓".green(), removed_count);
+        println!("{} Removed {} unused entries.", "✓".green(), removed.len());
     }
 
     Ok(())
@@ -130,6 +171,13 @@ mod tests {
         assert!(cache_dir.join("unused_lib").exists());
     }
 
+    #[test]
+    fn test_format_size() {
+        assert_eq!(format_size(512), "512 B");
+        assert_eq!(format_size(1536), "1.5 KiB");
+        assert_eq!(format_size(5 * 1024 * 1024 * 1024), "5.0 GiB");
+    }
+
     #[test]
     fn test_cache_path_is_in_home() {
         // Just test that the function doesn't panic
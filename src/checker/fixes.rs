@@ -0,0 +1,236 @@
+//! clang-tidy's `--export-fixes` YAML, read back as fix-it hints.
+//!
+//! clang-tidy prints the suggested code only as a caret snippet, so the
+//! replacements for `cx check --sarif` come from the exported file. It is
+//! LLVM YAML I/O output with one `key: value` per line and single-line
+//! scalars, which a line reader handles without a YAML dependency.
+
+use crate::diagnostics::{Diagnostic, FixIt, project_path};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Default)]
+struct Replacement {
+    file: String,
+    offset: usize,
+    length: usize,
+    text: String,
+}
+
+#[derive(Debug, Default)]
+struct ExportedDiagnostic {
+    check: String,
+    file: String,
+    offset: usize,
+    replacements: Vec<Replacement>,
+}
+
+/// Diagnostics (with their replacements) in an `--export-fixes` file.
+/// Replacements attached to notes are left out.
+fn parse_exported(yaml: &str) -> Vec<ExportedDiagnostic> {
+    let mut exported: Vec<ExportedDiagnostic> = Vec::new();
+    let mut in_notes = false;
+    let mut in_replacements = false;
+
+    for line in yaml.lines() {
+        let trimmed = line.trim_start();
+        let (is_item, entry) = match trimmed.strip_prefix("- ") {
+            Some(entry) => (true, entry),
+            None => (false, trimmed),
+        };
+        let Some((key, value)) = entry.split_once(':') else {
+            continue;
+        };
+        let value = scalar(value.trim());
+
+        if key == "DiagnosticName" {
+            exported.push(ExportedDiagnostic {
+                check: value,
+                ..Default::default()
+            });
+            in_notes = false;
+            in_replacements = false;
+            continue;
+        }
+        let Some(current) = exported.last_mut() else {
+            continue;
+        };
+        match key {
+            "Notes" => in_notes = true,
+            "Level" | "BuildDirectory" => in_notes = false,
+            _ if in_notes => {}
+            "Replacements" => in_replacements = true,
+            "FilePath" if in_replacements && is_item => current.replacements.push(Replacement {
+                file: value,
+                ..Default::default()
+            }),
+            "FilePath" if !in_replacements => current.file = value,
+            "FileOffset" => current.offset = value.parse().unwrap_or(0),
+            "Offset" | "Length" | "ReplacementText" if in_replacements => {
+                let Some(replacement) = current.replacements.last_mut() else {
+                    continue;
+                };
+                match key {
+                    "Offset" => replacement.offset = value.parse().unwrap_or(0),
+                    "Length" => replacement.length = value.parse().unwrap_or(0),
+                    _ => replacement.text = value,
+                }
+            }
+            _ => {}
+        }
+    }
+    exported
+}
+
+/// A YAML scalar: plain, `'single ''quoted'''` or `"double \"quoted\""`
+fn scalar(raw: &str) -> String {
+    if let Some(inner) = raw.strip_prefix('\'').and_then(|r| r.strip_suffix('\'')) {
+        return inner.replace("''", "'");
+    }
+    if let Some(inner) = raw.strip_prefix('"').and_then(|r| r.strip_suffix('"')) {
+        let mut out = String::with_capacity(inner.len());
+        let mut chars = inner.chars();
+        while let Some(c) = chars.next() {
+            if c != '\\' {
+                out.push(c);
+                continue;
+            }
+            match chars.next() {
+                Some('n') => out.push('\n'),
+                Some('t') => out.push('\t'),
+                Some('r') => out.push('\r'),
+                Some('0') => out.push('\0'),
+                Some(other) => out.push(other),
+                None => {}
+            }
+        }
+        return out;
+    }
+    raw.to_string()
+}
+
+/// 1-based line and byte column of a byte offset
+fn position(text: &str, offset: usize) -> (u32, u32) {
+    let before = &text.as_bytes()[..offset.min(text.len())];
+    let line = before.iter().filter(|&&b| b == b'\n').count() + 1;
+    let column = before.iter().rev().take_while(|&&b| b != b'\n').count() + 1;
+    (line as u32, column as u32)
+}
+
+/// Attach the fixes exported to `yaml` to the matching `diagnostics`
+/// (same check, file and position)
+pub fn attach(diagnostics: &mut [Diagnostic], yaml: &str) {
+    let mut sources: HashMap<String, Option<String>> = HashMap::new();
+    let mut source = |file: &str| -> Option<String> {
+        sources
+            .entry(file.to_string())
+            .or_insert_with(|| fs::read_to_string(file).ok())
+            .clone()
+    };
+
+    for exported in parse_exported(yaml) {
+        if exported.replacements.is_empty() {
+            continue;
+        }
+        let Some(text) = source(&exported.file) else {
+            continue;
+        };
+        let (line, column) = position(&text, exported.offset);
+        let file = project_path(Path::new("."), &exported.file);
+        let Some(diagnostic) = diagnostics.iter_mut().find(|d| {
+            d.code.as_deref() == Some(exported.check.as_str())
+                && d.file == file
+                && d.line == line
+                && d.column == Some(column)
+        }) else {
+            continue;
+        };
+        for replacement in &exported.replacements {
+            let Some(text) = source(&replacement.file) else {
+                continue;
+            };
+            let (start_line, start_column) = position(&text, replacement.offset);
+            let (end_line, end_column) = position(&text, replacement.offset + replacement.length);
+            diagnostic.fixes.push(FixIt {
+                file: project_path(Path::new("."), &replacement.file),
+                start_line,
+                start_column,
+                end_line,
+                end_column,
+                replacement: replacement.text.clone(),
+            });
+        }
+    }
+}
+
+/// Where clang-tidy exports the fixes for `source`
+pub fn export_path(source: &Path) -> PathBuf {
+    let name = source.to_string_lossy().replace(['/', '\\', ':'], "_");
+    Path::new(".cx")
+        .join("build")
+        .join("clang-tidy")
+        .join(format!("{}.yaml", name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_exported_fixes() {
+        let yaml = r#"---
+MainSourceFile:  '/proj/src/a.cpp'
+Diagnostics:
+  - DiagnosticName:  modernize-use-nullptr
+    DiagnosticMessage:
+      Message:         use nullptr
+      FilePath:        '/proj/src/a.cpp'
+      FileOffset:      27
+      Replacements:
+        - FilePath:        '/proj/src/a.cpp'
+          Offset:          27
+          Length:          1
+          ReplacementText: nullptr
+    Notes:
+      - Message:         'it''s here'
+        FilePath:        '/proj/src/b.cpp'
+        FileOffset:      3
+        Replacements:
+          - FilePath:        '/proj/src/b.cpp'
+            Offset:          3
+            Length:          0
+            ReplacementText: ignored
+    Level:           Warning
+    BuildDirectory:  '/proj'
+  - DiagnosticName:  readability-braces-around-statements
+    DiagnosticMessage:
+      Message:         statement should be inside braces
+      FilePath:        '/proj/src/a.cpp'
+      FileOffset:      40
+      Replacements:
+        - FilePath:        '/proj/src/a.cpp'
+          Offset:          40
+          Length:          0
+          ReplacementText: " {\n"
+    Level:           Warning
+...
+"#;
+        let exported = parse_exported(yaml);
+        assert_eq!(exported.len(), 2);
+        assert_eq!(exported[0].check, "modernize-use-nullptr");
+        assert_eq!(exported[0].file, "/proj/src/a.cpp");
+        assert_eq!(exported[0].offset, 27);
+        assert_eq!(exported[0].replacements.len(), 1);
+        assert_eq!(exported[0].replacements[0].text, "nullptr");
+        assert_eq!(exported[0].replacements[0].length, 1);
+        assert_eq!(exported[1].replacements[0].text, " {\n");
+    }
+
+    #[test]
+    fn test_position() {
+        let text = "int a;\nint* p = 0;\n";
+        assert_eq!(position(text, 0), (1, 1));
+        assert_eq!(position(text, 16), (2, 10));
+    }
+}
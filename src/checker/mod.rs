@@ -8,13 +8,17 @@
 //! - `cx fmt` - Format code using clang-format
 //! - `cx fmt --check` - Check formatting without modifying files
 //! - `cx check` - Run static analysis using clang-tidy
+//! - `cx check --sarif <path>` - Also write the findings as SARIF
 
+mod fixes;
+
+use crate::build::jobs::Scheduler;
 use crate::build::load_config;
 use crate::deps;
+use crate::diagnostics::{self, Diagnostic};
 use anyhow::Result;
 use colored::*;
 use indicatif::{ProgressBar, ProgressStyle};
-use rayon::prelude::*;
 use std::path::{Path, PathBuf};
 use std::process::Command;
 use walkdir::WalkDir;
@@ -136,12 +140,15 @@ fn dependency_include_flags(config: &crate::config::CxConfig) -> Vec<String> {
     include_flags
 }
 
+/// Run clang-tidy on one file; returns whether it found issues and, with
+/// `collect`, the parsed diagnostics (including exported fixes)
 fn check_file_with_clang_tidy(
     path: &Path,
     config: &crate::config::CxConfig,
     include_flags: &[String],
     pb: &ProgressBar,
-) -> usize {
+    collect: bool,
+) -> (bool, Vec<Diagnostic>) {
     let name = path
         .file_name()
         .unwrap_or_default()
@@ -149,7 +156,15 @@ fn check_file_with_clang_tidy(
         .to_string();
     pb.set_message(format!("Checking {}", name));
 
+    let export = fixes::export_path(path);
     let mut cmd = Command::new("clang-tidy");
+    if collect {
+        if let Some(parent) = export.parent() {
+            let _ = std::fs::create_dir_all(parent);
+        }
+        let _ = std::fs::remove_file(&export);
+        cmd.arg(format!("--export-fixes={}", export.display()));
+    }
     cmd.arg(path);
     cmd.arg("--");
     cmd.arg(format!("-std={}", config.package.edition));
@@ -159,6 +174,9 @@ fn check_file_with_clang_tidy(
     {
         cmd.args(flags);
     }
+    if let Some(build_cfg) = &config.build {
+        cmd.args(build_cfg.define_flags(false));
+    }
     cmd.args(include_flags);
 
     let output = cmd.output().ok();
@@ -168,6 +186,14 @@ fn check_file_with_clang_tidy(
         let has_issues =
             stdout.contains("warning:") || stdout.contains("error:") || !out.status.success();
 
+        let mut found = Vec::new();
+        if collect {
+            found = diagnostics::parse(&stdout, Path::new("."));
+            if let Ok(yaml) = std::fs::read_to_string(&export) {
+                fixes::attach(&mut found, &yaml);
+            }
+        }
+
         if has_issues {
             pb.suspend(|| {
                 println!("{} Issues in {}", "!".yellow(), name);
@@ -180,12 +206,12 @@ fn check_file_with_clang_tidy(
                 println!("{}", "-".repeat(40).dimmed());
             });
             pb.inc(1);
-            return 1;
+            return (true, found);
         }
     }
 
     pb.inc(1);
-    0
+    (false, Vec::new())
 }
 
 pub fn format_code(check_only: bool) -> Result<()> {
@@ -266,7 +292,7 @@ pub fn format_code(check_only: bool) -> Result<()> {
     }
 }
 
-pub fn check_code() -> Result<()> {
+pub fn check_code(jobs: Option<usize>, sarif: Option<&Path>) -> Result<()> {
     if !ensure_clang_tidy_exists() {
         println!(
             "{} clang-tidy not found. Please install it first.",
@@ -282,13 +308,31 @@ pub fn check_code() -> Result<()> {
     let files = collect_source_files();
     let pb = progress_bar(files.len());
 
-    let warnings: usize = files
-        .par_iter()
-        .map(|path| check_file_with_clang_tidy(path, &config, &include_flags, &pb))
-        .sum();
+    let scheduler = Scheduler::new(Some(&config), jobs);
+    let results = scheduler.run(&files, |_, path| {
+        Ok(check_file_with_clang_tidy(
+            path,
+            &config,
+            &include_flags,
+            &pb,
+            sarif.is_some(),
+        ))
+    })?;
+    let warnings = results.iter().filter(|(has_issues, _)| *has_issues).count();
 
     pb.finish_and_clear();
 
+    if let Some(path) = sarif {
+        let found: Vec<Diagnostic> = results.into_iter().flat_map(|(_, found)| found).collect();
+        diagnostics::write_sarif(path, "clang-tidy", &found)?;
+        println!(
+            "   {} {} diagnostic(s) written to {}",
+            "📋".blue(),
+            found.len(),
+            path.display()
+        );
+    }
+
     if warnings == 0 {
         println!(
             "{} Checked {} files. No issues found.",
@@ -2,7 +2,7 @@
 //!
 //! Handles `cx doctor`, `cx lock`, and `cx sync` commands.
 
-use anyhow::Result;
+use anyhow::{Result, bail};
 use colored::*;
 use std::collections::HashMap;
 
@@ -65,14 +65,16 @@ fn collect_expected_dep_diffs(
     (missing_in_lock, url_mismatch)
 }
 
+/// Lock entries neither in cx.toml nor required by one that is
 fn collect_extra_lock_entries(
     git_deps: &HashMap<String, String>,
     lockfile: &lock::LockFile,
 ) -> Vec<String> {
+    let required = lockfile.reachable(git_deps.keys().map(String::as_str));
     lockfile
         .packages
         .keys()
-        .filter(|name| !git_deps.contains_key(*name))
+        .filter(|name| !required.contains(name.as_str()))
         .cloned()
         .collect()
 }
@@ -93,6 +95,26 @@ fn compare_lockfile(config: &CxConfig, lockfile: &lock::LockFile) -> LockCompari
     }
 }
 
+/// Fail unless cx.lock records exactly the git dependencies in `deps` (`--frozen`)
+pub(crate) fn ensure_frozen_lockfile(
+    deps: &HashMap<String, Dependency>,
+    lockfile: &lock::LockFile,
+) -> Result<()> {
+    let config = CxConfig {
+        dependencies: Some(deps.clone()),
+        ..Default::default()
+    };
+    let comparison = compare_lockfile(&config, lockfile);
+    if !comparison.is_clean() {
+        print_lock_comparison(&comparison);
+        bail!(
+            "cx.lock is out of sync with cx.toml and --frozen forbids updating it\n  \
+             Run 'cx lock --update' without --frozen"
+        );
+    }
+    Ok(())
+}
+
 fn print_extra_lock_entry(dep: &str) {
     println!(
         "{} Lockfile contains '{}' which is no longer in cx.toml",
@@ -346,6 +368,22 @@ mod tests {
         assert_eq!(cmp.extra_in_lock, vec!["fmt".to_string()]);
     }
 
+    #[test]
+    fn compare_lockfile_accepts_transitive_entries() {
+        let config = test_config_with_git_dep("spdlog", "https://github.com/gabime/spdlog.git");
+        let mut lockfile = lock::LockFile::default();
+        for (name, url) in [
+            ("spdlog", "https://github.com/gabime/spdlog.git"),
+            ("fmt", "https://github.com/fmtlib/fmt.git"),
+            ("stale", "https://github.com/old/stale.git"),
+        ] {
+            lockfile.insert(name.to_string(), url.to_string(), "abc123".to_string());
+        }
+        lockfile.set_dependencies("spdlog", vec!["fmt".to_string()]);
+        let cmp = compare_lockfile(&config, &lockfile);
+        assert_eq!(cmp.extra_in_lock, vec!["stale".to_string()]);
+    }
+
     #[test]
     fn compare_lockfile_detects_url_mismatch() {
         let config = test_config_with_git_dep("fmt", "https://github.com/fmtlib/fmt.git");
@@ -359,4 +397,18 @@ mod tests {
         assert_eq!(cmp.url_mismatch.len(), 1);
         assert_eq!(cmp.url_mismatch[0].0, "fmt");
     }
+
+    #[test]
+    fn frozen_lockfile_must_match_dependencies() {
+        let config = test_config_with_git_dep("fmt", "https://github.com/fmtlib/fmt.git");
+        let deps = config.dependencies.unwrap();
+        let mut lockfile = lock::LockFile::default();
+        assert!(ensure_frozen_lockfile(&deps, &lockfile).is_err());
+        lockfile.insert(
+            "fmt".to_string(),
+            "https://github.com/fmtlib/fmt.git".to_string(),
+            "abc123".to_string(),
+        );
+        assert!(ensure_frozen_lockfile(&deps, &lockfile).is_ok());
+    }
 }
@@ -361,7 +361,7 @@ fn remove_build_framework_from_content(content: &str) -> String {
     output
 }
 
-fn parse_section_name(line: &str) -> Option<&str> {
+pub(crate) fn parse_section_name(line: &str) -> Option<&str> {
     let trimmed = line.trim();
     if trimmed.starts_with('[') && trimmed.ends_with(']') {
         return Some(trimmed.trim_start_matches('[').trim_end_matches(']').trim());
@@ -373,7 +373,7 @@ fn is_framework_assignment(line: &str) -> bool {
     assignment_key(line).is_some_and(|key| key == "framework")
 }
 
-fn assignment_key(line: &str) -> Option<&str> {
+pub(crate) fn assignment_key(line: &str) -> Option<&str> {
     let trimmed = line.trim_start();
     if trimmed.is_empty() || trimmed.starts_with('#') {
         return None;
@@ -1,13 +1,20 @@
 //! Generate command handler
 //!
 //! Handles `cx generate` subcommands for generating build system files.
+//!
+//! The selected profile (`--profile`, `--release` or `[build] default_profile`)
+//! is merged in first, so generated files carry the same defines, flags and
+//! libraries as `cx build`, including `[[build.overrides]]` per source.
 
 use anyhow::Result;
 use colored::*;
-use std::path::Path;
+use std::path::PathBuf;
 use walkdir::WalkDir;
 
 use crate::build;
+use crate::build::features::{Features, enabling_features, feature_define};
+use crate::build::overrides::{self, Overrides};
+use crate::build::profiles::resolve_profile;
 use crate::config::CxConfig;
 
 /// Generate format options
@@ -22,15 +29,28 @@ pub enum GenerateFormat {
 }
 
 /// Handle the `cx generate` command for build system file generation
-pub fn handle_generate_command(format: &GenerateFormat) -> Result<()> {
-    let config = build::load_config()?;
+pub fn handle_generate_command(
+    format: &GenerateFormat,
+    profile: Option<&str>,
+    release: bool,
+) -> Result<()> {
+    let mut config = build::load_config()?;
+    let profile = profile.map(str::to_string).or_else(|| {
+        config
+            .build
+            .as_ref()
+            .and_then(|b| b.default_profile.clone())
+    });
 
     match format {
-        GenerateFormat::Cmake => {
-            generate_cmake(&config)?;
-        }
-        GenerateFormat::Ninja => {
-            generate_ninja(&config)?;
+        GenerateFormat::Cmake | GenerateFormat::Ninja => {
+            let resolved = resolve_profile(&config.profiles, profile.as_deref(), release)?;
+            resolved.apply(&mut config);
+            if matches!(format, GenerateFormat::Cmake) {
+                generate_cmake(&config)?;
+            } else {
+                generate_ninja(&config)?;
+            }
         }
         GenerateFormat::CompileCommands => {
             println!(
@@ -44,6 +64,22 @@ pub fn handle_generate_command(format: &GenerateFormat) -> Result<()> {
     Ok(())
 }
 
+/// C/C++ sources under `src/`, in a stable order
+fn project_sources() -> Vec<PathBuf> {
+    let mut sources: Vec<PathBuf> = WalkDir::new("src")
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .map(|e| e.into_path())
+        .filter(|path| {
+            path.extension()
+                .and_then(|e| e.to_str())
+                .is_some_and(|e| ["cpp", "cc", "cxx", "c"].contains(&e))
+        })
+        .collect();
+    sources.sort();
+    sources
+}
+
 fn generate_cmake(config: &CxConfig) -> Result<()> {
     println!("{} Generating CMakeLists.txt...", "📝".cyan());
 
@@ -60,15 +96,23 @@ set(CMAKE_EXPORT_COMPILE_COMMANDS ON)
 
 # Source files
 file(GLOB_RECURSE SOURCES "src/*.cpp" "src/*.c")
+"#
+    );
 
+    append_cmake_feature_sources(&mut cmake, config);
+    cmake.push_str(
+        r#"
 # Executable
-add_executable(${{PROJECT_NAME}} ${{SOURCES}})
+add_executable(${PROJECT_NAME} ${SOURCES})
 
 # Include directories
-target_include_directories(${{PROJECT_NAME}} PRIVATE src)
-"#
+target_include_directories(${PROJECT_NAME} PRIVATE src)
+"#,
     );
 
+    append_cmake_definitions(&mut cmake, config);
+    append_cmake_overrides(&mut cmake, config);
+    append_cmake_features(&mut cmake, config)?;
     append_cmake_dependencies(&mut cmake, config);
     append_cmake_libraries(&mut cmake, config);
 
@@ -85,13 +129,171 @@ target_include_directories(${{PROJECT_NAME}} PRIVATE src)
     Ok(())
 }
 
+fn append_cmake_definitions(cmake: &mut String, config: &CxConfig) {
+    let Some(defines) = config.build.as_ref().and_then(|b| b.defines.as_ref()) else {
+        return;
+    };
+    if defines.is_empty() {
+        return;
+    }
+
+    cmake.push_str(
+        "\n# Preprocessor definitions\ntarget_compile_definitions(${PROJECT_NAME} PRIVATE",
+    );
+    for define in defines {
+        cmake.push_str(&format!(" {}", define));
+    }
+    cmake.push_str(")\n");
+}
+
+/// `[[build.overrides]]` as source properties on the files they match
+fn append_cmake_overrides(cmake: &mut String, config: &CxConfig) {
+    let Some(rules) = config.build.as_ref().and_then(|b| b.overrides.as_ref()) else {
+        return;
+    };
+    let sources = project_sources();
+
+    let mut header = "\n# Per-source overrides\n";
+    for rule in rules {
+        if rule.flags.is_empty() && rule.defines.is_empty() {
+            continue;
+        }
+        let files: Vec<String> = sources
+            .iter()
+            .filter(|src| overrides::matches(&rule.files, src))
+            .map(|src| overrides::normalize(src))
+            .collect();
+        if files.is_empty() {
+            continue;
+        }
+
+        cmake.push_str(header);
+        header = "";
+        // APPEND keeps declaration order when several overrides match
+        for (property, values) in [
+            ("COMPILE_DEFINITIONS", &rule.defines),
+            ("COMPILE_OPTIONS", &rule.flags),
+        ] {
+            if !values.is_empty() {
+                cmake.push_str(&format!(
+                    "set_property(SOURCE {} APPEND PROPERTY {} {})\n",
+                    files.join(" "),
+                    property,
+                    values.join(" ")
+                ));
+            }
+        }
+    }
+}
+
+/// Sorted `[features]` entries, `default` excluded
+fn declared_features(config: &CxConfig) -> Vec<(&String, &crate::config::Feature)> {
+    let mut features: Vec<_> = config
+        .features
+        .iter()
+        .flatten()
+        .filter(|(name, _)| *name != "default")
+        .collect();
+    features.sort_by_key(|(name, _)| *name);
+    features
+}
+
+/// Keep feature-only sources out of `SOURCES`; the feature blocks add them back
+fn append_cmake_feature_sources(cmake: &mut String, config: &CxConfig) {
+    for (name, feature) in declared_features(config) {
+        if feature.sources().is_empty() {
+            continue;
+        }
+        let var = format!("{}_SOURCES", feature_define(name));
+        // GLOB_RECURSE already descends, so `**` collapses to `*`
+        let globs: Vec<String> = feature
+            .sources()
+            .iter()
+            .map(|g| format!("\"{}\"", g.replace("**/", "").replace("**", "*")))
+            .collect();
+        cmake.push_str(&format!(
+            "file(GLOB_RECURSE {var} {})\nif({var})\n  list(REMOVE_ITEM SOURCES ${{{var}}})\nendif()\n",
+            globs.join(" ")
+        ));
+    }
+}
+
+/// One `option()` per feature, ON when enabled by `default`
+fn append_cmake_features(cmake: &mut String, config: &CxConfig) -> Result<()> {
+    let features = declared_features(config);
+    if features.is_empty() {
+        return Ok(());
+    }
+    let defaults = Features::resolve(config, &Default::default())?;
+
+    cmake.push_str("\n# Features\n");
+    for (name, _) in &features {
+        cmake.push_str(&format!(
+            "option({} \"Enable the '{}' feature\" {})\n",
+            feature_define(name),
+            name,
+            if defaults.enabled.contains(*name) {
+                "ON"
+            } else {
+                "OFF"
+            }
+        ));
+    }
+
+    let optional_deps: Vec<&String> = config
+        .dependencies
+        .iter()
+        .flatten()
+        .filter(|(_, dep)| dep.is_optional())
+        .map(|(name, _)| name)
+        .collect();
+    for (name, feature) in &features {
+        // Features enabling this one switch it on as well
+        let condition: Vec<String> = enabling_features(config, name)
+            .iter()
+            .map(|f| feature_define(f))
+            .collect();
+        cmake.push_str(&format!("\nif({})\n", condition.join(" OR ")));
+
+        let mut defines = vec![feature_define(name)];
+        defines.extend(feature.defines().iter().cloned());
+        cmake.push_str(&format!(
+            "  target_compile_definitions(${{PROJECT_NAME}} PRIVATE {})\n",
+            defines.join(" ")
+        ));
+        if !feature.sources().is_empty() {
+            cmake.push_str(&format!(
+                "  target_sources(${{PROJECT_NAME}} PRIVATE ${{{}_SOURCES}})\n",
+                feature_define(name)
+            ));
+        }
+        for entry in feature.enables() {
+            let dep = entry.strip_prefix("dep:").unwrap_or(entry);
+            if optional_deps.iter().any(|d| *d == dep) {
+                cmake.push_str(&format!("  # find_package({} REQUIRED)\n", dep));
+            }
+        }
+        cmake.push_str("endif()\n");
+    }
+    Ok(())
+}
+
 fn append_cmake_dependencies(cmake: &mut String, config: &CxConfig) {
     let Some(deps) = &config.dependencies else {
         return;
     };
+    // Optional ones are listed in their feature's block
+    let required: Vec<&String> = deps
+        .iter()
+        .filter(|(_, dep)| !dep.is_optional())
+        .map(|(name, _)| name)
+        .collect();
+    if required.is_empty() {
+        return;
+    }
 
     cmake.push_str("\n# Dependencies\n");
-    for dep_name in deps.keys() {
+    for dep_name in required {
         cmake.push_str(&format!("# find_package({} REQUIRED)\n", dep_name));
     }
 }
@@ -126,6 +328,17 @@ fn generate_ninja(config: &CxConfig) -> Result<()> {
     } else {
         build::utils::get_std_flag_gcc(edition)
     };
+    // Ninja has no options; bake in the default features
+    let features = Features::resolve(config, &Default::default())?;
+    let mut config = config.clone();
+    features.apply(&mut config);
+
+    // Standard plus `[build] defines` (profile defines merged in)
+    let mut compile_flags = vec![std_flag];
+    if let Some(build_cfg) = &config.build {
+        compile_flags.extend(build_cfg.define_flags(is_msvc));
+    }
+    let compile_flags = compile_flags.join(" ");
 
     let mut ninja = String::from("# Auto-generated by caxe\n\n");
 
@@ -133,7 +346,7 @@ fn generate_ninja(config: &CxConfig) -> Result<()> {
         ninja.push_str(&format!(
             r#"
 cxx = cl
-cxxflags = /nologo /EHsc {std_flag} /c
+cxxflags = /nologo /EHsc {compile_flags} /c
 linkflags = /nologo
 
 rule compile
@@ -150,7 +363,7 @@ rule link
         ninja.push_str(&format!(
             r#"
 cxx = g++
-cxxflags = {std_flag} -c
+cxxflags = {compile_flags} -c
 linkflags = 
 
 rule compile
@@ -165,26 +378,24 @@ rule link
         ));
     }
 
-    // Find source files
-    let src_dir = Path::new("src");
+    // Find source files; `[[build.overrides]]` extend cxxflags per edge
+    let overrides = Overrides::new(&config, is_msvc);
     let mut obj_files = Vec::new();
 
-    if src_dir.exists() {
-        for entry in WalkDir::new(src_dir).into_iter().filter_map(|e| e.ok()) {
-            let path = entry.path();
-            if path
-                .extension()
-                .and_then(|e| e.to_str())
-                .is_some_and(|e| ["cpp", "cc", "cxx", "c"].contains(&e))
-            {
-                let obj_name = path.file_stem().unwrap_or_default().to_string_lossy();
-                let obj_ext = if is_msvc { "obj" } else { "o" };
-                let obj_path = format!("build/{}.{}", obj_name, obj_ext);
-
-                ninja.push_str(&format!("build {}: compile {}\n", obj_path, path.display()));
-                obj_files.push(obj_path);
-            }
+    for path in project_sources() {
+        if features.excludes(&path) {
+            continue;
+        }
+        let obj_name = path.file_stem().unwrap_or_default().to_string_lossy();
+        let obj_ext = if is_msvc { "obj" } else { "o" };
+        let obj_path = format!("build/{}.{}", obj_name, obj_ext);
+
+        ninja.push_str(&format!("build {}: compile {}\n", obj_path, path.display()));
+        let extra = overrides.args_for(&path);
+        if !extra.is_empty() {
+            ninja.push_str(&format!("  cxxflags = $cxxflags {}\n", extra.join(" ")));
         }
+        obj_files.push(obj_path);
     }
 
     // Link
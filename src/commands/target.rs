@@ -1,14 +1,17 @@
 //! Target command handler
 //!
 //! Handles `cx target` subcommands for managing cross-compilation targets.
+//! `add` writes a `[profile:<name>]` from the built-in catalog, `remove`
+//! deletes it and `default` records `[build] default_profile`. Edits are
+//! line-based so comments and unrelated sections of `cx.toml` survive.
 
-use anyhow::{Result, anyhow};
+use super::framework::{assignment_key, parse_section_name};
+use crate::build::cross::{TARGET_PRESETS, TargetPreset, find_preset, resolve_compiler};
+use crate::ui;
+use anyhow::{Result, anyhow, bail};
 use colored::*;
 use std::path::Path;
 
-const TARGET_DEFERRED_REASON: &str =
-    "Target mutation commands are deferred in v0.3.x patch releases";
-
 /// Target subcommand operations
 #[derive(Clone, Debug)]
 pub enum TargetOp {
@@ -27,13 +30,14 @@ pub fn handle_target_command(op: &Option<TargetOp>) -> Result<()> {
     let config_path = Path::new("cx.toml");
 
     match op {
-        None | Some(TargetOp::List) => render_target_list(config_path),
-        Some(TargetOp::Add { name }) => return deferred_target_mutation("add", name),
-        Some(TargetOp::Remove { name }) => return deferred_target_mutation("remove", name),
-        Some(TargetOp::Default { name }) => return deferred_target_mutation("default", name),
+        None | Some(TargetOp::List) => {
+            render_target_list(config_path);
+            Ok(())
+        }
+        Some(TargetOp::Add { name }) => add_target(config_path, name),
+        Some(TargetOp::Remove { name }) => remove_target(config_path, name),
+        Some(TargetOp::Default { name }) => set_default_target(config_path, name),
     }
-
-    Ok(())
 }
 
 fn render_target_list(config_path: &Path) {
@@ -51,88 +55,432 @@ fn print_target_header() {
     );
     println!("{}", "─".repeat(50).dimmed());
     println!();
+}
+
+fn print_target_catalog() {
+    let mut table = ui::Table::new(&["Target", "Triple", "Description", "Toolchain"]);
+    for preset in TARGET_PRESETS {
+        let toolchain = match resolve_compiler(preset.triple, preset.compiler) {
+            Some((path, _)) => format!(
+                "{} {}",
+                "✓".green(),
+                path.file_name().unwrap_or_default().to_string_lossy()
+            ),
+            None => format!("{} not installed", "x".red()),
+        };
+        table.add_row(vec![
+            preset.name.green().bold().to_string(),
+            preset.triple.dimmed().to_string(),
+            preset.description.to_string(),
+            toolchain,
+        ]);
+    }
+    table.print();
+}
+
+fn print_profile_configuration_status(config_path: &Path) {
+    println!();
+    let Ok(content) = std::fs::read_to_string(config_path) else {
+        return;
+    };
+    let profiles = profile_names(&content);
+    if profiles.is_empty() {
+        println!(
+            "{} No target profiles configured. Run {}.",
+            "!".yellow(),
+            "cx target add <name>".cyan()
+        );
+        return;
+    }
+
+    let default = default_profile(&content);
+    let names: Vec<String> = profiles
+        .iter()
+        .map(|name| {
+            if default.as_deref() == Some(name.as_str()) {
+                format!("{} (default)", name.green().bold())
+            } else {
+                name.green().to_string()
+            }
+        })
+        .collect();
+    println!("{} Configured profiles: {}", "✓".green(), names.join(", "));
+}
+
+fn print_target_usage_hint() {
+    println!();
     println!(
-        "{} Target mutation commands ({}, {}, {}) are deferred in v0.3.x patch releases.",
-        "ℹ".blue(),
-        "add".yellow(),
-        "remove".yellow(),
-        "default".yellow()
+        "Usage: {}, {}, {}, {}",
+        "cx target list".cyan(),
+        "cx target add <name>".cyan(),
+        "cx target remove <name>".cyan(),
+        "cx target default <name|host>".cyan()
     );
     println!(
-        "   Configure cross targets with {} and run {}.",
-        "[profile:<name>]".cyan(),
+        "Hint: build a target with {}",
         "cx build --profile <name>".cyan()
     );
-    println!();
 }
 
-fn print_target_catalog() {
+fn read_config(config_path: &Path) -> Result<String> {
+    if !config_path.exists() {
+        bail!("cx.toml not found. Run `cx init` first.");
+    }
+    Ok(std::fs::read_to_string(config_path)?)
+}
+
+fn unknown_target(name: &str) -> anyhow::Error {
+    let names: Vec<&str> = TARGET_PRESETS.iter().map(|p| p.name).collect();
+    anyhow!(
+        "Unknown target '{}'. Available targets: {}",
+        name,
+        names.join(", ")
+    )
+}
+
+fn add_target(config_path: &Path, name: &str) -> Result<()> {
+    let preset = find_preset(name).ok_or_else(|| unknown_target(name))?;
+    let content = read_config(config_path)?;
+    if find_profile_section(&content, name).is_some() {
+        bail!(
+            "Profile '{}' already exists in cx.toml (remove it with `cx target remove {}`)",
+            name,
+            name
+        );
+    }
+
+    let installed = resolve_compiler(preset.triple, preset.compiler);
+    let compiler = preset_compiler(preset, installed.as_ref().map(|(path, _)| path.as_path()));
+    let new_content = add_profile_to_content(&content, preset, &compiler);
+    std::fs::write(config_path, new_content)?;
+
     println!(
-        "   {} (MSVC) - Windows 64-bit",
-        "windows-x64".green().bold()
+        "{} Added target {} ({})",
+        "✓".green(),
+        name.cyan().bold(),
+        preset.triple.dimmed()
     );
+    if installed.is_none() {
+        println!(
+            "  {} {} is not installed; install it or set {} in the profile.",
+            "!".yellow(),
+            compiler.yellow(),
+            "compiler".cyan()
+        );
+    }
     println!(
-        "   {} (MinGW) - Windows 64-bit GNU",
-        "windows-x64-gnu".green()
+        "  Build it with {}",
+        format!("cx build --profile {name}").cyan()
     );
-    println!("   {} (GCC/Clang) - Linux 64-bit", "linux-x64".blue());
-    println!("   {} (Cross) - Linux ARM64", "linux-arm64".blue());
-    println!("   {} (Clang) - macOS Intel", "macos-x64".magenta());
+    Ok(())
+}
+
+fn remove_target(config_path: &Path, name: &str) -> Result<()> {
+    let content = read_config(config_path)?;
+    let Some(mut new_content) = remove_profile_from_content(&content, name) else {
+        bail!("Profile '{}' is not configured in cx.toml", name);
+    };
+    let was_default = default_profile(&content).as_deref() == Some(name);
+    if was_default {
+        new_content = set_default_profile_in_content(&new_content, None);
+    }
+    std::fs::write(config_path, new_content)?;
+
+    println!("{} Removed target {}", "✓".green(), name.cyan().bold());
+    if was_default {
+        println!("  Default target reset to the host");
+    }
+    Ok(())
+}
+
+fn set_default_target(config_path: &Path, name: &str) -> Result<()> {
+    let content = read_config(config_path)?;
+    if name == "host" {
+        std::fs::write(config_path, set_default_profile_in_content(&content, None))?;
+        println!("{} Default target reset to the host", "✓".green());
+        return Ok(());
+    }
+
+    if find_profile_section(&content, name).is_none() {
+        if find_preset(name).is_some() {
+            bail!(
+                "Profile '{}' is not configured. Run `cx target add {}` first.",
+                name,
+                name
+            );
+        }
+        bail!("Profile '{}' is not configured in cx.toml", name);
+    }
+    std::fs::write(
+        config_path,
+        set_default_profile_in_content(&content, Some(name)),
+    )?;
     println!(
-        "   {} (Clang) - macOS Apple Silicon",
-        "macos-arm64".magenta()
+        "{} Default target set to {} (used by {} and {} without --profile)",
+        "✓".green(),
+        name.cyan().bold(),
+        "cx build".cyan(),
+        "cx generate".cyan()
     );
-    println!("   {} (Emscripten) - WebAssembly", "wasm32".yellow());
-    println!("   {} (ESP-IDF) - ESP32 Microcontroller", "esp32".red());
+    println!("  cx run and cx test still build for the host");
+    Ok(())
 }
 
-fn print_profile_configuration_status(config_path: &Path) {
-    println!();
-    if config_path.exists()
-        && let Ok(content) = std::fs::read_to_string(config_path)
+/// Compiler for a new profile: the preset's, the installed one, else `<triple>-g++`
+fn preset_compiler(preset: &TargetPreset, installed: Option<&Path>) -> String {
+    if let Some(compiler) = preset.compiler {
+        return compiler.to_string();
+    }
+    match installed.and_then(|path| path.file_name()) {
+        Some(name) => name.to_string_lossy().to_string(),
+        None => format!("{}-g++", preset.triple),
+    }
+}
+
+/// Profile name of a `[profile:<name>]`, `["profile:<name>"]` or `[profile.<name>]` header
+fn profile_header_name(line: &str) -> Option<&str> {
+    let section = parse_section_name(line)?;
+    let section = section
+        .strip_prefix('"')
+        .and_then(|s| s.strip_suffix('"'))
+        .or_else(|| {
+            section
+                .strip_prefix('\'')
+                .and_then(|s| s.strip_suffix('\''))
+        })
+        .unwrap_or(section);
+    section
+        .strip_prefix("profile:")
+        .or_else(|| section.strip_prefix("profile."))
+        .map(str::trim)
+}
+
+fn profile_names(content: &str) -> Vec<String> {
+    content
+        .lines()
+        .filter_map(profile_header_name)
+        .map(str::to_string)
+        .collect()
+}
+
+/// Line range of the `name` profile, up to (not including) the comments and
+/// blank lines in front of the next section
+fn find_profile_section(content: &str, name: &str) -> Option<(usize, usize)> {
+    let lines: Vec<&str> = content.lines().collect();
+    let start = lines
+        .iter()
+        .position(|line| profile_header_name(line) == Some(name))?;
+    let mut end = lines[start + 1..]
+        .iter()
+        .position(|line| parse_section_name(line).is_some())
+        .map_or(lines.len(), |offset| start + 1 + offset);
+    if end < lines.len() {
+        while end > start + 1 && {
+            let line = lines[end - 1].trim();
+            line.is_empty() || line.starts_with('#')
+        } {
+            end -= 1;
+        }
+    }
+    Some((start, end))
+}
+
+fn add_profile_to_content(content: &str, preset: &TargetPreset, compiler: &str) -> String {
+    let flags: Vec<String> = preset.flags.iter().map(|f| format!("\"{f}\"")).collect();
+    let mut output = content.trim_end().to_string();
+    if !output.is_empty() {
+        output.push_str("\n\n");
+    }
+    output.push_str(&format!(
+        "# {}\n[\"profile:{}\"]\ntarget = \"{}\"\ncompiler = \"{}\"\nflags = [{}]\n",
+        preset.description,
+        preset.name,
+        preset.triple,
+        compiler,
+        flags.join(", ")
+    ));
+    output
+}
+
+fn remove_profile_from_content(content: &str, name: &str) -> Option<String> {
+    let (start, mut end) = find_profile_section(content, name)?;
+    let mut lines: Vec<&str> = content.lines().collect();
+    // The comment describing the profile goes with it
+    let mut start = start;
+    while start > 0 && lines[start - 1].trim_start().starts_with('#') {
+        start -= 1;
+    }
+    // Don't leave two blank lines where the section was
+    while end < lines.len()
+        && lines[end].trim().is_empty()
+        && (start == 0 || lines[start - 1].trim().is_empty())
     {
-        if content.contains("[profile:") {
-            println!(
-                "{} Profile-based target configuration detected",
-                "✓".green()
-            );
-        } else {
-            println!(
-                "{} No target profiles configured. Add {} and run {}.",
-                "!".yellow(),
-                "[profile:<name>]".cyan(),
-                "cx build --profile <name>".cyan()
-            );
+        end += 1;
+    }
+    lines.drain(start..end);
+    while lines.last().is_some_and(|line| line.trim().is_empty()) {
+        lines.pop();
+    }
+    let mut output = lines.join("\n");
+    output.push('\n');
+    Some(output)
+}
+
+/// `[build] default_profile`, if set
+fn default_profile(content: &str) -> Option<String> {
+    let mut in_build_section = false;
+    for line in content.lines() {
+        if let Some(section_name) = parse_section_name(line) {
+            in_build_section = section_name.eq_ignore_ascii_case("build");
+            continue;
+        }
+        if in_build_section && assignment_key(line) == Some("default_profile") {
+            let (_, value) = line.split_once('=')?;
+            let value = value.split('#').next().unwrap_or_default().trim();
+            return Some(value.trim_matches(['"', '\'']).to_string());
         }
     }
+    None
 }
 
-fn print_target_usage_hint() {
-    println!();
-    println!("Usage: {}", "cx target list".cyan());
-    println!(
-        "Deferred: {}, {}, {}",
-        "cx target add <name>".dimmed(),
-        "cx target remove <name>".dimmed(),
-        "cx target default <name>".dimmed()
-    );
-    println!(
-        "Hint: configure cross builds with {} and run {}",
-        "[profile:<name>]".cyan(),
-        "cx build --profile <name>".cyan()
-    );
+/// Set (or with `None`, remove) `default_profile` in `[build]`
+fn set_default_profile_in_content(content: &str, profile: Option<&str>) -> String {
+    let mut lines: Vec<String> = content.lines().map(str::to_string).collect();
+    let build_start = lines
+        .iter()
+        .position(|line| parse_section_name(line).is_some_and(|s| s.eq_ignore_ascii_case("build")));
+    let new_line = profile.map(|name| format!("default_profile = \"{name}\""));
+
+    match build_start {
+        Some(build_start) => {
+            let build_end = lines[build_start + 1..]
+                .iter()
+                .position(|line| parse_section_name(line).is_some())
+                .map_or(lines.len(), |offset| build_start + 1 + offset);
+            let existing = (build_start + 1..build_end)
+                .find(|&i| assignment_key(&lines[i]) == Some("default_profile"));
+            match (existing, new_line) {
+                (Some(i), Some(line)) => lines[i] = line,
+                (Some(i), None) => {
+                    lines.remove(i);
+                }
+                (None, Some(line)) => {
+                    // Above blank lines and the comment heading the next section
+                    let mut at = build_end;
+                    while at > build_start + 1 && {
+                        let line = lines[at - 1].trim();
+                        line.is_empty() || (build_end < lines.len() && line.starts_with('#'))
+                    } {
+                        at -= 1;
+                    }
+                    lines.insert(at, line);
+                }
+                (None, None) => {}
+            }
+        }
+        None => {
+            if let Some(line) = new_line {
+                while lines.last().is_some_and(|l| l.trim().is_empty()) {
+                    lines.pop();
+                }
+                if !lines.is_empty() {
+                    lines.push(String::new());
+                }
+                lines.push("[build]".to_string());
+                lines.push(line);
+            }
+        }
+    }
+
+    let mut output = lines.join("\n");
+    output.push('\n');
+    output
 }
 
-fn deferred_target_mutation(operation: &str, target_name: &str) -> Result<()> {
-    eprintln!(
-        "{} '{}' is deferred in v0.3.x patch releases.",
-        "x".red(),
-        format!("cx target {operation} {target_name}").yellow()
-    );
-    eprintln!(
-        "  Configure targets via {} and run {}.",
-        "[profile:<name>]".cyan(),
-        "cx build --profile <name>".cyan()
-    );
-    Err(anyhow!(TARGET_DEFERRED_REASON))
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: &str = r#"# My project
+[package]
+name = "demo"
+version = "0.1.0"
+
+[build]
+sources = ["src/main.cpp"] # entry point
+
+# Flash builds
+["profile:esp32"]
+target = "xtensa-esp32-elf"
+
+["profile:embedded"]
+base = "release"
+"#;
+
+    #[test]
+    fn add_profile_writes_preset() {
+        let preset = find_preset("esp32").unwrap();
+        let output = add_profile_to_content(
+            "[package]\nname = \"demo\"\n",
+            preset,
+            "xtensa-esp32-elf-g++",
+        );
+        assert!(output.starts_with("[package]\nname = \"demo\"\n\n"));
+        assert!(output.contains("[\"profile:esp32\"]\ntarget = \"xtensa-esp32-elf\""));
+        assert!(output.contains("compiler = \"xtensa-esp32-elf-g++\""));
+        assert!(
+            output.contains(
+                "flags = [\"-mlongcalls\", \"-ffunction-sections\", \"-fdata-sections\"]"
+            )
+        );
+
+        let parsed: toml::Value = toml::from_str(&output).unwrap();
+        assert_eq!(
+            parsed["profile:esp32"]["target"].as_str(),
+            Some("xtensa-esp32-elf")
+        );
+    }
+
+    #[test]
+    fn remove_profile_keeps_comments_and_other_sections() {
+        let output = remove_profile_from_content(CONFIG, "esp32").unwrap();
+        assert!(!output.contains("esp32"));
+        assert!(!output.contains("# Flash builds"));
+        assert!(output.contains("# My project"));
+        assert!(output.contains("sources = [\"src/main.cpp\"] # entry point"));
+        assert!(output.contains("[\"profile:embedded\"]\nbase = \"release\""));
+        assert!(!output.contains("\n\n\n"));
+
+        let output = remove_profile_from_content(CONFIG, "embedded").unwrap();
+        assert!(output.ends_with("target = \"xtensa-esp32-elf\"\n"));
+        assert!(remove_profile_from_content(CONFIG, "wasm32").is_none());
+    }
+
+    #[test]
+    fn default_profile_is_set_replaced_and_removed() {
+        let output = set_default_profile_in_content(CONFIG, Some("esp32"));
+        assert!(output.contains(
+            "sources = [\"src/main.cpp\"] # entry point\ndefault_profile = \"esp32\"\n\n"
+        ));
+        assert_eq!(default_profile(&output).as_deref(), Some("esp32"));
+
+        let output = set_default_profile_in_content(&output, Some("embedded"));
+        assert_eq!(default_profile(&output).as_deref(), Some("embedded"));
+        assert_eq!(output.matches("default_profile").count(), 1);
+
+        let output = set_default_profile_in_content(&output, None);
+        assert_eq!(output, CONFIG);
+
+        let output = set_default_profile_in_content("[package]\nname = \"demo\"\n", Some("esp32"));
+        assert!(output.ends_with("\n\n[build]\ndefault_profile = \"esp32\"\n"));
+    }
+
+    #[test]
+    fn profile_headers_are_recognised_quoted_or_bare() {
+        assert_eq!(profile_header_name("[profile:esp32]"), Some("esp32"));
+        assert_eq!(profile_header_name("[\"profile:esp32\"]"), Some("esp32"));
+        assert_eq!(profile_header_name("[profile.bench]"), Some("bench"));
+        assert_eq!(profile_header_name("[build]"), None);
+        assert_eq!(profile_names(CONFIG), vec!["esp32", "embedded"]);
+    }
 }
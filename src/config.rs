@@ -42,6 +42,20 @@ pub struct CxConfig {
     pub workspace: Option<WorkspaceConfig>,
     /// Optional Arduino/IoT configuration.
     pub arduino: Option<ArduinoConfig>,
+    /// Optional compilation cache settings.
+    pub cache: Option<CacheConfig>,
+    /// Extra executables: [[bin]]
+    #[serde(rename = "bin", skip_serializing_if = "Option::is_none")]
+    pub bin_targets: Option<Vec<BinTarget>>,
+    /// Libraries built from the shared sources: [[lib]]
+    #[serde(rename = "lib", skip_serializing_if = "Option::is_none")]
+    pub lib_targets: Option<Vec<LibTarget>>,
+    /// Optional compile-time features (see `build::features`)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub features: Option<HashMap<String, Feature>>,
+    /// Platform-conditional settings: `[target.'cfg(unix)'.build]`
+    #[serde(rename = "target", skip_serializing_if = "Option::is_none")]
+    pub platforms: Option<HashMap<String, PlatformConfig>>,
     /// Named profiles for cross-compilation: [profile:name]
     #[serde(skip)]
     pub profiles: HashMap<String, Profile>,
@@ -50,19 +64,302 @@ pub struct CxConfig {
 /// Build profile for cross-compilation
 /// Used with [profile:name] sections in cx.toml
 #[derive(Deserialize, Serialize, Debug, Clone, Default)]
+#[serde(deny_unknown_fields)]
 pub struct Profile {
-    /// Base profile to inherit from (e.g., "release", "debug")
+    /// Profile to inherit from: another profile, "release" or "debug"
     pub base: Option<String>,
     /// Target triple (e.g., "xtensa-esp32-elf", "aarch64-linux-gnu")
     pub target: Option<String>,
+    /// Sysroot for cross builds (`--sysroot`)
+    pub sysroot: Option<String>,
+    /// Linker for cross builds (`-fuse-ld`, e.g. "lld")
+    pub linker: Option<String>,
+    /// Archiver for static libraries (e.g. "aarch64-linux-gnu-ar")
+    pub ar: Option<String>,
     /// Compiler override
     pub compiler: Option<String>,
     /// Compiler flags (preferred over cflags)
     pub flags: Option<Vec<String>>,
+    /// Preprocessor definitions, added to `[build] defines`
+    pub defines: Option<Vec<String>>,
     /// Libraries to link
     pub libs: Option<Vec<String>>,
+    /// Linker flags, added to `[build] ldflags`
+    pub ldflags: Option<Vec<String>>,
     /// Output binary name override
     pub bin: Option<String>,
+    /// Include paths, added to `[build] include`
+    pub include: Option<Vec<String>>,
+    /// Sources, replacing `[build] sources`
+    pub sources: Option<Vec<String>>,
+    /// Precompiled header override
+    pub pch: Option<String>,
+    /// C/C++ standard override (e.g. "c++20")
+    pub edition: Option<String>,
+    /// Dependencies added to (or replacing same-named) `[dependencies]`
+    pub dependencies: Option<HashMap<String, Dependency>>,
+    /// Optimisation level: 0-3, "s", "z" or "g"
+    #[serde(rename = "opt-level", alias = "opt_level")]
+    pub opt_level: Option<OptLevel>,
+    /// Debug info: false, "line-tables" or true
+    pub debug: Option<DebugInfo>,
+    /// Link-time optimisation: false, "thin" or true ("fat")
+    pub lto: Option<Lto>,
+    /// Strip symbols from linked binaries
+    pub strip: Option<bool>,
+    /// Keep frame pointers (`-fno-omit-frame-pointer`, `/Oy-`)
+    #[serde(rename = "frame-pointers", alias = "frame_pointers")]
+    pub frame_pointers: Option<bool>,
+    /// `false` defines `NDEBUG`
+    pub assertions: Option<bool>,
+}
+
+/// A profile knob written as a bool, an integer or a string
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum KnobValue {
+    Bool(bool),
+    Int(i64),
+    Str(String),
+}
+
+impl std::fmt::Display for KnobValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Bool(b) => write!(f, "{b}"),
+            Self::Int(i) => write!(f, "{i}"),
+            Self::Str(s) => write!(f, "\"{s}\""),
+        }
+    }
+}
+
+/// `opt-level` of a profile
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(try_from = "KnobValue")]
+pub enum OptLevel {
+    #[serde(rename = "0")]
+    O0,
+    #[serde(rename = "1")]
+    O1,
+    #[serde(rename = "2")]
+    O2,
+    #[serde(rename = "3")]
+    O3,
+    /// Optimise for size
+    #[serde(rename = "s")]
+    Size,
+    /// Optimise aggressively for size
+    #[serde(rename = "z")]
+    MinSize,
+    /// Optimise for debugging
+    #[serde(rename = "g")]
+    Debug,
+}
+
+impl TryFrom<KnobValue> for OptLevel {
+    type Error = String;
+
+    fn try_from(value: KnobValue) -> Result<Self, Self::Error> {
+        let level = match &value {
+            KnobValue::Int(i) => i.to_string(),
+            KnobValue::Str(s) => s.clone(),
+            KnobValue::Bool(_) => String::new(),
+        };
+        Ok(match level.as_str() {
+            "0" => Self::O0,
+            "1" => Self::O1,
+            "2" => Self::O2,
+            "3" => Self::O3,
+            "s" => Self::Size,
+            "z" => Self::MinSize,
+            "g" => Self::Debug,
+            _ => {
+                return Err(format!(
+                    "invalid opt-level {value}, expected 0-3, \"s\", \"z\" or \"g\""
+                ));
+            }
+        })
+    }
+}
+
+/// `debug` (debug info level) of a profile
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(try_from = "KnobValue")]
+pub enum DebugInfo {
+    #[serde(rename = "none")]
+    None,
+    /// Line tables only, enough for backtraces
+    #[serde(rename = "line-tables")]
+    LineTables,
+    #[serde(rename = "full")]
+    Full,
+}
+
+impl TryFrom<KnobValue> for DebugInfo {
+    type Error = String;
+
+    fn try_from(value: KnobValue) -> Result<Self, Self::Error> {
+        Ok(match &value {
+            KnobValue::Bool(false) | KnobValue::Int(0) => Self::None,
+            KnobValue::Bool(true) | KnobValue::Int(2) => Self::Full,
+            KnobValue::Int(1) => Self::LineTables,
+            KnobValue::Str(s) if s == "none" => Self::None,
+            KnobValue::Str(s) if s == "line-tables" => Self::LineTables,
+            KnobValue::Str(s) if s == "full" => Self::Full,
+            _ => {
+                return Err(format!(
+                    "invalid debug {value}, expected true, false, \"none\", \"line-tables\" or \"full\""
+                ));
+            }
+        })
+    }
+}
+
+/// `lto` of a profile
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(try_from = "KnobValue")]
+pub enum Lto {
+    #[default]
+    #[serde(rename = "off")]
+    Off,
+    /// Whole-program LTO (`-flto`, `/GL`)
+    #[serde(rename = "fat")]
+    Fat,
+    /// Clang's ThinLTO (`-flto=thin`); plain `-flto` on GCC
+    #[serde(rename = "thin")]
+    Thin,
+}
+
+impl Lto {
+    pub fn is_enabled(self) -> bool {
+        self != Self::Off
+    }
+}
+
+impl TryFrom<KnobValue> for Lto {
+    type Error = String;
+
+    fn try_from(value: KnobValue) -> Result<Self, Self::Error> {
+        Ok(match &value {
+            KnobValue::Bool(false) => Self::Off,
+            KnobValue::Bool(true) => Self::Fat,
+            KnobValue::Str(s) if s == "off" => Self::Off,
+            KnobValue::Str(s) if s == "fat" || s == "full" => Self::Fat,
+            KnobValue::Str(s) if s == "thin" => Self::Thin,
+            _ => {
+                return Err(format!(
+                    "invalid lto {value}, expected true, false, \"thin\" or \"fat\""
+                ));
+            }
+        })
+    }
+}
+
+/// One `[target.'<cfg or triple>']` table
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+#[serde(deny_unknown_fields)]
+pub struct PlatformConfig {
+    /// Added to `[build]` on matching platforms
+    pub build: Option<PlatformBuildConfig>,
+    /// Added to (or replacing same-named) `[dependencies]` on matching platforms
+    pub dependencies: Option<HashMap<String, Dependency>>,
+}
+
+/// `[target.'...'.build]`: lists appended to `[build]`
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+#[serde(deny_unknown_fields)]
+pub struct PlatformBuildConfig {
+    pub flags: Option<Vec<String>>,
+    pub defines: Option<Vec<String>>,
+    pub libs: Option<Vec<String>>,
+    pub ldflags: Option<Vec<String>>,
+    pub include: Option<Vec<String>>,
+    /// Source globs built only on matching platforms
+    pub sources: Option<Vec<String>>,
+}
+
+/// Executable target declared with [[bin]]
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+pub struct BinTarget {
+    /// Output name (e.g., "server")
+    pub name: String,
+    /// Entry point source; defaults to src/bin/<name>.cpp (or .c)
+    #[serde(alias = "path")]
+    pub main: Option<String>,
+}
+
+/// Library target declared with [[lib]]
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+pub struct LibTarget {
+    /// Output name (defaults to the package name)
+    pub name: Option<String>,
+    /// "static-library" (default) or "shared-library"
+    #[serde(rename = "type")]
+    pub lib_type: Option<String>,
+}
+
+/// One `[features]` entry
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(untagged)]
+pub enum Feature {
+    /// `tls = ["crypto", "dep:openssl"]`: other features and optional dependencies
+    Simple(Vec<String>),
+    /// `tls = { enables = [...], defines = [...], sources = [...] }`
+    Detailed(FeatureConfig),
+}
+
+impl Feature {
+    /// Features and `dep:` entries this feature turns on
+    pub fn enables(&self) -> &[String] {
+        match self {
+            Self::Simple(enables) => enables,
+            Self::Detailed(cfg) => &cfg.enables,
+        }
+    }
+
+    /// Extra preprocessor definitions while enabled
+    pub fn defines(&self) -> &[String] {
+        match self {
+            Self::Simple(_) => &[],
+            Self::Detailed(cfg) => &cfg.defines,
+        }
+    }
+
+    /// Source globs compiled only while enabled
+    pub fn sources(&self) -> &[String] {
+        match self {
+            Self::Simple(_) => &[],
+            Self::Detailed(cfg) => &cfg.sources,
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Default, Clone)]
+pub struct FeatureConfig {
+    /// Other features and optional dependencies (`dep:name`)
+    #[serde(default)]
+    pub enables: Vec<String>,
+    /// Preprocessor definitions, `NAME` or `NAME=value`
+    #[serde(default)]
+    pub defines: Vec<String>,
+    /// Globs relative to the project root, e.g. `src/tls/**/*.cpp`
+    #[serde(default)]
+    pub sources: Vec<String>,
+}
+
+/// Compilation cache settings: [cache]
+#[derive(Deserialize, Serialize, Debug, Default, Clone)]
+pub struct CacheConfig {
+    /// Native object cache in ~/.cx/objcache (default: true)
+    pub local: Option<bool>,
+    /// Object cache size limit, e.g. "5G" (default) or "500M"
+    pub max_size: Option<String>,
+    /// Shared HTTP object cache, e.g. "http://cache.internal:8080/cx"
+    pub remote: Option<String>,
+    /// "read" (default) or "read-write"; CI usually writes, laptops read
+    pub remote_mode: Option<String>,
+    /// URL layout: "flat" (`<remote>/<key>`, default) or "bazel" (`<remote>/ac/<key>`)
+    pub remote_layout: Option<String>,
 }
 
 #[derive(Deserialize, Serialize, Debug, Default, Clone)]
@@ -101,12 +398,29 @@ pub enum Dependency {
         branch: Option<String>,
         tag: Option<String>,
         rev: Option<String>,
+        /// Semver requirement resolved against the repository's tags, e.g. `"^3.11"`
+        version: Option<String>,
         // Build Features
         build: Option<String>,
         output: Option<String>,
+        /// Only used when a `[features]` entry enables `dep:<name>`
+        optional: Option<bool>,
     },
 }
 
+impl Dependency {
+    /// Declared with `optional = true`
+    pub fn is_optional(&self) -> bool {
+        matches!(
+            self,
+            Self::Complex {
+                optional: Some(true),
+                ..
+            }
+        )
+    }
+}
+
 #[derive(Deserialize, Serialize, Debug, Default, Clone)]
 pub struct PackageConfig {
     pub name: String,
@@ -124,6 +438,8 @@ pub struct BuildConfig {
     pub flags: Option<Vec<String>>,
     /// Deprecated: use `flags` instead
     pub cflags: Option<Vec<String>>,
+    /// Preprocessor definitions: `NAME` or `NAME=value`
+    pub defines: Option<Vec<String>>,
     pub libs: Option<Vec<String>>,
     /// Linker flags (e.g., /SUBSYSTEM:WINDOWS)
     pub ldflags: Option<Vec<String>>,
@@ -136,9 +452,23 @@ pub struct BuildConfig {
     pub framework: Option<String>,
     /// Include paths for compilation
     pub include: Option<Vec<String>>,
-    /// Build type (e.g., "header-only", "library", "executable")
+    /// Build type: "executable" (default), "static-library", "shared-library" or "header-only"
     #[serde(rename = "type")]
     pub build_type: Option<String>,
+    /// Discover extra executables in src/bin/ (default: true)
+    pub autobins: Option<bool>,
+    /// Incremental change detection: "mtime" (default) or "hash"
+    pub incremental: Option<String>,
+    /// Parallel compile jobs (default: one per core)
+    pub jobs: Option<usize>,
+    /// Don't start new compile jobs while less memory is available, e.g. "2G"
+    pub min_free_memory: Option<String>,
+    /// Per-source flags: `[[build.overrides]]` with `files` globs
+    pub overrides: Option<Vec<FlagOverride>>,
+    /// Profile used by `cx build` and `cx generate` when `--profile` is omitted
+    pub default_profile: Option<String>,
+    /// Unity builds: `unity = { batch_size = 16 }` (see `build::unity`)
+    pub unity: Option<UnityConfig>,
     /// Terminal encoding: "utf-8" (default) or "system"
     #[serde(default = "default_encoding")]
     pub encoding: String,
@@ -150,10 +480,97 @@ impl BuildConfig {
         self.flags.as_ref().or(self.cflags.as_ref())
     }
 
+    /// `defines` as compiler arguments (`-D` or MSVC's `/D`)
+    pub fn define_flags(&self, is_msvc: bool) -> Vec<String> {
+        self.defines
+            .iter()
+            .flatten()
+            .map(|define| define_flag(define, is_msvc))
+            .collect()
+    }
+
     /// Check if using deprecated cflags field
     pub fn uses_deprecated_cflags(&self) -> bool {
         self.cflags.is_some() && self.flags.is_none()
     }
+
+    /// Get the parsed build type, or `None` if `type` holds an unknown value
+    pub fn get_build_type(&self) -> Option<BuildType> {
+        match &self.build_type {
+            Some(raw) => BuildType::parse(raw),
+            None => Some(BuildType::Executable),
+        }
+    }
+}
+
+/// One preprocessor definition as a compiler argument
+pub fn define_flag(define: &str, is_msvc: bool) -> String {
+    format!("{}{}", if is_msvc { "/D" } else { "-D" }, define)
+}
+
+/// Extra flags for the sources matching `files` (see `build::overrides`)
+#[derive(Deserialize, Serialize, Debug, Default, Clone)]
+pub struct FlagOverride {
+    /// Globs relative to the project root, e.g. `src/gen/**/*.c`
+    pub files: Vec<String>,
+    /// Compiler flags, added after `[build] flags`
+    #[serde(default)]
+    pub flags: Vec<String>,
+    /// Preprocessor definitions, `NAME` or `NAME=value`
+    #[serde(default)]
+    pub defines: Vec<String>,
+    /// `false` keeps the matching files out of unity batches
+    pub unity: Option<bool>,
+}
+
+/// `[build] unity`: compile sources in batches of generated `#include` files
+#[derive(Deserialize, Serialize, Debug, Default, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct UnityConfig {
+    /// Sources per batch (default: 16)
+    pub batch_size: Option<usize>,
+}
+
+/// Kind of artifact produced by `[build] type = "..."`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BuildType {
+    /// Linked program (default)
+    #[default]
+    Executable,
+    /// Archive: `.a` (GCC/Clang) or `.lib` (MSVC)
+    StaticLibrary,
+    /// Shared object: `.so`/`.dylib`, or `.dll` plus import `.lib` on Windows
+    SharedLibrary,
+    /// No artifact; headers are only checked to compile on their own
+    HeaderOnly,
+}
+
+impl BuildType {
+    /// Parse a `type` value, accepting a few common spellings
+    pub fn parse(raw: &str) -> Option<Self> {
+        match raw.to_lowercase().replace('_', "-").as_str() {
+            "executable" | "exe" | "bin" | "binary" => Some(Self::Executable),
+            "static-library" | "static" | "staticlib" | "library" | "lib" => {
+                Some(Self::StaticLibrary)
+            }
+            "shared-library" | "shared" | "dylib" | "dll" | "cdylib" => Some(Self::SharedLibrary),
+            "header-only" | "headers" | "interface" => Some(Self::HeaderOnly),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Executable => "executable",
+            Self::StaticLibrary => "static-library",
+            Self::SharedLibrary => "shared-library",
+            Self::HeaderOnly => "header-only",
+        }
+    }
+
+    pub fn is_library(self) -> bool {
+        !matches!(self, Self::Executable)
+    }
 }
 
 fn default_edition() -> String {
@@ -191,6 +608,7 @@ pub fn create_ephemeral_config(
             bin: Some(bin_name.to_string()),
             flags: None,
             cflags: None,
+            defines: None,
             libs: None,
             ldflags: None,
             sources: Some(vec![name.to_string()]),
@@ -199,6 +617,13 @@ pub fn create_ephemeral_config(
             framework: None,
             include: None,
             build_type: None,
+            autobins: Some(false),
+            incremental: None,
+            jobs: None,
+            min_free_memory: None,
+            overrides: None,
+            default_profile: None,
+            unity: None,
             encoding: default_encoding(),
         }),
         dependencies: None,
@@ -206,6 +631,11 @@ pub fn create_ephemeral_config(
         test: None,
         workspace: None,
         arduino: None,
+        cache: None,
+        bin_targets: None,
+        lib_targets: None,
+        features: None,
+        platforms: None,
         profiles: HashMap::new(),
     }
 }
@@ -252,6 +682,36 @@ mod tests {
         assert!(!modern.uses_deprecated_cflags());
     }
 
+    #[test]
+    fn test_build_config_define_flags() {
+        let config = BuildConfig {
+            defines: Some(vec!["FOO".to_string(), "BAR=1".to_string()]),
+            ..Default::default()
+        };
+        assert_eq!(config.define_flags(false), vec!["-DFOO", "-DBAR=1"]);
+        assert_eq!(config.define_flags(true), vec!["/DFOO", "/DBAR=1"]);
+        assert!(BuildConfig::default().define_flags(false).is_empty());
+    }
+
+    #[test]
+    fn test_build_type_parsing() {
+        assert_eq!(BuildType::parse("executable"), Some(BuildType::Executable));
+        assert_eq!(
+            BuildType::parse("static-library"),
+            Some(BuildType::StaticLibrary)
+        );
+        assert_eq!(BuildType::parse("library"), Some(BuildType::StaticLibrary));
+        assert_eq!(
+            BuildType::parse("Shared_Library"),
+            Some(BuildType::SharedLibrary)
+        );
+        assert_eq!(BuildType::parse("header-only"), Some(BuildType::HeaderOnly));
+        assert_eq!(BuildType::parse("plugin"), None);
+
+        let default_cfg = BuildConfig::default();
+        assert_eq!(default_cfg.get_build_type(), Some(BuildType::Executable));
+    }
+
     #[test]
     fn test_create_ephemeral_config_cpp() {
         let config = create_ephemeral_config("main.cpp", "app", "g++", true);
@@ -306,6 +766,59 @@ libs = ["pthread"]
         );
     }
 
+    #[test]
+    fn test_parse_bin_and_lib_targets() {
+        let toml_str = r#"
+[package]
+name = "suite"
+version = "0.1.0"
+
+[[bin]]
+name = "server"
+main = "src/bin/server.cpp"
+
+[[bin]]
+name = "client"
+
+[[lib]]
+type = "shared-library"
+"#;
+        let config: CxConfig = toml::from_str(toml_str).unwrap();
+        let bins = config.bin_targets.unwrap();
+        assert_eq!(bins.len(), 2);
+        assert_eq!(bins[0].name, "server");
+        assert_eq!(bins[0].main.as_deref(), Some("src/bin/server.cpp"));
+        assert_eq!(bins[1].main, None);
+        let libs = config.lib_targets.unwrap();
+        assert_eq!(libs[0].name, None);
+        assert_eq!(libs[0].lib_type.as_deref(), Some("shared-library"));
+    }
+
+    #[test]
+    fn test_parse_features() {
+        let toml_str = r#"
+[package]
+name = "net"
+version = "0.1.0"
+
+[features]
+default = ["logging"]
+logging = []
+tls = { enables = ["dep:openssl"], defines = ["USE_TLS=1"], sources = ["src/tls/*.cpp"] }
+
+[dependencies]
+openssl = { pkg = "openssl", optional = true }
+"#;
+        let config: CxConfig = toml::from_str(toml_str).unwrap();
+        let features = config.features.unwrap();
+        assert_eq!(features["default"].enables(), ["logging"]);
+        assert!(features["logging"].enables().is_empty());
+        assert_eq!(features["tls"].enables(), ["dep:openssl"]);
+        assert_eq!(features["tls"].defines(), ["USE_TLS=1"]);
+        assert_eq!(features["tls"].sources(), ["src/tls/*.cpp"]);
+        assert!(config.dependencies.unwrap()["openssl"].is_optional());
+    }
+
     #[test]
     fn test_dependency_simple() {
         let toml_str = r#"
@@ -5,18 +5,32 @@
 //! ## Features
 //!
 //! - Git clone with tag/branch/rev pinning
+//! - Semver `version` requirements resolved against the repository's tags
 //! - Custom build commands per dependency
 //! - SHA256 hash verification for prebuilt binaries
-//! - Global cache at `~/.cx/cache`
-
-use crate::config::Dependency;
-use anyhow::{Context, Result};
+//! - Global cache at `~/.cx/cache/git`, one checkout per commit (see [`super::gitcache`])
+//! - Transitive dependencies from each dependency's own `cx.toml`
+//! - Independent dependencies fetched and built in parallel (`CX_JOBS` at a
+//!   time), each one's output printed in one piece (see [`super::progress`])
+
+use super::gitcache::{self, GitCache};
+use super::graph::{Added, DependencyGraph, Node, Pin, ROOT, requirement_report};
+use super::offline::{self, NetworkMode};
+use super::progress::{DepProgress, Progress};
+use super::version::{highest_match, matches_all, newest_tags, parse_tag_version};
+use crate::build::jobs::Scheduler;
+use crate::build::platform::{self, Platform};
+use crate::commands::doctor::ensure_frozen_lockfile;
+use crate::config::{CxConfig, Dependency, PlatformConfig};
+use crate::lock::{LockFile, PackageLock};
+use anyhow::{Context, Result, bail};
 use colored::*;
 
 use git2::Repository;
-use indicatif::{ProgressBar, ProgressStyle};
+use semver::VersionReq;
+use serde::Deserialize;
 use sha2::{Digest, Sha256};
-use std::collections::{HashMap, HashSet};
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::fs;
 use std::io::Read;
 use std::path::{Path, PathBuf};
@@ -51,7 +65,7 @@ pub fn verify_sha256(path: &Path, expected_hash: Option<&str>) -> Result<bool> {
     }
 
     let result = hasher.finalize();
-    let actual_hash = format!("{:x}", result);
+    let actual_hash: String = result.iter().map(|b| format!("{b:02x}")).collect();
 
     if actual_hash.eq_ignore_ascii_case(expected) {
         Ok(true)
@@ -158,16 +172,21 @@ fn try_download_prebuilt(
     tag: Option<&str>,
     lib_path: &Path,
     output_file: &str,
+    progress: &mut DepProgress,
 ) -> Result<bool> {
     // Only works on Windows for now
     #[cfg(not(windows))]
     {
-        let _ = (name, url, tag, lib_path, output_file);
+        let _ = (name, url, tag, lib_path, output_file, progress);
         Ok(false)
     }
 
     #[cfg(windows)]
     {
+        if super::offline::is_offline() {
+            return Ok(false);
+        }
+
         // Need a tag/version to find the right release
         let version = match tag {
             Some(t) => t.trim_start_matches('v').trim_start_matches("release-"),
@@ -202,7 +221,11 @@ fn try_download_prebuilt(
             return Ok(true);
         }
 
-        println!("   {} Checking for prebuilt {}...", "⚡".cyan(), name);
+        progress.log(format!(
+            "   {} Checking for prebuilt {}...",
+            "⚡".cyan(),
+            name
+        ));
 
         // Try to download
         let agent = ureq::agent();
@@ -218,11 +241,12 @@ fn try_download_prebuilt(
             return Ok(false);
         }
 
-        println!(
+        progress.set_message(format!("Downloading prebuilt {}...", name));
+        progress.log(format!(
             "   {} Downloading prebuilt {} (faster!)...",
             "📦".blue(),
             name
-        );
+        ));
 
         // Download to temp file
         let temp_zip = lib_path.join("_prebuilt.zip");
@@ -310,7 +334,7 @@ fn try_download_prebuilt(
         // Cleanup
         let _ = fs::remove_file(&temp_zip);
 
-        println!("   {} Prebuilt {} ready!", "✓".green(), name);
+        progress.log(format!("   {} Prebuilt {} ready!", "✓".green(), name));
 
         Ok(true)
     }
@@ -332,6 +356,9 @@ fn parse_github_url(url: &str) -> Option<(String, String)> {
     None
 }
 
+/// Lines of a failed build script's output shown with the error
+const BUILD_LOG_TAIL: usize = 20;
+
 /// Module file info: (module_source_path, dependency_root_path)
 pub type ModuleFile = (PathBuf, PathBuf);
 
@@ -356,39 +383,220 @@ pub fn fetch_dependencies_with_options(
     deps: &HashMap<String, Dependency>,
     options: FetchOptions,
 ) -> Result<FetchResult> {
-    let home_dir = dirs::home_dir().context("Could not find home directory")?;
-    let cache_dir = home_dir.join(".cx").join("cache");
-    fs::create_dir_all(&cache_dir)?;
+    let cache = GitCache::new()?;
 
-    let mut lockfile = crate::lock::LockFile::load().unwrap_or_default();
+    let frozen = offline::network_mode() == NetworkMode::Frozen;
+    let mut lockfile = if frozen {
+        let lockfile = LockFile::load()?;
+        ensure_frozen_lockfile(deps, &lockfile)?;
+        lockfile
+    } else {
+        LockFile::load().unwrap_or_default()
+    };
+    let original = lockfile.clone();
     let mut state = FetchState::default();
+    let mut resolution = Resolution::default();
 
     if !deps.is_empty() {
-        println!("{} Checking {} dependencies...", "📦".blue(), deps.len());
+        status!("{} Checking {} dependencies...", "📦".blue(), deps.len());
     }
 
-    for (name, dep_data) in deps {
-        if let Some(pkg_name) = pkg_dependency_name(dep_data) {
-            resolve_system_package(pkg_name, &mut state);
-            continue;
+    // Sorted, so resolution and conflict reports don't depend on hash order
+    let mut roots: Vec<_> = deps.iter().collect();
+    roots.sort_by_key(|(name, _)| *name);
+    for (name, dep_data) in roots {
+        resolution.require(ROOT, None, name, dep_data, &mut state)?;
+    }
+
+    let scheduler = Scheduler::new(None, None);
+    let progress = Progress::new();
+
+    // Fetch breadth-first, everything pending side by side; each checkout may
+    // bring its own [dependencies]. Manifests are read in node order, so the
+    // graph doesn't depend on which fetch finished first.
+    while !resolution.pending.is_empty() {
+        let batch: Vec<usize> = resolution.pending.drain(..).collect();
+        let checkouts = scheduler.run(&batch, |_, &idx| {
+            let node = &resolution.graph.nodes[idx];
+            fetch_git_dependency(node, &cache, options, &lockfile, &progress)
+        })?;
+        for (idx, checkout) in batch.into_iter().zip(checkouts) {
+            let node = &resolution.graph.nodes[idx];
+            let name = node.name.clone();
+            if let Some(rev) = &checkout.rev {
+                lockfile.insert(name.clone(), node.url.clone(), rev.clone());
+                lockfile.set_tag(&name, checkout.version_tag.clone());
+            }
+            resolution.graph.clear_edges(idx);
+            for (dep_name, dep_data) in manifest_dependencies(&checkout.path)? {
+                resolution.require(&name, Some(idx), &dep_name, &dep_data, &mut state)?;
+            }
+            resolution.checkouts[idx] = Some(checkout);
         }
+    }
 
-        let Some(spec) = extract_git_dependency_spec(dep_data) else {
-            continue;
-        };
-        process_git_dependency(name, &spec, &cache_dir, options, &mut lockfile, &mut state)?;
+    // Dependencies build before the packages that need them, those that
+    // don't need each other side by side
+    for level in resolution.graph.levels()? {
+        let built: Vec<(usize, &Checkout)> = level
+            .into_iter()
+            .filter_map(|idx| Some((idx, resolution.checkouts[idx].as_ref()?)))
+            .collect();
+        scheduler.run(&built, |_, &(idx, checkout)| {
+            let name = &resolution.graph.nodes[idx].name;
+            maybe_build_dependency(name, &resolution.specs[idx], checkout, &cache, &progress)
+        })?;
+    }
+
+    // Include paths and libraries follow dependents-first, the order static
+    // libs link in
+    for &idx in &resolution.graph.order()? {
+        if let Some(checkout) = &resolution.checkouts[idx] {
+            let lib_path = &checkout.path;
+            register_include_paths(lib_path, &mut state);
+            collect_module_files(lib_path, &mut state);
+            collect_link_outputs(
+                lib_path,
+                resolution.specs[idx].output_file.as_deref(),
+                &mut state.link_flags,
+            );
+        }
     }
 
+    let graph = &resolution.graph;
+    for node in &graph.nodes {
+        let requires = node
+            .dependencies
+            .iter()
+            .map(|&dep| graph.nodes[dep].name.clone())
+            .collect();
+        lockfile.set_dependencies(&node.name, requires);
+    }
+    let resolved: HashSet<&str> = graph.nodes.iter().map(|n| n.name.as_str()).collect();
     lockfile
         .packages
-        .retain(|name, _| state.expected_git_deps.contains(name));
-    lockfile.save()?;
+        .retain(|name, _| resolved.contains(name.as_str()));
+    if frozen {
+        if lockfile != original {
+            bail!(
+                "cx.lock is out of date and --frozen forbids updating it\n  \
+                 Run 'cx lock --update' without --frozen"
+            );
+        }
+    } else {
+        lockfile.save()?;
+    }
     Ok(state.into_result())
 }
 
+/// The git dependency graph while it is being fetched
+#[derive(Default)]
+struct Resolution {
+    graph: DependencyGraph,
+    /// Per node: the spec it is fetched and built with
+    specs: Vec<GitDependencySpec>,
+    /// Per node: the checkout, once fetched
+    checkouts: Vec<Option<Checkout>>,
+    pending: VecDeque<usize>,
+}
+
+/// A fetched dependency
+struct Checkout {
+    path: PathBuf,
+    /// Commit of a cached checkout; `None` for a vendored copy
+    rev: Option<String>,
+    /// Tag the checked-out commit was selected by, if any
+    tag: Option<String>,
+    /// Tag picked for a `version` requirement, recorded in `cx.lock`
+    version_tag: Option<String>,
+}
+
+impl Resolution {
+    /// Add `by`'s requirement on `name`; `parent` is `by`'s node, `None` for the project
+    fn require(
+        &mut self,
+        by: &str,
+        parent: Option<usize>,
+        name: &str,
+        dep_data: &Dependency,
+        state: &mut FetchState,
+    ) -> Result<()> {
+        if let Some(pkg_name) = pkg_dependency_name(dep_data) {
+            if state.system_packages.insert(pkg_name.to_string()) {
+                resolve_system_package(pkg_name, state);
+            }
+            return Ok(());
+        }
+        let Some(spec) = extract_git_dependency_spec(dep_data) else {
+            return Ok(());
+        };
+
+        let pin = spec
+            .pin()
+            .with_context(|| format!("Failed to resolve '{}' (required by {})", name, by))?;
+        let added = self.graph.require(name, &spec.url, by, pin)?;
+        match added {
+            Added::New(idx) => {
+                if parent.is_some() {
+                    status!("   {} {} requires {}", "↳".dimmed(), by, name);
+                }
+                self.specs.push(spec);
+                self.checkouts.push(None);
+                self.pending.push_back(idx);
+            }
+            Added::Pinned(idx) => {
+                // Check out again at the narrowed selection, keeping the build settings
+                if !self.pending.contains(&idx) {
+                    self.pending.push_back(idx);
+                }
+            }
+            Added::Existing(_) => {}
+        }
+        if let Some(parent) = parent {
+            self.graph.add_edge(parent, added.index());
+        }
+        Ok(())
+    }
+}
+
+/// The parts of a dependency's `cx.toml` that matter to its dependents
+#[derive(Deserialize)]
+struct Manifest {
+    dependencies: Option<HashMap<String, Dependency>>,
+    #[serde(rename = "target")]
+    platforms: Option<HashMap<String, PlatformConfig>>,
+}
+
+/// Non-optional `[dependencies]` of the checkout at `lib_path` for the
+/// host, sorted by name; empty when it has no `cx.toml`
+fn manifest_dependencies(lib_path: &Path) -> Result<Vec<(String, Dependency)>> {
+    let path = lib_path.join("cx.toml");
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let content = fs::read_to_string(&path)?;
+    let manifest: Manifest =
+        toml::from_str(&content).with_context(|| format!("Failed to parse {}", path.display()))?;
+    let mut config = CxConfig {
+        dependencies: manifest.dependencies,
+        platforms: manifest.platforms,
+        ..Default::default()
+    };
+    platform::apply(&mut config, &Platform::host())?;
+
+    let mut deps: Vec<(String, Dependency)> = config
+        .dependencies
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|(_, dep)| !dep.is_optional())
+        .collect();
+    deps.sort_by(|a, b| a.0.cmp(&b.0));
+    Ok(deps)
+}
+
 #[derive(Default)]
 struct FetchState {
-    expected_git_deps: HashSet<String>,
+    system_packages: HashSet<String>,
     include_paths: Vec<PathBuf>,
     include_seen: HashSet<PathBuf>,
     extra_cflags: Vec<String>,
@@ -422,6 +630,25 @@ struct GitDependencySpec {
     tag: Option<String>,
     branch: Option<String>,
     rev: Option<String>,
+    version: Option<String>,
+}
+
+impl GitDependencySpec {
+    fn pin(&self) -> Result<Option<Pin>> {
+        Ok(if let Some(rev) = &self.rev {
+            Some(Pin::Rev(rev.clone()))
+        } else if let Some(tag) = &self.tag {
+            Some(Pin::Tag(tag.clone()))
+        } else if let Some(branch) = &self.branch {
+            Some(Pin::Branch(branch.clone()))
+        } else if let Some(version) = &self.version {
+            let req = VersionReq::parse(version)
+                .with_context(|| format!("Invalid version requirement '{}'", version))?;
+            Some(Pin::Version(req))
+        } else {
+            None
+        })
+    }
 }
 
 fn pkg_dependency_name(dep_data: &Dependency) -> Option<&str> {
@@ -445,6 +672,7 @@ fn extract_git_dependency_spec(dep_data: &Dependency) -> Option<GitDependencySpe
             tag: None,
             branch: None,
             rev: None,
+            version: None,
         }),
         Dependency::Complex {
             git: Some(url),
@@ -453,6 +681,7 @@ fn extract_git_dependency_spec(dep_data: &Dependency) -> Option<GitDependencySpe
             tag,
             branch,
             rev,
+            version,
             ..
         } => Some(GitDependencySpec {
             url: url.clone(),
@@ -461,23 +690,24 @@ fn extract_git_dependency_spec(dep_data: &Dependency) -> Option<GitDependencySpe
             tag: tag.clone(),
             branch: branch.clone(),
             rev: rev.clone(),
+            version: version.clone(),
         }),
         _ => None,
     }
 }
 
 fn resolve_system_package(pkg_name: &str, state: &mut FetchState) {
-    println!("   {} Resolving system pkg: {}", "🔎".cyan(), pkg_name);
+    status!("   {} Resolving system pkg: {}", "🔎".cyan(), pkg_name);
 
     let cflags_ok = append_pkg_config_flags(pkg_name, "--cflags", &mut state.extra_cflags);
     if !cflags_ok {
-        println!("{} Warning: pkg-config tool not found", "!".yellow());
+        status!("{} Warning: pkg-config tool not found", "!".yellow());
         return;
     }
 
     let libs_ok = append_pkg_config_flags(pkg_name, "--libs", &mut state.link_flags);
     if !libs_ok {
-        println!(
+        status!(
             "{} Package '{}' not found via pkg-config",
             "x".red(),
             pkg_name
@@ -502,116 +732,192 @@ fn append_pkg_config_flags(pkg_name: &str, flag_kind: &str, out: &mut Vec<String
     true
 }
 
-fn process_git_dependency(
-    name: &str,
-    spec: &GitDependencySpec,
-    cache_dir: &Path,
+/// Check out the commit `node`'s requirements select (or the locked one)
+/// from its cached database; a vendored copy is used as is. The commit is
+/// for the caller to record in `cx.lock`.
+fn fetch_git_dependency(
+    node: &Node,
+    cache: &GitCache,
     options: FetchOptions,
-    lockfile: &mut crate::lock::LockFile,
-    state: &mut FetchState,
-) -> Result<()> {
-    state.expected_git_deps.insert(name.to_string());
+    lockfile: &LockFile,
+    progress: &Progress,
+) -> Result<Checkout> {
+    let name = node.name.as_str();
+    let mut progress = progress.start(format!("Fetching {}...", name));
+    let exact_tag = match node.exact_pin() {
+        Some(Pin::Tag(tag)) => Some(tag.clone()),
+        _ => None,
+    };
+    let vendor_path = std::env::current_dir()?.join("vendor").join(name);
+    if vendor_path.exists() {
+        progress.log(format!("   {} Using vendor: {}", "📦".blue(), name));
+        let tag = lockfile.get(name).and_then(|e| e.tag.clone()).or(exact_tag);
+        return Ok(Checkout {
+            path: vendor_path,
+            rev: None,
+            tag,
+            version_tag: None,
+        });
+    }
 
-    let (lib_path, is_vendor) = resolve_dependency_path(name, cache_dir)?;
-    let repo = open_or_clone_repo(name, &spec.url, &lib_path, is_vendor)?;
+    let _lock = cache.lock_db(name, &node.url)?;
+    let repo = if cache.has_db(name, &node.url) {
+        progress.log(format!("   {} Using cached: {}", "⚡".green(), name));
+        cache.open_db(name, &node.url)?
+    } else {
+        progress.set_message(format!("Downloading {}...", name));
+        let repo = cache.open_db(name, &node.url)?;
+        progress.log(format!("   {} Downloaded {}", "✓".green(), name));
+        repo
+    };
 
-    let locked_commit = locked_commit_for(lockfile, name, &spec.url, options.enforce_lock);
-    if let Some((oid, checkout_msg)) = select_checkout_target(&repo, spec, locked_commit.as_deref())
-    {
-        checkout_repo_target(&repo, oid, &checkout_msg)?;
-    }
-
-    refresh_lockfile_entry(&repo, lockfile, name, &spec.url);
-    maybe_build_dependency(name, spec, &lib_path)?;
-    register_include_paths(&lib_path, state);
-    collect_module_files(&lib_path, state);
-    collect_link_outputs(
-        &lib_path,
-        spec.output_file.as_deref(),
-        &mut state.link_flags,
-    );
-    Ok(())
+    let locked = locked_entry_for(lockfile, name, &node.url, options.enforce_lock);
+    let reqs = node.version_reqs();
+    let mut resolved_tag = None;
+    let (oid, checkout_msg) = if node.exact_pin().is_none() && !reqs.is_empty() {
+        let (oid, tag) = resolve_version(&repo, node, &reqs, locked, &mut progress)?;
+        let checkout_msg = format!("tag {}", tag);
+        resolved_tag = Some(tag);
+        (oid, checkout_msg)
+    } else {
+        let locked_commit = locked.map(|e| e.rev.as_str());
+        match select_checkout_target(&repo, node.exact_pin(), locked_commit) {
+            Some(target) => target,
+            None => {
+                // The pin may be newer than the cached database
+                if let Some(pin) = node.exact_pin() {
+                    gitcache::fetch(&repo)
+                        .with_context(|| format!("Failed to fetch '{}'", name))?;
+                    select_checkout_target(&repo, Some(pin), locked_commit).with_context(|| {
+                        format!("Could not find {} of '{}' ({})", pin, name, node.url)
+                    })?
+                } else {
+                    let head = repo.head()?.peel_to_commit()?.id();
+                    (head, format!("HEAD {}", short_hash(&head.to_string())))
+                }
+            }
+        }
+    };
+
+    let path = cache.checkout(&repo, name, oid)?;
+    progress.log(format!("   {} Locked to {}", "📌".blue(), checkout_msg));
+    Ok(Checkout {
+        path,
+        rev: Some(oid.to_string()),
+        tag: resolved_tag.clone().or(exact_tag),
+        version_tag: resolved_tag,
+    })
 }
 
-fn resolve_dependency_path(name: &str, cache_dir: &Path) -> Result<(PathBuf, bool)> {
-    let vendor_path = std::env::current_dir()?.join("vendor").join(name);
-    if vendor_path.exists() {
-        Ok((vendor_path, true))
-    } else {
-        Ok((cache_dir.join(name), false))
+/// Highest tag matching every requirement; the locked tag while it still
+/// matches, so builds don't move until `cx update`
+fn resolve_version(
+    repo: &Repository,
+    node: &Node,
+    reqs: &[&VersionReq],
+    locked: Option<&PackageLock>,
+    progress: &mut DepProgress,
+) -> Result<(git2::Oid, String)> {
+    if let Some(entry) = locked
+        && let Some(tag) = &entry.tag
+        && parse_tag_version(tag).is_some_and(|version| matches_all(reqs, &version))
+        && let Ok(oid) = git2::Oid::from_str(&entry.rev)
+        && repo.find_object(oid, None).is_ok()
+    {
+        return Ok((oid, tag.clone()));
     }
-}
 
-fn open_or_clone_repo(
-    name: &str,
-    url: &str,
-    lib_path: &Path,
-    is_vendor: bool,
-) -> Result<Repository> {
-    if lib_path.exists() {
-        if is_vendor {
-            println!("   {} Using vendor: {}", "📦".blue(), name);
+    let tags = list_tags(repo, &node.name, progress);
+    let Some(tag) = highest_match(&tags, reqs) else {
+        let mut report = requirement_report(
+            &format!(
+                "No tag of '{}' ({}) satisfies every version requirement:",
+                node.name, node.url
+            ),
+            node,
+        );
+        let newest = newest_tags(&tags, 5);
+        if newest.is_empty() {
+            report.push_str("\n  The repository has no version tags");
         } else {
-            println!("   {} Using cached: {}", "⚡".green(), name);
-        }
-        return Repository::open(lib_path)
-            .with_context(|| format!("Failed to open cached dependency '{}'", name));
-    }
-
-    let pb = ProgressBar::new_spinner();
-    pb.set_style(
-        ProgressStyle::default_spinner()
-            .template("{spinner:.blue} {msg}")
-            .unwrap_or_else(|_| ProgressStyle::default_spinner())
-            .tick_chars("⣾⣽⣻⢿⡿⣟⣯⣷"),
-    );
-    pb.set_message(format!("Downloading {}...", name));
-    pb.enable_steady_tick(std::time::Duration::from_millis(100));
-
-    match Repository::clone(url, lib_path) {
-        Ok(repo) => {
-            pb.finish_with_message(format!("{} Downloaded {}", "✓".green(), name));
-            Ok(repo)
+            report.push_str(&format!("\n  Newest tags: {}", newest.join(", ")));
         }
-        Err(err) => {
-            pb.finish_with_message(format!("{} Failed {}", "x".red(), name));
-            Err(anyhow::anyhow!(
-                "Failed to clone dependency '{}': {}",
+        bail!("{}", report);
+    };
+
+    let refname = format!("refs/tags/{}", tag);
+    if repo.find_reference(&refname).is_err() {
+        let mut remote = repo.find_remote("origin")?;
+        remote
+            .fetch(&[format!("+{refname}:{refname}")], None, None)
+            .with_context(|| format!("Failed to fetch tag {} of '{}'", tag, node.name))?;
+    }
+    let commit = repo.find_reference(&refname)?.peel_to_commit()?;
+    Ok((commit.id(), tag.to_string()))
+}
+
+/// Tags on the remote, or the cached ones when offline or it can't be reached
+fn list_tags(repo: &Repository, name: &str, progress: &mut DepProgress) -> Vec<String> {
+    if !offline::is_offline() {
+        match remote_tags(repo) {
+            Ok(tags) => return tags,
+            Err(err) => progress.log(format!(
+                "{} Warning: could not list tags of '{}' ({}), using cached tags",
+                "!".yellow(),
                 name,
-                err
-            ))
+                err.message()
+            )),
         }
     }
+    repo.tag_names(None)
+        .map(|names| {
+            names
+                .iter()
+                .filter_map(|tag| tag.ok().flatten().map(String::from))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn remote_tags(repo: &Repository) -> Result<Vec<String>, git2::Error> {
+    let mut remote = repo.find_remote("origin")?;
+    remote.connect(git2::Direction::Fetch)?;
+    let mut tags: Vec<String> = remote
+        .list()?
+        .iter()
+        .filter_map(|head| head.name().strip_prefix("refs/tags/"))
+        .map(|tag| tag.trim_end_matches("^{}").to_string())
+        .collect();
+    tags.sort();
+    tags.dedup();
+    Ok(tags)
 }
 
-fn locked_commit_for(
-    lockfile: &crate::lock::LockFile,
+fn locked_entry_for<'a>(
+    lockfile: &'a LockFile,
     name: &str,
     url: &str,
     enforce_lock: bool,
-) -> Option<String> {
+) -> Option<&'a PackageLock> {
     if !enforce_lock {
         return None;
     }
-    lockfile
-        .get(name)
-        .filter(|entry| entry.git == url)
-        .map(|entry| entry.rev.clone())
+    lockfile.get(name).filter(|entry| entry.git == url)
 }
 
 fn select_checkout_target(
     repo: &Repository,
-    spec: &GitDependencySpec,
+    pin: Option<&Pin>,
     locked_commit: Option<&str>,
 ) -> Option<(git2::Oid, String)> {
-    if let Some(rev) = spec.rev.as_deref()
+    if let Some(Pin::Rev(rev)) = pin
         && let Ok(oid) = git2::Oid::from_str(rev)
         && repo.find_object(oid, None).is_ok()
     {
         return Some((oid, format!("commit {}", short_hash(rev))));
     }
 
-    if let Some(tag) = spec.tag.as_deref() {
+    if let Some(Pin::Tag(tag)) = pin {
         let refname = format!("refs/tags/{}", tag);
         if let Ok(reference) = repo.find_reference(&refname)
             && let Ok(commit) = reference.peel_to_commit()
@@ -620,7 +926,7 @@ fn select_checkout_target(
         }
     }
 
-    if let Some(branch) = spec.branch.as_deref()
+    if let Some(Pin::Branch(branch)) = pin
         && let Some(branch_oid) = find_branch_commit(repo, branch)
     {
         return Some((branch_oid, format!("branch {}", branch)));
@@ -657,37 +963,36 @@ fn short_hash(rev: &str) -> &str {
     if rev.len() > 7 { &rev[..7] } else { rev }
 }
 
-fn checkout_repo_target(repo: &Repository, oid: git2::Oid, checkout_msg: &str) -> Result<()> {
-    repo.set_head_detached(oid)?;
-    let obj = repo.find_object(oid, None)?;
-    let mut checkout_opts = git2::build::CheckoutBuilder::new();
-    checkout_opts.force();
-    repo.checkout_tree(&obj, Some(&mut checkout_opts))
-        .context(format!("Failed to checkout {}", checkout_msg))?;
-    println!("   {} Locked to {}", "📌".blue(), checkout_msg);
-    Ok(())
-}
-
-fn refresh_lockfile_entry(
-    repo: &Repository,
-    lockfile: &mut crate::lock::LockFile,
+fn maybe_build_dependency(
     name: &str,
-    url: &str,
-) {
-    if let Ok(head) = repo.head()
-        && let Ok(target) = head.peel_to_commit()
-    {
-        lockfile.insert(name.to_string(), url.to_string(), target.id().to_string());
-    }
-}
-
-fn maybe_build_dependency(name: &str, spec: &GitDependencySpec, lib_path: &Path) -> Result<()> {
+    spec: &GitDependencySpec,
+    checkout: &Checkout,
+    cache: &GitCache,
+    progress: &Progress,
+) -> Result<()> {
     let output_name = spec.output_file.as_deref().unwrap_or("");
+    if output_name.is_empty() && spec.build_script.is_none() {
+        return Ok(());
+    }
+    let lib_path = checkout.path.as_path();
+    // Another project may be building the same checkout
+    let _lock = match &checkout.rev {
+        Some(rev) => Some(cache.lock_checkout(name, rev)?),
+        None => None,
+    };
+    let mut progress = progress.start(format!("Building {}...", name));
     let prebuilt_success = if output_name.is_empty() {
         false
     } else {
-        try_download_prebuilt(name, &spec.url, spec.tag.as_deref(), lib_path, output_name)
-            .unwrap_or(false)
+        try_download_prebuilt(
+            name,
+            &spec.url,
+            checkout.tag.as_deref(),
+            lib_path,
+            output_name,
+            &mut progress,
+        )
+        .unwrap_or(false)
     };
 
     if prebuilt_success {
@@ -706,26 +1011,41 @@ fn maybe_build_dependency(name: &str, spec: &GitDependencySpec, lib_path: &Path)
         return Ok(());
     }
 
-    println!("   {} Building {}...", "🔨".yellow(), name);
-    let status = if cfg!(target_os = "windows") {
+    progress.log(format!("   {} Building {}...", "🔨".yellow(), name));
+    // Captured rather than inherited, so parallel builds don't interleave
+    let output = if cfg!(target_os = "windows") {
         Command::new("cmd")
             .args(["/C", cmd_str])
             .current_dir(lib_path)
-            .status()
+            .output()
     } else {
         Command::new("sh")
             .args(["-c", cmd_str])
             .current_dir(lib_path)
-            .status()
+            .output()
     };
 
-    match status {
-        Ok(s) if s.success() => Ok(()),
-        _ => {
-            println!("{} Build script failed for {}", "x".red(), name);
-            Ok(())
+    match output {
+        Ok(output) if output.status.success() => {}
+        Ok(output) => {
+            progress.log(format!("{} Build script failed for {}", "x".red(), name));
+            let stdout = String::from_utf8_lossy(&output.stdout);
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            let lines: Vec<&str> = stdout.lines().chain(stderr.lines()).collect();
+            for line in &lines[lines.len().saturating_sub(BUILD_LOG_TAIL)..] {
+                progress.log(format!("     {}", line));
+            }
+        }
+        Err(err) => {
+            progress.log(format!(
+                "{} Build script failed for {}: {}",
+                "x".red(),
+                name,
+                err
+            ));
         }
     }
+    Ok(())
 }
 
 fn register_include_paths(lib_path: &Path, state: &mut FetchState) {
@@ -747,8 +1067,10 @@ fn collect_module_files(lib_path: &Path, state: &mut FetchState) {
         return;
     };
 
-    for entry in entries.flatten() {
-        let path = entry.path();
+    // Sorted, since directory order differs between machines
+    let mut paths: Vec<PathBuf> = entries.flatten().map(|entry| entry.path()).collect();
+    paths.sort();
+    for path in paths {
         if let Some(ext) = path.extension().and_then(|e| e.to_str())
             && ["cppm", "ixx", "mpp"].contains(&ext)
             && state.module_seen.insert(path.clone())
@@ -768,7 +1090,7 @@ fn collect_link_outputs(lib_path: &Path, output_file: Option<&str>, link_flags:
         if full_lib_path.exists() {
             link_flags.push(full_lib_path.to_string_lossy().to_string());
         } else {
-            println!(
+            status!(
                 "{} Warning: Output file not found: {}",
                 "!".yellow(),
                 full_lib_path.display()
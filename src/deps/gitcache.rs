@@ -0,0 +1,416 @@
+//! Global git cache shared by every project on the machine.
+//!
+//! ```text
+//! ~/.cx/cache/git/
+//! ├── db/<name>-<hash>/             bare repository per URL
+//! └── checkouts/<name>-<shortrev>/  files of one commit
+//! ```
+//!
+//! Each repository is cloned once into `db/` and fetched into from then on;
+//! every commit a project uses gets its own checkout, so two projects pinning
+//! different versions never flip a shared working tree. A checkout is written
+//! to a temporary directory and renamed into place, and `<dir>.lock` files
+//! serialize processes working on the same database or checkout.
+
+use super::graph::normalize_url;
+use super::offline;
+use anyhow::{Context, Result, bail};
+use colored::*;
+use git2::{Oid, Repository};
+use sha2::{Digest, Sha256};
+use std::collections::HashSet;
+use std::fs::{self, File, TryLockError};
+use std::path::{Path, PathBuf};
+
+/// Hex digits of the commit in a checkout's directory name
+const SHORT_REV_LEN: usize = 12;
+
+/// Refs mirrored into a database on every fetch
+const REFSPECS: [&str; 2] = ["+refs/heads/*:refs/heads/*", "+refs/tags/*:refs/tags/*"];
+
+pub struct GitCache {
+    root: PathBuf,
+}
+
+/// Exclusive lock on a cache entry, released on drop
+pub struct CacheLock {
+    _file: File,
+}
+
+impl GitCache {
+    /// The cache at `~/.cx/cache/git`
+    pub fn new() -> Result<Self> {
+        let home_dir = dirs::home_dir().context("Could not find home directory")?;
+        Ok(Self::at(home_dir.join(".cx").join("cache").join("git")))
+    }
+
+    pub fn at(root: PathBuf) -> Self {
+        Self { root }
+    }
+
+    pub fn db_path(&self, name: &str, url: &str) -> PathBuf {
+        let digest = Sha256::digest(normalize_url(url).as_bytes());
+        let hash: String = digest[..8].iter().map(|b| format!("{b:02x}")).collect();
+        self.root.join("db").join(format!("{name}-{hash}"))
+    }
+
+    pub fn checkout_path(&self, name: &str, rev: &str) -> PathBuf {
+        let short = &rev[..rev.len().min(SHORT_REV_LEN)];
+        self.root.join("checkouts").join(format!("{name}-{short}"))
+    }
+
+    /// Lock the database of `url`; hold it while reading or fetching refs
+    pub fn lock_db(&self, name: &str, url: &str) -> Result<CacheLock> {
+        lock(&self.db_path(name, url))
+    }
+
+    /// Lock the checkout of `rev`, e.g. while a build script writes into it
+    pub fn lock_checkout(&self, name: &str, rev: &str) -> Result<CacheLock> {
+        lock(&self.checkout_path(name, rev))
+    }
+
+    /// Whether `url` has been cloned already
+    pub fn has_db(&self, name: &str, url: &str) -> bool {
+        self.db_path(name, url).exists()
+    }
+
+    /// Open the database of `url`, cloning it on first use
+    pub fn open_db(&self, name: &str, url: &str) -> Result<Repository> {
+        let path = self.db_path(name, url);
+        if path.exists() {
+            return Repository::open_bare(&path)
+                .with_context(|| format!("Failed to open cached dependency '{}'", name));
+        }
+        if offline::is_offline() {
+            bail!(
+                "'{}' is not in the dependency cache and cx is offline\n  \
+                 Run 'cx fetch' while online, or copy it into vendor/ with 'cx vendor'",
+                name
+            );
+        }
+
+        // Clone into a temporary directory so an interrupted clone never
+        // looks like a database
+        let partial = sibling(&path, ".partial");
+        let _ = fs::remove_dir_all(&partial);
+        clone_db(url, &partial, &path).map_err(|err| {
+            let _ = fs::remove_dir_all(&partial);
+            anyhow::anyhow!("Failed to clone dependency '{}': {}", name, err)
+        })
+    }
+
+    /// Fetch the latest branches and tags of `url` into its database
+    pub fn update(&self, name: &str, url: &str) -> Result<()> {
+        let _lock = self.lock_db(name, url)?;
+        let repo = Repository::open_bare(self.db_path(name, url))?;
+        fetch(&repo).with_context(|| format!("Failed to fetch '{}'", name))
+    }
+
+    /// Names of the finished checkouts, sorted
+    pub fn checkouts(&self) -> Vec<String> {
+        let mut names: Vec<String> = fs::read_dir(self.root.join("checkouts"))
+            .into_iter()
+            .flatten()
+            .filter_map(|e| e.ok())
+            .filter(|e| e.path().is_dir())
+            .map(|e| e.file_name().to_string_lossy().to_string())
+            .filter(|name| !name.contains(".partial"))
+            .collect();
+        names.sort();
+        names
+    }
+
+    /// Whether there is nothing to prune: no databases, checkouts or
+    /// pre-database working trees
+    pub fn is_empty(&self) -> bool {
+        ["db", "checkouts"]
+            .iter()
+            .all(|kind| list_dir(&self.root.join(kind)).is_empty())
+            && self.legacy_trees().is_empty()
+    }
+
+    /// Remove the databases, checkouts and lock files of packages not in
+    /// `keep`, and every working tree left at `~/.cx/cache/<name>` by older
+    /// versions of cx; returns the removed entries.
+    ///
+    /// The package's databases stay locked until its checkouts are gone:
+    /// checkouts are only written under that lock, so no half-written one
+    /// is removed. `.partial*` staging directories are left to their owner.
+    pub fn prune(&self, keep: &HashSet<&str>) -> Result<Vec<String>> {
+        let mut removed = Vec::new();
+        let dbs = self.unused_entries("db", keep);
+        let db_locks = dbs
+            .iter()
+            .map(|path| lock(path))
+            .collect::<Result<Vec<_>>>()?;
+        for path in self.unused_entries("checkouts", keep) {
+            let checkout_lock = lock(&path)?;
+            remove_entry(&path, checkout_lock, &mut removed)?;
+        }
+        for (path, db_lock) in dbs.iter().zip(db_locks) {
+            remove_entry(path, db_lock, &mut removed)?;
+        }
+
+        for path in self.legacy_trees() {
+            fs::remove_dir_all(&path)
+                .with_context(|| format!("Failed to remove {}", path.display()))?;
+            removed.push(file_name(&path));
+        }
+        Ok(removed)
+    }
+
+    /// Entries of `db/` or `checkouts/` belonging to packages not in `keep`,
+    /// including lock files whose directory is already gone
+    fn unused_entries(&self, kind: &str, keep: &HashSet<&str>) -> Vec<PathBuf> {
+        let mut entries: Vec<PathBuf> = list_dir(&self.root.join(kind))
+            .into_iter()
+            .filter_map(|path| {
+                let name = file_name(&path);
+                if name.contains(".partial") {
+                    return None;
+                }
+                match name.strip_suffix(".lock") {
+                    Some(entry) => Some(path.with_file_name(entry)),
+                    None => path.is_dir().then_some(path),
+                }
+            })
+            .filter(|path| !keep.contains(entry_name(&file_name(path))))
+            .collect();
+        entries.sort();
+        entries.dedup();
+        entries
+    }
+
+    /// Per-package working trees from before the git cache had databases
+    fn legacy_trees(&self) -> Vec<PathBuf> {
+        let Some(parent) = self.root.parent() else {
+            return Vec::new();
+        };
+        list_dir(parent)
+            .into_iter()
+            .filter(|path| path.is_dir() && *path != self.root)
+            .collect()
+    }
+
+    /// Directory holding the files of `oid`, extracted from `repo` on first
+    /// use; call with the database lock held
+    pub fn checkout(&self, repo: &Repository, name: &str, oid: Oid) -> Result<PathBuf> {
+        let rev = oid.to_string();
+        let path = self.checkout_path(name, &rev);
+        if path.exists() {
+            return Ok(path);
+        }
+
+        let staging = sibling(&path, &format!(".partial-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&staging);
+        fs::create_dir_all(&staging)?;
+        let tree = repo.find_commit(oid)?.tree()?;
+        let mut checkout_opts = git2::build::CheckoutBuilder::new();
+        checkout_opts
+            .force()
+            .update_index(false)
+            .target_dir(&staging);
+        repo.checkout_tree(tree.as_object(), Some(&mut checkout_opts))
+            .with_context(|| format!("Failed to check out {} of '{}'", rev, name))?;
+        if let Err(err) = fs::rename(&staging, &path) {
+            let _ = fs::remove_dir_all(&staging);
+            if !path.exists() {
+                return Err(err.into());
+            }
+        }
+        Ok(path)
+    }
+}
+
+fn clone_db(url: &str, partial: &Path, path: &Path) -> Result<Repository> {
+    let repo = Repository::init_bare(partial)?;
+    repo.remote("origin", url)?;
+    fetch(&repo)?;
+    drop(repo);
+    fs::rename(partial, path)?;
+    Ok(Repository::open_bare(path)?)
+}
+
+/// Mirror the remote's branches and tags into a database and follow its
+/// default branch; call with the database lock held
+pub fn fetch(repo: &Repository) -> Result<()> {
+    offline::ensure_online("Fetching a git dependency")?;
+    let mut remote = repo.find_remote("origin")?;
+    remote.fetch(&REFSPECS, None, None)?;
+    if let Ok(head) = remote.default_branch()
+        && let Ok(head) = head.as_str()
+    {
+        repo.set_head(head)?;
+    }
+    Ok(())
+}
+
+/// Block until `<path>.lock` is ours
+fn lock(path: &Path) -> Result<CacheLock> {
+    let lock_path = sibling(path, ".lock");
+    if let Some(parent) = lock_path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let file = File::options()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(&lock_path)
+        .with_context(|| format!("Failed to open {}", lock_path.display()))?;
+    match file.try_lock() {
+        Ok(()) => {}
+        Err(TryLockError::WouldBlock) => {
+            status!(
+                "   {} Waiting for another cx process ({})",
+                "⏳".yellow(),
+                lock_path.display()
+            );
+            file.lock()?;
+        }
+        Err(TryLockError::Error(err)) => return Err(err.into()),
+    }
+    Ok(CacheLock { _file: file })
+}
+
+/// Remove a cache entry and then its lock file, while still holding the lock
+fn remove_entry(path: &Path, entry_lock: CacheLock, removed: &mut Vec<String>) -> Result<()> {
+    if path.exists() {
+        fs::remove_dir_all(path).with_context(|| format!("Failed to remove {}", path.display()))?;
+        removed.push(file_name(path));
+    }
+    let _ = fs::remove_file(sibling(path, ".lock"));
+    drop(entry_lock);
+    Ok(())
+}
+
+fn list_dir(dir: &Path) -> Vec<PathBuf> {
+    fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .collect()
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .to_string()
+}
+
+/// `path` with `suffix` appended to its file name
+fn sibling(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(suffix);
+    path.with_file_name(name)
+}
+
+/// Package name a cache entry belongs to: `fmt-0c9fce2d1f7a` is `fmt`
+fn entry_name(dir_name: &str) -> &str {
+    dir_name.rsplit_once('-').map_or(dir_name, |(name, _)| name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn commit_file(repo: &Repository, path: &str, content: &str) -> Oid {
+        let workdir = repo.workdir().unwrap();
+        fs::write(workdir.join(path), content).unwrap();
+        let mut index = repo.index().unwrap();
+        index.add_path(Path::new(path)).unwrap();
+        let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+        let sig = git2::Signature::now("cx", "cx@example.com").unwrap();
+        let parent = repo.head().ok().and_then(|h| h.peel_to_commit().ok());
+        let parents: Vec<&git2::Commit> = parent.iter().collect();
+        repo.commit(Some("HEAD"), &sig, &sig, "commit", &tree, &parents)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_db_path_is_per_url() {
+        let cache = GitCache::at(PathBuf::from("/cache"));
+        let fmt = cache.db_path("fmt", "https://github.com/fmtlib/fmt");
+        assert_eq!(
+            fmt,
+            cache.db_path("fmt", "https://github.com/fmtlib/fmt.git/")
+        );
+        assert_ne!(fmt, cache.db_path("fmt", "https://github.com/someone/fmt"));
+        assert_eq!(
+            entry_name(&fmt.file_name().unwrap().to_string_lossy()),
+            "fmt"
+        );
+        assert_eq!(
+            cache.checkout_path("fmt", "0c9fce2ffefecfdce794e1859584e25877b7b592"),
+            PathBuf::from("/cache/checkouts/fmt-0c9fce2ffefe")
+        );
+    }
+
+    #[test]
+    fn test_checkouts_are_per_commit() {
+        let dir = tempfile::tempdir().unwrap();
+        let upstream = Repository::init(dir.path().join("upstream")).unwrap();
+        let first = commit_file(&upstream, "lib.hpp", "#define LIB 1\n");
+        let second = commit_file(&upstream, "lib.hpp", "#define LIB 2\n");
+
+        let cache = GitCache::at(dir.path().join("cache").join("git"));
+        let url = upstream
+            .path()
+            .parent()
+            .unwrap()
+            .to_string_lossy()
+            .to_string();
+        let lock = cache.lock_db("lib", &url).unwrap();
+        let db = cache.open_db("lib", &url).unwrap();
+        assert!(db.is_bare());
+        assert_eq!(db.head().unwrap().peel_to_commit().unwrap().id(), second);
+
+        let old = cache.checkout(&db, "lib", first).unwrap();
+        let new = cache.checkout(&db, "lib", second).unwrap();
+        assert_ne!(old, new);
+        assert_eq!(
+            fs::read_to_string(old.join("lib.hpp")).unwrap(),
+            "#define LIB 1\n"
+        );
+        assert_eq!(
+            fs::read_to_string(new.join("lib.hpp")).unwrap(),
+            "#define LIB 2\n"
+        );
+        assert!(!old.join(".git").exists());
+        assert_eq!(cache.checkout(&db, "lib", first).unwrap(), old);
+        assert_eq!(cache.checkouts().len(), 2);
+
+        drop((db, lock));
+        assert!(cache.prune(&HashSet::from(["lib"])).unwrap().is_empty());
+        assert_eq!(cache.prune(&HashSet::new()).unwrap().len(), 3);
+        assert!(cache.checkouts().is_empty());
+        assert!(cache.is_empty());
+        assert!(list_dir(&dir.path().join("cache").join("git").join("db")).is_empty());
+    }
+
+    #[test]
+    fn test_prune_cleans_orphans_and_legacy_trees() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("git");
+        let cache = GitCache::at(root.clone());
+        let db = root.join("db");
+        let checkouts = root.join("checkouts");
+        fs::create_dir_all(db.join("fmt-0123456789abcdef")).unwrap();
+        fs::write(db.join("fmt-0123456789abcdef.lock"), "").unwrap();
+        fs::write(db.join("json-fedcba9876543210.lock"), "").unwrap();
+        fs::create_dir_all(checkouts.join("fmt-0c9fce2ffefe.partial-42")).unwrap();
+        fs::create_dir_all(dir.path().join("raylib")).unwrap();
+        // Nothing checked out, but the database and legacy tree are still there
+        assert!(cache.checkouts().is_empty());
+        assert!(!cache.is_empty());
+
+        let mut removed = cache.prune(&HashSet::new()).unwrap();
+        removed.sort();
+        assert_eq!(removed, ["fmt-0123456789abcdef", "raylib"]);
+        assert!(list_dir(&db).is_empty());
+        assert!(checkouts.join("fmt-0c9fce2ffefe.partial-42").exists());
+        assert!(!dir.path().join("raylib").exists());
+        assert!(root.exists());
+    }
+}
@@ -0,0 +1,412 @@
+//! Transitive dependency graph.
+//!
+//! A git dependency with its own `cx.toml` brings its `[dependencies]` along.
+//! Nodes are deduplicated by repository URL, so two packages asking for the
+//! same repository share one checkout. Two different pins (`tag`, `branch`
+//! or `rev`) on one repository are a conflict, and so is a pin next to a
+//! `version` requirement unless it is a tag within the range. Several
+//! `version` requirements are fine here; fetching picks the highest tag
+//! matching all of them. An unpinned requirement accepts whatever the
+//! others select.
+
+use super::version::{matches_all, parse_tag_version};
+use anyhow::{Result, bail};
+use semver::VersionReq;
+use std::collections::HashMap;
+use std::fmt;
+
+/// Who stands for the project itself in requirement reports
+pub const ROOT: &str = "cx.toml";
+
+/// Revision a requirement asks for
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Pin {
+    Tag(String),
+    Branch(String),
+    Rev(String),
+    /// Highest tag matching a semver requirement
+    Version(VersionReq),
+}
+
+impl fmt::Display for Pin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Tag(tag) => write!(f, "tag {tag}"),
+            Self::Branch(branch) => write!(f, "branch {branch}"),
+            Self::Rev(rev) => write!(f, "rev {rev}"),
+            Self::Version(req) => write!(f, "version {req}"),
+        }
+    }
+}
+
+/// One package's request for a node
+#[derive(Debug, Clone)]
+pub struct Requirement {
+    pub by: String,
+    pub pin: Option<Pin>,
+}
+
+#[derive(Debug)]
+pub struct Node {
+    pub name: String,
+    pub url: String,
+    pub requirements: Vec<Requirement>,
+    /// Indices of the nodes this one depends on
+    pub dependencies: Vec<usize>,
+}
+
+impl Node {
+    /// The `tag`, `branch` or `rev` every pinning requirement agrees on
+    pub fn exact_pin(&self) -> Option<&Pin> {
+        self.requirements
+            .iter()
+            .filter_map(|r| r.pin.as_ref())
+            .find(|pin| !matches!(pin, Pin::Version(_)))
+    }
+
+    /// Every distinct `version` requirement
+    pub fn version_reqs(&self) -> Vec<&VersionReq> {
+        let mut reqs: Vec<&VersionReq> = Vec::new();
+        for requirement in &self.requirements {
+            if let Some(Pin::Version(req)) = &requirement.pin
+                && !reqs.contains(&req)
+            {
+                reqs.push(req);
+            }
+        }
+        reqs
+    }
+
+    /// Whether one revision can satisfy every requirement
+    fn is_consistent(&self) -> bool {
+        let exact = self.exact_pin();
+        let agree = self
+            .requirements
+            .iter()
+            .filter_map(|r| r.pin.as_ref())
+            .filter(|pin| !matches!(pin, Pin::Version(_)))
+            .all(|pin| Some(pin) == exact);
+        let reqs = self.version_reqs();
+        agree
+            && match exact {
+                None => true,
+                _ if reqs.is_empty() => true,
+                Some(Pin::Tag(tag)) => {
+                    parse_tag_version(tag).is_some_and(|version| matches_all(&reqs, &version))
+                }
+                Some(_) => false,
+            }
+    }
+
+    /// What fetching checks out; it changes when a requirement narrows it
+    fn selection(&self) -> (Option<Pin>, Vec<VersionReq>) {
+        (
+            self.exact_pin().cloned(),
+            self.version_reqs().into_iter().cloned().collect(),
+        )
+    }
+}
+
+/// What `require` did with a requirement
+#[derive(Debug, PartialEq, Eq)]
+pub enum Added {
+    /// First requirement for this repository
+    New(usize),
+    /// Known repository whose selected revision may change
+    Pinned(usize),
+    /// Known repository, nothing changes
+    Existing(usize),
+}
+
+impl Added {
+    pub fn index(&self) -> usize {
+        match self {
+            Self::New(idx) | Self::Pinned(idx) | Self::Existing(idx) => *idx,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct DependencyGraph {
+    pub nodes: Vec<Node>,
+    by_url: HashMap<String, usize>,
+    by_name: HashMap<String, usize>,
+}
+
+impl DependencyGraph {
+    /// Record that `by` requires `url` (as `name`) at `pin`
+    pub fn require(&mut self, name: &str, url: &str, by: &str, pin: Option<Pin>) -> Result<Added> {
+        let key = normalize_url(url);
+        let requirement = Requirement {
+            by: by.to_string(),
+            pin,
+        };
+
+        let Some(&idx) = self.by_url.get(&key) else {
+            if let Some(&other) = self.by_name.get(name) {
+                bail!(
+                    "Dependency name '{}' refers to two repositories:\n  {}\n  {} (required by {})",
+                    name,
+                    self.nodes[other].url,
+                    url,
+                    by
+                );
+            }
+            let idx = self.nodes.len();
+            self.nodes.push(Node {
+                name: name.to_string(),
+                url: url.to_string(),
+                requirements: vec![requirement],
+                dependencies: Vec::new(),
+            });
+            self.by_url.insert(key, idx);
+            self.by_name.insert(name.to_string(), idx);
+            return Ok(Added::New(idx));
+        };
+
+        let node = &mut self.nodes[idx];
+        let previous = node.selection();
+        node.requirements.push(requirement);
+        if !node.is_consistent() {
+            bail!("{}", conflict_report(node));
+        }
+        Ok(if node.selection() != previous {
+            Added::Pinned(idx)
+        } else {
+            Added::Existing(idx)
+        })
+    }
+
+    pub fn add_edge(&mut self, from: usize, to: usize) {
+        if from != to && !self.nodes[from].dependencies.contains(&to) {
+            self.nodes[from].dependencies.push(to);
+        }
+    }
+
+    /// Forget `idx`'s edges before its manifest is read again
+    pub fn clear_edges(&mut self, idx: usize) {
+        self.nodes[idx].dependencies.clear();
+    }
+
+    /// Every node, dependents before their dependencies (the order static
+    /// libraries link in); cycles are an error
+    pub fn order(&self) -> Result<Vec<usize>> {
+        #[derive(Clone, Copy, PartialEq)]
+        enum Mark {
+            New,
+            Visiting,
+            Done,
+        }
+
+        fn visit(
+            graph: &DependencyGraph,
+            idx: usize,
+            marks: &mut [Mark],
+            stack: &mut Vec<usize>,
+            post: &mut Vec<usize>,
+        ) -> Result<()> {
+            match marks[idx] {
+                Mark::Done => return Ok(()),
+                Mark::Visiting => {
+                    let start = stack.iter().position(|&i| i == idx).unwrap_or(0);
+                    let cycle: Vec<&str> = stack[start..]
+                        .iter()
+                        .chain(std::iter::once(&idx))
+                        .map(|&i| graph.nodes[i].name.as_str())
+                        .collect();
+                    bail!("Dependency cycle: {}", cycle.join(" -> "));
+                }
+                Mark::New => {}
+            }
+            marks[idx] = Mark::Visiting;
+            stack.push(idx);
+            for &dep in &graph.nodes[idx].dependencies {
+                visit(graph, dep, marks, stack, post)?;
+            }
+            stack.pop();
+            marks[idx] = Mark::Done;
+            post.push(idx);
+            Ok(())
+        }
+
+        let mut marks = vec![Mark::New; self.nodes.len()];
+        let mut post = Vec::with_capacity(self.nodes.len());
+        for idx in 0..self.nodes.len() {
+            visit(self, idx, &mut marks, &mut Vec::new(), &mut post)?;
+        }
+        post.reverse();
+        Ok(post)
+    }
+
+    /// Every node grouped so that a node's dependencies are all in earlier
+    /// groups; each group can be built side by side once those before it are
+    pub fn levels(&self) -> Result<Vec<Vec<usize>>> {
+        let mut depth = vec![0; self.nodes.len()];
+        let mut levels: Vec<Vec<usize>> = Vec::new();
+        for idx in self.order()?.into_iter().rev() {
+            let level = self.nodes[idx]
+                .dependencies
+                .iter()
+                .map(|&dep| depth[dep] + 1)
+                .max()
+                .unwrap_or(0);
+            depth[idx] = level;
+            if levels.len() <= level {
+                levels.resize_with(level + 1, Vec::new);
+            }
+            levels[level].push(idx);
+        }
+        Ok(levels)
+    }
+}
+
+fn conflict_report(node: &Node) -> String {
+    requirement_report(
+        &format!("Conflicting pins for '{}' ({}):", node.name, node.url),
+        node,
+    )
+}
+
+/// `headline` followed by who requires what of `node`
+pub fn requirement_report(headline: &str, node: &Node) -> String {
+    let mut report = headline.to_string();
+    for requirement in &node.requirements {
+        let wanted = requirement
+            .pin
+            .as_ref()
+            .map(ToString::to_string)
+            .unwrap_or_else(|| "any revision".to_string());
+        report.push_str(&format!("\n  {} requires {}", requirement.by, wanted));
+    }
+    report
+}
+
+/// Repository identity: case-insensitive, without a trailing `/` or `.git`
+pub fn normalize_url(url: &str) -> String {
+    let url = url.trim().trim_end_matches('/').to_lowercase();
+    url.strip_suffix(".git").unwrap_or(&url).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tag(t: &str) -> Option<Pin> {
+        Some(Pin::Tag(t.to_string()))
+    }
+
+    #[test]
+    fn test_nodes_dedupe_by_url() {
+        let mut graph = DependencyGraph::default();
+        let fmt = graph
+            .require("fmt", "https://github.com/fmtlib/fmt", ROOT, None)
+            .unwrap();
+        assert_eq!(fmt, Added::New(0));
+        let again = graph
+            .require(
+                "fmtlib",
+                "https://github.com/fmtlib/fmt.git/",
+                "spdlog",
+                tag("10.2.1"),
+            )
+            .unwrap();
+        assert_eq!(again, Added::Pinned(0));
+        assert_eq!(graph.nodes.len(), 1);
+        assert_eq!(graph.nodes[0].exact_pin(), tag("10.2.1").as_ref());
+        let same = graph
+            .require("fmt", "https://github.com/fmtlib/fmt", "app", tag("10.2.1"))
+            .unwrap();
+        assert_eq!(same, Added::Existing(0));
+    }
+
+    #[test]
+    fn test_conflicting_pins_report_requirers() {
+        let mut graph = DependencyGraph::default();
+        graph
+            .require("fmt", "https://github.com/fmtlib/fmt", ROOT, tag("10.2.1"))
+            .unwrap();
+        let err = graph
+            .require(
+                "fmt",
+                "https://github.com/fmtlib/fmt",
+                "spdlog",
+                tag("9.1.0"),
+            )
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("Conflicting pins for 'fmt'"));
+        assert!(err.contains("cx.toml requires tag 10.2.1"));
+        assert!(err.contains("spdlog requires tag 9.1.0"));
+
+        let err = graph
+            .require("fmt", "https://github.com/someone/fmt", "log", None)
+            .unwrap_err();
+        assert!(err.to_string().contains("refers to two repositories"));
+    }
+
+    #[test]
+    fn test_version_requirements() {
+        let version = |req: &str| Some(Pin::Version(VersionReq::parse(req).unwrap()));
+        let mut graph = DependencyGraph::default();
+        let url = "https://github.com/nlohmann/json";
+        assert_eq!(
+            graph.require("json", url, ROOT, version("^3.11")).unwrap(),
+            Added::New(0)
+        );
+        // Another range narrows the selection; the same one changes nothing
+        assert_eq!(
+            graph
+                .require("json", url, "a", version(">=3.11.2"))
+                .unwrap(),
+            Added::Pinned(0)
+        );
+        assert_eq!(
+            graph.require("json", url, "b", version("^3.11")).unwrap(),
+            Added::Existing(0)
+        );
+        assert_eq!(graph.nodes[0].version_reqs().len(), 2);
+        // A tag inside the range is compatible, a branch never is
+        assert_eq!(
+            graph.require("json", url, "c", tag("v3.11.3")).unwrap(),
+            Added::Pinned(0)
+        );
+        let err = graph
+            .require("json", url, "d", Some(Pin::Branch("develop".to_string())))
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("cx.toml requires version ^3.11"));
+        assert!(err.contains("d requires branch develop"));
+
+        let mut graph = DependencyGraph::default();
+        graph.require("json", url, ROOT, version("^3.11")).unwrap();
+        assert!(graph.require("json", url, "e", tag("v2.1.0")).is_err());
+    }
+
+    #[test]
+    fn test_order_puts_dependents_first() {
+        let mut graph = DependencyGraph::default();
+        let app = graph
+            .require("spdlog", "u/spdlog", ROOT, None)
+            .unwrap()
+            .index();
+        let fmt = graph
+            .require("fmt", "u/fmt", "spdlog", None)
+            .unwrap()
+            .index();
+        let json = graph.require("json", "u/json", ROOT, None).unwrap().index();
+        graph.add_edge(app, fmt);
+        graph.add_edge(json, fmt);
+        let order = graph.order().unwrap();
+        let pos = |idx| order.iter().position(|&i| i == idx).unwrap();
+        assert!(pos(app) < pos(fmt));
+        assert!(pos(json) < pos(fmt));
+
+        let levels = graph.levels().unwrap();
+        assert_eq!(levels, vec![vec![fmt], vec![app, json]]);
+
+        graph.add_edge(fmt, app);
+        let err = graph.order().unwrap_err().to_string();
+        assert!(err.contains("Dependency cycle"));
+        assert!(err.contains("spdlog -> fmt -> spdlog"));
+    }
+}
@@ -7,15 +7,16 @@
 //! - `cx add <lib>` - Add a dependency
 //! - `cx remove <lib>` - Remove a dependency
 //! - `cx update` - Update all dependencies to latest
+//! - `cx fetch` - Download dependencies for later offline builds
 
+use crate::build::features::{FeatureSelection, Features};
 use crate::config::Dependency;
-use anyhow::{Context, Result};
+use anyhow::Result;
 use colored::*;
 
 use std::collections::HashMap;
 use std::fs;
 use std::path::Path;
-use std::process::Command;
 
 // Needed imports for add/remove/update logic
 
@@ -77,8 +78,10 @@ pub fn add_dependency(
             branch,
             tag,
             rev,
+            version: None,
             build: None,
             output: None,
+            optional: None,
         }
     };
 
@@ -145,57 +148,33 @@ pub fn update_dependencies() -> Result<()> {
     let config_str = fs::read_to_string("cx.toml")?;
     let config: crate::config::CxConfig = toml::from_str(&config_str)?;
 
-    let home_dir = dirs::home_dir().context("Could not find home directory")?;
-    let cache_dir = home_dir.join(".cx").join("cache");
+    let cache = super::gitcache::GitCache::new()?;
 
     if let Some(deps) = config.dependencies {
-        for (name, dep_data) in &deps {
-            let is_git = matches!(
-                dep_data,
-                crate::config::Dependency::Simple(_)
-                    | crate::config::Dependency::Complex { git: Some(_), .. }
-            );
-
-            if is_git {
-                let lib_path = cache_dir.join(name);
-                if lib_path.exists() {
-                    print!("   Updating {} ... ", name);
-
-                    if let Ok(repo) = git2::Repository::open(&lib_path) {
-                        // Fetch origin
-                        let mut remote = repo.find_remote("origin")?;
-                        remote.fetch(&["HEAD"], None, None)?;
-
-                        // Force checking out correct HEAD
-                        // Note: For 'update', we typically want to pull latest.
-                        // Use fetch + reset --hard to ensure we match upstream exactly, discarding local changes (it's a cache)
-                        let command = "git fetch origin && git reset --hard origin/HEAD";
-                        let status = if cfg!(target_os = "windows") {
-                            Command::new("cmd")
-                                .args(["/C", command])
-                                .current_dir(&lib_path)
-                                .output()
-                        } else {
-                            Command::new("sh")
-                                .args(["-c", command])
-                                .current_dir(&lib_path)
-                                .output()
-                        };
-
-                        if let Ok(out) = status {
-                            if out.status.success() {
-                                println!("{}", "✓".green());
-                            } else {
-                                let err = String::from_utf8_lossy(&out.stderr);
-                                println!("{} (git update failed: {})", "x".red(), err.trim());
-                            }
-                        } else {
-                            println!("{}", "Error executing git".red());
-                        }
-                    } else {
-                        println!("{}", "Not a valid git repo".yellow());
-                    }
-                }
+        // Git dependencies and, through cx.lock, the ones they require
+        let direct: Vec<&str> = deps
+            .iter()
+            .filter(|(_, dep_data)| {
+                matches!(
+                    dep_data,
+                    crate::config::Dependency::Simple(_)
+                        | crate::config::Dependency::Complex { git: Some(_), .. }
+                )
+            })
+            .map(|(name, _)| name.as_str())
+            .collect();
+        let lockfile = crate::lock::LockFile::load().unwrap_or_default();
+        for name in lockfile.reachable(direct) {
+            let Some(entry) = lockfile.get(name) else {
+                continue;
+            };
+            if !cache.db_path(name, &entry.git).exists() {
+                continue;
+            }
+            print!("   Updating {} ... ", name);
+            match cache.update(name, &entry.git) {
+                Ok(()) => println!("{}", "✓".green()),
+                Err(err) => println!("{} ({:#})", "x".red(), err),
             }
         }
 
@@ -213,3 +192,20 @@ pub fn update_dependencies() -> Result<()> {
     println!("{} Dependencies updated.", "✓".green());
     Ok(())
 }
+
+/// Fetch and build every dependency the selected features use, so later
+/// `--offline` builds find them in the cache
+pub fn fetch_project(selection: &FeatureSelection) -> Result<()> {
+    let mut config = crate::build::load_config()?;
+    let features = Features::resolve(&config, selection)?;
+    features.apply(&mut config);
+
+    let deps = config.dependencies.unwrap_or_default();
+    if deps.is_empty() {
+        println!("{} No dependencies to fetch.", "✓".green());
+        return Ok(());
+    }
+    super::fetch::fetch_dependencies(&deps)?;
+    println!("{} Dependencies fetched.", "✓".green());
+    Ok(())
+}
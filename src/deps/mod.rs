@@ -2,7 +2,10 @@
 //!
 //! This module handles all dependency-related operations including:
 //!
-//! - **Fetching**: Download dependencies from Git repositories
+//! - **Fetching**: Download dependencies from Git repositories into a cache
+//!   shared by all projects, one checkout per commit
+//! - **Resolution**: Follow dependencies that ship their own `cx.toml` and
+//!   pick the highest tag matching each `version` requirement
 //! - **Management**: Add, remove, and update dependencies in `cx.toml`
 //! - **Vendoring**: Copy dependencies locally for offline builds
 //!
@@ -11,14 +14,22 @@
 //! - `cx add <lib>` - Add a library from registry or Git URL
 //! - `cx remove <lib>` - Remove a dependency
 //! - `cx update` - Update all dependencies to latest versions
+//! - `cx fetch` - Download dependencies so `--offline` / `--frozen` builds work
 //! - `cx vendor` - Copy dependencies into `vendor/` directory
 
 mod fetch;
+mod gitcache;
+mod graph;
 mod manage;
+mod offline;
+mod progress;
 mod vendor;
+mod version;
 
 pub use fetch::{
     FetchOptions, FetchResult, ModuleFile, fetch_dependencies, fetch_dependencies_with_options,
 };
-pub use manage::{add_dependency, remove_dependency, update_dependencies};
+pub use gitcache::GitCache;
+pub use manage::{add_dependency, fetch_project, remove_dependency, update_dependencies};
+pub use offline::{NetworkMode, is_offline, network_mode, set_network_mode};
 pub use vendor::vendor_dependencies;
@@ -0,0 +1,48 @@
+//! `--offline` and `--frozen`, set once by the CLI.
+//!
+//! Offline, nothing touches the network: git dependencies come from the
+//! cache or `vendor/`, version tags are read from the cache and the
+//! registry from its cached copy. Frozen is offline plus a `cx.lock` that
+//! must already match `cx.toml` and is never rewritten. `CX_OFFLINE=1`
+//! turns offline mode on without the flag.
+
+use anyhow::{Result, bail};
+use std::sync::atomic::{AtomicU8, Ordering};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum NetworkMode {
+    #[default]
+    Online,
+    Offline,
+    Frozen,
+}
+
+static MODE: AtomicU8 = AtomicU8::new(NetworkMode::Online as u8);
+
+pub fn set_network_mode(mode: NetworkMode) {
+    MODE.store(mode as u8, Ordering::Relaxed);
+}
+
+pub fn network_mode() -> NetworkMode {
+    match MODE.load(Ordering::Relaxed) {
+        2 => NetworkMode::Frozen,
+        1 => NetworkMode::Offline,
+        _ if std::env::var("CX_OFFLINE").is_ok_and(|v| v != "0") => NetworkMode::Offline,
+        _ => NetworkMode::Online,
+    }
+}
+
+pub fn is_offline() -> bool {
+    network_mode() != NetworkMode::Online
+}
+
+/// Fail when offline; `what` says what needed the network
+pub fn ensure_online(what: &str) -> Result<()> {
+    if is_offline() {
+        bail!(
+            "{} needs the network, but cx is offline (--offline, --frozen or CX_OFFLINE)",
+            what
+        );
+    }
+    Ok(())
+}
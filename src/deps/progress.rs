@@ -0,0 +1,71 @@
+//! Output of dependencies fetched and built side by side.
+//!
+//! Each dependency being worked on gets a spinner on a shared
+//! [`MultiProgress`]. The lines it prints are held back and written together
+//! once it is done, so one dependency's output never interleaves another's.
+
+use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
+use std::time::Duration;
+
+pub struct Progress {
+    multi: MultiProgress,
+}
+
+/// Spinner and held-back output of one dependency; printed on drop
+pub struct DepProgress<'a> {
+    multi: &'a MultiProgress,
+    bar: ProgressBar,
+    lines: Vec<String>,
+}
+
+impl Progress {
+    pub fn new() -> Self {
+        Self {
+            multi: MultiProgress::new(),
+        }
+    }
+
+    /// Start a spinner showing `message`
+    pub fn start(&self, message: String) -> DepProgress<'_> {
+        let bar = self.multi.add(ProgressBar::new_spinner());
+        bar.set_style(
+            ProgressStyle::default_spinner()
+                .template("{spinner:.blue} {msg}")
+                .unwrap_or_else(|_| ProgressStyle::default_spinner())
+                .tick_chars("⣾⣽⣻⢿⡿⣟⣯⣷"),
+        );
+        bar.set_message(message);
+        bar.enable_steady_tick(Duration::from_millis(100));
+        DepProgress {
+            multi: &self.multi,
+            bar,
+            lines: Vec::new(),
+        }
+    }
+}
+
+impl DepProgress<'_> {
+    pub fn set_message(&self, message: String) {
+        self.bar.set_message(message);
+    }
+
+    /// Queue `line` for printing when this dependency is done
+    pub fn log(&mut self, line: String) {
+        self.lines.push(line);
+    }
+}
+
+impl Drop for DepProgress<'_> {
+    fn drop(&mut self) {
+        self.bar.finish_and_clear();
+        self.multi.remove(&self.bar);
+        let lines = std::mem::take(&mut self.lines);
+        if !lines.is_empty() {
+            self.multi.suspend(|| {
+                for line in lines {
+                    status!("{line}");
+                }
+            });
+        }
+    }
+}
@@ -6,12 +6,12 @@
 //! ## Usage
 //!
 //! ```bash
-//! cx vendor  # Copies ~/.cx/cache/* to ./vendor/
+//! cx vendor  # Copies the locked checkouts from ~/.cx/cache/git to ./vendor/
 //! ```
 
 use crate::build::load_config;
 use crate::config::Dependency;
-use anyhow::{Context, Result};
+use anyhow::Result;
 use colored::*;
 use std::fs;
 use std::path::Path;
@@ -38,33 +38,38 @@ pub fn vendor_dependencies() -> Result<()> {
         fs::create_dir(vendor_dir)?;
     }
 
-    // 3. Resolve Cache Path
-    let home_dir = dirs::home_dir().context("Could not find home directory")?;
-    let cache_dir = home_dir.join(".cx").join("cache");
+    // 3. Resolve Cache
+    let cache = super::gitcache::GitCache::new()?;
+
+    // Git dependencies plus what they require, as recorded in cx.lock
+    let direct: Vec<&str> = deps
+        .iter()
+        .filter(|(_, dep)| !matches!(dep, Dependency::Complex { pkg: Some(_), .. }))
+        .map(|(name, _)| name.as_str())
+        .collect();
+    let lockfile = crate::lock::LockFile::load().unwrap_or_default();
+    let names = lockfile.reachable(direct);
 
     println!(
         "{} Vendoring {} dependencies to ./vendor...",
         "📦".blue(),
-        deps.len()
+        names.len()
     );
 
-    for (name, dep) in deps {
-        // Skip pkg-config deps
-        if let Dependency::Complex { pkg: Some(_), .. } = dep {
-            continue;
-        }
+    for name in names {
+        let source_path = lockfile
+            .get(name)
+            .map(|entry| cache.checkout_path(name, &entry.rev));
+        let dest_path = vendor_dir.join(name);
 
-        let source_path = cache_dir.join(&name);
-        let dest_path = vendor_dir.join(&name);
-
-        if !source_path.exists() {
+        let Some(source_path) = source_path.filter(|path| path.exists()) else {
             println!(
                 "{} Source not found in cache: {}. Run 'cx update' first.",
                 "x".red(),
                 name
             );
             continue;
-        }
+        };
 
         if dest_path.exists() {
             println!("   {} Updating {}", "⚡".yellow(), name);
@@ -0,0 +1,119 @@
+//! Semver `version` requirements matched against git tags.
+//!
+//! ```toml
+//! [dependencies]
+//! json = { git = "https://github.com/nlohmann/json", version = "^3.11" }
+//! ```
+//!
+//! Tags are read as versions after dropping a textual prefix (`v3.11.2`,
+//! `release-2.0.22`, `SDL2-2.0.22`); one- and two-part versions are padded
+//! (`5.0` is `5.0.0`). The highest tag matching every requirement wins.
+
+use semver::{Version, VersionReq};
+use std::cmp::Reverse;
+
+/// Version a tag stands for, `None` when it doesn't look like one
+pub fn parse_tag_version(tag: &str) -> Option<Version> {
+    // "SDL2-2.0.22" could start at "2-2.0.22" or at "2.0.22"; the start
+    // giving the most version components wins, the earliest on a tie
+    tag.char_indices()
+        .filter(|&(i, c)| c.is_ascii_digit() && (i == 0 || !tag.as_bytes()[i - 1].is_ascii_digit()))
+        .take_while(|&(i, _)| {
+            tag[..i]
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '/'))
+        })
+        .filter_map(|(i, _)| parse_version(&tag[i..]))
+        .min_by_key(|&(components, _)| Reverse(components))
+        .map(|(_, version)| version)
+}
+
+/// `rest` as a version padded to three components, and how many it had
+fn parse_version(rest: &str) -> Option<(usize, Version)> {
+    let (core, suffix) = rest.split_at(rest.find(['-', '+']).unwrap_or(rest.len()));
+    let core = core.replace('_', ".");
+    let mut parts: Vec<&str> = core.split('.').collect();
+    let components = parts.len();
+    if components > 3
+        || parts
+            .iter()
+            .any(|p| p.is_empty() || !p.bytes().all(|b| b.is_ascii_digit()))
+    {
+        return None;
+    }
+    parts.resize(3, "0");
+    let version = Version::parse(&format!("{}{}", parts.join("."), suffix)).ok()?;
+    Some((components, version))
+}
+
+/// Whether `version` satisfies every requirement
+pub fn matches_all(reqs: &[&VersionReq], version: &Version) -> bool {
+    reqs.iter().all(|req| req.matches(version))
+}
+
+/// Tag with the highest version matching every requirement
+pub fn highest_match<'a>(tags: &'a [String], reqs: &[&VersionReq]) -> Option<&'a str> {
+    tags.iter()
+        .filter_map(|tag| Some((parse_tag_version(tag)?, tag)))
+        .filter(|(version, _)| matches_all(reqs, version))
+        .max_by(|a, b| a.0.cmp(&b.0))
+        .map(|(_, tag)| tag.as_str())
+}
+
+/// Up to `limit` tags, highest version first, for error messages
+pub fn newest_tags(tags: &[String], limit: usize) -> Vec<&str> {
+    let mut versions: Vec<(Version, &str)> = tags
+        .iter()
+        .filter_map(|tag| Some((parse_tag_version(tag)?, tag.as_str())))
+        .collect();
+    versions.sort_by(|a, b| b.0.cmp(&a.0));
+    versions.into_iter().take(limit).map(|(_, t)| t).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tags(names: &[&str]) -> Vec<String> {
+        names.iter().map(|t| t.to_string()).collect()
+    }
+
+    #[test]
+    fn test_parse_tag_version() {
+        let parse = |tag| parse_tag_version(tag).map(|v| v.to_string());
+        assert_eq!(parse("v3.11.2").as_deref(), Some("3.11.2"));
+        assert_eq!(parse("release-2.0.22").as_deref(), Some("2.0.22"));
+        assert_eq!(parse("json-3.11").as_deref(), Some("3.11.0"));
+        assert_eq!(parse("5.0").as_deref(), Some("5.0.0"));
+        assert_eq!(parse("v1_2_3").as_deref(), Some("1.2.3"));
+        assert_eq!(parse("v2.0.0-rc.1").as_deref(), Some("2.0.0-rc.1"));
+        assert_eq!(parse("latest"), None);
+        assert_eq!(parse("1.2.3.4"), None);
+        assert_eq!(parse("SDL2-2.0.22").as_deref(), Some("2.0.22"));
+        assert_eq!(parse("v1.0.0-1").as_deref(), Some("1.0.0-1"));
+    }
+
+    #[test]
+    fn test_highest_match() {
+        let available = tags(&[
+            "v3.10.5",
+            "v3.11.2",
+            "v3.11.3",
+            "v4.0.0",
+            "v3.12.0-rc.1",
+            "nightly",
+        ]);
+        let caret = VersionReq::parse("^3.11").unwrap();
+        assert_eq!(highest_match(&available, &[&caret]), Some("v3.11.3"));
+
+        let below = VersionReq::parse("<3.11.3").unwrap();
+        assert_eq!(
+            highest_match(&available, &[&caret, &below]),
+            Some("v3.11.2")
+        );
+
+        let five = VersionReq::parse("^5").unwrap();
+        assert_eq!(highest_match(&available, &[&five]), None);
+        assert_eq!(newest_tags(&available, 2), vec!["v4.0.0", "v3.12.0-rc.1"]);
+    }
+}
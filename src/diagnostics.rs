@@ -0,0 +1,429 @@
+//! Compiler and analyzer diagnostics.
+//!
+//! One model for messages parsed from GCC, Clang, MSVC and clang-tidy
+//! output. It feeds the `diagnostic` events of `--message-format=json` and
+//! the SARIF 2.1.0 reports written by `cx build --sarif` / `cx check --sarif`
+//! for code-scanning services.
+//!
+//! Rule IDs come from the `[-Wunused-variable]` / `[bugprone-...]` suffix
+//! (or MSVC's `C4101`); fix-it hints from Clang/GCC
+//! `-fdiagnostics-parseable-fixits` lines or clang-tidy's exported fixes.
+
+use anyhow::{Context, Result};
+use regex::Regex;
+use serde_json::{Value, json};
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use std::sync::LazyLock;
+
+static GNU: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r"^(?P<file>(?:[A-Za-z]:)?[^:]+):(?P<line>\d+):(?:(?P<col>\d+):)? (?P<sev>fatal error|error|warning|note|remark): (?P<msg>.*)$",
+    )
+    .expect("valid regex")
+});
+
+static MSVC: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r"^(?P<file>.+?)\((?P<line>\d+)(?:,(?P<col>\d+))?\)\s*:\s*(?P<sev>fatal error|error|warning|note)(?: (?P<code>[A-Z]+\d+))?\s*:\s*(?P<msg>.*)$",
+    )
+    .expect("valid regex")
+});
+
+/// `... [-Wunused-variable]`, `... [bugprone-use-after-move,-warnings-as-errors]`
+static RULE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"^(?P<msg>.*?)\s*\[(?P<rule>-W[\w+=-]+|[a-z][\w.]*-[\w.-]+)(?:,[^\]]*)?\]$")
+        .expect("valid regex")
+});
+
+/// `fix-it:"file":{3:12-3:15}:"replacement"`
+static FIX_IT: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"^fix-it:"(?P<file>[^"]+)":\{(?P<l1>\d+):(?P<c1>\d+)-(?P<l2>\d+):(?P<c2>\d+)\}:"(?P<text>.*)"$"#)
+        .expect("valid regex")
+});
+
+/// One compiler or analyzer message
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    /// Relative to the project root when it lies inside the project
+    pub file: PathBuf,
+    pub line: u32,
+    pub column: Option<u32>,
+    /// `error`, `warning`, `note` or `remark`
+    pub severity: String,
+    /// Rule ID: `-Wunused-variable`, `bugprone-use-after-move`, `C4101`
+    pub code: Option<String>,
+    pub message: String,
+    pub fixes: Vec<FixIt>,
+}
+
+/// Suggested edit: replace the range (end exclusive, 1-based) with `replacement`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FixIt {
+    pub file: PathBuf,
+    pub start_line: u32,
+    pub start_column: u32,
+    pub end_line: u32,
+    pub end_column: u32,
+    pub replacement: String,
+}
+
+/// Diagnostics in compiler/clang-tidy `output`, produced by a tool running
+/// in `work_dir`. Notes follow the diagnostic they belong to.
+pub fn parse(output: &str, work_dir: &Path) -> Vec<Diagnostic> {
+    let mut diagnostics: Vec<Diagnostic> = Vec::new();
+    for line in strip_ansi(output).lines() {
+        let line = line.trim_end();
+        if let Some(caps) = FIX_IT.captures(line) {
+            if let (Some(fix), Some(last)) = (fix_it(&caps, work_dir), diagnostics.last_mut()) {
+                last.fixes.push(fix);
+            }
+            continue;
+        }
+        let Some(caps) = GNU.captures(line).or_else(|| MSVC.captures(line)) else {
+            continue;
+        };
+        let Ok(line_number) = caps["line"].parse() else {
+            continue;
+        };
+        let severity = match &caps["sev"] {
+            "fatal error" => "error",
+            other => other,
+        };
+        let mut message = caps["msg"].trim().to_string();
+        let mut code = caps.name("code").map(|code| code.as_str().to_string());
+        if let Some(rule) = RULE.captures(&message) {
+            code.get_or_insert_with(|| rule["rule"].to_string());
+            message = rule["msg"].to_string();
+        }
+        diagnostics.push(Diagnostic {
+            file: project_path(work_dir, caps["file"].trim()),
+            line: line_number,
+            column: caps.name("col").and_then(|col| col.as_str().parse().ok()),
+            severity: severity.to_string(),
+            code,
+            message,
+            fixes: Vec::new(),
+        });
+    }
+    diagnostics
+}
+
+fn fix_it(caps: &regex::Captures, work_dir: &Path) -> Option<FixIt> {
+    Some(FixIt {
+        file: project_path(work_dir, &caps["file"]),
+        start_line: caps["l1"].parse().ok()?,
+        start_column: caps["c1"].parse().ok()?,
+        end_line: caps["l2"].parse().ok()?,
+        end_column: caps["c2"].parse().ok()?,
+        replacement: unescape(&caps["text"]),
+    })
+}
+
+/// Drop terminal color codes (`-fdiagnostics-color=always`)
+pub fn strip_ansi(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c == '\u{1b}' {
+            // CSI sequence: ESC [ params final-byte
+            for c in chars.by_ref() {
+                if c.is_ascii_alphabetic() {
+                    break;
+                }
+            }
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
+/// Undo C-style escaping in fix-it replacement text
+fn unescape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => out.push('\n'),
+            Some('t') => out.push('\t'),
+            Some(other) => out.push(other),
+            None => out.push('\\'),
+        }
+    }
+    out
+}
+
+/// `file` as seen from the project root (`../src/a.cpp` from `.cx` is `src/a.cpp`)
+pub fn project_path(work_dir: &Path, file: &str) -> PathBuf {
+    let path = Path::new(file);
+    if path.is_absolute() {
+        return std::env::current_dir()
+            .ok()
+            .and_then(|root| path.strip_prefix(root).ok().map(Path::to_path_buf))
+            .unwrap_or_else(|| path.to_path_buf());
+    }
+    let mut normalized = PathBuf::new();
+    for component in work_dir.join(path).components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir if normalized.file_name().is_some() => {
+                normalized.pop();
+            }
+            other => normalized.push(other),
+        }
+    }
+    normalized
+}
+
+/// SARIF 2.1.0 log with one run of `tool`. Notes become related locations
+/// of the result before them.
+pub fn sarif(tool: &str, diagnostics: &[Diagnostic]) -> Value {
+    let mut results: Vec<Value> = Vec::new();
+    let mut rules: Vec<String> = Vec::new();
+    let mut fixes: Vec<Vec<&FixIt>> = Vec::new();
+
+    for diagnostic in diagnostics {
+        if diagnostic.severity == "note"
+            && let Some(related) = results
+                .last_mut()
+                .and_then(|result| result["relatedLocations"].as_array_mut())
+        {
+            let mut location = location(diagnostic);
+            location["message"] = json!({ "text": diagnostic.message });
+            related.push(location);
+            if let Some(last) = fixes.last_mut() {
+                last.extend(&diagnostic.fixes);
+            }
+            continue;
+        }
+
+        let rule = diagnostic
+            .code
+            .clone()
+            .unwrap_or_else(|| diagnostic.severity.clone());
+        if !rules.contains(&rule) {
+            rules.push(rule.clone());
+        }
+        results.push(json!({
+            "ruleId": rule,
+            "level": match diagnostic.severity.as_str() {
+                "error" => "error",
+                "warning" => "warning",
+                _ => "note",
+            },
+            "message": { "text": diagnostic.message },
+            "locations": [location(diagnostic)],
+            "relatedLocations": [],
+        }));
+        fixes.push(diagnostic.fixes.iter().collect());
+    }
+
+    for (result, fixes) in results.iter_mut().zip(fixes) {
+        if result["relatedLocations"]
+            .as_array()
+            .is_some_and(|related| related.is_empty())
+            && let Some(result) = result.as_object_mut()
+        {
+            result.remove("relatedLocations");
+        }
+        if !fixes.is_empty() {
+            result["fixes"] = json!([{ "artifactChanges": artifact_changes(&fixes) }]);
+        }
+    }
+
+    json!({
+        "$schema": "https://json.schemastore.org/sarif-2.1.0.json",
+        "version": "2.1.0",
+        "runs": [{
+            "tool": {
+                "driver": {
+                    "name": tool,
+                    "informationUri": env!("CARGO_PKG_REPOSITORY"),
+                    "rules": rules.iter().map(|id| json!({ "id": id })).collect::<Vec<_>>(),
+                }
+            },
+            "originalUriBaseIds": { "%SRCROOT%": { "uri": root_uri() } },
+            "results": results,
+        }]
+    })
+}
+
+pub fn write_sarif(path: &Path, tool: &str, diagnostics: &[Diagnostic]) -> Result<()> {
+    if let Some(parent) = path.parent()
+        && !parent.as_os_str().is_empty()
+    {
+        fs::create_dir_all(parent)?;
+    }
+    let log = serde_json::to_string_pretty(&sarif(tool, diagnostics))?;
+    fs::write(path, log).with_context(|| format!("Failed to write {}", path.display()))
+}
+
+fn artifact_location(file: &Path) -> Value {
+    let uri = file.to_string_lossy().replace('\\', "/");
+    if file.is_absolute() {
+        let uri = uri.trim_start_matches('/');
+        json!({ "uri": format!("file:///{}", uri) })
+    } else {
+        json!({ "uri": uri, "uriBaseId": "%SRCROOT%" })
+    }
+}
+
+fn location(diagnostic: &Diagnostic) -> Value {
+    let mut region = json!({ "startLine": diagnostic.line });
+    if let Some(column) = diagnostic.column {
+        region["startColumn"] = json!(column);
+    }
+    json!({
+        "physicalLocation": {
+            "artifactLocation": artifact_location(&diagnostic.file),
+            "region": region,
+        }
+    })
+}
+
+/// Fix-it replacements grouped per file, in order
+fn artifact_changes(fixes: &[&FixIt]) -> Vec<Value> {
+    let mut files: Vec<&Path> = Vec::new();
+    for fix in fixes {
+        if !files.contains(&fix.file.as_path()) {
+            files.push(&fix.file);
+        }
+    }
+    files
+        .into_iter()
+        .map(|file| {
+            let replacements: Vec<Value> = fixes
+                .iter()
+                .filter(|fix| fix.file == file)
+                .map(|fix| {
+                    json!({
+                        "deletedRegion": {
+                            "startLine": fix.start_line,
+                            "startColumn": fix.start_column,
+                            "endLine": fix.end_line,
+                            "endColumn": fix.end_column,
+                        },
+                        "insertedContent": { "text": fix.replacement },
+                    })
+                })
+                .collect();
+            json!({
+                "artifactLocation": artifact_location(file),
+                "replacements": replacements,
+            })
+        })
+        .collect()
+}
+
+fn root_uri() -> String {
+    let root = std::env::current_dir().unwrap_or_default();
+    let root = root.to_string_lossy().replace('\\', "/");
+    format!(
+        "file:///{}/",
+        root.trim_start_matches('/').trim_end_matches('/')
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_gcc_and_clang_diagnostics() {
+        let output = "../src/a.cpp: In function 'int a()':\n\
+                      \u{1b}[01m\u{1b}[K../src/a.cpp:3:12:\u{1b}[m\u{1b}[K \u{1b}[01;31m\u{1b}[Kerror: \u{1b}[m\u{1b}[K'foo' was not declared in this scope\n\
+                      ../include/b.hpp:7:1: warning: unused variable 'x' [-Wunused-variable]\n\
+                      ../src/a.cpp:9: fatal error: missing.h: No such file or directory\n";
+        let diagnostics = parse(output, Path::new(".cx"));
+        assert_eq!(diagnostics.len(), 3);
+        assert_eq!(diagnostics[0].file, PathBuf::from("src/a.cpp"));
+        assert_eq!(diagnostics[0].line, 3);
+        assert_eq!(diagnostics[0].column, Some(12));
+        assert_eq!(diagnostics[0].severity, "error");
+        assert_eq!(
+            diagnostics[0].message,
+            "'foo' was not declared in this scope"
+        );
+        assert_eq!(diagnostics[0].code, None);
+        assert_eq!(diagnostics[1].file, PathBuf::from("include/b.hpp"));
+        assert_eq!(diagnostics[1].severity, "warning");
+        assert_eq!(diagnostics[1].code.as_deref(), Some("-Wunused-variable"));
+        assert_eq!(diagnostics[1].message, "unused variable 'x'");
+        assert_eq!(diagnostics[2].column, None);
+        assert_eq!(diagnostics[2].severity, "error");
+    }
+
+    #[test]
+    fn test_parse_msvc_diagnostics() {
+        let output = "a.cpp\n..\\src\\a.cpp(3,12): error C2065: 'foo': undeclared identifier\n\
+                      C:\\proj\\b.hpp(7): warning C4101: 'x': unreferenced local variable\n";
+        let diagnostics = parse(output, Path::new("."));
+        assert_eq!(diagnostics.len(), 2);
+        assert_eq!(diagnostics[0].line, 3);
+        assert_eq!(diagnostics[0].column, Some(12));
+        assert_eq!(diagnostics[0].code.as_deref(), Some("C2065"));
+        assert_eq!(diagnostics[0].message, "'foo': undeclared identifier");
+        assert_eq!(diagnostics[1].severity, "warning");
+        assert_eq!(diagnostics[1].column, None);
+    }
+
+    #[test]
+    fn test_parse_clang_tidy_rules_and_fix_its() {
+        let output = "src/a.cpp:4:14: warning: use nullptr [modernize-use-nullptr,-warnings-as-errors]\n\
+                      fix-it:\"src/a.cpp\":{4:14-4:15}:\"nullptr\"\n\
+                      src/a.cpp:9:3: error: use of undeclared identifier 'fo'; did you mean 'foo'? [clang-diagnostic-error]\n\
+                      src/a.cpp:2:5: note: 'foo' declared here\n\
+                      fix-it:\"src/a.cpp\":{9:3-9:5}:\"foo\\\"s\"\n";
+        let diagnostics = parse(output, Path::new("."));
+        assert_eq!(diagnostics.len(), 3);
+        assert_eq!(
+            diagnostics[0].code.as_deref(),
+            Some("modernize-use-nullptr")
+        );
+        assert_eq!(diagnostics[0].message, "use nullptr");
+        assert_eq!(
+            diagnostics[0].fixes,
+            vec![FixIt {
+                file: PathBuf::from("src/a.cpp"),
+                start_line: 4,
+                start_column: 14,
+                end_line: 4,
+                end_column: 15,
+                replacement: "nullptr".to_string(),
+            }]
+        );
+        assert_eq!(diagnostics[2].severity, "note");
+        assert_eq!(diagnostics[2].fixes[0].replacement, "foo\"s");
+
+        let log = sarif("clang-tidy", &diagnostics);
+        let run = &log["runs"][0];
+        assert_eq!(log["version"], "2.1.0");
+        assert_eq!(
+            run["tool"]["driver"]["rules"][0]["id"],
+            "modernize-use-nullptr"
+        );
+        let results = run["results"].as_array().unwrap();
+        // The note is folded into the error before it
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0]["level"], "warning");
+        let location = &results[0]["locations"][0]["physicalLocation"];
+        assert_eq!(location["artifactLocation"]["uri"], "src/a.cpp");
+        assert_eq!(location["region"]["startLine"], 4);
+        let replacement = &results[0]["fixes"][0]["artifactChanges"][0]["replacements"][0];
+        assert_eq!(replacement["insertedContent"]["text"], "nullptr");
+        assert_eq!(replacement["deletedRegion"]["endColumn"], 15);
+        assert_eq!(
+            results[1]["relatedLocations"][0]["message"]["text"],
+            "'foo' declared here"
+        );
+        assert_eq!(
+            results[1]["fixes"][0]["artifactChanges"][0]["replacements"][0]["insertedContent"]["text"],
+            "foo\"s"
+        );
+    }
+}
@@ -47,7 +47,9 @@ pub fn generate_docker_config() -> Result<()> {
     }
 
     let (project_name, bin_basename) = load_project_names()?;
-    let artifact_bin_dir = crate::build::artifact_bin_dir(true)
+    // The image builds natively with `cx build --release`
+    let artifact_bin_dir = crate::build::artifact_triple_dir("release", None)
+        .join("bin")
         .to_string_lossy()
         .replace('\\', "/");
     let artifact_bin_path = format!("{}/{}", artifact_bin_dir, bin_basename);
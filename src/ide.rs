@@ -29,6 +29,11 @@ fn default_config() -> CxConfig {
         test: None,
         workspace: None,
         arduino: None,
+        cache: None,
+        bin_targets: None,
+        lib_targets: None,
+        features: None,
+        platforms: None,
         profiles: std::collections::HashMap::new(),
     }
 }
@@ -45,11 +50,16 @@ fn ensure_vscode_dir() -> Result<PathBuf> {
     Ok(vscode_dir)
 }
 
-fn debug_binary_path(config: &CxConfig) -> String {
-    let debug_bin_rel = crate::build::artifact_bin_path(config, false, false)
+fn debug_binary_path(config: &CxConfig) -> Result<String> {
+    let profile = config
+        .build
+        .as_ref()
+        .and_then(|b| b.default_profile.as_deref());
+    let debug_bin_rel = crate::build::ArtifactDir::resolve(config, profile, false)?
+        .bin_path(config, false)
         .to_string_lossy()
         .replace('\\', "/");
-    format!("${{workspaceFolder}}/{}", debug_bin_rel)
+    Ok(format!("${{workspaceFolder}}/{}", debug_bin_rel))
 }
 
 fn tasks_json() -> serde_json::Value {
@@ -143,7 +153,7 @@ pub fn generate_ide_config() -> Result<()> {
 
     let vscode_dir = ensure_vscode_dir()?;
     let config = load_or_default_config();
-    let bin_path_debug = debug_binary_path(&config);
+    let bin_path_debug = debug_binary_path(&config)?;
 
     write_json_if_missing(&vscode_dir.join("tasks.json"), &tasks_json())?;
     write_json_if_missing(
@@ -134,6 +134,7 @@ pub fn scan_project(path: &Path) -> Result<Option<CxConfig>> {
             bin: Some("app".to_string()),
             flags: Some(cflags),
             cflags: None,
+            defines: None,
             libs: None, // Hard to guess libs from source
             ldflags: None,
             sources: if source_list.is_empty() {
@@ -146,6 +147,13 @@ pub fn scan_project(path: &Path) -> Result<Option<CxConfig>> {
             framework: None,
             include: None,
             build_type: None,
+            autobins: None,
+            incremental: None,
+            jobs: None,
+            min_free_memory: None,
+            overrides: None,
+            default_profile: None,
+            unity: None,
             encoding: "utf-8".to_string(),
         }),
         dependencies: None, // Hard to guess deps
@@ -153,6 +161,11 @@ pub fn scan_project(path: &Path) -> Result<Option<CxConfig>> {
         test: None,
         workspace: None,
         arduino: None,
+        cache: None,
+        bin_targets: None,
+        lib_targets: None,
+        features: None,
+        platforms: None,
         profiles: std::collections::HashMap::new(),
     };
 
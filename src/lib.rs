@@ -28,6 +28,19 @@
 //! - [`toolchain`] - Compiler detection and selection
 //! - [`commands`] - CLI command handlers
 
+/// `println!` for human-readable progress. Goes to stderr once
+/// `--message-format=json` has claimed stdout for its events.
+#[macro_export]
+macro_rules! status {
+    ($($arg:tt)*) => {
+        if $crate::build::messages::human_on_stderr() {
+            eprintln!($($arg)*)
+        } else {
+            println!($($arg)*)
+        }
+    };
+}
+
 /// Core build system with parallel compilation.
 pub mod build;
 
@@ -49,6 +62,9 @@ pub mod config;
 /// Dependency fetching and management.
 pub mod deps;
 
+/// Compiler and clang-tidy diagnostics, SARIF export.
+pub mod diagnostics;
+
 /// Documentation generation (Doxygen).
 pub mod doc;
 
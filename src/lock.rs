@@ -1,19 +1,25 @@
 use anyhow::Result;
 use serde::{Deserialize, Serialize};
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet};
 use std::fs;
 use std::path::Path;
 
-#[derive(Debug, Serialize, Deserialize, Default)]
+#[derive(Debug, Serialize, Deserialize, Default, Clone, PartialEq)]
 pub struct LockFile {
     #[serde(rename = "package")]
     pub packages: BTreeMap<String, PackageLock>,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 pub struct PackageLock {
     pub git: String,
     pub rev: String,
+    /// Tag `rev` was resolved from when the dependency asks for a `version`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tag: Option<String>,
+    /// Names of the packages this one requires through its own `cx.toml`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub dependencies: Vec<String>,
 }
 
 impl LockFile {
@@ -37,7 +43,50 @@ impl LockFile {
     }
 
     pub fn insert(&mut self, name: String, git: String, rev: String) {
-        self.packages.insert(name, PackageLock { git, rev });
+        let dependencies = self
+            .packages
+            .get(&name)
+            .map(|entry| entry.dependencies.clone())
+            .unwrap_or_default();
+        self.packages.insert(
+            name,
+            PackageLock {
+                git,
+                rev,
+                tag: None,
+                dependencies,
+            },
+        );
+    }
+
+    /// Record the packages `name` requires; no-op for unknown names
+    pub fn set_dependencies(&mut self, name: &str, mut dependencies: Vec<String>) {
+        if let Some(entry) = self.packages.get_mut(name) {
+            dependencies.sort();
+            entry.dependencies = dependencies;
+        }
+    }
+
+    /// Record the tag `name`'s version requirement resolved to
+    pub fn set_tag(&mut self, name: &str, tag: Option<String>) {
+        if let Some(entry) = self.packages.get_mut(name) {
+            entry.tag = tag;
+        }
+    }
+
+    /// `roots` plus every package reachable from them through `dependencies`
+    pub fn reachable<'a>(&'a self, roots: impl IntoIterator<Item = &'a str>) -> BTreeSet<&'a str> {
+        let mut found = BTreeSet::new();
+        let mut pending: Vec<&str> = roots.into_iter().collect();
+        while let Some(name) = pending.pop() {
+            if !found.insert(name) {
+                continue;
+            }
+            if let Some(entry) = self.packages.get(name) {
+                pending.extend(entry.dependencies.iter().map(String::as_str));
+            }
+        }
+        found
     }
 }
 
@@ -77,6 +126,32 @@ mod tests {
         assert!(toml_str.contains("v3.11.2"));
     }
 
+    #[test]
+    fn test_lockfile_transitive_dependencies() {
+        let toml_str = r#"
+[package]
+spdlog = { git = "https://github.com/gabime/spdlog", rev = "abc123", dependencies = ["fmt"] }
+fmt = { git = "https://github.com/fmtlib/fmt", rev = "def456" }
+stale = { git = "https://github.com/old/stale", rev = "0" }
+"#;
+        let mut lock: LockFile = toml::from_str(toml_str).unwrap();
+        assert_eq!(
+            lock.reachable(["spdlog"]),
+            BTreeSet::from(["fmt", "spdlog"])
+        );
+
+        // Re-locking a package keeps the edges recorded for it
+        lock.insert(
+            "spdlog".to_string(),
+            "https://github.com/gabime/spdlog".to_string(),
+            "fff000".to_string(),
+        );
+        assert_eq!(lock.get("spdlog").unwrap().dependencies, vec!["fmt"]);
+        let saved = toml::to_string_pretty(&lock).unwrap();
+        assert!(saved.contains("dependencies = [\"fmt\"]"));
+        assert!(!saved.contains("dependencies = []"));
+    }
+
     #[test]
     fn test_lockfile_parse() {
         let toml_str = r#"
@@ -8,7 +8,7 @@
 //! Commands are organized into categories:
 //! - **Project**: `new`, `init`, `info`, `stats`
 //! - **Build**: `build`, `run`, `clean`, `watch`, `test`
-//! - **Dependencies**: `add`, `remove`, `update`, `vendor`, `tree`
+//! - **Dependencies**: `add`, `remove`, `update`, `fetch`, `vendor`, `tree`
 //! - **Quality**: `fmt`, `check`, `doc`
 //! - **Toolchain**: `toolchain`, `target`, `doctor`
 //! - **Ecosystem**: `ci`, `docker`, `setup-ide`, `generate`
@@ -19,9 +19,7 @@ use clap_complete::{Shell, generate};
 use colored::*;
 use inquire::{Select, Text};
 use std::fs;
-use std::path::Path;
-#[cfg(windows)]
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use caxe::build;
 use caxe::cache;
@@ -71,6 +69,12 @@ fn enable_windows_utf8_console() {}
 struct Cli {
     #[command(subcommand)]
     command: Option<Commands>,
+    /// Never touch the network; dependencies must be cached or vendored
+    #[arg(long, global = true)]
+    offline: bool,
+    /// Like --offline, and require cx.lock to match cx.toml exactly
+    #[arg(long, global = true)]
+    frozen: bool,
 }
 
 #[derive(Subcommand)]
@@ -115,6 +119,26 @@ enum Commands {
         /// Use a named profile (e.g., --profile esp32)
         #[arg(long)]
         profile: Option<String>,
+        /// Build only the named binary target ([[bin]] or src/bin/<name>.cpp)
+        #[arg(long)]
+        bin: Option<String>,
+        /// Number of parallel compile jobs (default: one per core)
+        #[arg(short, long)]
+        jobs: Option<usize>,
+        /// Keep compiling after errors and summarize every failing file
+        #[arg(short, long)]
+        keep_going: bool,
+        /// Emit build events as JSON lines on stdout (for editors and CI)
+        #[arg(long, value_enum, default_value = "human")]
+        message_format: build::MessageFormat,
+        /// Write diagnostics as SARIF 2.1.0 (for code-scanning tools)
+        #[arg(long, value_name = "PATH")]
+        sarif: Option<PathBuf>,
+        /// Compile sources in batches of generated unity files
+        #[arg(long)]
+        unity: bool,
+        #[command(flatten)]
+        features: build::FeatureSelection,
     },
     /// Compile and run the output binary
     Run {
@@ -127,6 +151,17 @@ enum Commands {
         /// Show what would be executed without running
         #[arg(long)]
         dry_run: bool,
+        /// Name of the binary target to run
+        #[arg(long)]
+        bin: Option<String>,
+        /// Number of parallel compile jobs (default: one per core)
+        #[arg(short, long)]
+        jobs: Option<usize>,
+        /// Emit build events as JSON lines on stdout (for editors and CI)
+        #[arg(long, value_enum, default_value = "human")]
+        message_format: build::MessageFormat,
+        #[command(flatten)]
+        features: build::FeatureSelection,
         /// Arguments passed to the target program
         #[arg(num_args = 0.., allow_hyphen_values = true)]
         args: Vec<String>,
@@ -145,6 +180,11 @@ enum Commands {
         #[arg(long)]
         rev: Option<String>,
     },
+    /// Download every dependency into the cache for later --offline builds
+    Fetch {
+        #[command(flatten)]
+        features: build::FeatureSelection,
+    },
     /// Manage the dependency lockfile
     Lock {
         /// Update the lockfile to the latest compatible versions
@@ -193,6 +233,14 @@ enum Commands {
         /// Filter tests by name
         #[arg(long)]
         filter: Option<String>,
+        /// Number of parallel compile jobs (default: one per core)
+        #[arg(short, long)]
+        jobs: Option<usize>,
+        /// Emit build events as JSON lines on stdout (for editors and CI)
+        #[arg(long, value_enum, default_value = "human")]
+        message_format: build::MessageFormat,
+        #[command(flatten)]
+        features: build::FeatureSelection,
     },
     /// Show system and project setup info
     Info,
@@ -205,7 +253,14 @@ enum Commands {
     /// Generate documentation using Doxygen
     Doc,
     /// Static analysis using clang-tidy / cppcheck
-    Check,
+    Check {
+        /// Number of parallel clang-tidy jobs (default: one per core)
+        #[arg(short, long)]
+        jobs: Option<usize>,
+        /// Write diagnostics as SARIF 2.1.0 (for code-scanning tools)
+        #[arg(long, value_name = "PATH")]
+        sarif: Option<PathBuf>,
+    },
     /// Update dependencies to latest versions
     Update,
     /// Upgrade caxe itself (if installed via cargo)
@@ -245,10 +300,13 @@ enum Commands {
     /// Generate IDE configuration (VSCode)
     SetupIde,
     /// Visualize dependency tree
-    Tree,
+    Tree {
+        #[command(flatten)]
+        features: build::FeatureSelection,
+    },
     /// Show project statistics
     Stats,
-    /// Manage cross-compilation targets (mutation subcommands are deferred in v0.3.x)
+    /// Manage cross-compilation targets (list, add, remove, default)
     Target {
         #[command(subcommand)]
         op: Option<TargetOp>,
@@ -257,6 +315,12 @@ enum Commands {
     Generate {
         #[command(subcommand)]
         format: GenerateFormat,
+        /// Generate for the release profile
+        #[arg(long, global = true)]
+        release: bool,
+        /// Generate for a named profile (default: `[build] default_profile`)
+        #[arg(long, global = true)]
+        profile: Option<String>,
     },
     /// Upload Arduino sketch to board
     Upload {
@@ -275,11 +339,17 @@ enum Commands {
 #[derive(Subcommand)]
 enum CacheOp {
     /// Clean the cache
-    Clean,
+    Clean {
+        /// Clear the compiled object cache (~/.cx/objcache) instead
+        #[arg(long)]
+        objects: bool,
+    },
     /// List cached items
     Ls,
     /// Print cache directory path
     Path,
+    /// Show object cache size, limit and hit rate
+    Stats,
 }
 
 #[derive(Subcommand)]
@@ -303,19 +373,19 @@ enum ToolchainOp {
 enum TargetOp {
     /// List all available targets
     List,
-    /// Add a target to the project (deferred in v0.3.x; use profiles)
+    /// Add a [profile:<name>] for a built-in target to cx.toml
     Add {
-        /// Target name (windows-x64, linux-x64, macos-x64, wasm32, esp32)
+        /// Target name (windows-x64, linux-x64, linux-arm64, macos-arm64, wasm32, esp32, ...)
         name: String,
     },
-    /// Remove a target from the project (deferred in v0.3.x; use profiles)
+    /// Remove a target profile from cx.toml
     Remove {
-        /// Target name
+        /// Profile name
         name: String,
     },
-    /// Set the default target (deferred in v0.3.x; use profiles)
+    /// Set the profile `cx build` uses without --profile
     Default {
-        /// Target name
+        /// Profile name, or `host` to build for the host again
         name: String,
     },
 }
@@ -357,6 +427,23 @@ fn main() -> Result<()> {
     enable_windows_utf8_console();
 
     let cli = Cli::parse();
+    if cli.frozen {
+        deps::set_network_mode(deps::NetworkMode::Frozen);
+    } else if cli.offline {
+        deps::set_network_mode(deps::NetworkMode::Offline);
+    }
+
+    // Keep stdout for JSON events, and the text around them free of color codes
+    if let Some(
+        Commands::Build { message_format, .. }
+        | Commands::Run { message_format, .. }
+        | Commands::Test { message_format, .. },
+    ) = &cli.command
+        && message_format.is_json()
+    {
+        colored::control::set_override(false);
+        message_format.claim_stdout();
+    }
 
     match &cli.command {
         Some(Commands::New {
@@ -379,6 +466,8 @@ fn main() -> Result<()> {
             Ok(())
         }
 
+        Some(Commands::Fetch { features }) => deps::fetch_project(features),
+
         Some(Commands::Lock { update, check }) => {
             commands::doctor::handle_lock(*update, *check);
             Ok(())
@@ -403,6 +492,13 @@ fn main() -> Result<()> {
             sanitize,
             arduino,
             profile,
+            bin,
+            jobs,
+            keep_going,
+            message_format,
+            sarif,
+            unity,
+            features,
         }) => {
             // Auto-detect Arduino projects: check for .ino files or [arduino] config
             let has_ino_files = std::fs::read_dir(".")
@@ -431,12 +527,24 @@ fn main() -> Result<()> {
                 wasm: *wasm,
                 lto: *lto,
                 sanitize: sanitize.clone(),
-                profile: profile.clone(),
+                profile: profile.clone().or_else(|| {
+                    config
+                        .build
+                        .as_ref()
+                        .and_then(|b| b.default_profile.clone())
+                }),
+                bin: bin.clone(),
+                jobs: *jobs,
+                keep_going: *keep_going,
+                message_format: *message_format,
+                sarif: sarif.clone(),
+                features: features.clone(),
+                unity: *unity,
             };
 
             // Workspace Support
             if let Some(ws) = &config.workspace {
-                println!(
+                caxe::status!(
                     "{} Building Workspace ({} members)...",
                     "🚀".cyan(),
                     ws.members.len()
@@ -446,25 +554,30 @@ fn main() -> Result<()> {
                 for member in &ws.members {
                     let member_path = root_dir.join(member);
                     if !member_path.exists() {
-                        println!("{} Member '{}' not found", "x".red(), member);
+                        caxe::status!("{} Member '{}' not found", "x".red(), member);
                         continue;
                     }
 
-                    println!("\n{} Building member: {}", "📦".blue(), member);
+                    caxe::status!("\n{} Building member: {}", "📦".blue(), member);
                     std::env::set_current_dir(&member_path)?;
 
                     // Reload config for member
                     match build::load_config() {
                         Ok(member_config) => {
                             if let Err(e) = build::build_project(&member_config, &options) {
-                                println!("{} Build failed for {}: {}", "x".red(), member, e);
+                                caxe::status!("{} Build failed for {}: {}", "x".red(), member, e);
                                 // Continue or exit? Usually fail fast?
                                 std::env::set_current_dir(&root_dir)?;
                                 std::process::exit(1);
                             }
                         }
                         Err(e) => {
-                            println!("{} Failed to load config for {}: {}", "x".red(), member, e);
+                            caxe::status!(
+                                "{} Failed to load config for {}: {}",
+                                "x".red(),
+                                member,
+                                e
+                            );
                             std::env::set_current_dir(&root_dir)?;
                             std::process::exit(1);
                         }
@@ -500,6 +613,10 @@ fn main() -> Result<()> {
             release,
             verbose,
             dry_run,
+            bin,
+            jobs,
+            message_format,
+            features,
             args,
         }) => {
             // Detect script mode: if first arg looks like a source file, use it as script_path
@@ -518,12 +635,27 @@ fn main() -> Result<()> {
             } else {
                 (None, args.clone())
             };
-            build::build_and_run(*release, *verbose, *dry_run, run_args, script_path)
+            let options = build::BuildOptions {
+                release: *release,
+                verbose: *verbose,
+                dry_run: *dry_run,
+                bin: bin.clone(),
+                jobs: *jobs,
+                message_format: *message_format,
+                features: features.clone(),
+                ..Default::default()
+            };
+            build::build_and_run(&options, run_args, script_path)
         }
 
         Some(Commands::Watch { test }) => build::watch(*test),
         Some(Commands::Clean { cache, all, unused }) => build::clean(*cache, *all, *unused),
-        Some(Commands::Test { filter }) => build::run_tests(filter.clone()),
+        Some(Commands::Test {
+            filter,
+            jobs,
+            message_format,
+            features,
+        }) => build::run_tests(filter.clone(), *jobs, *message_format, features),
         Some(Commands::Add {
             lib,
             tag,
@@ -534,14 +666,21 @@ fn main() -> Result<()> {
         Some(Commands::Info) => print_info(),
         Some(Commands::Fmt { check }) => checker::format_code(*check),
         Some(Commands::Doc) => doc::generate_docs(),
-        Some(Commands::Check) => checker::check_code(),
+        Some(Commands::Check { jobs, sarif }) => checker::check_code(*jobs, sarif.as_deref()),
         Some(Commands::Update) => deps::update_dependencies(),
         Some(Commands::Upgrade) => upgrade::check_and_upgrade(),
         Some(Commands::Init) => init_project(),
         Some(Commands::Cache { op }) => match op {
-            CacheOp::Clean => cache::clean(),
+            CacheOp::Clean { objects } => {
+                if *objects {
+                    cache::clean_objects()
+                } else {
+                    cache::clean()
+                }
+            }
             CacheOp::Ls => cache::list(),
             CacheOp::Path => cache::print_path(),
+            CacheOp::Stats => cache::stats(),
         },
         Some(Commands::Completion { shell }) => {
             let mut cmd = Cli::command();
@@ -582,7 +721,7 @@ fn main() -> Result<()> {
         Some(Commands::CI) => ci::generate_ci_config(),
         Some(Commands::Docker) => docker::generate_docker_config(),
         Some(Commands::SetupIde) => ide::generate_ide_config(),
-        Some(Commands::Tree) => tree::print_tree(),
+        Some(Commands::Tree { features }) => tree::print_tree(features),
         Some(Commands::Stats) => stats::print_stats(),
         Some(Commands::Target { op }) => {
             let local_op = op.as_ref().map(|o| match o {
@@ -597,7 +736,11 @@ fn main() -> Result<()> {
             });
             commands::target::handle_target_command(&local_op)
         }
-        Some(Commands::Generate { format }) => {
+        Some(Commands::Generate {
+            format,
+            release,
+            profile,
+        }) => {
             let local_format = match format {
                 GenerateFormat::Cmake => commands::generate::GenerateFormat::Cmake,
                 GenerateFormat::Ninja => commands::generate::GenerateFormat::Ninja,
@@ -605,7 +748,7 @@ fn main() -> Result<()> {
                     commands::generate::GenerateFormat::CompileCommands
                 }
             };
-            commands::generate::handle_generate_command(&local_format)
+            commands::generate::handle_generate_command(&local_format, profile.as_deref(), *release)
         }
         Some(Commands::Upload { port, verbose }) => {
             build::arduino::upload_arduino(port.clone(), *verbose)
@@ -619,7 +762,7 @@ fn main() -> Result<()> {
             let run_args = args[1..].to_vec();
 
             // Script mode defaults: release=false, verbose=false, dry_run=false
-            build::build_and_run(false, false, false, run_args, script_path)
+            build::build_and_run(&build::BuildOptions::default(), run_args, script_path)
         }
         None => {
             print_splash();
@@ -38,6 +38,13 @@ pub fn package_project(output_name: Option<String>, release: bool) -> Result<()>
         lto: true, // optimize for size/speed for package
         sanitize: None,
         profile: None,
+        bin: None,
+        jobs: None,
+        keep_going: false,
+        message_format: build::MessageFormat::Human,
+        sarif: None,
+        features: Default::default(),
+        unity: false,
     };
 
     if let Err(e) = build::build_project(&config, &build_opts) {
@@ -47,9 +54,14 @@ pub fn package_project(output_name: Option<String>, release: bool) -> Result<()>
     // 2. Determine Output Paths
     let project_name = config.package.name.clone();
     let version = config.package.version.clone();
-    let binary_name = build::binary_name(&config, false);
-    let binary_path = build::artifact_bin_path(&config, release, false);
-    let bin_dir = build::artifact_bin_dir(release);
+    let artifacts = build::ArtifactDir::resolve(&config, None, release)?;
+    let binary_path = artifacts.bin_path(&config, false);
+    let binary_name = binary_path
+        .file_name()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .to_string();
+    let bin_dir = artifacts.bin_dir;
 
     if !binary_path.exists() {
         return Err(anyhow::anyhow!(
@@ -75,7 +75,16 @@ impl Registry {
             return Ok(Self(reg));
         }
 
-        // 2. Fetch from Remote
+        // 2. Offline: any cached copy, however old
+        if crate::deps::is_offline() {
+            return Ok(fs::read_to_string(&cache_path)
+                .ok()
+                .and_then(|content| serde_json::from_str(&content).ok())
+                .map(Self)
+                .unwrap_or_else(Self::default));
+        }
+
+        // 3. Fetch from Remote
         print!("{} Fetching registry... ", "⚡".yellow());
         match ureq::get(REGISTRY_URL).call() {
             Ok(mut response) => {
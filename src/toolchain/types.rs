@@ -73,8 +73,15 @@ pub struct Toolchain {
 impl Toolchain {
     /// Creates a new toolchain with minimal info (for non-MSVC compilers)
     pub fn new_simple(compiler_type: CompilerType, cxx_path: PathBuf, version: String) -> Self {
-        let cc_path = if cxx_path.to_string_lossy().contains("++") {
-            PathBuf::from(cxx_path.to_string_lossy().replace("++", ""))
+        // g++ -> gcc, clang++ -> clang (keeping any target prefix/version suffix)
+        let file_name = cxx_path
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let cc_path = if file_name.contains("g++") {
+            cxx_path.with_file_name(file_name.replacen("g++", "gcc", 1))
+        } else if file_name.contains("++") {
+            cxx_path.with_file_name(file_name.replacen("++", "", 1))
         } else {
             cxx_path.clone()
         };
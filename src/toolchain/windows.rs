@@ -540,7 +540,7 @@ fn maybe_resolve_without_vs(
                 return Ok(None);
             }
             if let Some(gxx) = mingw_path {
-                println!(
+                status!(
                     "{} Visual Studio not found, falling back to MinGW.",
                     "!".yellow()
                 );
@@ -8,16 +8,26 @@
 //! ```text
 //! my-project v1.0.0
 //! ├── raylib (tag: 5.0)
-//! ├── json (tag: v3.11.2)
-//! └── fmt (git: https://github.com/fmtlib/fmt)
+//! ├── spdlog (tag: v1.14.1)
+//! │   └── fmt (rev: 0c9fce2)
+//! └── json (version: ^3.11 → v3.11.3)
 //! ```
+//!
+//! Optional dependencies are listed only while a feature enables them.
+//! Transitive dependencies come from `cx.lock`, which records what each
+//! locked package requires.
 
+use crate::build::features::{FeatureSelection, Features};
 use crate::build::load_config;
+use crate::lock::LockFile;
 use anyhow::Result;
 use colored::*;
+use std::collections::HashSet;
 
-pub fn print_tree() -> Result<()> {
-    let config = load_config()?;
+pub fn print_tree(selection: &FeatureSelection) -> Result<()> {
+    let mut config = load_config()?;
+    let features = Features::resolve(&config, selection)?;
+    features.apply(&mut config);
 
     // Root
     println!(
@@ -25,8 +35,15 @@ pub fn print_tree() -> Result<()> {
         config.package.name.bold().cyan(),
         config.package.version
     );
+    if !features.enabled.is_empty() {
+        let enabled: Vec<&str> = features.enabled.iter().map(String::as_str).collect();
+        println!("{} {}", "features:".dimmed(), enabled.join(", ").cyan());
+    }
 
-    if let Some(deps) = config.dependencies {
+    let lockfile = LockFile::load().unwrap_or_default();
+    if let Some(deps) = config.dependencies.filter(|d| !d.is_empty()) {
+        let mut deps: Vec<_> = deps.into_iter().collect();
+        deps.sort_by(|a, b| a.0.cmp(&b.0));
         let count = deps.len();
         for (i, (name, dep)) in deps.iter().enumerate() {
             let is_last = i == count - 1;
@@ -41,6 +58,7 @@ pub fn print_tree() -> Result<()> {
                     tag,
                     branch,
                     rev,
+                    version,
                     ..
                 } => {
                     if let Some(t) = tag {
@@ -49,6 +67,11 @@ pub fn print_tree() -> Result<()> {
                         format!("branch: {}", b.yellow())
                     } else if let Some(r) = rev {
                         format!("rev: {:.7}", r.dimmed())
+                    } else if let Some(v) = version {
+                        match lockfile.get(name).and_then(|e| e.tag.as_deref()) {
+                            Some(t) => format!("version: {} → {}", v.green(), t.green()),
+                            None => format!("version: {}", v.green()),
+                        }
                     } else if let Some(g) = git {
                         format!("git: {}", g.dimmed())
                     } else if let Some(p) = pkg {
@@ -59,12 +82,21 @@ pub fn print_tree() -> Result<()> {
                 }
             };
 
-            println!("{} {} ({})", prefix, name.bold(), info);
+            if dep.is_optional() {
+                println!(
+                    "{} {} ({}) {}",
+                    prefix,
+                    name.bold(),
+                    info,
+                    "[optional]".magenta()
+                );
+            } else {
+                println!("{} {} ({})", prefix, name.bold(), info);
+            }
 
-            // In a real sophisticated tree, we would recursively check lockfiles or
-            // query the registry for sub-dependencies.
-            // For now, Caxe is flat or only tracking top-level until we parse vendored deps properly.
-            // So we stop here.
+            let indent = if is_last { "    " } else { "│   " };
+            let mut path = HashSet::from([name.clone()]);
+            print_locked_children(&lockfile, name, indent, &mut path);
         }
     } else {
         println!("└── (no dependencies)");
@@ -73,6 +105,46 @@ pub fn print_tree() -> Result<()> {
     Ok(())
 }
 
+/// What `name` requires according to `cx.lock`, recursively; `path` holds
+/// the packages above, so a cycle is cut instead of followed forever
+fn print_locked_children(
+    lockfile: &LockFile,
+    name: &str,
+    indent: &str,
+    path: &mut HashSet<String>,
+) {
+    let Some(entry) = lockfile.get(name) else {
+        return;
+    };
+    let count = entry.dependencies.len();
+    for (i, child) in entry.dependencies.iter().enumerate() {
+        let is_last = i == count - 1;
+        let prefix = if is_last { "└──" } else { "├──" };
+        let info = lockfile
+            .get(child)
+            .map(|c| match &c.tag {
+                Some(t) => format!("tag: {}", t.green()),
+                None => format!("rev: {:.7}", c.rev.dimmed()),
+            })
+            .unwrap_or_else(|| "not locked".dimmed().to_string());
+        if !path.insert(child.clone()) {
+            println!(
+                "{}{} {} ({}) {}",
+                indent,
+                prefix,
+                child,
+                info,
+                "(cycle)".red()
+            );
+            continue;
+        }
+        println!("{}{} {} ({})", indent, prefix, child, info);
+        let child_indent = format!("{}{}", indent, if is_last { "    " } else { "│   " });
+        print_locked_children(lockfile, child, &child_indent, path);
+        path.remove(child);
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use crate::config::Dependency;
@@ -94,8 +166,10 @@ mod tests {
             tag: Some("v3.11.2".to_string()),
             branch: None,
             rev: None,
+            version: None,
             build: None,
             output: None,
+            optional: None,
         };
 
         match dep {
@@ -114,8 +188,10 @@ mod tests {
             tag: None,
             branch: Some("SDL2".to_string()),
             rev: None,
+            version: None,
             build: None,
             output: None,
+            optional: None,
         };
 
         match dep {
@@ -134,8 +210,10 @@ mod tests {
             tag: None,
             branch: None,
             rev: None,
+            version: None,
             build: None,
             output: None,
+            optional: None,
         };
 
         match dep {
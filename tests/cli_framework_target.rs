@@ -2,7 +2,7 @@
 //!
 //! Focuses on v0.3.9 hardening around:
 //! - Framework support-status UX and mutation safety
-//! - Deferred target mutation commands and guidance
+//! - Target profiles written by `cx target add/remove/default`
 
 use std::fs;
 use std::path::{Path, PathBuf};
@@ -24,6 +24,15 @@ fn test_project_dir(name: &str) -> PathBuf {
         .join(name)
 }
 
+/// Removes a scratch project when dropped, so failing assertions don't leave it behind.
+struct ProjectGuard(PathBuf);
+
+impl Drop for ProjectGuard {
+    fn drop(&mut self) {
+        fs::remove_dir_all(&self.0).ok();
+    }
+}
+
 fn create_basic_project(name: &str, framework: Option<&str>) -> PathBuf {
     let temp_dir = test_project_dir(name);
 
@@ -149,48 +158,76 @@ fn framework_add_daxe_updates_build_framework() {
 }
 
 #[test]
-fn target_add_is_deferred_with_non_zero_and_profile_guidance() {
-    let name = unique_name("target-add-deferred");
+fn target_add_default_and_remove_edit_profiles_in_place() {
+    let name = unique_name("target-profiles");
     let project_dir = create_basic_project(&name, None);
+    let _guard = ProjectGuard(project_dir.clone());
+    let toml_path = project_dir.join("cx.toml");
+    let original = fs::read_to_string(&toml_path).expect("Failed to read cx.toml");
+    fs::write(&toml_path, format!("# keep me\n{original}")).expect("Failed to write cx.toml");
 
     let output = run_cx(&project_dir, &["target", "add", "esp32"]);
     let text = output_text(&output);
+    assert!(
+        output.status.success(),
+        "target add should succeed.\n{}",
+        text
+    );
+    let cx_toml = fs::read_to_string(&toml_path).expect("Failed to read cx.toml");
+    assert!(cx_toml.starts_with("# keep me\n[package]"), "{}", cx_toml);
+    assert!(
+        cx_toml.contains("[\"profile:esp32\"]\ntarget = \"xtensa-esp32-elf\""),
+        "target add should write the preset profile.\n{}",
+        cx_toml
+    );
+    assert!(cx_toml.contains("\"-mlongcalls\""), "{}", cx_toml);
 
+    let output = run_cx(&project_dir, &["target", "add", "esp32"]);
     assert!(
         !output.status.success(),
-        "target add should fail non-zero while deferred.\n{}",
-        text
+        "Adding an existing profile should fail.\n{}",
+        output_text(&output)
     );
+
+    let output = run_cx(&project_dir, &["target", "default", "esp32"]);
+    assert!(output.status.success(), "{}", output_text(&output));
+    let cx_toml = fs::read_to_string(&toml_path).expect("Failed to read cx.toml");
     assert!(
-        text.contains("deferred in v0.3.x patch releases"),
-        "Expected deferred-status messaging.\n{}",
-        text
+        cx_toml.contains("[build]\nsources = [\"src/main.cpp\"]\ndefault_profile = \"esp32\""),
+        "target default should record [build].default_profile.\n{}",
+        cx_toml
     );
+
+    let output = run_cx(&project_dir, &["build", "--dry-run", "--verbose"]);
+    let text = output_text(&output);
     assert!(
-        text.contains("cx build --profile <name>"),
-        "Expected profile-first guidance.\n{}",
+        text.contains("xtensa-esp32-elf"),
+        "cx build should use the default profile.\n{}",
         text
     );
 
-    fs::remove_dir_all(&project_dir).ok();
+    let output = run_cx(&project_dir, &["target", "remove", "esp32"]);
+    assert!(output.status.success(), "{}", output_text(&output));
+    let cx_toml = fs::read_to_string(&toml_path).expect("Failed to read cx.toml");
+    assert_eq!(cx_toml, format!("# keep me\n{original}"));
 }
 
 #[test]
-fn target_help_marks_mutations_as_deferred() {
-    let name = unique_name("target-help");
+fn target_add_rejects_unknown_targets() {
+    let name = unique_name("target-add-unknown");
     let project_dir = create_basic_project(&name, None);
 
-    let output = run_cx(&project_dir, &["target", "--help"]);
+    let output = run_cx(&project_dir, &["target", "add", "amiga"]);
     let text = output_text(&output);
 
     assert!(
-        output.status.success(),
-        "target --help should succeed.\n{}",
+        !output.status.success(),
+        "Unknown targets should fail non-zero.\n{}",
         text
     );
     assert!(
-        text.contains("deferred in v0.3.x; use profiles"),
-        "Help output should mark mutation commands as deferred.\n{}",
+        text.contains("Available targets: windows-x64"),
+        "Expected the catalog in the error.\n{}",
         text
     );
 
@@ -4,7 +4,7 @@
 //! by creating temporary projects and running builds.
 
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::process::Command;
 
 fn test_projects_root() -> PathBuf {
@@ -76,6 +76,17 @@ fn get_cx_binary() -> PathBuf {
     target_dir.join("debug").join(bin_name)
 }
 
+/// `cx` invocation in `project_dir` with an object cache private to that
+/// project, so test runs never touch the developer's `~/.cx/objcache`.
+/// Cache tests override `CX_OBJCACHE_DIR` to share a directory on purpose.
+fn cx_command(cx: &Path, project_dir: &Path) -> Command {
+    let mut command = Command::new(cx);
+    command
+        .current_dir(project_dir)
+        .env("CX_OBJCACHE_DIR", project_dir.join(".cx-objcache"));
+    command
+}
+
 #[test]
 fn test_build_simple_cpp_project() {
     let project_dir = create_test_project("test_cpp_build", true);
@@ -86,9 +97,8 @@ fn test_build_simple_cpp_project() {
         return;
     }
 
-    let output = Command::new(&cx)
+    let output = cx_command(&cx, &project_dir)
         .arg("build")
-        .current_dir(&project_dir)
         .output()
         .expect("Failed to execute cx build");
 
@@ -132,9 +142,8 @@ fn test_build_simple_c_project() {
         return;
     }
 
-    let output = Command::new(&cx)
+    let output = cx_command(&cx, &project_dir)
         .arg("build")
-        .current_dir(&project_dir)
         .output()
         .expect("Failed to execute cx build");
 
@@ -158,9 +167,8 @@ fn test_build_release_mode() {
         return;
     }
 
-    let output = Command::new(&cx)
+    let output = cx_command(&cx, &project_dir)
         .args(["build", "--release"])
-        .current_dir(&project_dir)
         .output()
         .expect("Failed to execute cx build --release");
 
@@ -184,9 +192,8 @@ fn test_dry_run_mode() {
         return;
     }
 
-    let output = Command::new(&cx)
+    let output = cx_command(&cx, &project_dir)
         .args(["build", "--dry-run"])
-        .current_dir(&project_dir)
         .output()
         .expect("Failed to execute cx build --dry-run");
 
@@ -207,3 +214,1983 @@ fn test_dry_run_mode() {
     // Cleanup
     fs::remove_dir_all(&project_dir).ok();
 }
+
+#[test]
+fn test_build_static_library_project() {
+    let project_dir = create_test_project("test_static_lib", true);
+
+    let cx = get_cx_binary();
+    if !cx.exists() {
+        eprintln!("Skipping test: cx binary not found at {:?}", cx);
+        return;
+    }
+
+    // A library has no main(); it must still build
+    fs::write(
+        project_dir.join("cx.toml"),
+        r#"[package]
+name = "test_static_lib"
+version = "0.1.0"
+edition = "c++17"
+
+[build]
+type = "static-library"
+sources = ["src/lib.cpp"]
+"#,
+    )
+    .expect("Failed to write cx.toml");
+    fs::write(
+        project_dir.join("src").join("lib.cpp"),
+        "int add(int a, int b) { return a + b; }\n",
+    )
+    .expect("Failed to write lib.cpp");
+
+    let output = cx_command(&cx, &project_dir)
+        .arg("build")
+        .output()
+        .expect("Failed to execute cx build");
+
+    assert!(
+        output.status.success(),
+        "Static library build failed: {}{}",
+        String::from_utf8_lossy(&output.stdout),
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let archive = if cfg!(windows) {
+        "test_static_lib.lib"
+    } else {
+        "libtest_static_lib.a"
+    };
+    assert!(
+        project_dir
+            .join(".cx")
+            .join("debug")
+            .join("bin")
+            .join(archive)
+            .exists(),
+        "Static library archive not created"
+    );
+
+    // Cleanup
+    fs::remove_dir_all(&project_dir).ok();
+}
+
+#[test]
+fn test_build_multiple_bin_targets() {
+    let project_dir = create_test_project("test_multi_bin", true);
+
+    let cx = get_cx_binary();
+    if !cx.exists() {
+        eprintln!("Skipping test: cx binary not found at {:?}", cx);
+        return;
+    }
+
+    // Shared source + package main + a discovered src/bin/ entry point
+    fs::write(
+        project_dir.join("cx.toml"),
+        r#"[package]
+name = "test_multi_bin"
+version = "0.1.0"
+edition = "c++17"
+"#,
+    )
+    .expect("Failed to write cx.toml");
+    fs::write(
+        project_dir.join("src").join("greet.cpp"),
+        "#include <cstdio>\nvoid greet(const char* who) { std::printf(\"hello %s\\n\", who); }\n",
+    )
+    .expect("Failed to write greet.cpp");
+    fs::create_dir_all(project_dir.join("src").join("bin")).expect("Failed to create src/bin");
+    fs::write(
+        project_dir.join("src").join("bin").join("server.cpp"),
+        "void greet(const char*);\nint main() { greet(\"server\"); return 0; }\n",
+    )
+    .expect("Failed to write server.cpp");
+
+    let output = cx_command(&cx, &project_dir)
+        .arg("build")
+        .output()
+        .expect("Failed to execute cx build");
+
+    assert!(
+        output.status.success(),
+        "Multi-target build failed: {}{}",
+        String::from_utf8_lossy(&output.stdout),
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let bin_dir = project_dir.join(".cx").join("debug").join("bin");
+    for name in ["test_multi_bin", "server"] {
+        let file = if cfg!(windows) {
+            format!("{name}.exe")
+        } else {
+            name.to_string()
+        };
+        assert!(bin_dir.join(&file).exists(), "Binary {file} not created");
+    }
+
+    let output = cx_command(&cx, &project_dir)
+        .args(["run", "--bin", "server"])
+        .output()
+        .expect("Failed to execute cx run");
+    assert!(output.status.success(), "cx run --bin server failed");
+    assert!(String::from_utf8_lossy(&output.stdout).contains("hello server"));
+
+    // Cleanup
+    fs::remove_dir_all(&project_dir).ok();
+}
+
+#[test]
+fn test_tests_skip_bin_entry_objects() {
+    let project_dir = create_test_project("test_bin_entry_tests", true);
+
+    let cx = get_cx_binary();
+    if !cx.exists() {
+        eprintln!("Skipping test: cx binary not found at {:?}", cx);
+        return;
+    }
+
+    fs::write(
+        project_dir.join("cx.toml"),
+        r#"[package]
+name = "test_bin_entry_tests"
+version = "0.1.0"
+edition = "c++17"
+"#,
+    )
+    .expect("Failed to write cx.toml");
+    fs::write(
+        project_dir.join("src").join("greet.cpp"),
+        "int answer() { return 42; }\n",
+    )
+    .expect("Failed to write greet.cpp");
+    fs::create_dir_all(project_dir.join("src").join("bin")).expect("Failed to create src/bin");
+    fs::write(
+        project_dir.join("src").join("bin").join("tool.cpp"),
+        "int answer();\nint main() { return answer() == 42 ? 0 : 1; }\n",
+    )
+    .expect("Failed to write tool.cpp");
+    fs::create_dir_all(project_dir.join("tests")).expect("Failed to create tests");
+    fs::write(
+        project_dir.join("tests").join("t.cpp"),
+        "int answer();\nint main() { return answer() == 42 ? 0 : 1; }\n",
+    )
+    .expect("Failed to write t.cpp");
+
+    let output = cx_command(&cx, &project_dir)
+        .arg("build")
+        .output()
+        .expect("Failed to execute cx build");
+    assert!(output.status.success(), "Initial build failed");
+
+    // Neither src/main.cpp nor src/bin/tool.cpp may reach the test binary
+    let output = cx_command(&cx, &project_dir)
+        .arg("test")
+        .output()
+        .expect("Failed to execute cx test");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(output.status.success(), "cx test failed: {stdout}{stderr}");
+    assert!(
+        !stdout.contains("multiple definition") && !stderr.contains("multiple definition"),
+        "Entry objects were linked into the test: {stdout}{stderr}"
+    );
+
+    // Cleanup
+    fs::remove_dir_all(&project_dir).ok();
+}
+
+#[test]
+fn test_flag_change_triggers_rebuild() {
+    let project_dir = create_test_project("test_flag_rebuild", true);
+
+    let cx = get_cx_binary();
+    if !cx.exists() {
+        eprintln!("Skipping test: cx binary not found at {:?}", cx);
+        return;
+    }
+
+    let build = || {
+        cx_command(&cx, &project_dir)
+            .args(["build", "--verbose"])
+            .output()
+            .expect("Failed to execute cx build")
+    };
+
+    assert!(build().status.success(), "Initial build failed");
+
+    // Same flags: nothing to recompile
+    let output = build();
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Up to date"));
+
+    // Only cx.toml changes; the source mtime stays older than the object
+    fs::write(
+        project_dir.join("cx.toml"),
+        r#"[package]
+name = "test_flag_rebuild"
+version = "0.1.0"
+edition = "c++17"
+
+[build]
+sources = ["src/main.cpp"]
+flags = ["-DCX_FLAG_CHANGED"]
+"#,
+    )
+    .expect("Failed to write cx.toml");
+
+    let output = build();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success(), "Rebuild failed: {stdout}");
+    assert!(
+        stdout.contains("flags changed: added -DCX_FLAG_CHANGED"),
+        "Object was not rebuilt after a flag change: {stdout}"
+    );
+
+    // Cleanup
+    fs::remove_dir_all(&project_dir).ok();
+}
+
+#[test]
+fn test_link_flag_change_triggers_relink() {
+    let project_dir = create_test_project("test_link_relink", true);
+
+    let cx = get_cx_binary();
+    if cfg!(windows) || !cx.exists() {
+        eprintln!("Skipping test: cx binary or a -lm linker not available");
+        return;
+    }
+
+    let build = || {
+        cx_command(&cx, &project_dir)
+            .args(["build", "--verbose"])
+            .output()
+            .expect("Failed to execute cx build")
+    };
+    assert!(build().status.success(), "Initial build failed");
+
+    // Objects stay fresh; only the link command changes
+    fs::write(
+        project_dir.join("cx.toml"),
+        r#"[package]
+name = "test_link_relink"
+version = "0.1.0"
+edition = "c++17"
+
+[build]
+sources = ["src/main.cpp"]
+libs = ["m"]
+"#,
+    )
+    .expect("Failed to write cx.toml");
+
+    let output = build();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success(), "Relink failed: {stdout}");
+    assert!(
+        stdout.contains("flags changed: added -lm") && !stdout.contains("Up to date"),
+        "Binary was not relinked after a libs change: {stdout}"
+    );
+
+    let output = build();
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Up to date"));
+
+    // Cleanup
+    fs::remove_dir_all(&project_dir).ok();
+}
+
+#[test]
+fn test_hash_mode_ignores_touched_sources() {
+    let project_dir = create_test_project("test_hash_incremental", true);
+
+    let cx = get_cx_binary();
+    if !cx.exists() {
+        eprintln!("Skipping test: cx binary not found at {:?}", cx);
+        return;
+    }
+
+    fs::write(
+        project_dir.join("cx.toml"),
+        r#"[package]
+name = "test_hash_incremental"
+version = "0.1.0"
+edition = "c++17"
+
+[build]
+sources = ["src/main.cpp"]
+incremental = "hash"
+"#,
+    )
+    .expect("Failed to write cx.toml");
+
+    let build = || {
+        cx_command(&cx, &project_dir)
+            .args(["build", "--verbose"])
+            .output()
+            .expect("Failed to execute cx build")
+    };
+    assert!(build().status.success(), "Initial build failed");
+
+    // Same content, newer mtime (what a checkout or cache restore does)
+    std::thread::sleep(std::time::Duration::from_millis(50));
+    let main_path = project_dir.join("src").join("main.cpp");
+    let content = fs::read(&main_path).expect("Failed to read main.cpp");
+    fs::write(&main_path, &content).expect("Failed to rewrite main.cpp");
+
+    let output = build();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success(), "Rebuild failed: {stdout}");
+    assert!(
+        stdout.contains("Up to date") && !stdout.contains("sources changed"),
+        "Unchanged content triggered a rebuild: {stdout}"
+    );
+
+    // A CI cache restore rewrites the objects too, older than the sources
+    let obj_dir = project_dir.join(".cx").join("debug").join("obj");
+    for entry in fs::read_dir(&obj_dir).expect("Failed to read obj dir") {
+        let path = entry.expect("Failed to read obj dir entry").path();
+        if path
+            .extension()
+            .is_some_and(|ext| ext == "o" || ext == "obj")
+        {
+            let content = fs::read(&path).expect("Failed to read object");
+            fs::write(&path, &content).expect("Failed to rewrite object");
+        }
+    }
+    std::thread::sleep(std::time::Duration::from_millis(50));
+    fs::write(&main_path, &content).expect("Failed to rewrite main.cpp");
+
+    let output = build();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success(), "Rebuild failed: {stdout}");
+    assert!(
+        stdout.contains("Up to date") && !stdout.contains("sources changed"),
+        "Restored objects triggered a rebuild: {stdout}"
+    );
+
+    // Cleanup
+    fs::remove_dir_all(&project_dir).ok();
+}
+
+#[test]
+fn test_object_cache_restores_after_clean() {
+    let project_dir = create_test_project("test_object_cache", true);
+
+    let cx = get_cx_binary();
+    if !cx.exists() {
+        eprintln!("Skipping test: cx binary not found at {:?}", cx);
+        return;
+    }
+
+    let cache_dir = project_dir.join("objcache");
+    let run = |args: &[&str]| {
+        cx_command(&cx, &project_dir)
+            .args(args)
+            .env("CX_OBJCACHE_DIR", &cache_dir)
+            .output()
+            .expect("Failed to execute cx")
+    };
+
+    assert!(run(&["build"]).status.success(), "Initial build failed");
+    assert!(run(&["clean"]).status.success(), "cx clean failed");
+
+    let output = run(&["build"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success(), "Rebuild failed: {stdout}");
+    assert!(
+        stdout.contains("Object cache: 1 hit(s)"),
+        "Object was not restored from the cache: {stdout}"
+    );
+
+    let output = run(&["cache", "stats"]);
+    assert!(output.status.success(), "cx cache stats failed");
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Hit rate"));
+
+    // Cleanup
+    fs::remove_dir_all(&project_dir).ok();
+}
+
+/// Minimal GET/PUT key-value HTTP server standing in for a remote cache.
+/// Returns the base URL; the server thread lives until the test exits.
+fn spawn_cache_server() -> String {
+    use std::collections::HashMap;
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+
+    let listener = TcpListener::bind("127.0.0.1:0").expect("Failed to bind cache server");
+    let addr = listener.local_addr().unwrap();
+    std::thread::spawn(move || {
+        let mut store: HashMap<String, Vec<u8>> = HashMap::new();
+        for stream in listener.incoming().flatten() {
+            let mut reader = BufReader::new(stream);
+            let mut request_line = String::new();
+            if reader.read_line(&mut request_line).is_err() {
+                continue;
+            }
+            let mut parts = request_line.split_whitespace();
+            let method = parts.next().unwrap_or_default().to_string();
+            let path = parts.next().unwrap_or_default().to_string();
+
+            let mut content_length = 0;
+            loop {
+                let mut header = String::new();
+                if reader.read_line(&mut header).is_err() || header.trim().is_empty() {
+                    break;
+                }
+                if let Some((name, value)) = header.split_once(':')
+                    && name.eq_ignore_ascii_case("content-length")
+                {
+                    content_length = value.trim().parse().unwrap_or(0);
+                }
+            }
+            let mut body = vec![0; content_length];
+            reader.read_exact(&mut body).ok();
+
+            let (status, payload) = match method.as_str() {
+                "GET" => match store.get(&path) {
+                    Some(bytes) => ("200 OK", bytes.clone()),
+                    None => ("404 Not Found", Vec::new()),
+                },
+                "PUT" => {
+                    store.insert(path, body);
+                    ("200 OK", Vec::new())
+                }
+                _ => ("405 Method Not Allowed", Vec::new()),
+            };
+            let mut stream = reader.into_inner();
+            let header = format!(
+                "HTTP/1.1 {status}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                payload.len()
+            );
+            stream.write_all(header.as_bytes()).ok();
+            stream.write_all(&payload).ok();
+        }
+    });
+    format!("http://{addr}")
+}
+
+#[test]
+fn test_remote_cache_shares_objects() {
+    let project_dir = create_test_project("test_remote_cache", true);
+
+    let cx = get_cx_binary();
+    if !cx.exists() {
+        eprintln!("Skipping test: cx binary not found at {:?}", cx);
+        return;
+    }
+
+    let remote = spawn_cache_server();
+    let run = |args: &[&str], local_cache: &str, mode: &str| {
+        cx_command(&cx, &project_dir)
+            .args(args)
+            .env("CX_OBJCACHE_DIR", project_dir.join(local_cache))
+            .env("CX_CACHE_REMOTE", &remote)
+            .env("CX_CACHE_REMOTE_MODE", mode)
+            .output()
+            .expect("Failed to execute cx")
+    };
+
+    // "CI" fills the remote cache
+    let output = run(&["build"], "ci_cache", "read-write");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success(), "Initial build failed: {stdout}");
+    assert!(
+        stdout.contains("1 upload(s)"),
+        "Object was not uploaded: {stdout}"
+    );
+    assert!(run(&["clean"], "ci_cache", "read").status.success());
+
+    // A "developer" with an empty local cache reads it back
+    let output = run(&["build"], "dev_cache", "read");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success(), "Rebuild failed: {stdout}");
+    assert!(
+        stdout.contains("1 download(s), 0 upload(s)"),
+        "Object was not fetched from the remote cache: {stdout}"
+    );
+    assert!(run(&["clean"], "dev_cache", "read").status.success());
+
+    // Offline builds never contact the remote, even on a local miss
+    let output = run(&["build", "--offline"], "offline_cache", "read");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success(), "Offline build failed: {stdout}");
+    assert!(
+        !stdout.contains("download(s)"),
+        "Offline build used the remote cache: {stdout}"
+    );
+
+    // Cleanup
+    fs::remove_dir_all(&project_dir).ok();
+}
+
+#[test]
+fn test_modules_build_in_import_order() {
+    let project_dir = create_test_project("test_module_order", true);
+
+    let cx = get_cx_binary();
+    if !cx.exists() {
+        eprintln!("Skipping test: cx binary not found at {:?}", cx);
+        return;
+    }
+
+    fs::write(
+        project_dir.join("cx.toml"),
+        "[package]\nname = \"test_module_order\"\nversion = \"0.1.0\"\nedition = \"c++20\"\n",
+    )
+    .unwrap();
+
+    // Alphabetical order is exactly wrong: a imports b's module and c's
+    // partition, and the partition in detail.cpp
+    fs::write(
+        project_dir.join("src/a.cppm"),
+        "export module app;\nexport import :util;\nimport :detail;\nimport base;\n\
+         export int app_value() { return util_value() + base_value() + detail_value(); }\n",
+    )
+    .unwrap();
+    fs::write(
+        project_dir.join("src/b.cppm"),
+        "export module base;\nexport int base_value() { return 1; }\n",
+    )
+    .unwrap();
+    fs::write(
+        project_dir.join("src/c.cppm"),
+        "export module app:util;\nimport base;\nexport int util_value() { return base_value() + 1; }\n",
+    )
+    .unwrap();
+    fs::write(
+        project_dir.join("src/detail.cpp"),
+        "module app:detail;\nint detail_value() { return 3; }\n",
+    )
+    .unwrap();
+    fs::write(
+        project_dir.join("src/main.cpp"),
+        "import app;\nint main() { return app_value() == 6 ? 0 : 1; }\n",
+    )
+    .unwrap();
+
+    let run = |args: &[&str]| {
+        cx_command(&cx, &project_dir)
+            .args(args)
+            .env("CX_OBJCACHE", "0")
+            .output()
+            .expect("Failed to execute cx")
+    };
+
+    let output = run(&["run"]);
+    assert!(
+        output.status.success(),
+        "Module build failed: {}{}",
+        String::from_utf8_lossy(&output.stdout),
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    // Touching the base module rebuilds its importers, nothing else
+    std::thread::sleep(std::time::Duration::from_millis(1100));
+    fs::write(
+        project_dir.join("src/b.cppm"),
+        "export module base;\nexport int base_value() { return 1; }\n\n",
+    )
+    .unwrap();
+    let output = run(&["build", "--verbose"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success(), "Rebuild failed: {stdout}");
+    assert!(
+        stdout.contains("src/c.cppm: imported module base rebuilt"),
+        "{stdout}"
+    );
+    assert!(!stdout.contains("src/detail.cpp"), "{stdout}");
+
+    // Cleanup
+    fs::remove_dir_all(&project_dir).ok();
+}
+
+#[test]
+fn test_jobs_flag_limits_parallelism() {
+    let project_dir = create_test_project("test_jobs", true);
+
+    let cx = get_cx_binary();
+    if !cx.exists() {
+        eprintln!("Skipping test: cx binary not found at {:?}", cx);
+        return;
+    }
+
+    let output = cx_command(&cx, &project_dir)
+        .args(["build", "--verbose", "-j", "1"])
+        .env("CX_JOBS", "8")
+        .output()
+        .expect("Failed to execute cx");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success(), "Build failed: {stdout}");
+    // -j wins over CX_JOBS
+    assert!(stdout.contains("Jobs: 1 job(s)"), "{stdout}");
+    // Timings are kept to schedule the slowest sources first next time
+    assert!(
+        project_dir
+            .join(".cx")
+            .join("build")
+            .join("compile_times.json")
+            .exists()
+    );
+
+    // Cleanup
+    fs::remove_dir_all(&project_dir).ok();
+}
+
+#[test]
+fn test_keep_going_reports_every_failure() {
+    let project_dir = create_test_project("test_keep_going", true);
+    fs::write(
+        project_dir.join("cx.toml"),
+        "[package]\nname = \"test_keep_going\"\nversion = \"0.1.0\"\nedition = \"c++17\"\n",
+    )
+    .expect("Failed to write cx.toml");
+    fs::write(
+        project_dir.join("src").join("first.cpp"),
+        "int first() { return missing_one; }\n",
+    )
+    .expect("Failed to write first.cpp");
+    fs::write(
+        project_dir.join("src").join("second.cpp"),
+        "int second() { return missing_two; }\n",
+    )
+    .expect("Failed to write second.cpp");
+
+    let cx = get_cx_binary();
+    if !cx.exists() {
+        eprintln!("Skipping test: cx binary not found at {:?}", cx);
+        return;
+    }
+
+    let output = cx_command(&cx, &project_dir)
+        .args(["build", "--keep-going", "-j", "1"])
+        .output()
+        .expect("Failed to execute cx");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(!output.status.success(), "Build should fail: {stdout}");
+    assert!(stdout.contains("2 file(s) failed to compile"), "{stdout}");
+    assert!(stdout.contains("missing_one"), "{stdout}");
+    assert!(stdout.contains("missing_two"), "{stdout}");
+    // The good translation unit still compiled
+    assert!(
+        fs::read_dir(project_dir.join(".cx").join("debug").join("obj"))
+            .expect("Failed to read obj dir")
+            .filter_map(|e| e.ok())
+            .any(|e| e.file_name().to_string_lossy().starts_with("main-")
+                && e.path()
+                    .extension()
+                    .is_some_and(|ext| ext == "o" || ext == "obj"))
+    );
+
+    // Cleanup
+    fs::remove_dir_all(&project_dir).ok();
+}
+
+#[test]
+fn test_message_format_json_reports_diagnostics() {
+    let project_dir = create_test_project("test_message_format", true);
+    fs::write(
+        project_dir.join("src").join("main.cpp"),
+        "int main() {\n    return missing_value;\n}\n",
+    )
+    .expect("Failed to write main.cpp");
+
+    let cx = get_cx_binary();
+    if !cx.exists() {
+        eprintln!("Skipping test: cx binary not found at {:?}", cx);
+        return;
+    }
+
+    let output = cx_command(&cx, &project_dir)
+        .args(["build", "--message-format=json"])
+        .output()
+        .expect("Failed to execute cx");
+    assert!(!output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let events: Vec<serde_json::Value> = stdout
+        .lines()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect();
+    let reasons: Vec<&str> = events
+        .iter()
+        .filter_map(|event| event["reason"].as_str())
+        .collect();
+    assert_eq!(reasons.first(), Some(&"build-started"), "{stdout}");
+    assert_eq!(reasons.last(), Some(&"build-finished"), "{stdout}");
+    assert!(reasons.contains(&"compile-started"), "{stdout}");
+
+    let diagnostic = events
+        .iter()
+        .find(|event| event["reason"] == "diagnostic")
+        .expect("no diagnostic event");
+    assert_eq!(diagnostic["file"], "src/main.cpp");
+    assert_eq!(diagnostic["line"], 2);
+    assert_eq!(diagnostic["severity"], "error");
+    assert!(
+        diagnostic["message"]
+            .as_str()
+            .is_some_and(|msg| msg.contains("missing_value"))
+    );
+    assert_eq!(events.last().unwrap()["success"], false);
+
+    // Cleanup
+    fs::remove_dir_all(&project_dir).ok();
+}
+
+#[test]
+fn test_message_format_json_keeps_stdout_machine_readable() {
+    let cx = get_cx_binary();
+    if !cx.exists() || Command::new("git").arg("--version").output().is_err() {
+        eprintln!("Skipping test: cx binary or git not available");
+        return;
+    }
+
+    let root = test_projects_root().join("test_json_stdout");
+    fs::remove_dir_all(&root).ok();
+    let (libj, home) = (root.join("libj"), root.join("home"));
+    fs::create_dir_all(&home).unwrap();
+    git_repo(
+        &libj,
+        &[("include/libj.hpp", "inline int j_value() { return 7; }\n")],
+    );
+
+    // Dependency fetching, a post-build script and a chatty test program
+    let project_dir = create_test_project("test_json_stdout_app", true);
+    fs::write(
+        project_dir.join("cx.toml"),
+        format!(
+            "[package]\nname = \"test_json_stdout_app\"\nversion = \"0.1.0\"\nedition = \"c++17\"\n\n\
+             [dependencies]\nlibj = \"{}\"\n\n\
+             [scripts]\npost_build = \"echo post-build done\"\n",
+            libj.display()
+        ),
+    )
+    .unwrap();
+    fs::write(
+        project_dir.join("src/main.cpp"),
+        "#include <libj.hpp>\nint main() { return j_value() == 7 ? 0 : 1; }\n",
+    )
+    .unwrap();
+    fs::create_dir_all(project_dir.join("tests")).unwrap();
+    fs::write(
+        project_dir.join("tests/t.cpp"),
+        "#include <cstdio>\nint main() { std::puts(\"test says hi\"); return 0; }\n",
+    )
+    .unwrap();
+
+    for command in ["build", "test"] {
+        let output = cx_command(&cx, &project_dir)
+            .args([command, "--message-format=json"])
+            .env("HOME", &home)
+            .env("USERPROFILE", &home)
+            .output()
+            .expect("Failed to execute cx");
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(
+            output.status.success(),
+            "cx {command} failed: {stdout}{stderr}"
+        );
+        for line in stdout.lines() {
+            let event: serde_json::Value = serde_json::from_str(line)
+                .unwrap_or_else(|_| panic!("cx {command} printed non-JSON to stdout: {line:?}"));
+            assert!(event["reason"].is_string(), "{line}");
+        }
+        if command == "build" {
+            assert!(stderr.contains("Checking 1 dependencies"), "{stderr}");
+            assert!(stderr.contains("post-build done"), "{stderr}");
+        } else {
+            assert!(stderr.contains("test says hi"), "{stderr}");
+        }
+    }
+
+    // Cleanup
+    fs::remove_dir_all(&project_dir).ok();
+    fs::remove_dir_all(&root).ok();
+}
+
+#[test]
+fn test_build_writes_sarif_report() {
+    let project_dir = create_test_project("test_sarif", true);
+    fs::write(
+        project_dir.join("cx.toml"),
+        "[package]\nname = \"test_sarif\"\nversion = \"0.1.0\"\nedition = \"c++17\"\n\n[build]\nflags = [\"-Wall\"]\n",
+    )
+    .expect("Failed to write cx.toml");
+    fs::write(
+        project_dir.join("src").join("main.cpp"),
+        "int main() {\n    int unused = 1;\n    return 0;\n}\n",
+    )
+    .expect("Failed to write main.cpp");
+
+    let cx = get_cx_binary();
+    if !cx.exists() {
+        eprintln!("Skipping test: cx binary not found at {:?}", cx);
+        return;
+    }
+
+    let output = cx_command(&cx, &project_dir)
+        .args(["build", "--sarif", "report.sarif"])
+        .output()
+        .expect("Failed to execute cx");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success(), "Build failed: {stdout}");
+
+    let report = fs::read_to_string(project_dir.join("report.sarif")).expect("no SARIF report");
+    let report: serde_json::Value = serde_json::from_str(&report).expect("invalid SARIF JSON");
+    assert_eq!(report["version"], "2.1.0");
+    let result = &report["runs"][0]["results"][0];
+    assert_eq!(result["ruleId"], "-Wunused-variable");
+    assert_eq!(result["level"], "warning");
+    let location = &result["locations"][0]["physicalLocation"];
+    assert_eq!(location["artifactLocation"]["uri"], "src/main.cpp");
+    assert_eq!(location["region"]["startLine"], 2);
+
+    // Cleanup
+    fs::remove_dir_all(&project_dir).ok();
+}
+
+#[test]
+fn test_sarif_report_covers_up_to_date_objects() {
+    let project_dir = create_test_project("test_sarif_rerun", true);
+    fs::write(
+        project_dir.join("cx.toml"),
+        "[package]\nname = \"test_sarif_rerun\"\nversion = \"0.1.0\"\nedition = \"c++17\"\n\n[build]\nflags = [\"-Wall\"]\n",
+    )
+    .expect("Failed to write cx.toml");
+    fs::write(
+        project_dir.join("src").join("main.cpp"),
+        "int main() {\n    int unused = 1;\n    return 0;\n}\n",
+    )
+    .expect("Failed to write main.cpp");
+
+    let cx = get_cx_binary();
+    if !cx.exists() {
+        eprintln!("Skipping test: cx binary not found at {:?}", cx);
+        return;
+    }
+
+    let build = || {
+        let output = cx_command(&cx, &project_dir)
+            .args(["build", "--sarif", "report.sarif"])
+            .output()
+            .expect("Failed to execute cx");
+        let stdout = String::from_utf8_lossy(&output.stdout).into_owned();
+        assert!(output.status.success(), "Build failed: {stdout}");
+        let report = fs::read_to_string(project_dir.join("report.sarif")).expect("no SARIF report");
+        let report: serde_json::Value = serde_json::from_str(&report).expect("invalid SARIF JSON");
+        (stdout, report["runs"][0]["results"].clone())
+    };
+
+    let (_, first) = build();
+    assert_eq!(first.as_array().map(Vec::len), Some(1));
+
+    // Nothing recompiles, but the report still has the warning
+    let (stdout, second) = build();
+    assert!(
+        stdout.contains("Up to date"),
+        "Object was rebuilt: {stdout}"
+    );
+    assert_eq!(second, first, "Up-to-date build lost its diagnostics");
+
+    // Cleanup
+    fs::remove_dir_all(&project_dir).ok();
+}
+
+#[test]
+fn test_build_overrides_apply_per_file() {
+    let project_dir = create_test_project("test_overrides", true);
+    fs::write(
+        project_dir.join("cx.toml"),
+        r#"[package]
+name = "test_overrides"
+version = "0.1.0"
+edition = "c++17"
+
+[[build.overrides]]
+files = ["src/gen/**/*.cpp"]
+flags = ["-Wno-unused-variable"]
+defines = ["GENERATED=7"]
+"#,
+    )
+    .expect("Failed to write cx.toml");
+    fs::create_dir_all(project_dir.join("src").join("gen")).unwrap();
+    fs::write(
+        project_dir.join("src").join("gen").join("table.cpp"),
+        "#ifndef GENERATED\n#error GENERATED not defined\n#endif\nint table() { return GENERATED; }\n",
+    )
+    .expect("Failed to write table.cpp");
+    fs::write(
+        project_dir.join("src").join("main.cpp"),
+        "#ifdef GENERATED\n#error override leaked into main.cpp\n#endif\nint table();\nint main() { return table() - 7; }\n",
+    )
+    .expect("Failed to write main.cpp");
+
+    let cx = get_cx_binary();
+    if !cx.exists() {
+        eprintln!("Skipping test: cx binary not found at {:?}", cx);
+        return;
+    }
+
+    let output = cx_command(&cx, &project_dir)
+        .args(["build", "--verbose"])
+        .output()
+        .expect("Failed to execute cx");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success(), "Build failed: {stdout}");
+    assert!(
+        stdout
+            .lines()
+            .any(|l| l.contains("table.cpp") && l.contains("-DGENERATED=7")),
+        "effective flags not shown: {stdout}"
+    );
+
+    let commands = fs::read_to_string(project_dir.join(".cx/build/compile_commands.json"))
+        .expect("no compile_commands.json");
+    let commands: serde_json::Value = serde_json::from_str(&commands).unwrap();
+    for entry in commands.as_array().unwrap() {
+        let file = entry["file"].as_str().unwrap();
+        let command = entry["command"].as_str().unwrap();
+        assert_eq!(
+            command.contains("-DGENERATED=7"),
+            file.ends_with("table.cpp"),
+            "{file}: {command}"
+        );
+    }
+
+    // Cleanup
+    fs::remove_dir_all(&project_dir).ok();
+}
+
+#[test]
+fn test_build_defines_and_profile_defines() {
+    let project_dir = create_test_project("test_defines", true);
+    fs::write(
+        project_dir.join("cx.toml"),
+        r#"[package]
+name = "test_defines"
+version = "0.1.0"
+edition = "c++17"
+
+[build]
+defines = ["APP_NAME=\"demo\"", "FEATURE_X"]
+
+["profile:embedded"]
+defines = ["EMBEDDED=1"]
+"#,
+    )
+    .expect("Failed to write cx.toml");
+    fs::write(
+        project_dir.join("src").join("main.cpp"),
+        "#include <cstring>\n#ifndef FEATURE_X\n#error FEATURE_X not defined\n#endif\n#ifndef EMBEDDED\n#define EMBEDDED 0\n#endif\nint main() { return std::strcmp(APP_NAME, \"demo\") + EMBEDDED - 1; }\n",
+    )
+    .expect("Failed to write main.cpp");
+
+    let cx = get_cx_binary();
+    if !cx.exists() {
+        eprintln!("Skipping test: cx binary not found at {:?}", cx);
+        return;
+    }
+
+    let output = cx_command(&cx, &project_dir)
+        .args(["build", "--profile", "embedded"])
+        .output()
+        .expect("Failed to execute cx");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success(), "Build failed: {stdout}");
+    let commands = fs::read_to_string(project_dir.join(".cx/build/compile_commands.json"))
+        .expect("no compile_commands.json");
+    assert!(commands.contains("-DFEATURE_X"));
+    assert!(commands.contains("-DEMBEDDED=1"));
+
+    // Without the profile EMBEDDED is 0 and main returns non-zero
+    let output = cx_command(&cx, &project_dir)
+        .args(["run"])
+        .output()
+        .expect("Failed to execute cx");
+    assert!(!output.status.success());
+
+    // Cleanup
+    fs::remove_dir_all(&project_dir).ok();
+}
+
+#[test]
+fn test_generate_uses_profile_and_override_defines() {
+    let project_dir = create_test_project("test_generate_defines", true);
+    fs::write(
+        project_dir.join("cx.toml"),
+        r#"[package]
+name = "test_generate_defines"
+version = "0.1.0"
+edition = "c++17"
+
+[build]
+defines = ["FEATURE_X"]
+
+[[build.overrides]]
+files = ["src/gen/*.cpp"]
+defines = ["GENERATED=1"]
+flags = ["-w"]
+
+["profile:embedded"]
+defines = ["EMBEDDED=1"]
+"#,
+    )
+    .expect("Failed to write cx.toml");
+    fs::create_dir_all(project_dir.join("src").join("gen")).expect("Failed to create src/gen");
+    fs::write(
+        project_dir.join("src").join("gen").join("table.cpp"),
+        "int table() { return GENERATED; }\n",
+    )
+    .expect("Failed to write table.cpp");
+
+    let cx = get_cx_binary();
+    if !cx.exists() {
+        eprintln!("Skipping test: cx binary not found at {:?}", cx);
+        return;
+    }
+
+    for format in ["cmake", "ninja"] {
+        let output = cx_command(&cx, &project_dir)
+            .args(["generate", format, "--profile", "embedded"])
+            .output()
+            .expect("Failed to execute cx");
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert!(
+            output.status.success(),
+            "cx generate {format} failed: {stdout}"
+        );
+    }
+
+    let cmake = fs::read_to_string(project_dir.join("CMakeLists.txt")).expect("no CMakeLists.txt");
+    assert!(cmake.contains("FEATURE_X EMBEDDED=1"), "{cmake}");
+    assert!(
+        cmake.contains(
+            "set_property(SOURCE src/gen/table.cpp APPEND PROPERTY COMPILE_DEFINITIONS GENERATED=1)"
+        ),
+        "{cmake}"
+    );
+    assert!(
+        cmake.contains("set_property(SOURCE src/gen/table.cpp APPEND PROPERTY COMPILE_OPTIONS -w)"),
+        "{cmake}"
+    );
+
+    let ninja = fs::read_to_string(project_dir.join("build.ninja")).expect("no build.ninja");
+    assert!(ninja.contains("EMBEDDED=1"), "{ninja}");
+    assert!(ninja.contains("  cxxflags = $cxxflags -w "), "{ninja}");
+
+    let output = cx_command(&cx, &project_dir)
+        .args(["build", "--profile", "embedded"])
+        .output()
+        .expect("Failed to execute cx");
+    assert!(
+        output.status.success(),
+        "cx build --profile embedded failed"
+    );
+    let commands = fs::read_to_string(
+        project_dir
+            .join(".cx")
+            .join("build")
+            .join("compile_commands.json"),
+    )
+    .expect("no compile_commands.json");
+    assert!(commands.contains("-DEMBEDDED=1"));
+    assert!(commands.contains("-DGENERATED=1"));
+
+    // Cleanup
+    fs::remove_dir_all(&project_dir).ok();
+}
+
+#[test]
+fn test_build_features() {
+    let project_dir = create_test_project("test_features", true);
+    fs::write(
+        project_dir.join("cx.toml"),
+        r#"[package]
+name = "test_features"
+version = "0.1.0"
+edition = "c++17"
+
+[features]
+default = ["logging"]
+logging = []
+tls = { enables = ["crypto"], defines = ["TLS_LEVEL=3"], sources = ["src/tls/*.cpp"] }
+crypto = []
+"#,
+    )
+    .expect("Failed to write cx.toml");
+    fs::create_dir_all(project_dir.join("src").join("tls")).expect("Failed to create src/tls");
+    fs::write(
+        project_dir.join("src").join("tls").join("conn.cpp"),
+        "#ifndef CX_FEATURE_TLS\n#error built without the tls feature\n#endif\nint tls_level() { return TLS_LEVEL; }\n",
+    )
+    .expect("Failed to write conn.cpp");
+    fs::write(
+        project_dir.join("src").join("main.cpp"),
+        "#ifdef CX_FEATURE_TLS\nint tls_level();\n#else\nint tls_level() { return 0; }\n#endif\n#ifdef CX_FEATURE_LOGGING\nconst int logging = 1;\n#else\nconst int logging = 0;\n#endif\n#ifdef CX_FEATURE_CRYPTO\nconst int crypto = 1;\n#else\nconst int crypto = 0;\n#endif\nint main() { return tls_level() * 10 + crypto * 4 + logging; }\n",
+    )
+    .expect("Failed to write main.cpp");
+
+    let cx = get_cx_binary();
+    if !cx.exists() {
+        eprintln!("Skipping test: cx binary not found at {:?}", cx);
+        return;
+    }
+
+    // Default features: logging only, src/tls stays out of the build
+    let output = cx_command(&cx, &project_dir)
+        .args(["run"])
+        .output()
+        .expect("Failed to execute cx");
+    assert_eq!(output.status.code(), Some(1));
+
+    // tls pulls in crypto and its source; logging is off
+    let output = cx_command(&cx, &project_dir)
+        .args(["run", "--features", "tls", "--no-default-features"])
+        .output()
+        .expect("Failed to execute cx");
+    assert_eq!(output.status.code(), Some(34));
+
+    let output = cx_command(&cx, &project_dir)
+        .args(["build", "--features", "quic"])
+        .output()
+        .expect("Failed to execute cx");
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("Unknown feature 'quic'"), "{stderr}");
+
+    // Cleanup
+    fs::remove_dir_all(&project_dir).ok();
+}
+
+#[test]
+fn test_profile_target_cross_build() {
+    // Cross-compile for the host triple, so only the prefixed GCC is needed
+    let Some(triple) = Command::new("g++")
+        .arg("-dumpmachine")
+        .output()
+        .ok()
+        .map(|o| String::from_utf8_lossy(&o.stdout).trim().to_string())
+        .filter(|t| !t.is_empty())
+    else {
+        eprintln!("Skipping test: g++ not found");
+        return;
+    };
+    if Command::new(format!("{triple}-g++"))
+        .arg("--version")
+        .output()
+        .is_err()
+    {
+        eprintln!("Skipping test: {triple}-g++ not found");
+        return;
+    }
+
+    let project_dir = create_test_project("test_cross", true);
+    fs::write(
+        project_dir.join("cx.toml"),
+        format!(
+            r#"[package]
+name = "test_cross"
+version = "0.1.0"
+edition = "c++17"
+
+["profile:host-cross"]
+target = "{triple}"
+sysroot = "/"
+ldflags = ["-Wl,--as-needed"]
+"#
+        ),
+    )
+    .expect("Failed to write cx.toml");
+
+    let cx = get_cx_binary();
+    if !cx.exists() {
+        eprintln!("Skipping test: cx binary not found at {:?}", cx);
+        return;
+    }
+
+    let output = cx_command(&cx, &project_dir)
+        .args(["build", "--profile", "host-cross"])
+        .output()
+        .expect("Failed to execute cx");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success(), "Build failed: {stdout}");
+    assert!(
+        project_dir
+            .join(".cx")
+            .join(&triple)
+            .join("host-cross")
+            .join("bin")
+            .join("test_cross")
+            .exists()
+    );
+    let commands = fs::read_to_string(project_dir.join(".cx/build/compile_commands.json"))
+        .expect("no compile_commands.json");
+    assert!(commands.contains(&format!("{triple}-g++")));
+    assert!(commands.contains("--sysroot=/"));
+
+    // Cleanup
+    fs::remove_dir_all(&project_dir).ok();
+}
+
+#[test]
+fn test_profile_inheritance_chain() {
+    let project_dir = create_test_project("test_profile_chain", true);
+    fs::write(
+        project_dir.join("cx.toml"),
+        r#"[package]
+name = "test_profile_chain"
+version = "0.1.0"
+edition = "c++17"
+
+["profile:board"]
+base = "debug"
+defines = ["BOARD=1"]
+include = ["board"]
+
+["profile:board-fast"]
+base = "board"
+flags = ["-O2"]
+
+["profile:board-fast-v2"]
+base = "board-fast"
+defines = ["BOARD_REV=2"]
+edition = "c++20"
+"#,
+    )
+    .expect("Failed to write cx.toml");
+    fs::create_dir_all(project_dir.join("board")).unwrap();
+    fs::write(
+        project_dir.join("board/board.h"),
+        "#define BOARD_NAME \"v2\"\n",
+    )
+    .unwrap();
+    fs::write(
+        project_dir.join("src/main.cpp"),
+        r#"#include "board.h"
+#if BOARD != 1 || BOARD_REV != 2 || __cplusplus < 202002L
+#error "profile chain not applied"
+#endif
+int main() { return 0; }
+"#,
+    )
+    .unwrap();
+
+    let cx = get_cx_binary();
+    if !cx.exists() {
+        eprintln!("Skipping test: cx binary not found at {:?}", cx);
+        return;
+    }
+
+    let output = cx_command(&cx, &project_dir)
+        .args(["build", "--profile", "board-fast-v2"])
+        .output()
+        .expect("Failed to execute cx");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success(), "Build failed: {stdout}");
+
+    // Malformed profile tables name the offending key
+    fs::write(
+        project_dir.join("cx.toml"),
+        r#"[package]
+name = "test_profile_chain"
+version = "0.1.0"
+
+["profile:board"]
+defines = "BOARD=1"
+"#,
+    )
+    .expect("Failed to write cx.toml");
+    let output = cx_command(&cx, &project_dir)
+        .args(["build", "--dry-run"])
+        .output()
+        .expect("Failed to execute cx");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(!output.status.success());
+    assert!(
+        stderr.contains("Invalid key 'defines' in [profile:board]"),
+        "{stderr}"
+    );
+
+    // Cleanup
+    fs::remove_dir_all(&project_dir).ok();
+}
+
+#[test]
+fn test_configurable_builtin_and_named_profiles() {
+    let project_dir = create_test_project("test_profile_knobs", true);
+    fs::write(
+        project_dir.join("cx.toml"),
+        r#"[package]
+name = "test_profile_knobs"
+version = "0.1.0"
+edition = "c++17"
+
+[profile.release]
+opt-level = "s"
+assertions = false
+
+[profile.bench]
+base = "release"
+opt-level = 2
+frame-pointers = true
+"#,
+    )
+    .expect("Failed to write cx.toml");
+
+    let cx = get_cx_binary();
+    if !cx.exists() {
+        eprintln!("Skipping test: cx binary not found at {:?}", cx);
+        return;
+    }
+
+    let output = cx_command(&cx, &project_dir)
+        .args(["build", "--release"])
+        .output()
+        .expect("Failed to execute cx");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success(), "Build failed: {stdout}");
+    let commands = fs::read_to_string(project_dir.join(".cx/build/compile_commands.json"))
+        .expect("no compile_commands.json");
+    assert!(commands.contains("-Os") && commands.contains("-DNDEBUG"));
+    assert!(!commands.contains("-O3"));
+
+    let output = cx_command(&cx, &project_dir)
+        .args(["build", "--profile", "bench"])
+        .output()
+        .expect("Failed to execute cx");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success(), "Build failed: {stdout}");
+    assert!(
+        project_dir
+            .join(".cx/bench/bin/test_profile_knobs")
+            .exists()
+    );
+    let commands = fs::read_to_string(project_dir.join(".cx/build/compile_commands.json"))
+        .expect("no compile_commands.json");
+    assert!(commands.contains("-O2") && commands.contains("-fno-omit-frame-pointer"));
+
+    // Cleanup
+    fs::remove_dir_all(&project_dir).ok();
+}
+
+#[test]
+fn test_platform_conditional_sections() {
+    let project_dir = create_test_project("test_platform_cfg", true);
+    fs::write(
+        project_dir.join("cx.toml"),
+        r#"[package]
+name = "test_platform_cfg"
+version = "0.1.0"
+edition = "c++17"
+
+[target.'cfg(unix)'.build]
+defines = ["ON_UNIX"]
+sources = ["src/posix/*.cpp"]
+
+[target.'cfg(windows)'.build]
+defines = ["ON_WINDOWS"]
+libs = ["ws2_32"]
+sources = ["src/win32/*.cpp"]
+"#,
+    )
+    .expect("Failed to write cx.toml");
+    for (dir, body) in [
+        ("posix", "int platform_id() { return 1; }\n"),
+        ("win32", "int platform_id() { return 2; }\n"),
+    ] {
+        fs::create_dir_all(project_dir.join("src").join(dir)).unwrap();
+        fs::write(project_dir.join("src").join(dir).join("platform.cpp"), body).unwrap();
+    }
+    fs::write(
+        project_dir.join("src/main.cpp"),
+        r#"int platform_id();
+#if defined(ON_UNIX) == defined(ON_WINDOWS)
+#error "exactly one platform section should apply"
+#endif
+int main() { return platform_id() == 0; }
+"#,
+    )
+    .unwrap();
+
+    let cx = get_cx_binary();
+    if !cx.exists() {
+        eprintln!("Skipping test: cx binary not found at {:?}", cx);
+        return;
+    }
+
+    // Both platform.cpp files define platform_id(), so linking only
+    // succeeds when the other platform's sources are left out
+    let output = cx_command(&cx, &project_dir)
+        .args(["build"])
+        .output()
+        .expect("Failed to execute cx");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success(), "Build failed: {stdout}");
+
+    // Cleanup
+    fs::remove_dir_all(&project_dir).ok();
+}
+
+#[test]
+fn test_unity_build() {
+    let project_dir = create_test_project("test_unity", true);
+    fs::write(
+        project_dir.join("cx.toml"),
+        r#"[package]
+name = "test_unity"
+version = "0.1.0"
+edition = "c++17"
+
+[[build.overrides]]
+files = ["src/legacy.cpp"]
+unity = false
+"#,
+    )
+    .expect("Failed to write cx.toml");
+    // legacy.cpp reuses a file-local name, so it can't share a batch
+    for (name, body) in [
+        (
+            "a",
+            "static int helper() { return 1; }\nint a() { return helper(); }\n",
+        ),
+        ("b", "int b() { return 2; }\n"),
+        ("c", "int c() { return 3; }\n"),
+        (
+            "legacy",
+            "static int helper() { return 4; }\nint legacy() { return helper(); }\n",
+        ),
+    ] {
+        fs::write(project_dir.join("src").join(format!("{name}.cpp")), body).unwrap();
+    }
+    fs::write(
+        project_dir.join("src/main.cpp"),
+        "int a(); int b(); int c(); int legacy();\n\
+         int main() { return a() + b() + c() + legacy() == 10 ? 0 : 1; }\n",
+    )
+    .unwrap();
+
+    let cx = get_cx_binary();
+    if !cx.exists() {
+        eprintln!("Skipping test: cx binary not found at {:?}", cx);
+        return;
+    }
+
+    let output = cx_command(&cx, &project_dir)
+        .args(["build", "--unity"])
+        .output()
+        .expect("Failed to execute cx");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success(), "Build failed: {stdout}");
+    assert!(stdout.contains("Unity build: 3 source(s) in 1 batch(es)"));
+
+    let batch = fs::read_to_string(project_dir.join(".cx/build/unity/debug/unity_0.cpp"))
+        .expect("unity batch should be generated");
+    assert!(batch.contains("src/a.cpp\""));
+    assert!(!batch.contains("legacy"));
+    assert!(!batch.contains("main"));
+
+    // IDEs still see every real source
+    let commands = fs::read_to_string(project_dir.join(".cx/build/compile_commands.json")).unwrap();
+    let commands: Vec<serde_json::Value> = serde_json::from_str(&commands).unwrap();
+    let mut files: Vec<String> = commands
+        .iter()
+        .filter_map(|entry| entry["file"].as_str())
+        .filter_map(|file| std::path::Path::new(file).file_name())
+        .map(|name| name.to_string_lossy().to_string())
+        .collect();
+    files.sort();
+    assert_eq!(files, ["a.cpp", "b.cpp", "c.cpp", "legacy.cpp", "main.cpp"]);
+
+    let status = Command::new(project_dir.join(".cx/debug/bin/test_unity"))
+        .status()
+        .expect("Failed to run binary");
+    assert!(status.success());
+
+    // Cleanup
+    fs::remove_dir_all(&project_dir).ok();
+}
+
+/// Commit `files` into a fresh git repository at `dir` (on branch `main`)
+fn git_repo(dir: &std::path::Path, files: &[(&str, &str)]) {
+    for (path, content) in files {
+        let path = dir.join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, content).unwrap();
+    }
+    for args in [
+        &["init", "-q", "-b", "main"][..],
+        &["add", "."],
+        &[
+            "-c",
+            "user.name=cx",
+            "-c",
+            "user.email=cx@example.com",
+            "commit",
+            "-q",
+            "-m",
+            "init",
+        ],
+        &["tag", "v1"],
+    ] {
+        let status = Command::new("git")
+            .args(args)
+            .current_dir(dir)
+            .status()
+            .expect("Failed to run git");
+        assert!(status.success(), "git {:?} failed", args);
+    }
+}
+
+/// Commit every change in `dir` and tag it
+fn git_release(dir: &std::path::Path, tag: &str) {
+    for args in [
+        &[
+            "-c",
+            "user.name=cx",
+            "-c",
+            "user.email=cx@example.com",
+            "commit",
+            "-q",
+            "-am",
+            tag,
+        ][..],
+        &["tag", tag],
+    ] {
+        let status = Command::new("git")
+            .args(args)
+            .current_dir(dir)
+            .status()
+            .expect("Failed to run git");
+        assert!(status.success(), "git {:?} failed", args);
+    }
+}
+
+#[test]
+fn test_transitive_dependencies() {
+    let cx = get_cx_binary();
+    if !cx.exists() || Command::new("git").arg("--version").output().is_err() {
+        eprintln!("Skipping test: cx binary or git not available");
+        return;
+    }
+
+    let root = test_projects_root().join("test_transitive");
+    fs::remove_dir_all(&root).ok();
+    let (libb, liba, home) = (root.join("libb"), root.join("liba"), root.join("home"));
+    fs::create_dir_all(&home).unwrap();
+    git_repo(
+        &libb,
+        &[("include/libb.hpp", "inline int b_value() { return 2; }\n")],
+    );
+    git_repo(
+        &liba,
+        &[
+            (
+                "include/liba.hpp",
+                "#include <libb.hpp>\ninline int a_value() { return b_value() + 1; }\n",
+            ),
+            (
+                "cx.toml",
+                &format!(
+                    "[package]\nname = \"liba\"\nversion = \"0.1.0\"\n\n\
+                     [dependencies]\nlibb = {{ git = \"{}\", branch = \"main\" }}\n",
+                    libb.display()
+                ),
+            ),
+        ],
+    );
+
+    let project_dir = create_test_project("test_transitive_app", true);
+    let manifest = format!(
+        "[package]\nname = \"test_transitive_app\"\nversion = \"0.1.0\"\nedition = \"c++17\"\n\n\
+         [dependencies]\nliba = \"{}\"\n",
+        liba.display()
+    );
+    fs::write(project_dir.join("cx.toml"), &manifest).unwrap();
+    fs::write(
+        project_dir.join("src/main.cpp"),
+        "#include <liba.hpp>\nint main() { return a_value() == 3 ? 0 : 1; }\n",
+    )
+    .unwrap();
+
+    // libb's headers are only reachable through liba's cx.toml
+    let output = cx_command(&cx, &project_dir)
+        .arg("build")
+        .env("HOME", &home)
+        .env("USERPROFILE", &home)
+        .output()
+        .expect("Failed to execute cx");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success(), "Build failed: {stdout}");
+    assert!(stdout.contains("liba requires libb"));
+
+    let lock = fs::read_to_string(project_dir.join("cx.lock")).unwrap();
+    let lock: toml::Value = toml::from_str(&lock).unwrap();
+    let packages = &lock["package"];
+    assert!(packages.get("libb").is_some());
+    assert_eq!(
+        packages["liba"]["dependencies"].as_array().unwrap(),
+        &vec![toml::Value::from("libb")]
+    );
+
+    // A different pin on the same repository is a conflict naming both sides
+    fs::write(
+        project_dir.join("cx.toml"),
+        format!(
+            "{manifest}libb = {{ git = \"{}\", tag = \"v1\" }}\n",
+            libb.display()
+        ),
+    )
+    .unwrap();
+    let output = cx_command(&cx, &project_dir)
+        .arg("build")
+        .env("HOME", &home)
+        .env("USERPROFILE", &home)
+        .output()
+        .expect("Failed to execute cx");
+    let combined = format!(
+        "{}{}",
+        String::from_utf8_lossy(&output.stdout),
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert!(!output.status.success());
+    assert!(
+        combined.contains("Conflicting pins for 'libb'"),
+        "{combined}"
+    );
+    assert!(combined.contains("cx.toml requires tag v1"));
+    assert!(combined.contains("liba requires branch main"));
+
+    // Cleanup
+    fs::remove_dir_all(&project_dir).ok();
+    fs::remove_dir_all(&root).ok();
+}
+
+#[test]
+fn test_version_requirement_resolves_tags() {
+    let cx = get_cx_binary();
+    if !cx.exists() || Command::new("git").arg("--version").output().is_err() {
+        eprintln!("Skipping test: cx binary or git not available");
+        return;
+    }
+
+    let root = test_projects_root().join("test_version_req");
+    fs::remove_dir_all(&root).ok();
+    let (libv, home) = (root.join("libv"), root.join("home"));
+    fs::create_dir_all(&home).unwrap();
+    git_repo(&libv, &[("include/libv.hpp", "#define LIBV 10\n")]);
+    let release = |value: u32, tag: &str| {
+        fs::write(
+            libv.join("include/libv.hpp"),
+            format!("#define LIBV {value}\n"),
+        )
+        .unwrap();
+        git_release(&libv, tag);
+    };
+    release(12, "v1.2.0");
+    release(20, "release-2.0.0");
+
+    let project_dir = create_test_project("test_version_req_app", true);
+    let manifest = |req: &str| {
+        format!(
+            "[package]\nname = \"test_version_req_app\"\nversion = \"0.1.0\"\nedition = \"c++17\"\n\n\
+             [dependencies]\nlibv = {{ git = \"{}\", version = \"{req}\" }}\n",
+            libv.display()
+        )
+    };
+    fs::write(project_dir.join("cx.toml"), manifest("^1")).unwrap();
+    fs::write(
+        project_dir.join("src/main.cpp"),
+        "#include <libv.hpp>\nint main() { return LIBV >= 12 && LIBV < 20 ? 0 : 1; }\n",
+    )
+    .unwrap();
+    let run = |cmd: &str| {
+        let output = cx_command(&cx, &project_dir)
+            .arg(cmd)
+            .env("HOME", &home)
+            .env("USERPROFILE", &home)
+            .output()
+            .expect("Failed to execute cx");
+        let combined = format!(
+            "{}{}",
+            String::from_utf8_lossy(&output.stdout),
+            String::from_utf8_lossy(&output.stderr)
+        );
+        (output.status.success(), combined)
+    };
+    let locked_tag = || {
+        let lock = fs::read_to_string(project_dir.join("cx.lock")).unwrap();
+        let lock: toml::Value = toml::from_str(&lock).unwrap();
+        lock["package"]["libv"]["tag"].as_str().unwrap().to_string()
+    };
+
+    // Highest tag in range; release-2.0.0 is out of it
+    let (ok, out) = run("build");
+    assert!(ok, "Build failed: {out}");
+    assert_eq!(locked_tag(), "v1.2.0");
+
+    // A new release in range is picked up by `cx update`, not by a build
+    release(13, "v1.3.0");
+    let (ok, out) = run("build");
+    assert!(ok, "Build failed: {out}");
+    assert_eq!(locked_tag(), "v1.2.0");
+    let (ok, out) = run("update");
+    assert!(ok, "Update failed: {out}");
+    assert_eq!(locked_tag(), "v1.3.0");
+
+    // No tag in range names the requirement and the newest tags
+    fs::write(project_dir.join("cx.toml"), manifest("^5")).unwrap();
+    let (ok, out) = run("build");
+    assert!(!ok);
+    assert!(
+        out.contains("No tag of 'libv'") && out.contains("cx.toml requires version ^5"),
+        "{out}"
+    );
+    assert!(out.contains("release-2.0.0, v1.3.0"), "{out}");
+
+    // Cleanup
+    fs::remove_dir_all(&project_dir).ok();
+    fs::remove_dir_all(&root).ok();
+}
+
+#[test]
+fn test_projects_share_cache_with_different_pins() {
+    let cx = get_cx_binary();
+    if !cx.exists() || Command::new("git").arg("--version").output().is_err() {
+        eprintln!("Skipping test: cx binary or git not available");
+        return;
+    }
+
+    let root = test_projects_root().join("test_cache_pins");
+    fs::remove_dir_all(&root).ok();
+    let (libp, home) = (root.join("libp"), root.join("home"));
+    fs::create_dir_all(&home).unwrap();
+    git_repo(&libp, &[("include/libp.hpp", "#define LIBP 1\n")]);
+    fs::write(libp.join("include/libp.hpp"), "#define LIBP 2\n").unwrap();
+    git_release(&libp, "v2");
+
+    let projects: Vec<_> = [1, 2]
+        .into_iter()
+        .map(|value| {
+            let name = format!("test_cache_pins_v{value}");
+            let project_dir = create_test_project(&name, true);
+            fs::write(
+                project_dir.join("cx.toml"),
+                format!(
+                    "[package]\nname = \"{name}\"\nversion = \"0.1.0\"\nedition = \"c++17\"\n\n\
+                     [dependencies]\nlibp = {{ git = \"{}\", tag = \"v{value}\" }}\n",
+                    libp.display()
+                ),
+            )
+            .unwrap();
+            fs::write(
+                project_dir.join("src/main.cpp"),
+                format!("#include <libp.hpp>\nint main() {{ return LIBP == {value} ? 0 : 1; }}\n"),
+            )
+            .unwrap();
+            project_dir
+        })
+        .collect();
+
+    // Building one project must not move the other's checkout
+    for project_dir in projects.iter().chain(&projects) {
+        let output = cx_command(&cx, project_dir)
+            .arg("run")
+            .env("HOME", &home)
+            .env("USERPROFILE", &home)
+            .output()
+            .expect("Failed to execute cx");
+        assert!(
+            output.status.success(),
+            "Run failed in {}: {}",
+            project_dir.display(),
+            String::from_utf8_lossy(&output.stdout)
+        );
+    }
+
+    let git_cache = home.join(".cx").join("cache").join("git");
+    let count = |dir: &str| {
+        fs::read_dir(git_cache.join(dir))
+            .unwrap()
+            .filter_map(|e| e.ok())
+            .filter(|e| e.path().is_dir())
+            .count()
+    };
+    assert_eq!(count("db"), 1);
+    assert_eq!(count("checkouts"), 2);
+
+    // Cleanup
+    for project_dir in &projects {
+        fs::remove_dir_all(project_dir).ok();
+    }
+    fs::remove_dir_all(&root).ok();
+}
+
+#[test]
+fn test_offline_and_frozen_builds() {
+    let cx = get_cx_binary();
+    if !cx.exists() || Command::new("git").arg("--version").output().is_err() {
+        eprintln!("Skipping test: cx binary or git not available");
+        return;
+    }
+
+    let root = test_projects_root().join("test_offline");
+    fs::remove_dir_all(&root).ok();
+    let (libo, libn, home) = (root.join("libo"), root.join("libn"), root.join("home"));
+    fs::create_dir_all(&home).unwrap();
+    git_repo(&libo, &[("include/libo.hpp", "#define LIBO 1\n")]);
+    git_repo(&libn, &[("include/libn.hpp", "#define LIBN 1\n")]);
+
+    let project_dir = create_test_project("test_offline_app", true);
+    let manifest = format!(
+        "[package]\nname = \"test_offline_app\"\nversion = \"0.1.0\"\nedition = \"c++17\"\n\n\
+         [dependencies]\nlibo = {{ git = \"{}\", tag = \"v1\" }}\n",
+        libo.display()
+    );
+    fs::write(project_dir.join("cx.toml"), &manifest).unwrap();
+    fs::write(
+        project_dir.join("src/main.cpp"),
+        "#include <libo.hpp>\nint main() { return LIBO == 1 ? 0 : 1; }\n",
+    )
+    .unwrap();
+    let run = |args: &[&str]| {
+        let output = cx_command(&cx, &project_dir)
+            .args(args)
+            .env("HOME", &home)
+            .env("USERPROFILE", &home)
+            .env_remove("CX_OFFLINE")
+            .output()
+            .expect("Failed to execute cx");
+        let combined = format!(
+            "{}{}",
+            String::from_utf8_lossy(&output.stdout),
+            String::from_utf8_lossy(&output.stderr)
+        );
+        (output.status.success(), combined)
+    };
+
+    // Nothing cached yet
+    let (ok, out) = run(&["build", "--offline"]);
+    assert!(!ok);
+    assert!(
+        out.contains("'libo' is not in the dependency cache"),
+        "{out}"
+    );
+
+    // `cx fetch` fills the cache; then offline and frozen builds work
+    let (ok, out) = run(&["fetch"]);
+    assert!(ok, "Fetch failed: {out}");
+    let lock = fs::read_to_string(project_dir.join("cx.lock")).unwrap();
+    let (ok, out) = run(&["build", "--offline"]);
+    assert!(ok, "Offline build failed: {out}");
+    let (ok, out) = run(&["build", "--frozen"]);
+    assert!(ok, "Frozen build failed: {out}");
+    assert_eq!(
+        fs::read_to_string(project_dir.join("cx.lock")).unwrap(),
+        lock
+    );
+
+    // A dependency missing from cx.lock fails --frozen without touching it
+    fs::write(
+        project_dir.join("cx.toml"),
+        format!("{manifest}libn = \"{}\"\n", libn.display()),
+    )
+    .unwrap();
+    let (ok, out) = run(&["build", "--frozen"]);
+    assert!(!ok);
+    assert!(
+        out.contains("Dependency 'libn' missing from cx.lock"),
+        "{out}"
+    );
+    assert!(out.contains("--frozen forbids updating it"), "{out}");
+    assert_eq!(
+        fs::read_to_string(project_dir.join("cx.lock")).unwrap(),
+        lock
+    );
+
+    // Cleanup
+    fs::remove_dir_all(&project_dir).ok();
+    fs::remove_dir_all(&root).ok();
+}
+
+#[test]
+fn test_parallel_dependency_builds() {
+    let cx = get_cx_binary();
+    if cfg!(windows) || !cx.exists() || Command::new("git").arg("--version").output().is_err() {
+        eprintln!("Skipping test: cx binary, git or sh not available");
+        return;
+    }
+
+    let root = test_projects_root().join("test_parallel_deps");
+    fs::remove_dir_all(&root).ok();
+    let (base, mid, broken, home) = (
+        root.join("libbase"),
+        root.join("libmid"),
+        root.join("libbroken"),
+        root.join("home"),
+    );
+    fs::create_dir_all(&home).unwrap();
+    let build_log = root.join("build.log");
+    git_repo(&base, &[("include/libbase.hpp", "#define LIBBASE 1\n")]);
+    git_repo(
+        &mid,
+        &[
+            ("include/libmid.hpp", "#define LIBMID 1\n"),
+            (
+                "cx.toml",
+                &format!(
+                    "[package]\nname = \"libmid\"\nversion = \"0.1.0\"\n\n\
+                     [dependencies]\nlibbase = {{ git = \"{}\", \
+                     build = \"sleep 0.3 && echo libbase >> $CX_TEST_BUILD_LOG\" }}\n",
+                    base.display()
+                ),
+            ),
+        ],
+    );
+    git_repo(
+        &broken,
+        &[("include/libbroken.hpp", "#define LIBBROKEN 1\n")],
+    );
+
+    let project_dir = create_test_project("test_parallel_deps_app", true);
+    fs::write(
+        project_dir.join("cx.toml"),
+        format!(
+            "[package]\nname = \"test_parallel_deps_app\"\nversion = \"0.1.0\"\nedition = \"c++17\"\n\n\
+             [dependencies]\n\
+             libmid = {{ git = \"{}\", build = \"echo libmid >> $CX_TEST_BUILD_LOG\" }}\n\
+             libbroken = {{ git = \"{}\", build = \"echo broken-build-output && exit 1\" }}\n",
+            mid.display(),
+            broken.display()
+        ),
+    )
+    .unwrap();
+
+    let output = cx_command(&cx, &project_dir)
+        .arg("fetch")
+        .env("HOME", &home)
+        .env("USERPROFILE", &home)
+        .env("CX_TEST_BUILD_LOG", &build_log)
+        .output()
+        .expect("Failed to execute cx");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success(), "Fetch failed: {stdout}");
+
+    // A dependency builds only after the ones it needs
+    assert_eq!(fs::read_to_string(&build_log).unwrap(), "libbase\nlibmid\n");
+    // A failed build's output is shown right under its error
+    let lines: Vec<&str> = stdout.lines().collect();
+    let failed = lines
+        .iter()
+        .position(|line| line.contains("Build script failed for libbroken"))
+        .unwrap_or_else(|| panic!("No build failure reported: {stdout}"));
+    assert!(
+        lines[failed + 1].contains("broken-build-output"),
+        "{stdout}"
+    );
+
+    // Cleanup
+    fs::remove_dir_all(&project_dir).ok();
+    fs::remove_dir_all(&root).ok();
+}
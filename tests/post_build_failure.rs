@@ -4,7 +4,7 @@
 //! as build failures, not silently ignored.
 
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::process::Command;
 
 fn test_project_dir(name: &str) -> PathBuf {
@@ -107,6 +107,17 @@ fn get_cx_binary() -> PathBuf {
     target_dir.join("debug").join(bin_name)
 }
 
+/// `cx` invocation in `project_dir` with an object cache private to that
+/// project, so test runs never touch the developer's `~/.cx/objcache`.
+/// Cache tests override `CX_OBJCACHE_DIR` to share a directory on purpose.
+fn cx_command(cx: &Path, project_dir: &Path) -> Command {
+    let mut command = Command::new(cx);
+    command
+        .current_dir(project_dir)
+        .env("CX_OBJCACHE_DIR", project_dir.join(".cx-objcache"));
+    command
+}
+
 #[test]
 fn test_post_build_failure_causes_build_failure() {
     let project_dir = create_project_with_failing_post_build("test_postbuild_fail");
@@ -117,11 +128,7 @@ fn test_post_build_failure_causes_build_failure() {
         return;
     }
 
-    let output = Command::new(&cx)
-        .arg("build")
-        .current_dir(&project_dir)
-        .output()
-        .unwrap();
+    let output = cx_command(&cx, &project_dir).arg("build").output().unwrap();
 
     let stdout = String::from_utf8_lossy(&output.stdout);
     let stderr = String::from_utf8_lossy(&output.stderr);
@@ -153,11 +160,7 @@ fn test_post_build_success_allows_build_success() {
         return;
     }
 
-    let output = Command::new(&cx)
-        .arg("build")
-        .current_dir(&project_dir)
-        .output()
-        .unwrap();
+    let output = cx_command(&cx, &project_dir).arg("build").output().unwrap();
 
     assert!(
         output.status.success(),